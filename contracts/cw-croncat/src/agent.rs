@@ -1,16 +1,25 @@
+use crate::balancer::agent_selection_index;
 use crate::error::ContractError;
 use crate::helpers::{send_tokens, GenericBalance};
-use crate::state::{Config, CwCroncat};
+use crate::state::{AgentChurn, Config, CwCroncat, QueueItem};
 use cosmwasm_std::{
-    has_coins, Addr, Coin, Deps, DepsMut, Env, MessageInfo, Response, StdError, StdResult, Storage,
-    SubMsg, Uint64,
+    has_coins, to_binary, Addr, BankMsg, Coin, Deps, DepsMut, Env, MessageInfo, Order, Response,
+    StdError, StdResult, Storage, SubMsg, Timestamp, Uint128, Uint64, WasmMsg,
 };
 use cw20::Balance;
 use std::ops::Div;
 
 use crate::ContractError::AgentNotRegistered;
-use cw_croncat_core::msg::{AgentTaskResponse, GetAgentIdsResponse};
-use cw_croncat_core::types::{Agent, AgentResponse, AgentStatus};
+use cw_croncat_core::msg::{
+    AgentNextSlotResponse, AgentProjectedEarningsResponse, AgentRecord, AgentTaskResponse,
+    DustSwapMsg, GetAgentChurnResponse, GetAgentIdsResponse,
+};
+use cw_croncat_core::types::{Agent, AgentResponse, AgentStatus, SlashDestination};
+use cw_storage_plus::{Bound, Map};
+
+// Agent churn is counted in hourly buckets over the last day
+const AGENT_CHURN_BUCKET_SECONDS: u64 = 3_600;
+const AGENT_CHURN_WINDOW_BUCKETS: u64 = 24;
 
 impl<'a> CwCroncat<'a> {
     /// Get a single agent details
@@ -34,6 +43,7 @@ impl<'a> CwCroncat<'a> {
             total_tasks_executed: a.total_tasks_executed,
             last_missed_slot: a.last_missed_slot,
             register_start: a.register_start,
+            last_executed_slot: a.last_executed_slot,
         };
 
         if active.contains(&account_id) {
@@ -54,6 +64,17 @@ impl<'a> CwCroncat<'a> {
         Ok(Some(agent_response))
     }
 
+    /// Status of a registered agent, so pending agents know when to check in
+    pub(crate) fn query_get_agent_status(
+        &self,
+        deps: Deps,
+        env: Env,
+        account_id: Addr,
+    ) -> StdResult<AgentStatus> {
+        self.get_agent_status(deps.storage, env, account_id)
+            .map_err(|err| StdError::generic_err(err.to_string()))
+    }
+
     /// Get a list of agent addresses
     pub(crate) fn query_get_agent_ids(&self, deps: Deps) -> StdResult<GetAgentIdsResponse> {
         let active: Vec<Addr> = self.agent_active_queue.load(deps.storage)?;
@@ -62,6 +83,33 @@ impl<'a> CwCroncat<'a> {
         Ok(GetAgentIdsResponse { active, pending })
     }
 
+    /// Get full agent records, paginated over the active and then the pending queue
+    pub(crate) fn query_get_agents(
+        &self,
+        deps: Deps,
+        env: Env,
+        from_index: Option<u64>,
+        limit: Option<u64>,
+    ) -> StdResult<Vec<AgentRecord>> {
+        let active: Vec<Addr> = self.agent_active_queue.load(deps.storage)?;
+        let pending: Vec<Addr> = self.agent_pending_queue.load(deps.storage)?;
+        let from_index = from_index.unwrap_or_default();
+        let limit = limit.unwrap_or(100).min(1000);
+
+        let mut agents = vec![];
+        for account_id in active
+            .into_iter()
+            .chain(pending)
+            .skip(from_index as usize)
+            .take(limit as usize)
+        {
+            if let Some(agent) = self.query_get_agent(deps, env.clone(), account_id.clone())? {
+                agents.push(AgentRecord { account_id, agent });
+            }
+        }
+        Ok(agents)
+    }
+
     // TODO: Change this to solid round-table implementation. Setup this simple version for PoC
     /// Get how many tasks an agent can execute
     pub(crate) fn query_get_agent_tasks(
@@ -71,14 +119,18 @@ impl<'a> CwCroncat<'a> {
         account_id: Addr,
     ) -> StdResult<Option<AgentTaskResponse>> {
         let active = self.agent_active_queue.load(deps.storage)?;
-        if !active.contains(&account_id) {
-            // TODO: unsure if we can return AgentNotRegistered
-            return Err(StdError::GenericErr {
-                msg: AgentNotRegistered {}.to_string(),
-            });
-        }
+        let c: Config = self.config.load(deps.storage)?;
+        let agent_index = match agent_selection_index(&c, &env, &active, &account_id) {
+            Some(index) => index as u64,
+            None => {
+                // TODO: unsure if we can return AgentNotRegistered
+                return Err(StdError::GenericErr {
+                    msg: AgentNotRegistered {}.to_string(),
+                });
+            }
+        };
 
-        // Get all tasks (the final None means no limit when we take)
+        // Get the current slot ids (the final None means no limit when we take)
         let slot_items = self.get_current_slot_items(&env.block, deps.storage, None);
 
         if slot_items == (None, None) {
@@ -86,51 +138,173 @@ impl<'a> CwCroncat<'a> {
         }
         let mut num_block_tasks = Uint64::from(0u64);
         let mut num_cron_tasks = Uint64::from(0u64);
-        let num_block_tasks_extra = Uint64::from(0u64);
-        let num_cron_tasks_extra = Uint64::from(0u64);
+        let mut num_block_tasks_extra = Uint64::from(0u64);
+        let mut num_cron_tasks_extra = Uint64::from(0u64);
+        let extra_task = |total: u64| u64::from(agent_index < total % active.len() as u64);
+        let mut block_slot = None;
+        let mut cron_slot = None;
         // This below line is commented out and will be used with
         // the rotating index (see Config's agent_active_indices)
         // let agent_active_queue_indices: Vec<usize> = (0..active.len()).collect();
-        if let Some(current_block_task_total) = slot_items.0 {
+        if let Some(block_id) = slot_items.0 {
+            let current_block_task_total = self
+                .block_slots
+                .may_load(deps.storage, block_id)?
+                .map_or(0, |hashes| hashes.len() as u64);
             // Integer division to determine how much each gets
             let task_total_each_agent = current_block_task_total / active.len() as u64;
 
-            // Divvy up the modulo leftovers using the active index
-            // TODO: we must give the leftover tasks to some agents.
-            // Still need to implement the "round table" idea, where we use Config's agent_active_indices
-            // let agent_active_index = agent_active_queue
-            //     .iter()
-            //     .position(|x| x == &account_id)
-            //     .expect("Agent not active");
-            // let leftover_tasks = total_tasks % agent_active_queue.len() as u64;
-
+            // Divvy up the modulo leftovers using the selection index
             num_block_tasks = task_total_each_agent.into();
+            num_block_tasks_extra = extra_task(current_block_task_total).into();
+            if task_total_each_agent > 0 || !num_block_tasks_extra.is_zero() {
+                block_slot = Some(block_id);
+            }
         }
         // Do time slots
-        if let Some(current_cron_task_total) = slot_items.1 {
+        if let Some(time_id) = slot_items.1 {
+            let current_cron_task_total = self
+                .time_slots
+                .may_load(deps.storage, time_id)?
+                .map_or(0, |hashes| hashes.len() as u64);
             // Integer division to determine how much each gets
             let task_total_each_agent = current_cron_task_total / active.len() as u64;
 
-            // Divvy up the modulo leftovers using the active index
-            // TODO: we must give the leftover tasks to some agents.
-            // Still need to implement the "round table" idea, where we use Config's agent_active_indices
-            // let agent_active_index = agent_active_queue
-            //     .iter()
-            //     .position(|x| x == &account_id)
-            //     .expect("Agent not active");
-            // let leftover_tasks = total_tasks % agent_active_queue.len() as u64;
-
+            // Divvy up the modulo leftovers using the selection index
             num_cron_tasks = task_total_each_agent.into();
+            num_cron_tasks_extra = extra_task(current_cron_task_total).into();
+            if task_total_each_agent > 0 || !num_cron_tasks_extra.is_zero() {
+                cron_slot = Some(time_id);
+            }
         }
 
         Ok(Some(AgentTaskResponse {
             num_block_tasks,
             num_block_tasks_extra,
-            num_cron_tasks_extra,
             num_cron_tasks,
+            num_cron_tasks_extra,
+            block_slot,
+            cron_slot,
         }))
     }
 
+    /// Gets the earliest block and time slots an active agent is scheduled to serve.
+    /// Follows the selection order: in a slot with N tasks, the first N active agents get one.
+    /// With randomized selection the order is rotated for the current block.
+    /// Returns None if the agent isn't active or no slot has a task for it.
+    pub(crate) fn query_get_agent_next_slot(
+        &self,
+        deps: Deps,
+        env: Env,
+        account_id: Addr,
+    ) -> StdResult<Option<AgentNextSlotResponse>> {
+        let active = self.agent_active_queue.load(deps.storage)?;
+        let c: Config = self.config.load(deps.storage)?;
+        let agent_index = match agent_selection_index(&c, &env, &active, &account_id) {
+            Some(index) => index,
+            None => return Ok(None),
+        };
+
+        let first_served = |slots: &Map<u64, Vec<Vec<u8>>>| -> StdResult<Option<u64>> {
+            for slot in slots.range(deps.storage, None, None, Order::Ascending) {
+                let (slot_id, hashes) = slot?;
+                if hashes.len() > agent_index {
+                    return Ok(Some(slot_id));
+                }
+            }
+            Ok(None)
+        };
+        let block_slot = first_served(&self.block_slots)?;
+        let time_slot = first_served(&self.time_slots)?;
+
+        if block_slot.is_none() && time_slot.is_none() {
+            return Ok(None);
+        }
+        Ok(Some(AgentNextSlotResponse {
+            block_slot,
+            time_slot,
+        }))
+    }
+
+    /// Estimates how much an active agent earns per day, assuming every task runs once a day
+    /// and the work is split evenly over the active agents, each run paying the agent fee.
+    /// This is only an estimate, real earnings depend on task intervals and agent uptime.
+    /// Returns None if the agent isn't active.
+    pub(crate) fn query_get_agent_projected_earnings(
+        &self,
+        deps: Deps,
+        account_id: Addr,
+    ) -> StdResult<Option<AgentProjectedEarningsResponse>> {
+        let active = self.agent_active_queue.load(deps.storage)?;
+        if !active.contains(&account_id) {
+            return Ok(None);
+        }
+        let c: Config = self.config.load(deps.storage)?;
+        let total_tasks = self.task_total(deps.storage)?;
+        let active_agents = active.len() as u64;
+        let estimated_daily_reward = Coin {
+            denom: c.agent_fee.denom.clone(),
+            amount: c.agent_fee.amount * Uint128::from(total_tasks) / Uint128::from(active_agents),
+        };
+        Ok(Some(AgentProjectedEarningsResponse {
+            estimated_daily_reward,
+            total_tasks,
+            active_agents,
+        }))
+    }
+
+    /// Counts of agent registrations, unregistrations and ejections within the churn window
+    pub(crate) fn query_get_agent_churn(
+        &self,
+        deps: Deps,
+        env: Env,
+    ) -> StdResult<GetAgentChurnResponse> {
+        let start = Bound::inclusive(agent_churn_window_start(&env));
+        let mut res = GetAgentChurnResponse {
+            window_seconds: AGENT_CHURN_BUCKET_SECONDS * AGENT_CHURN_WINDOW_BUCKETS,
+            ..Default::default()
+        };
+        for bucket in self
+            .agent_churn
+            .range(deps.storage, Some(start), None, Order::Ascending)
+        {
+            let (_, churn) = bucket?;
+            res.registered += churn.registered;
+            res.unregistered += churn.unregistered;
+            res.ejected += churn.ejected;
+        }
+        Ok(res)
+    }
+
+    /// Counts an agent lifecycle event into the current churn bucket and drops expired ones
+    fn record_agent_churn(
+        &self,
+        storage: &mut dyn Storage,
+        env: &Env,
+        count: impl FnOnce(&mut AgentChurn),
+    ) -> StdResult<()> {
+        let expired: Vec<u64> = self
+            .agent_churn
+            .keys(
+                storage,
+                None,
+                Some(Bound::exclusive(agent_churn_window_start(env))),
+                Order::Ascending,
+            )
+            .collect::<StdResult<_>>()?;
+        for bucket in expired {
+            self.agent_churn.remove(storage, bucket);
+        }
+
+        let bucket = env.block.time.seconds() / AGENT_CHURN_BUCKET_SECONDS;
+        let mut churn = self
+            .agent_churn
+            .may_load(storage, bucket)?
+            .unwrap_or_default();
+        count(&mut churn);
+        self.agent_churn.save(storage, bucket, &churn)
+    }
+
     /// Add any account as an agent that will be able to execute tasks.
     /// Registering allows for rewards accruing with micro-payments which will accumulate to more long-term.
     ///
@@ -156,6 +330,15 @@ impl<'a> CwCroncat<'a> {
         }
 
         let account = info.sender;
+        if c.agent_allowlist_enabled
+            && !self
+                .agent_allowlist
+                .may_load(deps.storage)?
+                .unwrap_or_default()
+                .contains(&account)
+        {
+            return Err(ContractError::Unauthorized {});
+        }
 
         // REF: https://github.com/CosmWasm/cw-tokens/tree/main/contracts/cw20-escrow
         // Check if native token balance is sufficient for a few txns, in this case 4 txns
@@ -205,11 +388,13 @@ impl<'a> CwCroncat<'a> {
                             last_missed_slot: 0,
                             // REF: https://github.com/CosmWasm/cosmwasm/blob/main/packages/std/src/types.rs#L57
                             register_start: env.block.time,
+                            last_executed_slot: env.block.height,
                         })
                     }
                 }
             },
         )?;
+        self.record_agent_churn(deps.storage, &env, |churn| churn.registered += 1)?;
 
         Ok(Response::new()
             .add_attribute("method", "register_agent")
@@ -250,21 +435,173 @@ impl<'a> CwCroncat<'a> {
         Ok(Response::new().add_attribute("method", "update_agent"))
     }
 
-    /// Allows an agent to withdraw all rewards, paid to the specified payable account id.
+    /// Lets an agent pick a relayer that can call proxy_call for it, rewards still go to the agent.
+    pub fn set_agent_relayer(
+        &self,
+        deps: DepsMut,
+        info: MessageInfo,
+        relayer: Option<Addr>,
+    ) -> Result<Response, ContractError> {
+        if !self.agents.has(deps.storage, info.sender.clone()) {
+            return Err(ContractError::AgentNotRegistered {});
+        }
+        let relayer = match relayer {
+            Some(relayer) => {
+                let relayer = deps.api.addr_validate(relayer.as_str())?;
+                self.agent_relayers
+                    .save(deps.storage, info.sender.clone(), &relayer)?;
+                relayer.to_string()
+            }
+            None => {
+                self.agent_relayers
+                    .remove(deps.storage, info.sender.clone());
+                "none".to_string()
+            }
+        };
+
+        Ok(Response::new()
+            .add_attribute("method", "set_agent_relayer")
+            .add_attribute("account_id", info.sender)
+            .add_attribute("relayer", relayer))
+    }
+
+    /// Part of the agent reward balance that hasn't vested yet
+    pub(crate) fn locked_agent_reward(
+        &self,
+        storage: &dyn Storage,
+        c: &Config,
+        agent_id: &Addr,
+        time: Timestamp,
+    ) -> StdResult<Uint128> {
+        if c.reward_vesting_seconds == 0 {
+            return Ok(Uint128::zero());
+        }
+        let vesting = self
+            .agent_reward_vesting
+            .may_load(storage, agent_id.clone())?;
+        Ok(vesting.map_or(Uint128::zero(), |v| {
+            v.locked_at(time, c.reward_vesting_seconds)
+        }))
+    }
+
+    /// Returns rewards still vesting to the contract, for an agent that leaves before they unlock
+    fn forfeit_locked_reward(
+        &self,
+        storage: &mut dyn Storage,
+        env: &Env,
+        agent_id: &Addr,
+    ) -> StdResult<Uint128> {
+        let mut c: Config = self.config.load(storage)?;
+        let locked = self.locked_agent_reward(storage, &c, agent_id, env.block.time)?;
+        if !locked.is_zero() {
+            let forfeited = Coin::new(locked.u128(), c.agent_fee.denom.clone());
+            c.available_balance
+                .add_tokens(Balance::from(vec![forfeited]));
+            self.config.save(storage, &c)?;
+        }
+        Ok(locked)
+    }
+
+    /// Allows an agent to withdraw all vested rewards, paid to the specified payable account id.
     pub(crate) fn withdraw_balances(
         &self,
         storage: &mut dyn Storage,
+        env: &Env,
         info: MessageInfo,
+        amount: Option<Coin>,
     ) -> Result<Vec<SubMsg>, ContractError> {
-        let a = self.agents.may_load(storage, info.sender)?;
+        let a = self.agents.may_load(storage, info.sender.clone())?;
         if a.is_none() {
             return Err(ContractError::AgentNotRegistered {});
         }
-        let agent = a.unwrap();
-
-        // This will send all token balances to Agent
-        let (messages, balances) = send_tokens(&agent.payable_account_id, &agent.balance)?;
+        let mut agent = a.unwrap();
         let mut config = self.config.load(storage)?;
+
+        // Rewards still vesting stay with the agent
+        let locked = self.locked_agent_reward(storage, &config, &info.sender, env.block.time)?;
+        let mut withdrawable = agent.balance.clone();
+        for coin in withdrawable.native.iter_mut() {
+            if coin.denom == config.agent_fee.denom {
+                coin.amount = coin.amount.saturating_sub(locked);
+            }
+        }
+        withdrawable.native.retain(|coin| !coin.amount.is_zero());
+
+        // A partial withdrawal leaves the rest of the rewards in the contract
+        let partial = amount.is_some();
+        if let Some(amount) = amount {
+            let available = withdrawable
+                .native
+                .iter()
+                .find(|c| c.denom == amount.denom)
+                .map_or(Uint128::zero(), |c| c.amount);
+            if amount.amount > available {
+                return Err(ContractError::NotEnoughFunds {});
+            }
+            withdrawable = GenericBalance {
+                native: Some(amount)
+                    .filter(|c| !c.amount.is_zero())
+                    .into_iter()
+                    .collect(),
+                cw20: vec![],
+            };
+        }
+
+        // Small rewards in other denoms get swapped to the native denom on the way out
+        let swap_contract = config
+            .dust_swap_contract
+            .clone()
+            .filter(|_| !config.dust_threshold.is_zero());
+        let mut dust: Vec<Coin> = vec![];
+        if swap_contract.is_some() {
+            let (small, rest): (Vec<Coin>, Vec<Coin>) =
+                withdrawable.native.into_iter().partition(|coin| {
+                    coin.denom != config.native_denom && coin.amount < config.dust_threshold
+                });
+            dust = small;
+            withdrawable.native = rest;
+        }
+
+        // This will send all withdrawable token balances to Agent
+        let (mut messages, mut balances) = send_tokens(&agent.payable_account_id, &withdrawable)?;
+        if let Some(swap_contract) = swap_contract.filter(|_| !dust.is_empty()) {
+            let reply_id = self.rq_push(
+                storage,
+                QueueItem {
+                    contract_addr: Some(swap_contract.clone()),
+                    prev_idx: None,
+                    task_hash: None,
+                    action_idx: None,
+                },
+            )?;
+            self.dust_swaps.save(
+                storage,
+                reply_id,
+                &(agent.payable_account_id.clone(), dust.clone()),
+            )?;
+            messages.push(SubMsg::reply_always(
+                WasmMsg::Execute {
+                    contract_addr: swap_contract.to_string(),
+                    msg: to_binary(&DustSwapMsg::Swap {
+                        ask_denom: config.native_denom.clone(),
+                        recipient: agent.payable_account_id.clone(),
+                    })?,
+                    funds: dust.clone(),
+                },
+                reply_id,
+            ));
+            balances.native.extend(dust);
+        }
+        agent
+            .balance
+            .minus_tokens(Balance::from(balances.native.clone()));
+        agent.balance.native.retain(|coin| !coin.amount.is_zero());
+        // cw20 rewards don't vest, all of them got sent
+        if !partial {
+            agent.balance.cw20.clear();
+        }
+        self.agents.save(storage, info.sender, &agent)?;
+
         config
             .available_balance
             .minus_tokens(Balance::from(balances.native));
@@ -277,14 +614,16 @@ impl<'a> CwCroncat<'a> {
         Ok(messages)
     }
 
-    /// Allows an agent to withdraw all rewards, paid to the specified payable account id.
+    /// Allows an agent to withdraw all rewards, or only `amount` of them,
+    /// paid to the specified payable account id.
     pub fn withdraw_agent_balance(
         &self,
         deps: DepsMut,
         info: MessageInfo,
-        _env: Env,
+        env: Env,
+        amount: Option<Coin>,
     ) -> Result<Response, ContractError> {
-        let messages = self.withdraw_balances(deps.storage, info.clone())?;
+        let messages = self.withdraw_balances(deps.storage, &env, info.clone(), amount)?;
 
         Ok(Response::new()
             .add_attribute("method", "withdraw_agent_balance")
@@ -292,6 +631,177 @@ impl<'a> CwCroncat<'a> {
             .add_submessages(messages))
     }
 
+    /// Routes funds slashed from an agent to the configured slash destination.
+    /// Slashed funds are taken from the agent balance, so they are not part of the available balance yet.
+    /// Falls back to the available balance without an affected task, or when it no longer exists.
+    pub fn route_slashed_funds(
+        &self,
+        storage: &mut dyn Storage,
+        task_hash: Option<Vec<u8>>,
+        slashed: Vec<Coin>,
+    ) -> Result<Vec<SubMsg>, ContractError> {
+        if slashed.is_empty() {
+            return Ok(vec![]);
+        }
+        let mut c: Config = self.config.load(storage)?;
+        let messages = match c.slash_destination {
+            SlashDestination::Treasury => vec![SubMsg::new(BankMsg::Send {
+                to_address: c
+                    .treasury_id
+                    .clone()
+                    .unwrap_or_else(|| c.owner_id.clone())
+                    .to_string(),
+                amount: slashed,
+            })],
+            SlashDestination::Burn => vec![SubMsg::new(BankMsg::Burn { amount: slashed })],
+            SlashDestination::Task => {
+                let task = match task_hash {
+                    Some(task_hash) => self
+                        .tasks
+                        .may_load(storage, task_hash.clone())?
+                        .map(|task| (task_hash, task)),
+                    None => None,
+                };
+                if let Some((task_hash, mut task)) = task {
+                    for coin in slashed.iter() {
+                        match task
+                            .total_deposit
+                            .iter_mut()
+                            .find(|d| d.denom == coin.denom)
+                        {
+                            Some(deposit) => deposit.amount += coin.amount,
+                            None => task.total_deposit.push(coin.clone()),
+                        }
+                    }
+                    self.tasks.save(storage, task_hash, &task)?;
+                }
+                c.available_balance.add_tokens(Balance::from(slashed));
+                self.config.save(storage, &c)?;
+                vec![]
+            }
+        };
+        Ok(messages)
+    }
+
+    /// Removes an active agent and pays out its vested rewards, for agents that stopped serving.
+    /// Rewards still vesting are slashed to the configured slash destination.
+    /// With `eject_to_pending` the agent is demoted to the back of the pending queue instead,
+    /// keeping its registration and balance.
+    /// Returns None without ejecting when the active queue would drop below `min_active_agents`,
+    /// a network with imperfect agents still beats a stalled one.
+    pub fn eject_agent(
+        &self,
+        storage: &mut dyn Storage,
+        env: &Env,
+        agent_id: Addr,
+    ) -> Result<Option<Vec<SubMsg>>, ContractError> {
+        let c: Config = self.config.load(storage)?;
+        let mut active_agents: Vec<Addr> = self.agent_active_queue.load(storage)?;
+        let index = match active_agents.iter().position(|addr| *addr == agent_id) {
+            Some(index) => index,
+            None => return Err(AgentNotRegistered {}),
+        };
+        if active_agents.len() as u64 <= c.min_active_agents {
+            return Ok(None);
+        }
+        if c.eject_to_pending {
+            active_agents.remove(index);
+            self.agent_active_queue.save(storage, &active_agents)?;
+            let mut pending_agents = self.agent_pending_queue.load(storage)?;
+            pending_agents.push(agent_id);
+            self.agent_pending_queue.save(storage, &pending_agents)?;
+            self.record_agent_churn(storage, env, |churn| churn.ejected += 1)?;
+            return Ok(Some(vec![]));
+        }
+
+        let info = MessageInfo {
+            sender: agent_id.clone(),
+            funds: vec![],
+        };
+        let mut messages = self.withdraw_balances(storage, env, info, None)?;
+        // Rewards still vesting are the penalty for going stale
+        let locked = self.locked_agent_reward(storage, &c, &agent_id, env.block.time)?;
+        let slashed: Vec<Coin> = (!locked.is_zero())
+            .then(|| Coin::new(locked.u128(), c.agent_fee.denom.clone()))
+            .into_iter()
+            .collect();
+        messages.extend(self.route_slashed_funds(storage, None, slashed)?);
+        active_agents.remove(index);
+        self.agent_active_queue.save(storage, &active_agents)?;
+        self.agents.remove(storage, agent_id.clone());
+        self.agent_reward_vesting.remove(storage, agent_id.clone());
+        self.agent_relayers.remove(storage, agent_id);
+        self.record_agent_churn(storage, env, |churn| churn.ejected += 1)?;
+        Ok(Some(messages))
+    }
+
+    /// Ejects active agents that haven't executed for more than `agents_eject_threshold` blocks,
+    /// down to `min_active_agents`. Anyone can call it, freed seats open nomination to pending agents
+    pub fn eject_stale_agents(
+        &self,
+        deps: DepsMut,
+        info: MessageInfo,
+        env: Env,
+    ) -> Result<Response, ContractError> {
+        if !info.funds.is_empty() {
+            return Err(ContractError::CustomError {
+                val: "Do not attach funds".to_string(),
+            });
+        }
+        let c: Config = self.config.load(deps.storage)?;
+        let active_agents: Vec<Addr> = self.agent_active_queue.load(deps.storage)?;
+        let mut submsgs: Vec<SubMsg> = vec![];
+        let mut ejected: Vec<Addr> = vec![];
+        for agent_id in active_agents {
+            let agent = match self.agents.may_load(deps.storage, agent_id.clone())? {
+                Some(agent) => agent,
+                None => continue,
+            };
+            if env.block.height.saturating_sub(agent.last_executed_slot) <= c.agents_eject_threshold
+            {
+                continue;
+            }
+            match self.eject_agent(deps.storage, &env, agent_id.clone())? {
+                Some(messages) => {
+                    submsgs.extend(messages);
+                    ejected.push(agent_id);
+                }
+                // Only the minimum number of agents is left
+                None => break,
+            }
+        }
+
+        // Let the next pending agents check in for the freed seats
+        let mut attrs: Vec<(&str, String)> = vec![];
+        if !ejected.is_empty() {
+            let num_active_agents = self.agent_active_queue.load(deps.storage)?.len() as u64;
+            let total_tasks = self.task_total(deps.storage)?;
+            if self.agents_to_let_in(&c.min_tasks_per_agent, &num_active_agents, &total_tasks) > 0
+                && self
+                    .agent_nomination_begin_time
+                    .load(deps.storage)?
+                    .is_none()
+            {
+                self.agent_nomination_begin_time
+                    .save(deps.storage, &Some(env.block.time))?;
+                attrs.push((
+                    "agent_nomination_begin_time",
+                    env.block.time.nanos().to_string(),
+                ));
+            }
+        }
+
+        Ok(Response::new()
+            .add_attribute("method", "eject_stale_agents")
+            .add_attributes(
+                ejected
+                    .iter()
+                    .map(|agent_id| ("ejected_agent", agent_id.to_string())),
+            )
+            .add_attributes(attrs)
+            .add_submessages(submsgs))
+    }
+
     /// Allows an agent to accept a nomination within a certain amount of time to become an active agent.
     pub fn accept_nomination_agent(
         &self,
@@ -300,7 +810,19 @@ impl<'a> CwCroncat<'a> {
         env: Env,
     ) -> Result<Response, ContractError> {
         // Compare current time and Config's agent_nomination_begin_time to see if agent can join
+        // Retried check-ins from active agents aren't an error
+        let active_agents: Vec<Addr> = self.agent_active_queue.load(deps.storage)?;
+        if active_agents.contains(&info.sender) {
+            return Ok(Response::new()
+                .add_attribute("method", "accept_nomination_agent")
+                .add_attribute("agent_status", "already_active"));
+        }
         let c: Config = self.config.load(deps.storage)?;
+        if c.nomination_paused {
+            return Err(ContractError::CustomError {
+                val: "Agent nomination paused".to_string(),
+            });
+        }
 
         let time_difference =
             if let Some(nomination_start) = self.agent_nomination_begin_time.load(deps.storage)? {
@@ -339,6 +861,20 @@ impl<'a> CwCroncat<'a> {
                 let mut active_agents: Vec<Addr> = self.agent_active_queue.load(deps.storage)?;
                 active_agents.push(info.sender.clone());
                 self.agent_active_queue.save(deps.storage, &active_agents)?;
+                // Staleness counts from the check-in
+                self.agents.update(
+                    deps.storage,
+                    info.sender.clone(),
+                    |a: Option<Agent>| -> Result<_, ContractError> {
+                        match a {
+                            Some(mut agent) => {
+                                agent.last_executed_slot = env.block.height;
+                                Ok(agent)
+                            }
+                            None => Err(AgentNotRegistered {}),
+                        }
+                    },
+                )?;
 
                 // and update the config, setting the nomination begin time to None,
                 // which indicates no one will be nominated until more tasks arrive
@@ -354,22 +890,29 @@ impl<'a> CwCroncat<'a> {
             return Err(ContractError::AgentNotRegistered {});
         }
         // Find difference
-        Ok(Response::new().add_attribute("method", "accept_nomination_agent"))
+        Ok(Response::new()
+            .add_attribute("method", "accept_nomination_agent")
+            .add_attribute("agent_status", "active"))
     }
 
     /// Removes the agent from the active set of agents.
-    /// Withdraws all reward balances to the agent payable account id.
+    /// Withdraws all vested reward balances to the agent payable account id,
+    /// rewards still vesting are forfeited back to the contract.
     pub fn unregister_agent(
         &self,
         deps: DepsMut,
         info: MessageInfo,
-        _env: Env,
+        env: Env,
     ) -> Result<Response, ContractError> {
         // Get withdraw messages, if any
         // NOTE: Since this also checks if agent exists, safe to not have redundant logic
-        let messages = self.withdraw_balances(deps.storage, info.clone())?;
+        let messages = self.withdraw_balances(deps.storage, &env, info.clone(), None)?;
         let agent_id = info.sender;
+        self.forfeit_locked_reward(deps.storage, &env, &agent_id)?;
         self.agents.remove(deps.storage, agent_id.clone());
+        self.agent_reward_vesting
+            .remove(deps.storage, agent_id.clone());
+        self.agent_relayers.remove(deps.storage, agent_id.clone());
 
         // Remove from the list of active agents if the agent in this list
         let mut active_agents: Vec<Addr> = self
@@ -392,6 +935,7 @@ impl<'a> CwCroncat<'a> {
                     .save(deps.storage, &pending_agents)?;
             }
         }
+        self.record_agent_churn(deps.storage, &env, |churn| churn.unregistered += 1)?;
 
         let responses = Response::new()
             .add_attribute("method", "unregister_agent")
@@ -405,15 +949,28 @@ impl<'a> CwCroncat<'a> {
     }
 }
 
+/// First churn bucket still within the window
+fn agent_churn_window_start(env: &Env) -> u64 {
+    (env.block.time.seconds() / AGENT_CHURN_BUCKET_SECONDS)
+        .saturating_sub(AGENT_CHURN_WINDOW_BUCKETS - 1)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::error::ContractError;
+    use crate::helpers::test_helpers::mock_init;
     use crate::helpers::CwTemplateContract;
-    use cosmwasm_std::testing::{mock_env, mock_info, MOCK_CONTRACT_ADDR};
-    use cosmwasm_std::{coin, coins, from_slice, Addr, BlockInfo, CosmosMsg, Empty, StakingMsg};
-    use cw_croncat_core::msg::{ExecuteMsg, InstantiateMsg, QueryMsg, TaskRequest, TaskResponse};
-    use cw_croncat_core::types::{Action, Interval};
+    use crate::state::RewardVesting;
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info, MOCK_CONTRACT_ADDR};
+    use cosmwasm_std::{
+        coin, coins, from_binary, from_slice, Addr, BlockInfo, CosmosMsg, Empty, Reply, StakingMsg,
+        SubMsgResult,
+    };
+    use cw_croncat_core::msg::{
+        ExecuteMsg, GetSlotIdsResponse, InstantiateMsg, QueryMsg, TaskRequest, TaskResponse,
+    };
+    use cw_croncat_core::types::{Action, ActionOrder, BoundaryValidated, Interval, Task};
     use cw_multi_test::{App, AppBuilder, AppResponse, Contract, ContractWrapper, Executor};
 
     pub fn contract_template() -> Box<dyn Contract<Empty>> {
@@ -500,6 +1057,7 @@ mod tests {
                 &QueryMsg::GetTasks {
                     from_index: None,
                     limit: None,
+                    include_status: None,
                 },
             )
             .unwrap();
@@ -525,6 +1083,9 @@ mod tests {
                         gas_limit: Some(150_000),
                     }],
                     rules: None,
+                    metadata: None,
+                    successor: None,
+                    action_order: None,
                 },
             },
             send_funds.as_ref(),
@@ -556,6 +1117,9 @@ mod tests {
                         gas_limit: Some(150_000),
                     }],
                     rules: None,
+                    metadata: None,
+                    successor: None,
+                    action_order: None,
                 },
             },
             send_funds.as_ref(),
@@ -587,6 +1151,9 @@ mod tests {
                         gas_limit: Some(150_000),
                     }],
                     rules: None,
+                    metadata: None,
+                    successor: None,
+                    action_order: None,
                 },
             },
             send_funds.as_ref(),
@@ -619,6 +1186,9 @@ mod tests {
                     gas_limit: Some(150_000),
                 }],
                 rules: None,
+                metadata: None,
+                successor: None,
+                action_order: None,
             },
         )
     }
@@ -733,14 +1303,37 @@ mod tests {
         // Test Can't register if contract is paused
         let payload_1 = ExecuteMsg::UpdateSettings {
             paused: Some(true),
-            owner_id: None,
-            // treasury_id: None,
+            treasury_id: None,
             agent_fee: None,
             min_tasks_per_agent: None,
             agents_eject_threshold: None,
             gas_price: None,
             proxy_callback_gas: None,
             slot_granularity: None,
+            dead_task_bounty: None,
+            max_rules_per_task: None,
+            slash_destination: None,
+            low_balance_pause_threshold: None,
+            min_active_agents_for_execution: None,
+            max_submsgs_per_proxy_call: None,
+            protocol_fee: None,
+            native_denom: None,
+            task_creation_fee: None,
+            nomination_paused: None,
+            reward_vesting_seconds: None,
+            max_future_slot_seconds: None,
+            randomized_agent_selection: None,
+            max_tasks_per_owner: None,
+            dust_threshold: None,
+            dust_swap_contract: None,
+            min_active_agents: None,
+            min_agent_version: None,
+            removal_grace_slots: None,
+            eject_to_pending: None,
+            max_task_limit: None,
+            agent_allowlist_enabled: None,
+            max_task_gas: None,
+            min_task_balance: None,
         };
 
         app.execute_contract(
@@ -763,14 +1356,37 @@ mod tests {
         // Test wallet rejected if doesnt have enough funds
         let payload_2 = ExecuteMsg::UpdateSettings {
             paused: Some(false),
-            owner_id: None,
-            // treasury_id: None,
+            treasury_id: None,
             agent_fee: None,
             min_tasks_per_agent: None,
             agents_eject_threshold: None,
             gas_price: None,
             proxy_callback_gas: None,
             slot_granularity: None,
+            dead_task_bounty: None,
+            max_rules_per_task: None,
+            slash_destination: None,
+            low_balance_pause_threshold: None,
+            min_active_agents_for_execution: None,
+            max_submsgs_per_proxy_call: None,
+            protocol_fee: None,
+            native_denom: None,
+            task_creation_fee: None,
+            nomination_paused: None,
+            reward_vesting_seconds: None,
+            max_future_slot_seconds: None,
+            randomized_agent_selection: None,
+            max_tasks_per_owner: None,
+            dust_threshold: None,
+            dust_swap_contract: None,
+            min_active_agents: None,
+            min_agent_version: None,
+            removal_grace_slots: None,
+            eject_to_pending: None,
+            max_task_limit: None,
+            agent_allowlist_enabled: None,
+            max_task_gas: None,
+            min_task_balance: None,
         };
 
         app.execute_contract(
@@ -979,7 +1595,7 @@ mod tests {
             .unwrap();
 
         // Fails for non-existent agents
-        let wthdrw_msg = ExecuteMsg::WithdrawReward {};
+        let wthdrw_msg = ExecuteMsg::WithdrawReward { amount: None };
         let update_err = app
             .execute_contract(
                 Addr::unchecked(AGENT0),
@@ -1152,6 +1768,47 @@ mod tests {
         );
     }
 
+    #[test]
+    fn check_in_agent_twice() {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+
+        register_agent_exec(&mut app, &contract_addr, AGENT1, &AGENT_BENEFICIARY);
+        add_task_exec(&mut app, &contract_addr, PARTICIPANT0);
+        register_agent_exec(&mut app, &contract_addr, AGENT2, &AGENT_BENEFICIARY);
+        add_task_exec(&mut app, &contract_addr, PARTICIPANT1);
+        add_task_exec(&mut app, &contract_addr, PARTICIPANT2);
+        add_task_exec(&mut app, &contract_addr, PARTICIPANT3);
+        app.update_block(add_little_time);
+
+        let agent_status_attr = |res: AppResponse| {
+            res.events
+                .iter()
+                .flat_map(|e| e.attributes.iter())
+                .find(|a| a.key == "agent_status")
+                .map(|a| a.value.clone())
+        };
+        let res = check_in_exec(&mut app, &contract_addr, AGENT2).unwrap();
+        assert_eq!(agent_status_attr(res), Some("active".to_string()));
+
+        // A retry reports the agent is already active
+        let res = check_in_exec(&mut app, &contract_addr, AGENT2).unwrap();
+        assert_eq!(agent_status_attr(res), Some("already_active".to_string()));
+        let (_, num_active_agents, num_pending_agents) = get_agent_ids(&app, &contract_addr);
+        assert_eq!(num_active_agents, 2);
+        assert_eq!(num_pending_agents, 0);
+
+        // Agents that aren't nominated still fail
+        register_agent_exec(&mut app, &contract_addr, AGENT3, &AGENT_BENEFICIARY);
+        let res_err = check_in_exec(&mut app, &contract_addr, AGENT3).unwrap_err();
+        assert_eq!(
+            ContractError::CustomError {
+                val: "Not accepting new agents".to_string()
+            },
+            res_err.downcast().unwrap()
+        );
+    }
+
     #[test]
     fn test_get_agent_status() {
         // Give the contract and the agents balances
@@ -1342,4 +1999,1142 @@ mod tests {
             .query_wasm_smart(contract_addr.clone(), &msg_agent_tasks);
         println!("aloha query_task_res {:?}", query_task_res);
     }
+
+    #[test]
+    fn route_slashed_funds() {
+        let mut deps = mock_dependencies();
+        let store = CwCroncat::default();
+        mock_init(&store, deps.as_mut()).unwrap();
+
+        let task = Task {
+            owner_id: Addr::unchecked(PARTICIPANT0),
+            interval: Interval::Immediate,
+            boundary: BoundaryValidated {
+                start: None,
+                end: None,
+                max_executions: None,
+            },
+            stop_on_fail: false,
+            total_deposit: coins(100, NATIVE_DENOM),
+            actions: vec![],
+            rules: None,
+            metadata: None,
+            successor: None,
+            generation: 0,
+            action_order: ActionOrder::Sequential,
+            total_cw20_deposit: vec![],
+        };
+        let task_hash = task.to_hash_vec();
+        store
+            .tasks
+            .save(&mut deps.storage, task_hash.clone(), &task)
+            .unwrap();
+        let slashed = coins(7, NATIVE_DENOM);
+        let set_destination = |storage: &mut dyn Storage, destination: SlashDestination| {
+            store
+                .config
+                .update(storage, |mut c| -> StdResult<_> {
+                    c.slash_destination = destination;
+                    Ok(c)
+                })
+                .unwrap();
+        };
+
+        // Treasury
+        set_destination(&mut deps.storage, SlashDestination::Treasury);
+        let msgs = store
+            .route_slashed_funds(&mut deps.storage, Some(task_hash.clone()), slashed.clone())
+            .unwrap();
+        assert_eq!(
+            msgs,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "creator".to_string(),
+                amount: slashed.clone(),
+            })]
+        );
+        store
+            .config
+            .update(&mut deps.storage, |mut c| -> StdResult<_> {
+                c.treasury_id = Some(Addr::unchecked("treasury"));
+                Ok(c)
+            })
+            .unwrap();
+        let msgs = store
+            .route_slashed_funds(&mut deps.storage, Some(task_hash.clone()), slashed.clone())
+            .unwrap();
+        assert_eq!(
+            msgs,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "treasury".to_string(),
+                amount: slashed.clone(),
+            })]
+        );
+
+        // Burn
+        set_destination(&mut deps.storage, SlashDestination::Burn);
+        let msgs = store
+            .route_slashed_funds(&mut deps.storage, Some(task_hash.clone()), slashed.clone())
+            .unwrap();
+        assert_eq!(
+            msgs,
+            vec![SubMsg::new(BankMsg::Burn {
+                amount: slashed.clone(),
+            })]
+        );
+
+        // Task, the default
+        set_destination(&mut deps.storage, SlashDestination::Task);
+        let available_before = store
+            .config
+            .load(&deps.storage)
+            .unwrap()
+            .available_balance
+            .native;
+        let msgs = store
+            .route_slashed_funds(&mut deps.storage, Some(task_hash.clone()), slashed)
+            .unwrap();
+        assert!(msgs.is_empty());
+        let task = store.tasks.load(&deps.storage, task_hash).unwrap();
+        assert_eq!(task.total_deposit, coins(107, NATIVE_DENOM));
+        let available_after = store
+            .config
+            .load(&deps.storage)
+            .unwrap()
+            .available_balance
+            .native;
+        let amount_of = |balance: &[Coin]| {
+            balance
+                .iter()
+                .find(|c| c.denom == NATIVE_DENOM)
+                .map(|c| c.amount.u128())
+                .unwrap_or_default()
+        };
+        assert_eq!(
+            amount_of(&available_after),
+            amount_of(&available_before) + 7
+        );
+    }
+
+    #[test]
+    fn query_agent_next_slot() {
+        let mut deps = mock_dependencies();
+        let mut store = CwCroncat::default();
+        mock_init(&store, deps.as_mut()).unwrap();
+
+        store
+            .agent_active_queue
+            .save(
+                &mut deps.storage,
+                &vec![Addr::unchecked(AGENT0), Addr::unchecked(AGENT1)],
+            )
+            .unwrap();
+        let hashes = |n: usize| -> Vec<Vec<u8>> {
+            (0..n).map(|i| format!("hash{}", i).into_bytes()).collect()
+        };
+        store
+            .block_slots
+            .save(&mut deps.storage, 100, &hashes(1))
+            .unwrap();
+        store
+            .block_slots
+            .save(&mut deps.storage, 105, &hashes(2))
+            .unwrap();
+        store
+            .time_slots
+            .save(&mut deps.storage, 5_000, &hashes(1))
+            .unwrap();
+
+        let mut next_slot = |account_id: &str| -> Option<AgentNextSlotResponse> {
+            let res = store
+                .query(
+                    deps.as_ref(),
+                    mock_env(),
+                    QueryMsg::GetAgentNextSlot {
+                        account_id: Addr::unchecked(account_id),
+                    },
+                )
+                .unwrap();
+            from_binary(&res).unwrap()
+        };
+
+        // First agent gets the single task slots, second one waits for a slot with two tasks
+        assert_eq!(
+            next_slot(AGENT0),
+            Some(AgentNextSlotResponse {
+                block_slot: Some(100),
+                time_slot: Some(5_000),
+            })
+        );
+        assert_eq!(
+            next_slot(AGENT1),
+            Some(AgentNextSlotResponse {
+                block_slot: Some(105),
+                time_slot: None,
+            })
+        );
+        assert_eq!(next_slot(AGENT2), None);
+    }
+
+    #[test]
+    fn query_agent_slots_follow_randomized_selection() {
+        let mut deps = mock_dependencies();
+        let mut store = CwCroncat::default();
+        mock_init(&store, deps.as_mut()).unwrap();
+        store
+            .config
+            .update(&mut deps.storage, |mut c| -> StdResult<_> {
+                c.randomized_agent_selection = true;
+                Ok(c)
+            })
+            .unwrap();
+        store
+            .agent_active_queue
+            .save(
+                &mut deps.storage,
+                &vec![Addr::unchecked(AGENT0), Addr::unchecked(AGENT1)],
+            )
+            .unwrap();
+        store
+            .block_slots
+            .save(&mut deps.storage, 100, &vec![b"hash0".to_vec()])
+            .unwrap();
+        store
+            .block_slots
+            .save(
+                &mut deps.storage,
+                105,
+                &vec![b"hash0".to_vec(), b"hash1".to_vec()],
+            )
+            .unwrap();
+
+        // Find a block that rotates the second agent to the front
+        let mut env = mock_env();
+        while crate::balancer::selection_offset(&env, 2) != 1 {
+            env.block.height += 1;
+        }
+
+        let mut next_slot = |account_id: &str| -> Option<AgentNextSlotResponse> {
+            let res = store
+                .query(
+                    deps.as_ref(),
+                    env.clone(),
+                    QueryMsg::GetAgentNextSlot {
+                        account_id: Addr::unchecked(account_id),
+                    },
+                )
+                .unwrap();
+            from_binary(&res).unwrap()
+        };
+        assert_eq!(
+            next_slot(AGENT1),
+            Some(AgentNextSlotResponse {
+                block_slot: Some(100),
+                time_slot: None,
+            })
+        );
+        assert_eq!(
+            next_slot(AGENT0),
+            Some(AgentNextSlotResponse {
+                block_slot: Some(105),
+                time_slot: None,
+            })
+        );
+
+        // The single due task is the second agent's to take
+        let mut agent_tasks = |account_id: &str| -> AgentTaskResponse {
+            let res = store
+                .query(
+                    deps.as_ref(),
+                    env.clone(),
+                    QueryMsg::GetAgentTasks {
+                        account_id: Addr::unchecked(account_id),
+                    },
+                )
+                .unwrap();
+            from_binary::<Option<AgentTaskResponse>>(&res)
+                .unwrap()
+                .unwrap()
+        };
+        let first = agent_tasks(AGENT1);
+        assert_eq!(first.num_block_tasks_extra, Uint64::new(1));
+        assert_eq!(first.block_slot, Some(100));
+        let second = agent_tasks(AGENT0);
+        assert_eq!(second.num_block_tasks_extra, Uint64::zero());
+        assert_eq!(second.block_slot, None);
+    }
+
+    #[test]
+    fn query_agent_projected_earnings() {
+        let mut deps = mock_dependencies();
+        let mut store = CwCroncat::default();
+        mock_init(&store, deps.as_mut()).unwrap();
+        store.task_total.save(&mut deps.storage, &10).unwrap();
+
+        let mut projection = |agents: Vec<Addr>| -> Option<AgentProjectedEarningsResponse> {
+            store
+                .agent_active_queue
+                .save(&mut deps.storage, &agents)
+                .unwrap();
+            let res = store
+                .query(
+                    deps.as_ref(),
+                    mock_env(),
+                    QueryMsg::GetAgentProjectedEarnings {
+                        account_id: Addr::unchecked(AGENT0),
+                    },
+                )
+                .unwrap();
+            from_binary(&res).unwrap()
+        };
+
+        // Agent fee of 5 for each of the 10 tasks
+        let single = projection(vec![Addr::unchecked(AGENT0)]).unwrap();
+        assert_eq!(single.estimated_daily_reward, coin(50, NATIVE_DENOM));
+        assert_eq!(single.active_agents, 1);
+        assert_eq!(single.total_tasks, 10);
+
+        // Twice as many agents halves the projection
+        let shared = projection(vec![Addr::unchecked(AGENT0), Addr::unchecked(AGENT1)]).unwrap();
+        assert_eq!(shared.estimated_daily_reward, coin(25, NATIVE_DENOM));
+        assert_eq!(shared.active_agents, 2);
+
+        // Not an active agent
+        assert_eq!(projection(vec![Addr::unchecked(AGENT1)]), None);
+    }
+
+    #[test]
+    fn withdraw_vested_reward() {
+        let mut deps = mock_dependencies();
+        let mut store = CwCroncat::default();
+        mock_init(&store, deps.as_mut()).unwrap();
+        store
+            .config
+            .update(&mut deps.storage, |mut c| -> StdResult<_> {
+                c.reward_vesting_seconds = 100;
+                Ok(c)
+            })
+            .unwrap();
+        let agent = Agent {
+            payable_account_id: Addr::unchecked(AGENT_BENEFICIARY),
+            balance: GenericBalance::default(),
+            total_tasks_executed: 0,
+            last_missed_slot: 0,
+            register_start: mock_env().block.time,
+            last_executed_slot: 0,
+        };
+        store
+            .agents
+            .save(&mut deps.storage, Addr::unchecked(AGENT1), &agent)
+            .unwrap();
+
+        // Two rewards of 5 accrue at the same time
+        let info = mock_info(AGENT1, &[]);
+        for _ in 0..2 {
+            let agent = store
+                .agents
+                .load(&deps.storage, Addr::unchecked(AGENT1))
+                .unwrap();
+            store.send_base_agent_reward(&mut deps.storage, &mock_env(), agent, info.clone());
+        }
+
+        let mut withdraw = |seconds: u64| -> Vec<SubMsg> {
+            let mut env = mock_env();
+            env.block.time = env.block.time.plus_seconds(seconds);
+            store
+                .execute(
+                    deps.as_mut(),
+                    env,
+                    info.clone(),
+                    ExecuteMsg::WithdrawReward { amount: None },
+                )
+                .unwrap()
+                .messages
+        };
+        let paid = |amount: u128| {
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: AGENT_BENEFICIARY.to_string(),
+                amount: coins(amount, NATIVE_DENOM),
+            })]
+        };
+
+        // Halfway through the period only half of the rewards can be withdrawn
+        assert_eq!(withdraw(50), paid(5));
+        assert_eq!(withdraw(50), vec![]);
+        assert_eq!(withdraw(100), paid(5));
+        assert_eq!(withdraw(200), vec![]);
+    }
+
+    #[test]
+    fn unregister_forfeits_locked_reward() {
+        let mut deps = mock_dependencies();
+        let mut store = CwCroncat::default();
+        mock_init(&store, deps.as_mut()).unwrap();
+        store
+            .config
+            .update(&mut deps.storage, |mut c| -> StdResult<_> {
+                c.reward_vesting_seconds = 100;
+                c.available_balance.native = coins(100, NATIVE_DENOM);
+                Ok(c)
+            })
+            .unwrap();
+        let agent = Agent {
+            payable_account_id: Addr::unchecked(AGENT_BENEFICIARY),
+            balance: GenericBalance::default(),
+            total_tasks_executed: 0,
+            last_missed_slot: 0,
+            register_start: mock_env().block.time,
+            last_executed_slot: 0,
+        };
+        store
+            .agents
+            .save(&mut deps.storage, Addr::unchecked(AGENT1), &agent)
+            .unwrap();
+        let info = mock_info(AGENT1, &[]);
+        for _ in 0..2 {
+            let agent = store
+                .agents
+                .load(&deps.storage, Addr::unchecked(AGENT1))
+                .unwrap();
+            store.send_base_agent_reward(&mut deps.storage, &mock_env(), agent, info.clone());
+        }
+        let before = store.config.load(&deps.storage).unwrap().available_balance;
+
+        // Halfway through the period, half is paid out and the locked half stays with the contract
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(50);
+        let res = store
+            .execute(deps.as_mut(), env, info, ExecuteMsg::UnregisterAgent {})
+            .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: AGENT_BENEFICIARY.to_string(),
+                amount: coins(5, NATIVE_DENOM),
+            })]
+        );
+        let (paid, forfeited) = (Uint128::new(5), Uint128::new(5));
+        let after = store.config.load(&deps.storage).unwrap().available_balance;
+        assert_eq!(
+            after.native,
+            coins(
+                (before.native[0].amount - paid + forfeited).u128(),
+                NATIVE_DENOM
+            )
+        );
+        assert!(store
+            .agent_reward_vesting
+            .may_load(&deps.storage, Addr::unchecked(AGENT1))
+            .unwrap()
+            .is_none());
+    }
+    #[test]
+    fn withdraw_partial_reward() {
+        let mut deps = mock_dependencies();
+        let mut store = CwCroncat::default();
+        mock_init(&store, deps.as_mut()).unwrap();
+        let agent = Agent {
+            payable_account_id: Addr::unchecked(AGENT_BENEFICIARY),
+            balance: GenericBalance {
+                native: coins(100, NATIVE_DENOM),
+                cw20: vec![],
+            },
+            total_tasks_executed: 0,
+            last_missed_slot: 0,
+            register_start: mock_env().block.time,
+            last_executed_slot: 0,
+        };
+        store
+            .agents
+            .save(&mut deps.storage, Addr::unchecked(AGENT1), &agent)
+            .unwrap();
+
+        let mut withdraw = |amount: u128| {
+            store.execute(
+                deps.as_mut(),
+                mock_env(),
+                mock_info(AGENT1, &[]),
+                ExecuteMsg::WithdrawReward {
+                    amount: Some(coin(amount, NATIVE_DENOM)),
+                },
+            )
+        };
+
+        let res = withdraw(40).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: AGENT_BENEFICIARY.to_string(),
+                amount: coins(40, NATIVE_DENOM),
+            })]
+        );
+
+        // Can't take out more than what's left
+        let err = withdraw(61).unwrap_err();
+        assert_eq!(err, ContractError::NotEnoughFunds {});
+
+        let agent = store
+            .agents
+            .load(&deps.storage, Addr::unchecked(AGENT1))
+            .unwrap();
+        assert_eq!(agent.balance.native, coins(60, NATIVE_DENOM));
+
+        // Without an amount the rest goes out
+        let res = store
+            .execute(
+                deps.as_mut(),
+                mock_env(),
+                mock_info(AGENT1, &[]),
+                ExecuteMsg::WithdrawReward { amount: None },
+            )
+            .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: AGENT_BENEFICIARY.to_string(),
+                amount: coins(60, NATIVE_DENOM),
+            })]
+        );
+        let agent = store
+            .agents
+            .load(&deps.storage, Addr::unchecked(AGENT1))
+            .unwrap();
+        assert!(agent.balance.native.is_empty());
+    }
+
+    #[test]
+    fn withdraw_reward_swaps_dust() {
+        let mut deps = mock_dependencies();
+        let mut store = CwCroncat::default();
+        mock_init(&store, deps.as_mut()).unwrap();
+        store
+            .config
+            .update(&mut deps.storage, |mut c| -> StdResult<_> {
+                c.dust_swap_contract = Some(Addr::unchecked("swapper"));
+                c.dust_threshold = Uint128::new(10);
+                Ok(c)
+            })
+            .unwrap();
+        let agent = Agent {
+            payable_account_id: Addr::unchecked(AGENT_BENEFICIARY),
+            balance: GenericBalance {
+                native: vec![coin(100, NATIVE_DENOM), coin(3, "uosmo"), coin(50, "ujuno")],
+                cw20: vec![],
+            },
+            total_tasks_executed: 0,
+            last_missed_slot: 0,
+            register_start: mock_env().block.time,
+            last_executed_slot: 0,
+        };
+        store
+            .agents
+            .save(&mut deps.storage, Addr::unchecked(AGENT1), &agent)
+            .unwrap();
+
+        // Only the small non-native balance goes through the swap
+        let res = store
+            .execute(
+                deps.as_mut(),
+                mock_env(),
+                mock_info(AGENT1, &[]),
+                ExecuteMsg::WithdrawReward { amount: None },
+            )
+            .unwrap();
+        let reply_id = res.messages[1].id;
+        assert_eq!(
+            res.messages,
+            vec![
+                SubMsg::new(BankMsg::Send {
+                    to_address: AGENT_BENEFICIARY.to_string(),
+                    amount: vec![coin(100, NATIVE_DENOM), coin(50, "ujuno")],
+                }),
+                SubMsg::reply_always(
+                    WasmMsg::Execute {
+                        contract_addr: "swapper".to_string(),
+                        msg: to_binary(&DustSwapMsg::Swap {
+                            ask_denom: NATIVE_DENOM.to_string(),
+                            recipient: Addr::unchecked(AGENT_BENEFICIARY),
+                        })
+                        .unwrap(),
+                        funds: coins(3, "uosmo"),
+                    },
+                    reply_id,
+                ),
+            ]
+        );
+        let agent = store
+            .agents
+            .load(&deps.storage, Addr::unchecked(AGENT1))
+            .unwrap();
+        assert!(agent.balance.native.is_empty());
+
+        // When the swap fails the dust is paid out as it is
+        let res = store
+            .reply(
+                deps.as_mut(),
+                mock_env(),
+                Reply {
+                    id: reply_id,
+                    result: SubMsgResult::Err("no pool".to_string()),
+                },
+            )
+            .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: AGENT_BENEFICIARY.to_string(),
+                amount: coins(3, "uosmo"),
+            })]
+        );
+        assert_eq!(
+            store.dust_swaps.may_load(&deps.storage, reply_id).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn eject_agent_keeps_min_active_agents() {
+        let mut deps = mock_dependencies();
+        let store = CwCroncat::default();
+        mock_init(&store, deps.as_mut()).unwrap();
+        let env = mock_env();
+        for agent_id in [AGENT1, AGENT2] {
+            let agent = Agent {
+                payable_account_id: Addr::unchecked(AGENT_BENEFICIARY),
+                balance: GenericBalance {
+                    native: coins(10, NATIVE_DENOM),
+                    cw20: vec![],
+                },
+                total_tasks_executed: 0,
+                last_missed_slot: 0,
+                register_start: env.block.time,
+                last_executed_slot: 0,
+            };
+            store
+                .agents
+                .save(&mut deps.storage, Addr::unchecked(agent_id), &agent)
+                .unwrap();
+        }
+        store
+            .agent_active_queue
+            .save(
+                &mut deps.storage,
+                &vec![Addr::unchecked(AGENT1), Addr::unchecked(AGENT2)],
+            )
+            .unwrap();
+
+        // One of two agents can go, it gets its rewards on the way out
+        let messages = store
+            .eject_agent(&mut deps.storage, &env, Addr::unchecked(AGENT1))
+            .unwrap();
+        assert_eq!(
+            messages,
+            Some(vec![SubMsg::new(BankMsg::Send {
+                to_address: AGENT_BENEFICIARY.to_string(),
+                amount: coins(10, NATIVE_DENOM),
+            })])
+        );
+        assert!(store
+            .agents
+            .may_load(&deps.storage, Addr::unchecked(AGENT1))
+            .unwrap()
+            .is_none());
+
+        // The last one stays, even if it's stale
+        let messages = store
+            .eject_agent(&mut deps.storage, &env, Addr::unchecked(AGENT2))
+            .unwrap();
+        assert_eq!(messages, None);
+        assert_eq!(
+            store.agent_active_queue.load(&deps.storage).unwrap(),
+            vec![Addr::unchecked(AGENT2)]
+        );
+        assert!(store
+            .agents
+            .may_load(&deps.storage, Addr::unchecked(AGENT2))
+            .unwrap()
+            .is_some());
+    }
+
+    #[test]
+    fn eject_agent_to_pending() {
+        let mut deps = mock_dependencies();
+        let store = CwCroncat::default();
+        mock_init(&store, deps.as_mut()).unwrap();
+        store
+            .config
+            .update(&mut deps.storage, |mut c| -> StdResult<_> {
+                c.eject_to_pending = true;
+                Ok(c)
+            })
+            .unwrap();
+        let env = mock_env();
+        let agent = Agent {
+            payable_account_id: Addr::unchecked(AGENT_BENEFICIARY),
+            balance: GenericBalance {
+                native: coins(10, NATIVE_DENOM),
+                cw20: vec![],
+            },
+            total_tasks_executed: 3,
+            last_missed_slot: 0,
+            register_start: env.block.time,
+            last_executed_slot: 0,
+        };
+        for agent_id in [AGENT1, AGENT2, AGENT3] {
+            store
+                .agents
+                .save(&mut deps.storage, Addr::unchecked(agent_id), &agent)
+                .unwrap();
+        }
+        store
+            .agent_active_queue
+            .save(
+                &mut deps.storage,
+                &vec![Addr::unchecked(AGENT1), Addr::unchecked(AGENT2)],
+            )
+            .unwrap();
+        store
+            .agent_pending_queue
+            .save(&mut deps.storage, &vec![Addr::unchecked(AGENT3)])
+            .unwrap();
+
+        // Nothing is paid out, the agent just waits in line again
+        let messages = store
+            .eject_agent(&mut deps.storage, &env, Addr::unchecked(AGENT1))
+            .unwrap();
+        assert_eq!(messages, Some(vec![]));
+        assert_eq!(
+            store.agent_active_queue.load(&deps.storage).unwrap(),
+            vec![Addr::unchecked(AGENT2)]
+        );
+        assert_eq!(
+            store.agent_pending_queue.load(&deps.storage).unwrap(),
+            vec![Addr::unchecked(AGENT3), Addr::unchecked(AGENT1)]
+        );
+        assert_eq!(
+            store
+                .agents
+                .load(&deps.storage, Addr::unchecked(AGENT1))
+                .unwrap(),
+            agent
+        );
+    }
+
+    #[test]
+    fn eject_agent_slashes_locked_reward() {
+        let mut deps = mock_dependencies();
+        let store = CwCroncat::default();
+        mock_init(&store, deps.as_mut()).unwrap();
+        store
+            .config
+            .update(&mut deps.storage, |mut c| -> StdResult<_> {
+                c.reward_vesting_seconds = 100;
+                c.slash_destination = SlashDestination::Burn;
+                Ok(c)
+            })
+            .unwrap();
+        let agent = Agent {
+            payable_account_id: Addr::unchecked(AGENT_BENEFICIARY),
+            balance: GenericBalance {
+                native: coins(10, NATIVE_DENOM),
+                cw20: vec![],
+            },
+            total_tasks_executed: 3,
+            last_missed_slot: 0,
+            register_start: mock_env().block.time,
+            last_executed_slot: 0,
+        };
+        for agent_id in [AGENT1, AGENT2] {
+            store
+                .agents
+                .save(&mut deps.storage, Addr::unchecked(agent_id), &agent)
+                .unwrap();
+        }
+        store
+            .agent_active_queue
+            .save(
+                &mut deps.storage,
+                &vec![Addr::unchecked(AGENT1), Addr::unchecked(AGENT2)],
+            )
+            .unwrap();
+        store
+            .agent_reward_vesting
+            .save(
+                &mut deps.storage,
+                Addr::unchecked(AGENT1),
+                &RewardVesting {
+                    locked: Uint128::new(10),
+                    start: mock_env().block.time,
+                },
+            )
+            .unwrap();
+
+        // Halfway through the period, the vested half is paid and the locked half burned
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(50);
+        let messages = store
+            .eject_agent(&mut deps.storage, &env, Addr::unchecked(AGENT1))
+            .unwrap();
+        assert_eq!(
+            messages,
+            Some(vec![
+                SubMsg::new(BankMsg::Send {
+                    to_address: AGENT_BENEFICIARY.to_string(),
+                    amount: coins(5, NATIVE_DENOM),
+                }),
+                SubMsg::new(BankMsg::Burn {
+                    amount: coins(5, NATIVE_DENOM),
+                }),
+            ])
+        );
+    }
+    #[test]
+    fn query_agent_churn() {
+        let mut deps = mock_dependencies();
+        let mut store = CwCroncat::default();
+        mock_init(&store, deps.as_mut()).unwrap();
+        for agent in [AGENT0, AGENT1, AGENT2] {
+            deps.querier
+                .update_balance(agent, coins(1_000_000, NATIVE_DENOM));
+        }
+
+        let mut env = mock_env();
+        let mut call = |sender: &str, msg: ExecuteMsg, env: &Env| {
+            store
+                .execute(deps.as_mut(), env.clone(), mock_info(sender, &[]), msg)
+                .unwrap();
+        };
+        let register = || ExecuteMsg::RegisterAgent {
+            payable_account_id: None,
+        };
+        call(AGENT0, register(), &env);
+        call(AGENT1, register(), &env);
+        call(AGENT2, register(), &env);
+        call(AGENT2, ExecuteMsg::UnregisterAgent {}, &env);
+        env.block.time = env.block.time.plus_seconds(3_600);
+        call(AGENT2, register(), &env);
+
+        // The second active agent gets ejected, the first stays to keep the network running
+        store
+            .agent_pending_queue
+            .save(&mut deps.storage, &vec![Addr::unchecked(AGENT2)])
+            .unwrap();
+        store
+            .agent_active_queue
+            .save(
+                &mut deps.storage,
+                &vec![Addr::unchecked(AGENT0), Addr::unchecked(AGENT1)],
+            )
+            .unwrap();
+        assert!(store
+            .eject_agent(&mut deps.storage, &env, Addr::unchecked(AGENT1))
+            .unwrap()
+            .is_some());
+
+        let churn = |env: &Env| -> GetAgentChurnResponse {
+            let res = store
+                .query(deps.as_ref(), env.clone(), QueryMsg::GetAgentChurn {})
+                .unwrap();
+            from_binary(&res).unwrap()
+        };
+        assert_eq!(
+            churn(&env),
+            GetAgentChurnResponse {
+                window_seconds: 86_400,
+                registered: 4,
+                unregistered: 1,
+                ejected: 1,
+            }
+        );
+
+        // The first hour falls out of the window
+        env.block.time = env.block.time.plus_seconds(23 * 3_600);
+        assert_eq!(
+            churn(&env),
+            GetAgentChurnResponse {
+                window_seconds: 86_400,
+                registered: 1,
+                unregistered: 0,
+                ejected: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn query_get_agents() {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+
+        register_agent_exec(&mut app, &contract_addr, AGENT1, &AGENT_BENEFICIARY);
+        register_agent_exec(&mut app, &contract_addr, AGENT2, &AGENT_BENEFICIARY);
+        register_agent_exec(&mut app, &contract_addr, AGENT3, &AGENT_BENEFICIARY);
+
+        let get_agents = |from_index: Option<u64>, limit: Option<u64>| -> Vec<AgentRecord> {
+            app.wrap()
+                .query_wasm_smart(&contract_addr, &QueryMsg::GetAgents { from_index, limit })
+                .unwrap()
+        };
+
+        // The batch matches what each agent looks like on its own
+        let agents = get_agents(None, None);
+        let ids: Vec<Addr> = agents.iter().map(|a| a.account_id.clone()).collect();
+        assert_eq!(
+            ids,
+            vec![
+                Addr::unchecked(AGENT1),
+                Addr::unchecked(AGENT2),
+                Addr::unchecked(AGENT3)
+            ]
+        );
+        for record in agents.iter() {
+            let agent: Option<AgentResponse> = app
+                .wrap()
+                .query_wasm_smart(
+                    &contract_addr,
+                    &QueryMsg::GetAgent {
+                        account_id: record.account_id.clone(),
+                    },
+                )
+                .unwrap();
+            assert_eq!(agent, Some(record.agent.clone()));
+        }
+        assert_eq!(agents[0].agent.status, AgentStatus::Active);
+        assert_ne!(agents[1].agent.status, AgentStatus::Active);
+
+        // Pages run across the active and pending queues
+        assert_eq!(get_agents(Some(1), Some(1)), vec![agents[1].clone()]);
+        assert_eq!(get_agents(Some(2), None), vec![agents[2].clone()]);
+        assert!(get_agents(Some(3), None).is_empty());
+    }
+
+    #[test]
+    fn query_get_agent_status() {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+
+        register_agent_exec(&mut app, &contract_addr, AGENT1, &AGENT_BENEFICIARY);
+        register_agent_exec(&mut app, &contract_addr, AGENT2, &AGENT_BENEFICIARY);
+
+        let get_status = |account_id: &str| -> StdResult<AgentStatus> {
+            app.wrap().query_wasm_smart(
+                &contract_addr,
+                &QueryMsg::GetAgentStatus {
+                    account_id: Addr::unchecked(account_id),
+                },
+            )
+        };
+
+        assert_eq!(get_status(AGENT1).unwrap(), AgentStatus::Active);
+        assert_eq!(get_status(AGENT2).unwrap(), AgentStatus::Pending);
+
+        // Unregistered accounts get an error instead of a status
+        let err = get_status(AGENT3).unwrap_err();
+        assert!(err.to_string().contains("Agent not registered"));
+    }
+
+    #[test]
+    fn eject_stale_agents() {
+        let mut deps = mock_dependencies();
+        let store = CwCroncat::default();
+        mock_init(&store, deps.as_mut()).unwrap();
+        let env = mock_env();
+        // AGENT1 executed right at the threshold, the others are past it
+        for (agent_id, last_executed_slot) in [
+            (AGENT1, env.block.height - 600),
+            (AGENT2, env.block.height - 601),
+            (AGENT3, 0),
+        ] {
+            let agent = Agent {
+                payable_account_id: Addr::unchecked(AGENT_BENEFICIARY),
+                balance: GenericBalance {
+                    native: coins(10, NATIVE_DENOM),
+                    cw20: vec![],
+                },
+                total_tasks_executed: 0,
+                last_missed_slot: 0,
+                register_start: env.block.time,
+                last_executed_slot,
+            };
+            store
+                .agents
+                .save(&mut deps.storage, Addr::unchecked(agent_id), &agent)
+                .unwrap();
+        }
+        store
+            .agent_active_queue
+            .save(
+                &mut deps.storage,
+                &vec![
+                    Addr::unchecked(AGENT1),
+                    Addr::unchecked(AGENT2),
+                    Addr::unchecked(AGENT3),
+                ],
+            )
+            .unwrap();
+
+        let err = store
+            .eject_stale_agents(
+                deps.as_mut(),
+                mock_info(PARTICIPANT0, &coins(1, NATIVE_DENOM)),
+                env.clone(),
+            )
+            .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::CustomError {
+                val: "Do not attach funds".to_string()
+            }
+        );
+
+        let res = store
+            .eject_stale_agents(deps.as_mut(), mock_info(PARTICIPANT0, &[]), env.clone())
+            .unwrap();
+        let ejected: Vec<String> = res
+            .attributes
+            .iter()
+            .filter(|a| a.key == "ejected_agent")
+            .map(|a| a.value.clone())
+            .collect();
+        assert_eq!(ejected, vec![AGENT2.to_string(), AGENT3.to_string()]);
+        assert_eq!(res.messages.len(), 2);
+        assert_eq!(
+            store.agent_active_queue.load(&deps.storage).unwrap(),
+            vec![Addr::unchecked(AGENT1)]
+        );
+        assert!(store
+            .agents
+            .may_load(&deps.storage, Addr::unchecked(AGENT2))
+            .unwrap()
+            .is_none());
+
+        // The last active agent isn't ejected, however stale
+        let mut env = env;
+        env.block.height += 1;
+        let res = store
+            .eject_stale_agents(deps.as_mut(), mock_info(PARTICIPANT0, &[]), env)
+            .unwrap();
+        assert!(res.attributes.iter().all(|a| a.key != "ejected_agent"));
+        assert_eq!(
+            store.agent_active_queue.load(&deps.storage).unwrap(),
+            vec![Addr::unchecked(AGENT1)]
+        );
+    }
+
+    #[test]
+    fn query_agent_tasks_reports_slots() {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+        let block_info = app.block_info();
+
+        register_agent_exec(&mut app, &contract_addr, AGENT1, &AGENT_BENEFICIARY);
+        // Two block tasks share a slot, one cron task gets its own
+        add_block_task_exec(&mut app, &contract_addr, PARTICIPANT0, 6);
+        add_block_task_exec(&mut app, &contract_addr, PARTICIPANT1, 6);
+        add_cron_task_exec(&mut app, &contract_addr, PARTICIPANT2, 6);
+
+        let slot_ids: GetSlotIdsResponse = app
+            .wrap()
+            .query_wasm_smart(&contract_addr, &QueryMsg::GetSlotIds {})
+            .unwrap();
+        assert_eq!(slot_ids.block_ids.len(), 1);
+        assert_eq!(slot_ids.time_ids.len(), 1);
+
+        // Nothing is due yet
+        let res: Option<AgentTaskResponse> = app
+            .wrap()
+            .query_wasm_smart(
+                &contract_addr,
+                &QueryMsg::GetAgentTasks {
+                    account_id: Addr::unchecked(AGENT1),
+                },
+            )
+            .unwrap();
+        assert_eq!(res, None);
+
+        app.update_block(|block| {
+            block.time = block.time.plus_seconds(6 * 666);
+            block.height = block_info.height + 666;
+        });
+        let res: Option<AgentTaskResponse> = app
+            .wrap()
+            .query_wasm_smart(
+                &contract_addr,
+                &QueryMsg::GetAgentTasks {
+                    account_id: Addr::unchecked(AGENT1),
+                },
+            )
+            .unwrap();
+        let res = res.unwrap();
+        assert_eq!(res.num_block_tasks, Uint64::new(2));
+        assert_eq!(res.num_cron_tasks, Uint64::new(1));
+        assert_eq!(res.block_slot, Some(slot_ids.block_ids[0]));
+        assert_eq!(res.cron_slot, Some(slot_ids.time_ids[0]));
+    }
+
+    #[test]
+    fn agents_to_let_in_without_min_tasks() {
+        let store = CwCroncat::default();
+        assert_eq!(store.agents_to_let_in(&0, &1, &10), u64::MAX);
+        assert_eq!(store.agents_to_let_in(&0, &1, &0), 0);
+        assert_eq!(store.agents_to_let_in(&3, &1, &10), 3);
+    }
+
+    #[test]
+    fn agent_allowlist() {
+        let mut deps = mock_dependencies();
+        let mut store = CwCroncat::default();
+        mock_init(&store, deps.as_mut()).unwrap();
+        for agent in [AGENT0, AGENT1] {
+            deps.querier
+                .update_balance(agent, coins(1_000_000, NATIVE_DENOM));
+        }
+        let register = || ExecuteMsg::RegisterAgent {
+            payable_account_id: None,
+        };
+        let mut call = |sender: &str, msg: ExecuteMsg| {
+            store.execute(deps.as_mut(), mock_env(), mock_info(sender, &[]), msg)
+        };
+
+        // Disabled by default, registration is open
+        call(AGENT0, register()).unwrap();
+
+        // Only the owner manages the allowlist
+        let allow = ExecuteMsg::AddAgentToAllowlist {
+            agent_id: Addr::unchecked(AGENT1),
+        };
+        let err = call(AGENT0, allow.clone()).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+        call("creator", allow).unwrap();
+        call(
+            "creator",
+            ExecuteMsg::AddAgentToAllowlist {
+                agent_id: Addr::unchecked(AGENT2),
+            },
+        )
+        .unwrap();
+        call(
+            "creator",
+            ExecuteMsg::RemoveAgentFromAllowlist {
+                agent_id: Addr::unchecked(AGENT2),
+            },
+        )
+        .unwrap();
+
+        store
+            .config
+            .update(&mut deps.storage, |mut c| -> StdResult<_> {
+                c.agent_allowlist_enabled = true;
+                Ok(c)
+            })
+            .unwrap();
+        assert_eq!(
+            store.agent_allowlist.load(&deps.storage).unwrap(),
+            vec![Addr::unchecked(AGENT1)]
+        );
+        let mut call = |sender: &str, msg: ExecuteMsg| {
+            store.execute(deps.as_mut(), mock_env(), mock_info(sender, &[]), msg)
+        };
+        let err = call(AGENT2, register()).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+        call(AGENT1, register()).unwrap();
+    }
 }
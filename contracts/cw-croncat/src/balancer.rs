@@ -6,6 +6,7 @@ use cosmwasm_std::{DepsMut, Uint64};
 use cw_croncat_core::msg::AgentTaskResponse;
 use cw_croncat_core::types::SlotType;
 use cw_storage_plus::Item;
+use sha2::{Digest, Sha256};
 
 #[derive(PartialEq, Eq)]
 pub enum BalancerMode {
@@ -53,11 +54,43 @@ impl RoundRobinBalancer {
     //     }
     // }
 }
+/// Deterministic rotation for the active queue, derived from the current block.
+/// Every node computes the same offset, but agents can't know it ahead of the block.
+pub(crate) fn selection_offset(env: &Env, agent_count: u64) -> u64 {
+    if agent_count == 0 {
+        return 0;
+    }
+    let mut hasher = Sha256::new();
+    hasher.update(env.block.height.to_be_bytes());
+    hasher.update(env.block.time.nanos().to_be_bytes());
+    let hash = hasher.finalize();
+    let mut seed = [0u8; 8];
+    seed.copy_from_slice(&hash[..8]);
+    u64::from_be_bytes(seed) % agent_count
+}
+
+/// Position of the agent in the active queue, rotated by the block-seeded offset
+/// while randomized selection is on. Returns None if the agent isn't active
+pub(crate) fn agent_selection_index(
+    c: &Config,
+    env: &Env,
+    active: &[Addr],
+    agent_id: &Addr,
+) -> Option<usize> {
+    let index = active.iter().position(|a| a == agent_id)?;
+    if !c.randomized_agent_selection {
+        return Some(index);
+    }
+    let agent_count = active.len() as u64;
+    let offset = selection_offset(env, agent_count);
+    Some(((index as u64 + agent_count - offset) % agent_count) as usize)
+}
+
 impl<'a> Balancer<'a> for RoundRobinBalancer {
     fn get_agent_tasks(
         &mut self,
         deps: DepsMut,
-        _env: Env,
+        env: Env,
         config: &Item<'a, Config>,
         active_agents: &Item<'a, Vec<Addr>>,
         agent_id: Addr,
@@ -73,9 +106,7 @@ impl<'a> Balancer<'a> for RoundRobinBalancer {
         let agent_count = active.len() as u64;
         let agent_active_indices_config = conf.agent_active_indices;
         let agent_active_indices: Vec<usize> = (0..active.len()).collect();
-        let agent_index = active
-            .iter()
-            .position(|x| x == &agent_id)
+        let agent_index = agent_selection_index(&conf, &env, &active, &agent_id)
             .expect("Agent not active or not registered!") as u64;
 
         if slot_items == (None, None) {
@@ -148,6 +179,9 @@ impl<'a> Balancer<'a> for RoundRobinBalancer {
                     num_block_tasks_extra,
                     num_cron_tasks,
                     num_cron_tasks_extra,
+                    // Slot ids aren't known here, only the task totals
+                    block_slot: None,
+                    cron_slot: None,
                 }))
             }
             BalancerMode::Equalizer => todo!(),
@@ -165,9 +199,9 @@ mod tests {
         mock_dependencies_with_balance, mock_env, mock_info, MockStorage, MOCK_CONTRACT_ADDR,
     };
     use cosmwasm_std::{
-        coin, coins, from_slice, Addr, BlockInfo, Coin, CosmosMsg, Empty, StakingMsg,
+        coin, coins, from_slice, Addr, BlockInfo, Coin, CosmosMsg, Empty, StakingMsg, Uint128,
     };
-    use cw_croncat_core::types::{Agent, SlotType, Task};
+    use cw_croncat_core::types::{Agent, SlashDestination, SlotType, Task};
 
     use cw_croncat_core::msg::{ExecuteMsg, InstantiateMsg, QueryMsg, TaskRequest, TaskResponse};
     use cw_croncat_core::types::{Action, Boundary, Interval};
@@ -186,17 +220,42 @@ mod tests {
         Config {
             paused: false,
             owner_id: Addr::unchecked(ADMIN),
-            // treasury_id: None,
+            treasury_id: None,
             min_tasks_per_agent: 3,
             agent_active_indices: Vec::<(SlotType, u32, u32)>::with_capacity(0),
             agents_eject_threshold: 600, // how many slots an agent can miss before being ejected. 10 * 60 = 1hr
             available_balance: GenericBalance::default(),
             staked_balance: GenericBalance::default(),
+            protocol_fees_collected: GenericBalance::default(),
             agent_fee: Coin::new(5, NATIVE_DENOM.clone()), // TODO: CHANGE AMOUNT HERE!!! 0.0005 Juno (2000 tasks = 1 Juno)
             gas_price: 1,
             gas_base_fee: GAS_BASE_FEE_JUNO,
             proxy_callback_gas: 3,
             slot_granularity: 60_000_000_000,
+            dead_task_bounty: Coin::new(5, NATIVE_DENOM),
+            max_rules_per_task: 10,
+            slash_destination: SlashDestination::Task,
+            low_balance_pause_threshold: Uint128::zero(),
+            min_active_agents_for_execution: 1,
+            max_submsgs_per_proxy_call: 20,
+            protocol_fee: Coin::new(0, NATIVE_DENOM),
+            task_creation_fee: None,
+            nomination_paused: false,
+            reward_vesting_seconds: 0,
+            task_creator_whitelist: None,
+            max_future_slot_seconds: 315_360_000,
+            randomized_agent_selection: false,
+            max_tasks_per_owner: None,
+            dust_swap_contract: None,
+            dust_threshold: Uint128::zero(),
+            min_active_agents: 1,
+            min_agent_version: None,
+            removal_grace_slots: 0,
+            eject_to_pending: false,
+            max_task_limit: 100_000,
+            agent_allowlist_enabled: false,
+            max_task_gas: 10_000_000,
+            min_task_balance: Coin::new(0, NATIVE_DENOM),
             native_denom: NATIVE_DENOM.to_owned(),
             cw20_whitelist: vec![],
             agent_nomination_duration: 9,
@@ -361,6 +420,67 @@ mod tests {
         assert_eq!(result.num_cron_tasks_extra.u64(), 0);
     }
 
+    #[test]
+    fn test_randomized_selection_is_block_seeded() {
+        let store = CwCroncat::default();
+        let mut deps = mock_dependencies_with_balance(&coins(200, NATIVE_DENOM));
+        let mut balancer = RoundRobinBalancer::default();
+        let mut config = mock_config();
+        config.randomized_agent_selection = true;
+        store.config.save(&mut deps.storage, &config).unwrap();
+
+        let agents: Vec<Addr> = vec![AGENT0, AGENT1, AGENT2, AGENT3, AGENT4]
+            .into_iter()
+            .map(Addr::unchecked)
+            .collect();
+        store
+            .agent_active_queue
+            .save(&mut deps.storage, &agents)
+            .unwrap();
+
+        // A single task goes to exactly one agent, find out which one
+        let mut selected_agent = |env: &Env| -> Addr {
+            let selected: Vec<Addr> = agents
+                .iter()
+                .filter(|agent| {
+                    let result = balancer
+                        .get_agent_tasks(
+                            deps.as_mut(),
+                            env.clone(),
+                            &store.config,
+                            &store.agent_active_queue,
+                            (*agent).clone(),
+                            (Some(1), None),
+                        )
+                        .unwrap()
+                        .unwrap();
+                    result.num_block_tasks.u64() == 1
+                })
+                .cloned()
+                .collect();
+            assert_eq!(selected.len(), 1);
+            selected[0].clone()
+        };
+
+        // Same block, same agent
+        let env = mock_env();
+        let first = selected_agent(&env);
+        assert_eq!(selected_agent(&env), first);
+
+        // Selection moves around across blocks
+        let mut seen = vec![first];
+        for i in 1..20 {
+            let mut env = mock_env();
+            env.block.height += i;
+            env.block.time = env.block.time.plus_seconds(i * 6);
+            let agent = selected_agent(&env);
+            if !seen.contains(&agent) {
+                seen.push(agent);
+            }
+        }
+        assert!(seen.len() > 1);
+    }
+
     fn test_rebalance_agent_removal() {}
     fn test_rebalance_agent_gets_extra() {}
 }
@@ -3,12 +3,13 @@ use crate::helpers::GenericBalance;
 use crate::state::{Config, CwCroncat};
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::{
-    to_binary, Binary, Coin, Deps, DepsMut, Env, MessageInfo, Reply, Response, StdResult,
+    to_binary, Addr, BankMsg, Binary, Coin, Deps, DepsMut, Env, MessageInfo, Reply, Response,
+    StdResult, SubMsgResult, Uint128,
 };
 use cw2::set_contract_version;
 use cw20::Balance;
 use cw_croncat_core::msg::{ExecuteMsg, InstantiateMsg, QueryMsg};
-use cw_croncat_core::types::SlotType;
+use cw_croncat_core::types::{SlashDestination, SlotType};
 
 // version info for migration info
 const CONTRACT_NAME: &str = "crates.io:cw-croncat";
@@ -17,6 +18,10 @@ const DEFAULT_NOMINATION_DURATION: u16 = 360;
 
 // default for juno
 pub(crate) const GAS_BASE_FEE_JUNO: u64 = 400_000;
+// tasks can't be scheduled further out than this, about 10 years
+const DEFAULT_MAX_FUTURE_SLOT_SECONDS: u64 = 10 * 365 * 24 * 60 * 60;
+const DEFAULT_MAX_TASK_LIMIT: u64 = 100_000;
+const DEFAULT_MAX_TASK_GAS: u64 = 10_000_000;
 
 // #[cfg(not(feature = "library"))]
 impl<'a> CwCroncat<'a> {
@@ -49,17 +54,42 @@ impl<'a> CwCroncat<'a> {
         let config = Config {
             paused: false,
             owner_id: owner_acct,
-            // treasury_id: None,
+            treasury_id: None,
             min_tasks_per_agent: 3,
             agent_active_indices: vec![(SlotType::Block, 0, 0), (SlotType::Cron, 0, 0)],
             agents_eject_threshold: 600, // how many slots an agent can miss before being ejected. 10 * 60 = 1hr
             available_balance,
             staked_balance: GenericBalance::default(),
+            protocol_fees_collected: GenericBalance::default(),
             agent_fee: Coin::new(5, msg.denom.clone()), // TODO: CHANGE AMOUNT HERE!!! 0.0005 Juno (2000 tasks = 1 Juno)
             gas_price: 1,
             proxy_callback_gas: 3,
             gas_base_fee,
             slot_granularity: 60_000_000_000,
+            dead_task_bounty: Coin::new(5, msg.denom.clone()),
+            max_rules_per_task: 10,
+            slash_destination: SlashDestination::Task,
+            low_balance_pause_threshold: Uint128::zero(),
+            min_active_agents_for_execution: 1,
+            max_submsgs_per_proxy_call: 20,
+            protocol_fee: Coin::new(0, msg.denom.clone()),
+            task_creation_fee: None,
+            nomination_paused: false,
+            reward_vesting_seconds: 0,
+            task_creator_whitelist: None,
+            max_future_slot_seconds: DEFAULT_MAX_FUTURE_SLOT_SECONDS,
+            randomized_agent_selection: false,
+            max_tasks_per_owner: None,
+            dust_swap_contract: None,
+            dust_threshold: Uint128::zero(),
+            min_active_agents: 1,
+            min_agent_version: None,
+            removal_grace_slots: 0,
+            eject_to_pending: false,
+            max_task_limit: DEFAULT_MAX_TASK_LIMIT,
+            agent_allowlist_enabled: false,
+            max_task_gas: DEFAULT_MAX_TASK_GAS,
+            min_task_balance: Coin::new(0, msg.denom.clone()),
             native_denom: msg.denom,
             cw20_whitelist: vec![],
             // TODO: ????
@@ -83,13 +113,13 @@ impl<'a> CwCroncat<'a> {
             .add_attribute("method", "instantiate")
             .add_attribute("paused", config.paused.to_string())
             .add_attribute("owner_id", config.owner_id.to_string())
-            // .add_attribute(
-            //     "treasury_id",
-            //     config
-            //         .treasury_id
-            //         .unwrap_or_else(|| Addr::unchecked(""))
-            //         .to_string(),
-            // )
+            .add_attribute(
+                "treasury_id",
+                config
+                    .treasury_id
+                    .unwrap_or_else(|| Addr::unchecked(""))
+                    .to_string(),
+            )
             .add_attribute(
                 "min_tasks_per_agent",
                 config.min_tasks_per_agent.to_string(),
@@ -126,6 +156,22 @@ impl<'a> CwCroncat<'a> {
                 balances,
                 account_id,
             } => self.move_balances(deps, info, env, balances, account_id),
+            ExecuteMsg::StakeBalances { balances } => self.stake_balances(deps, info, balances),
+            ExecuteMsg::UnstakeBalances { balances } => self.unstake_balances(deps, info, balances),
+
+            ExecuteMsg::ProposeNewOwner { addr } => self.propose_new_owner(deps, info, addr),
+            ExecuteMsg::AcceptOwnership {} => self.accept_ownership(deps, info),
+            ExecuteMsg::CancelOwnershipProposal {} => self.cancel_ownership_proposal(deps, info),
+            ExecuteMsg::AddTaskCreators { addrs } => self.add_task_creators(deps, info, addrs),
+            ExecuteMsg::RemoveTaskCreators { addrs } => {
+                self.remove_task_creators(deps, info, addrs)
+            }
+            ExecuteMsg::AddAgentToAllowlist { agent_id } => {
+                self.add_agent_to_allowlist(deps, info, agent_id)
+            }
+            ExecuteMsg::RemoveAgentFromAllowlist { agent_id } => {
+                self.remove_agent_from_allowlist(deps, info, agent_id)
+            }
 
             ExecuteMsg::RegisterAgent { payable_account_id } => {
                 self.register_agent(deps, info, env, payable_account_id)
@@ -133,47 +179,141 @@ impl<'a> CwCroncat<'a> {
             ExecuteMsg::UpdateAgent { payable_account_id } => {
                 self.update_agent(deps, info, env, payable_account_id)
             }
+            ExecuteMsg::SetAgentRelayer { relayer } => self.set_agent_relayer(deps, info, relayer),
             ExecuteMsg::UnregisterAgent {} => self.unregister_agent(deps, info, env),
-            ExecuteMsg::WithdrawReward {} => self.withdraw_agent_balance(deps, info, env),
+            ExecuteMsg::WithdrawReward { amount } => {
+                self.withdraw_agent_balance(deps, info, env, amount)
+            }
             ExecuteMsg::CheckInAgent {} => self.accept_nomination_agent(deps, info, env),
+            ExecuteMsg::EjectStaleAgents {} => self.eject_stale_agents(deps, info, env),
 
             ExecuteMsg::CreateTask { task } => self.create_task(deps, info, env, task),
-            ExecuteMsg::RemoveTask { task_hash } => self.remove_task(deps, task_hash),
-            ExecuteMsg::RefillTaskBalance { task_hash } => self.refill_task(deps, info, task_hash),
-            ExecuteMsg::ProxyCall {} => self.proxy_call(deps, info, env),
+            ExecuteMsg::RemoveTask {
+                task_hash,
+                refund_to,
+            } => self.remove_task_with_refund(deps, info, env, task_hash, refund_to),
+            ExecuteMsg::RefillTaskBalance { task_hash } => {
+                self.refill_task(deps, info, env, task_hash)
+            }
+            ExecuteMsg::Receive(msg) => self.receive_cw20(deps, info, msg),
+            ExecuteMsg::UpdateTask {
+                task_hash,
+                actions,
+                rules,
+            } => self.update_task(deps, info, env, task_hash, actions, rules),
+            ExecuteMsg::ProxyCall {
+                reward_to,
+                agent_version,
+                task_hash,
+            } => self.proxy_call(deps, info, env, reward_to, agent_version, task_hash),
+            ExecuteMsg::ReapDeadTasks { limit } => self.reap_dead_tasks(deps, info, env, limit),
         }
     }
 
     pub fn query(&mut self, deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
         match msg {
             QueryMsg::GetConfig {} => to_binary(&self.query_config(deps)?),
+            QueryMsg::GetPaused {} => to_binary(&self.query_paused(deps)?),
             QueryMsg::GetBalances {} => to_binary(&self.query_balances(deps)?),
+            QueryMsg::GetBalanceReconciliation {} => {
+                to_binary(&self.query_balance_reconciliation(deps, env)?)
+            }
 
             QueryMsg::GetAgent { account_id } => {
                 to_binary(&self.query_get_agent(deps, env, account_id)?)
             }
+            QueryMsg::GetAgentStatus { account_id } => {
+                to_binary(&self.query_get_agent_status(deps, env, account_id)?)
+            }
             QueryMsg::GetAgentIds {} => to_binary(&self.query_get_agent_ids(deps)?),
+            QueryMsg::GetAgents { from_index, limit } => {
+                to_binary(&self.query_get_agents(deps, env, from_index, limit)?)
+            }
             QueryMsg::GetAgentTasks { account_id } => {
                 to_binary(&self.query_get_agent_tasks(deps, env, account_id)?)
             }
-
-            QueryMsg::GetTasks { from_index, limit } => {
-                to_binary(&self.query_get_tasks(deps, from_index, limit)?)
+            QueryMsg::GetAgentNextSlot { account_id } => {
+                to_binary(&self.query_get_agent_next_slot(deps, env, account_id)?)
+            }
+            QueryMsg::GetAgentProjectedEarnings { account_id } => {
+                to_binary(&self.query_get_agent_projected_earnings(deps, account_id)?)
             }
-            QueryMsg::GetTasksByOwner { owner_id } => {
-                to_binary(&self.query_get_tasks_by_owner(deps, owner_id)?)
+            QueryMsg::GetAgentChurn {} => to_binary(&self.query_get_agent_churn(deps, env)?),
+            QueryMsg::GetIneligibleTasks { agent_id, limit } => {
+                to_binary(&self.query_get_ineligible_tasks(deps, env, agent_id, limit)?)
+            }
+
+            QueryMsg::GetTasks {
+                from_index,
+                limit,
+                include_status,
+            } => to_binary(&self.query_get_tasks(deps, env, from_index, limit, include_status)?),
+            QueryMsg::GetExpiringTasks {
+                within_seconds,
+                limit,
+            } => to_binary(&self.query_get_expiring_tasks(deps, env, within_seconds, limit)?),
+            QueryMsg::GetUnderfundedTasks { limit } => {
+                to_binary(&self.query_get_underfunded_tasks(deps, limit)?)
             }
+            QueryMsg::GetTasksCreatedBetween {
+                start,
+                end,
+                from_index,
+                limit,
+            } => to_binary(
+                &self.query_get_tasks_created_between(deps, start, end, from_index, limit)?,
+            ),
+            QueryMsg::GetTasksByOwner {
+                owner_id,
+                from_index,
+                limit,
+            } => to_binary(&self.query_get_tasks_by_owner(deps, owner_id, from_index, limit)?),
             QueryMsg::GetTask { task_hash } => to_binary(&self.query_get_task(deps, task_hash)?),
+            QueryMsg::GetOwnerTaskByIndex { owner_id, index } => {
+                to_binary(&self.query_get_owner_task_by_index(deps, owner_id, index)?)
+            }
             QueryMsg::GetTaskHash { task } => to_binary(&self.query_get_task_hash(*task)?),
-            QueryMsg::ValidateInterval { interval } => {
-                to_binary(&self.query_validate_interval(interval)?)
+            QueryMsg::GetTaskFundingHistory { task_hash } => {
+                to_binary(&self.query_get_task_funding_history(deps, task_hash)?)
+            }
+            QueryMsg::GetExecutionCostBreakdown { task_hash } => {
+                to_binary(&self.query_get_execution_cost_breakdown(deps, task_hash)?)
+            }
+            QueryMsg::CheckTaskFunding { task, deposit } => {
+                to_binary(&self.query_check_task_funding(deps, env, task, deposit)?)
+            }
+            QueryMsg::SimulateCreateTask { owner_id, task } => {
+                to_binary(&self.query_simulate_create_task(deps, env, owner_id, task)?)
+            }
+            QueryMsg::CheckTaskRules { task_hash } => {
+                to_binary(&self.query_check_task_rules(deps, task_hash)?)
+            }
+            QueryMsg::ValidateInterval { interval, boundary } => {
+                to_binary(&self.query_validate_interval(interval, boundary)?)
+            }
+            QueryMsg::GetSlotHashes { slot, limit } => {
+                to_binary(&self.query_slot_tasks(deps, slot, limit)?)
             }
-            QueryMsg::GetSlotHashes { slot } => to_binary(&self.query_slot_tasks(deps, slot)?),
             QueryMsg::GetSlotIds {} => to_binary(&self.query_slot_ids(deps)?),
+            QueryMsg::GetSlotCounts { from_index, limit } => {
+                to_binary(&self.query_slot_counts(deps, from_index, limit)?)
+            }
+            QueryMsg::GetOldestOverdueSlot {} => {
+                to_binary(&self.query_oldest_overdue_slot(deps, env)?)
+            }
+            QueryMsg::GetTaskTypeBreakdown {} => to_binary(&self.query_task_type_breakdown(deps)?),
+            QueryMsg::GetTaskCount {} => to_binary(&self.query_task_count(deps)?),
+            QueryMsg::GetNextExecution {} => to_binary(&self.query_next_execution(deps, env)?),
+            QueryMsg::GetNextSlotRewards {} => to_binary(&self.query_next_slot_rewards(deps, env)?),
         }
     }
 
-    pub fn reply(&self, deps: DepsMut, env: Env, msg: Reply) -> Result<Response, ContractError> {
+    pub fn reply(
+        &self,
+        mut deps: DepsMut,
+        env: Env,
+        msg: Reply,
+    ) -> Result<Response, ContractError> {
         // Route the next fns with the reply queue id meta
         let queue_item = self.reply_queue.may_load(deps.storage, msg.id)?;
 
@@ -185,10 +325,33 @@ impl<'a> CwCroncat<'a> {
         // Clean up the reply queue
         self.rq_remove(deps.storage, msg.id);
 
+        // A failed dust swap pays the dust out as-is
+        if let Some((recipient, dust)) = self.dust_swaps.may_load(deps.storage, msg.id)? {
+            self.dust_swaps.remove(deps.storage, msg.id);
+            let res = Response::new().add_attribute("method", "dust_swap_reply");
+            return Ok(match msg.result {
+                SubMsgResult::Ok(_) => res.add_attribute("dust_swap", "swapped"),
+                SubMsgResult::Err(err) => res
+                    .add_attribute("dust_swap", "failed")
+                    .add_attribute("error", err)
+                    .add_message(BankMsg::Send {
+                        to_address: recipient.to_string(),
+                        amount: dust,
+                    }),
+            });
+        }
+
         // If contract_addr matches THIS contract, it is the proxy callback
         // proxy_callback is also responsible for handling reply modes: "handle_failure", "handle_success"
         if item.contract_addr.is_some() && item.contract_addr.unwrap() == env.contract.address {
-            return self.proxy_callback(deps, env, msg, item.task_hash.unwrap());
+            let task_hash = item.task_hash.unwrap();
+            // Dispatch the next action of the task, proxy_callback runs once all are done
+            if let Some(res) =
+                self.proxy_next_action(deps.branch(), &env, &msg, &task_hash, item.action_idx)?
+            {
+                return Ok(res);
+            }
+            return self.proxy_callback(deps, env, msg, task_hash);
         }
 
         // NOTE: Currently only handling proxy callbacks
@@ -237,7 +400,7 @@ mod tests {
         let value: GetConfigResponse = from_binary(&res).unwrap();
         assert_eq!(false, value.paused);
         assert_eq!(info.sender, value.owner_id);
-        // assert_eq!(None, value.treasury_id);
+        assert_eq!(None, value.treasury_id);
         assert_eq!(3, value.min_tasks_per_agent);
         assert_eq!(
             vec![(SlotType::Block, 0, 0), (SlotType::Cron, 0, 0)],
@@ -249,6 +412,8 @@ mod tests {
         assert_eq!(1, value.gas_price);
         assert_eq!(3, value.proxy_callback_gas);
         assert_eq!(60_000_000_000, value.slot_granularity);
+        assert_eq!(coin(5, "atom"), value.dead_task_bounty);
+        assert_eq!(10, value.max_rules_per_task);
     }
 
     #[test]
@@ -283,6 +448,7 @@ mod tests {
                     prev_idx: None,
                     task_hash: Some(task_hash.clone()),
                     contract_addr: None,
+                    action_idx: None,
                 },
             )
             .unwrap();
@@ -315,6 +481,7 @@ mod tests {
                     prev_idx: None,
                     task_hash: Some(task_hash),
                     contract_addr: Some(Addr::unchecked(MOCK_CONTRACT_ADDR)),
+                    action_idx: None,
                 },
             )
             .unwrap();
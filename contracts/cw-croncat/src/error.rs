@@ -1,4 +1,4 @@
-use cosmwasm_std::StdError;
+use cosmwasm_std::{Coin, StdError};
 use thiserror::Error;
 
 #[derive(Error, Debug, PartialEq)]
@@ -33,6 +33,15 @@ pub enum ContractError {
     #[error("Can't attach deposit")]
     AttachedDeposit {},
 
+    #[error("Task limit reached, max allowed: {max}")]
+    TaskLimitReached { max: u64 },
+
+    #[error("Not enough funds")]
+    NotEnoughFunds {},
+
+    #[error("Task balance is below the minimum of {required}")]
+    InsufficientTaskBalance { required: Coin },
+
     #[error("Custom Error val: {val:?}")]
     CustomError { val: String },
     // Add any other custom errors you like here.
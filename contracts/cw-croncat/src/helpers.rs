@@ -64,6 +64,17 @@ pub(crate) fn has_cw_coins(coins: &[Cw20CoinVerified], required: &Cw20CoinVerifi
         .unwrap_or(false)
 }
 
+/// Parses an agent version like "1.2.3" into comparable parts
+pub(crate) fn parse_agent_version(version: &str) -> Option<(u64, u64, u64)> {
+    let mut parts = version.split('.').map(|part| part.parse::<u64>().ok());
+    match (parts.next(), parts.next(), parts.next(), parts.next()) {
+        (Some(Some(major)), Some(Some(minor)), Some(Some(patch)), None) => {
+            Some((major, minor, patch))
+        }
+        _ => None,
+    }
+}
+
 impl<'a> CwCroncat<'a> {
     pub fn get_agent_status(
         &self,
@@ -100,7 +111,7 @@ impl<'a> CwCroncat<'a> {
                 self.agents_to_let_in(&min_tasks_per_agent, &num_active_agents, &total_tasks);
             let agent_nomination_begin_time = self.agent_nomination_begin_time.load(storage)?;
             match agent_nomination_begin_time {
-                Some(begin_time) if num_agents_to_accept > 0 => {
+                Some(begin_time) if num_agents_to_accept > 0 && !c.nomination_paused => {
                     let time_difference = block_time - begin_time.seconds();
 
                     let max_index = cmp::max(
@@ -133,6 +144,10 @@ impl<'a> CwCroncat<'a> {
         num_active_agents: &u64,
         total_tasks: &u64,
     ) -> u64 {
+        // No minimum per agent means any pending agent is welcome while there are tasks
+        if *max_tasks == 0 {
+            return if *total_tasks > 0 { u64::MAX } else { 0 };
+        }
         let num_tasks_covered = num_active_agents * max_tasks;
         if total_tasks > &num_tasks_covered {
             // It's possible there are more "covered tasks" than total tasks,
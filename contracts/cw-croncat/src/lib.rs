@@ -5,6 +5,7 @@ mod error;
 pub mod helpers;
 pub mod manager;
 pub mod owner;
+pub mod rules;
 pub mod slots;
 pub mod state;
 pub mod tasks;
@@ -1,11 +1,20 @@
+use crate::balancer::agent_selection_index;
 use crate::error::ContractError;
-use crate::state::{Config, CwCroncat, QueueItem};
+use crate::helpers::{parse_agent_version, send_tokens, GenericBalance};
+use crate::rules::check_rule;
+use crate::state::{Config, CwCroncat, QueueItem, RewardVesting};
 use cosmwasm_std::{
-    Addr, DepsMut, Empty, Env, MessageInfo, Reply, Response, StdResult, Storage, SubMsg,
+    has_coins, Addr, Coin, DepsMut, Empty, Env, MessageInfo, Reply, Response, StdResult, Storage,
+    SubMsg, SubMsgResponse, SubMsgResult, Uint128,
 };
 use cw20::Balance;
 use cw_croncat_core::traits::Intervals;
-use cw_croncat_core::types::{Agent, SlotType};
+use cw_croncat_core::types::{ActionOrder, Agent, Interval, SlotType, Task};
+
+// Failing tasks get removed once they failed this many times in a row
+const MAX_FAILURE_STREAK: u32 = 5;
+// Ended tasks stop spawning successors past this generation
+const MAX_TASK_GENERATION: u64 = 10;
 
 impl<'a> CwCroncat<'a> {
     /// Executes a task based on the current task slot
@@ -17,6 +26,9 @@ impl<'a> CwCroncat<'a> {
         deps: DepsMut,
         info: MessageInfo,
         env: Env,
+        reward_to: Option<Addr>,
+        agent_version: Option<String>,
+        task_hash: Option<String>,
     ) -> Result<Response, ContractError> {
         if !info.funds.is_empty() {
             return Err(ContractError::CustomError {
@@ -29,6 +41,40 @@ impl<'a> CwCroncat<'a> {
                 val: "Contract paused".to_string(),
             });
         }
+        // Outdated agents might not understand the upgraded contract
+        if let Some(min_version) = &c.min_agent_version {
+            let up_to_date = agent_version
+                .as_deref()
+                .and_then(parse_agent_version)
+                .zip(parse_agent_version(min_version))
+                .map_or(false, |(version, min)| version >= min);
+            if !up_to_date {
+                return Err(ContractError::CustomError {
+                    val: format!(
+                        "Agent version {} is outdated, please upgrade to {} or newer",
+                        agent_version.as_deref().unwrap_or("unknown"),
+                        min_version
+                    ),
+                });
+            }
+        }
+
+        // A relayer executes as the agent it calls for, so the agent gets the rewards
+        let info = match reward_to {
+            Some(agent_id) => {
+                let relayer = self
+                    .agent_relayers
+                    .may_load(deps.storage, agent_id.clone())?;
+                if relayer.as_ref() != Some(&info.sender) {
+                    return Err(ContractError::Unauthorized {});
+                }
+                MessageInfo {
+                    sender: agent_id,
+                    funds: info.funds,
+                }
+            }
+            None => info,
+        };
 
         if c.available_balance.native.is_empty() {
             return Err(ContractError::CustomError {
@@ -46,45 +92,101 @@ impl<'a> CwCroncat<'a> {
         if !active_agents.contains(&info.sender) {
             return Err(ContractError::AgentNotRegistered {});
         }
-        let agent = agent_opt.unwrap();
+        // a lone agent could censor or front-run tasks, wait for enough of them
+        if (active_agents.len() as u64) < c.min_active_agents_for_execution {
+            return Err(ContractError::CustomError {
+                val: format!(
+                    "Not enough active agents to execute tasks: {} of {} required",
+                    active_agents.len(),
+                    c.min_active_agents_for_execution
+                ),
+            });
+        }
+        let mut agent = agent_opt.unwrap();
+        // With randomized selection each agent takes the task at its rotated position in the slot,
+        // so the task an agent lands changes from block to block
+        let selection_index = if c.randomized_agent_selection {
+            agent_selection_index(&c, &env, &active_agents, &info.sender)
+        } else {
+            None
+        };
 
         // get slot items, find the next task hash available
         // if empty slot found, let agent get paid for helping keep house clean
         let slot = self.get_current_slot_items(&env.block, deps.storage, Some(1));
         // Give preference for block-based slots
         let slot_id: u64;
+        let slot_kind: SlotType;
         let some_hash: Option<Vec<u8>>;
-        if slot.0.is_none() {
+        if let Some(task_hash) = task_hash {
+            // Agents can claim a task they know is due, instead of racing for the next one
+            let hash = task_hash.into_bytes();
+            if self.tasks.may_load(deps.storage, hash.clone())?.is_none() {
+                return Err(ContractError::NoTaskFound {});
+            }
+            if self.executing_tasks.has(deps.storage, hash.clone()) {
+                return Err(ContractError::CustomError {
+                    val: "Task is already executing".to_string(),
+                });
+            }
+            match self.take_due_slot_item(deps.storage, &env.block, &hash)? {
+                Some((id, kind)) => {
+                    slot_id = id;
+                    slot_kind = kind;
+                    some_hash = Some(hash);
+                }
+                None => {
+                    return Err(ContractError::CustomError {
+                        val: "Task is not due yet".to_string(),
+                    });
+                }
+            }
+        } else if slot.0.is_none() {
             // See if there are cron (time-based) tasks to execute
             if slot.1.is_none() {
-                self.send_base_agent_reward(deps.storage, agent, info);
+                self.send_base_agent_reward(deps.storage, &env, agent, info);
                 return Err(ContractError::CustomError {
                     val: "No Tasks For Slot".to_string(),
                 });
             } else {
                 slot_id = slot.1.unwrap();
+                slot_kind = SlotType::Cron;
                 // There aren't block tasks but there are cron tasks
-                some_hash = self.pop_slot_item(deps.storage, &slot_id, &SlotType::Cron);
+                some_hash =
+                    self.pop_slot_item_at(deps.storage, &slot_id, &slot_kind, selection_index);
             }
         } else {
             // There are block tasks (which we prefer to execute before time-based ones at this point)
             slot_id = slot.0.unwrap();
-            some_hash = self.pop_slot_item(deps.storage, &slot.0.unwrap(), &SlotType::Block);
+            slot_kind = SlotType::Block;
+            some_hash = self.pop_slot_item_at(deps.storage, &slot_id, &slot_kind, selection_index);
         }
         if some_hash.is_none() {
-            self.send_base_agent_reward(deps.storage, agent, info);
+            self.send_base_agent_reward(deps.storage, &env, agent, info);
             return Err(ContractError::CustomError {
                 val: "No Tasks For Slot".to_string(),
             });
         }
 
+        // Let the agent know how many tasks are still waiting in this slot
+        let remaining_in_slot = match slot_kind {
+            SlotType::Block => self.block_slots.may_load(deps.storage, slot_id)?,
+            SlotType::Cron => self.time_slots.may_load(deps.storage, slot_id)?,
+        }
+        .map_or(0, |hashes| hashes.len());
+
         // Get the task details
         // if no task, exit and reward agent.
         let hash = some_hash.unwrap();
+        if self.executing_tasks.has(deps.storage, hash.clone()) {
+            return Err(ContractError::CustomError {
+                val: "Task is already executing".to_string(),
+            });
+        }
         let some_task = self.tasks.may_load(deps.storage, hash.clone())?;
         if some_task.is_none() {
             // NOTE: This could should never get reached, however we cover just in case
-            self.send_base_agent_reward(deps.storage, agent, info);
+            self.send_base_agent_reward(deps.storage, &env, agent, info);
             return Err(ContractError::NoTaskFound {});
         }
 
@@ -93,7 +195,115 @@ impl<'a> CwCroncat<'a> {
         // AGENT Task Allowance Logic: see line 339
         // ----------------------------------------------------
 
-        let task = some_task.unwrap();
+        let mut task = some_task.unwrap();
+
+        // Expired tasks won't run again, end them and refund the owner instead of executing
+        if task.is_expired(&env) {
+            self.send_base_agent_reward(deps.storage, &env, agent, info.clone());
+            let task_hash = task.to_hash();
+            let resp = self.remove_task(deps, env, task_hash.clone(), None)?;
+            return Ok(Response::new()
+                .add_attribute("method", "proxy_call")
+                .add_attribute("agent", info.sender)
+                .add_attribute("slot_id", slot_id.to_string())
+                .add_attribute("skipped_task", task_hash.clone())
+                .add_attribute("skip_reason", "expired")
+                .add_attribute("remaining_in_slot", remaining_in_slot.to_string())
+                .add_attributes(resp.attributes)
+                .add_submessages(resp.messages)
+                .add_events(resp.events)
+                .add_attribute("ended_task", task_hash));
+        }
+
+        // Out of funds tasks wait for a refill instead of running, until their grace slots are used up
+        if let Some(grace_slots) = self.exhausted_tasks.may_load(deps.storage, hash.clone())? {
+            self.send_base_agent_reward(deps.storage, &env, agent, info.clone());
+            let response = Response::new()
+                .add_attribute("method", "proxy_call")
+                .add_attribute("agent", info.sender)
+                .add_attribute("slot_id", slot_id.to_string())
+                .add_attribute("skipped_task", task.to_hash())
+                .add_attribute("skip_reason", "out_of_funds")
+                .add_attribute("remaining_in_slot", remaining_in_slot.to_string());
+            if grace_slots == 0 {
+                let task_hash = task.to_hash();
+                let resp = self.remove_task(deps, env, task_hash.clone(), None)?;
+                return Ok(response
+                    .add_attributes(resp.attributes)
+                    .add_submessages(resp.messages)
+                    .add_events(resp.events)
+                    .add_attribute("ended_task", task_hash));
+            }
+            self.exhausted_tasks
+                .save(deps.storage, hash.clone(), &(grace_slots - 1))?;
+            let response = response.add_attribute("grace_slots", (grace_slots - 1).to_string());
+            return self.reschedule_or_end(deps, env, &task, response);
+        }
+
+        // Skip tasks that can't fit in a single proxy_call, without penalizing the agent
+        // The task moves on to its next slot, so it runs again once the limit allows it
+        let submsg_count = task.actions.len() + task.rules.as_ref().map_or(0, Vec::len);
+        if submsg_count as u64 > c.max_submsgs_per_proxy_call {
+            self.send_base_agent_reward(deps.storage, &env, agent, info.clone());
+            let response = Response::new()
+                .add_attribute("method", "proxy_call")
+                .add_attribute("agent", info.sender)
+                .add_attribute("slot_id", slot_id.to_string())
+                .add_attribute("skipped_task", task.to_hash())
+                .add_attribute("skip_reason", "max_submsgs_per_proxy_call")
+                .add_attribute("remaining_in_slot", remaining_in_slot.to_string());
+            return self.reschedule_or_end(deps, env, &task, response);
+        }
+
+        // Rules gate the run, a rule query that errors counts as not satisfied
+        // so a broken rule contract skips the task instead of reverting the agent's call
+        let unmet_rule = task
+            .rules
+            .iter()
+            .flatten()
+            .enumerate()
+            .find_map(|(idx, rule)| check_rule(&deps.querier, rule).err().map(|r| (idx, r)));
+        if let Some((rule_idx, reason)) = unmet_rule {
+            self.send_base_agent_reward(deps.storage, &env, agent, info.clone());
+            let response = Response::new()
+                .add_attribute("method", "proxy_call")
+                .add_attribute("agent", info.sender)
+                .add_attribute("slot_id", slot_id.to_string())
+                .add_attribute("skipped_task", task.to_hash())
+                .add_attribute("skip_reason", reason)
+                .add_attribute("rule_index", rule_idx.to_string())
+                .add_attribute("remaining_in_slot", remaining_in_slot.to_string());
+            return self.reschedule_or_end(deps, env, &task, response);
+        }
+
+        let deposit_before = task.total_deposit.clone();
+
+        // Take the protocol fee from the task balance, it stays with the owner as unreserved funds
+        // The task must still cover the agent fee on top of it, otherwise it ends here
+        if !c.protocol_fee.amount.is_zero() {
+            if !covers_protocol_fee(&c, &task) {
+                self.send_base_agent_reward(deps.storage, &env, agent, info.clone());
+                let response = Response::new()
+                    .add_attribute("method", "proxy_call")
+                    .add_attribute("agent", info.sender)
+                    .add_attribute("slot_id", slot_id.to_string());
+                return self.end_or_hold_exhausted(deps, env, &task, response);
+            }
+
+            for coin in task.total_deposit.iter_mut() {
+                if coin.denom == c.protocol_fee.denom {
+                    coin.amount -= c.protocol_fee.amount;
+                }
+            }
+            self.tasks.save(deps.storage, hash.clone(), &task)?;
+            self.config
+                .update(deps.storage, |mut config| -> StdResult<_> {
+                    config
+                        .protocol_fees_collected
+                        .add_tokens(Balance::from(vec![c.protocol_fee.clone()]));
+                    Ok(config)
+                })?;
+        }
 
         // TODO: Bring this back!
         // // Fee breakdown:
@@ -164,32 +374,171 @@ impl<'a> CwCroncat<'a> {
         //     }
         // }
 
-        // Setup submessages for actions for this task
+        // No-op tasks only prove the scheduler is alive, nothing gets dispatched
+        // The run takes the agent fee and gas from the task balance, then reschedules like any successful run
+        if task.is_no_op() {
+            let cost = no_op_run_cost(&c, &task);
+            let native_deposit = task
+                .total_deposit
+                .iter()
+                .find(|coin| coin.denom == c.native_denom)
+                .map(|coin| coin.amount.u128())
+                .unwrap_or_default();
+            if native_deposit < cost {
+                self.send_base_agent_reward(deps.storage, &env, agent, info.clone());
+                let response = Response::new()
+                    .add_attribute("method", "proxy_call")
+                    .add_attribute("agent", info.sender)
+                    .add_attribute("slot_id", slot_id.to_string())
+                    .add_attribute("skip_reason", "insufficient_task_balance");
+                return self.end_or_hold_exhausted(deps, env, &task, response);
+            }
+            for coin in task.total_deposit.iter_mut() {
+                if coin.denom == c.native_denom {
+                    coin.amount -= Uint128::from(cost);
+                }
+            }
+            self.tasks.save(deps.storage, hash.clone(), &task)?;
+            if task.boundary.max_executions.is_some() {
+                self.task_executions.update(
+                    deps.storage,
+                    hash.clone(),
+                    |count| -> StdResult<_> { Ok(count.unwrap_or_default() + 1) },
+                )?;
+            }
+            agent.total_tasks_executed = agent.total_tasks_executed.saturating_add(1);
+            self.send_base_agent_reward(deps.storage, &env, agent, info.clone());
+
+            let done = Reply {
+                id: 0,
+                result: SubMsgResult::Ok(SubMsgResponse {
+                    events: vec![],
+                    data: None,
+                }),
+            };
+            let callback = self.proxy_callback(deps, env, done, hash)?;
+            return Ok(Response::new()
+                .add_attribute("method", "proxy_call")
+                .add_attribute("agent", info.sender)
+                .add_attribute("task_hash", task.to_hash())
+                .add_attribute("no_op", "true")
+                .add_attribute("funds_spent", Coin::new(cost, &c.native_denom).to_string())
+                .add_attributes(callback.attributes)
+                .add_submessages(callback.messages));
+        }
+
+        // Attached funds come out of the task balance, actions it can't cover get skipped,
+        // or end the task when it stops on failure
+        let (action_idx, skipped_actions) = draw_next_action_funds(&mut task, 0);
+        let mut skipped_attrs: Vec<(&str, String)> = skipped_actions
+            .iter()
+            .map(|idx| ("skipped_action", idx.to_string()))
+            .collect();
+        if task.stop_on_fail && !skipped_actions.is_empty() {
+            self.send_base_agent_reward(deps.storage, &env, agent, info.clone());
+            let response = Response::new()
+                .add_attribute("method", "proxy_call")
+                .add_attribute("agent", info.sender)
+                .add_attribute("slot_id", slot_id.to_string())
+                .add_attributes(skipped_attrs)
+                .add_attribute("skip_reason", "insufficient_task_balance");
+            return self.end_or_hold_exhausted(deps, env, &task, response);
+        }
+        let action_idx = match action_idx {
+            Some(action_idx) => action_idx,
+            None => {
+                self.send_base_agent_reward(deps.storage, &env, agent, info.clone());
+                let response = Response::new()
+                    .add_attribute("method", "proxy_call")
+                    .add_attribute("agent", info.sender)
+                    .add_attribute("slot_id", slot_id.to_string())
+                    .add_attributes(skipped_attrs)
+                    .add_attribute("skip_reason", "insufficient_task_balance")
+                    .add_attribute("remaining_in_slot", remaining_in_slot.to_string());
+                return self.reschedule_or_end(deps, env, &task, response);
+            }
+        };
+        // Unordered tasks send every action they can pay for at once
+        let mut action_idxs = vec![action_idx];
+        if task.action_order == ActionOrder::Unordered {
+            loop {
+                let start = action_idxs.last().map_or(0, |idx| idx + 1);
+                let (next_idx, skipped) = draw_next_action_funds(&mut task, start);
+                skipped_attrs.extend(
+                    skipped
+                        .iter()
+                        .map(|idx| ("skipped_action", idx.to_string())),
+                );
+                match next_idx {
+                    Some(idx) => action_idxs.push(idx),
+                    None => break,
+                }
+            }
+        }
+        self.tasks.save(deps.storage, hash.clone(), &task)?;
+        if task.boundary.max_executions.is_some() {
+            self.task_executions
+                .update(deps.storage, hash.clone(), |count| -> StdResult<_> {
+                    Ok(count.unwrap_or_default() + 1)
+                })?;
+        }
+
+        // Everything drawn from the task balance on this run, so it can be audited per execution
+        let funds_spent: Vec<String> = deposit_before
+            .iter()
+            .filter_map(|before| {
+                let after = task
+                    .total_deposit
+                    .iter()
+                    .find(|coin| coin.denom == before.denom)
+                    .map(|coin| coin.amount)
+                    .unwrap_or_default();
+                let spent = before.amount.saturating_sub(after);
+                (!spent.is_zero()).then(|| Coin::new(spent.u128(), &before.denom).to_string())
+            })
+            .collect();
+        let funds_spent = if funds_spent.is_empty() {
+            Coin::new(0, &c.native_denom).to_string()
+        } else {
+            funds_spent.join(",")
+        };
+
+        // Only completed runs count towards the agent's stats, skipped tasks just pay the base reward
+        agent.total_tasks_executed = agent.total_tasks_executed.saturating_add(1);
+        agent.last_executed_slot = env.block.height;
+        self.agents
+            .save(deps.storage, info.sender.clone(), &agent)?;
+
+        // Locked until the proxy callback, so a reentrant call can't run the task again meanwhile
+        self.executing_tasks
+            .save(deps.storage, hash.clone(), &env.block.height)?;
+
+        // Setup submessage for the first action of this task
         // Each submessage in storage, computes & stores the "next" reply to allow for chained message processing.
+        // The remaining actions of a sequential task are dispatched from the reply, so they can use the previous result
         let mut sub_msgs: Vec<SubMsg<Empty>> = vec![];
-        let next_idx = self.rq_next_id(deps.storage)?;
-        let actions = task.clone().actions;
-        let self_addr = env.contract.address;
+        for action_idx in action_idxs {
+            let next_idx = self.rq_next_id(deps.storage)?;
+            let action = task.actions[action_idx].with_ibc_timeout(&env);
 
-        // Add submessages for all actions
-        for action in actions {
             let sub_msg: SubMsg = SubMsg::reply_always(action.msg, next_idx);
             if let Some(gas_limit) = action.gas_limit {
                 sub_msgs.push(sub_msg.with_gas_limit(gas_limit));
             } else {
                 sub_msgs.push(sub_msg);
             }
-        }
 
-        // Keep track for later scheduling
-        self.rq_push(
-            deps.storage,
-            QueueItem {
-                prev_idx: None,
-                task_hash: Some(hash),
-                contract_addr: Some(self_addr),
-            },
-        )?;
+            // Keep track for later scheduling
+            self.rq_push(
+                deps.storage,
+                QueueItem {
+                    prev_idx: None,
+                    task_hash: Some(hash.clone()),
+                    contract_addr: Some(env.contract.address.clone()),
+                    action_idx: Some(action_idx as u64),
+                },
+            )?;
+        }
 
         // TODO: Add supported msgs if not a SubMessage?
         // Add the messages, reply handler responsible for task rescheduling
@@ -199,22 +548,148 @@ impl<'a> CwCroncat<'a> {
             .add_attribute("slot_id", slot_id.to_string())
             .add_attribute("slot_kind", format!("{:?}", SlotType::Block))
             .add_attribute("task_hash", task.to_hash())
+            .add_attribute("remaining_in_slot", remaining_in_slot.to_string())
+            .add_attribute("protocol_fee", c.protocol_fee.to_string())
+            .add_attribute("funds_spent", funds_spent)
+            .add_attributes(skipped_attrs)
             // .add_attributes(rule_responses)
             .add_submessages(sub_msgs);
 
         Ok(final_res)
     }
 
+    /// Dispatch the task action following the one that just replied
+    /// Placeholders in the action are filled in from the previous action's reply,
+    /// returns None once there are no more actions, or the chain broke on a failure
+    pub(crate) fn proxy_next_action(
+        &self,
+        mut deps: DepsMut,
+        env: &Env,
+        msg: &Reply,
+        task_hash: &[u8],
+        action_idx: Option<u64>,
+    ) -> Result<Option<Response>, ContractError> {
+        let prev_idx = match action_idx {
+            Some(idx) => idx as usize,
+            None => return Ok(None),
+        };
+        let mut task = match self.tasks.may_load(deps.storage, task_hash.to_vec())? {
+            Some(task) => task,
+            None => return Ok(None),
+        };
+        // Unordered actions were sent at once and reply in that order,
+        // the task gets rescheduled from the reply of the last one
+        let awaiting_replies = task.action_order == ActionOrder::Unordered
+            && self
+                .reply_queue
+                .may_load(deps.storage, msg.id + 1)?
+                .map_or(false, |item| item.task_hash.as_deref() == Some(task_hash));
+        let awaiting_response = || {
+            Response::new()
+                .add_attribute("method", "proxy_next_action")
+                .add_attribute("replied_action", prev_idx.to_string())
+        };
+        // Later actions of a sequential task could depend on the failed one, so skip them
+        let events = match &msg.result {
+            SubMsgResult::Ok(res) => &res.events,
+            SubMsgResult::Err(_) => {
+                // The failed action never sent its funds, give them back to the task
+                let funds = task
+                    .actions
+                    .get(prev_idx)
+                    .map(|action| action.funds())
+                    .unwrap_or_default();
+                if !funds.is_empty() {
+                    for coin in funds {
+                        match task
+                            .total_deposit
+                            .iter_mut()
+                            .find(|deposit| deposit.denom == coin.denom)
+                        {
+                            Some(deposit) => deposit.amount += coin.amount,
+                            None => task.total_deposit.push(coin),
+                        }
+                    }
+                    self.tasks.save(deps.storage, task_hash.to_vec(), &task)?;
+                }
+                return Ok(awaiting_replies.then(awaiting_response));
+            }
+        };
+        if task.action_order == ActionOrder::Unordered {
+            return Ok(awaiting_replies.then(awaiting_response));
+        }
+
+        let (action_idx, skipped_actions) = draw_next_action_funds(&mut task, prev_idx + 1);
+        let skipped_attrs: Vec<(&str, String)> = skipped_actions
+            .iter()
+            .map(|idx| ("skipped_action", idx.to_string()))
+            .collect();
+        if task.stop_on_fail && !skipped_actions.is_empty() {
+            let response = Response::new()
+                .add_attribute("method", "proxy_next_action")
+                .add_attributes(skipped_attrs)
+                .add_attribute("skip_reason", "insufficient_task_balance");
+            // The run ends here, release the task as proxy_callback would
+            self.executing_tasks
+                .remove(deps.storage, task_hash.to_vec());
+            return self
+                .end_or_hold_exhausted(deps, env.clone(), &task, response)
+                .map(Some);
+        }
+        let action_idx = match action_idx {
+            Some(action_idx) => action_idx,
+            None if skipped_actions.is_empty() => return Ok(None),
+            None => {
+                // Nothing left the task can pay for, reschedule it right away
+                let res = self.proxy_callback(
+                    deps.branch(),
+                    env.clone(),
+                    msg.clone(),
+                    task_hash.to_vec(),
+                )?;
+                return Ok(Some(res.add_attributes(skipped_attrs)));
+            }
+        };
+        self.tasks.save(deps.storage, task_hash.to_vec(), &task)?;
+        let action = task.actions[action_idx]
+            .with_prev_result(events)
+            .with_ibc_timeout(env);
+
+        let next_idx = self.rq_next_id(deps.storage)?;
+        let mut sub_msg: SubMsg = SubMsg::reply_always(action.msg, next_idx);
+        if let Some(gas_limit) = action.gas_limit {
+            sub_msg = sub_msg.with_gas_limit(gas_limit);
+        }
+        self.rq_push(
+            deps.storage,
+            QueueItem {
+                prev_idx: Some(msg.id),
+                task_hash: Some(task_hash.to_vec()),
+                contract_addr: Some(env.contract.address.clone()),
+                action_idx: Some(action_idx as u64),
+            },
+        )?;
+
+        Ok(Some(
+            Response::new()
+                .add_attribute("method", "proxy_next_action")
+                .add_attribute("action_idx", action_idx.to_string())
+                .add_attributes(skipped_attrs)
+                .add_submessage(sub_msg),
+        ))
+    }
+
     /// Logic executed on the completion of a proxy call
     /// Reschedule next task
     pub(crate) fn proxy_callback(
         &self,
-        deps: DepsMut,
+        mut deps: DepsMut,
         env: Env,
         msg: Reply,
         task_hash: Vec<u8>,
     ) -> Result<Response, ContractError> {
         let mut response = Response::new().add_attribute("method", "proxy_callback");
+        self.executing_tasks.remove(deps.storage, task_hash.clone());
 
         // check if reply had failure
         let mut reply_submsg_failed = false;
@@ -242,7 +717,7 @@ impl<'a> CwCroncat<'a> {
             // if non-recurring, exit
             if task.stop_on_fail && reply_submsg_failed {
                 // Process task exit, if no future task can execute
-                let rt = self.remove_task(deps, task_hash);
+                let rt = self.remove_task(deps, env, task_hash, None);
                 if let Ok(..) = rt {
                     let resp = rt.unwrap();
                     response = response
@@ -254,11 +729,22 @@ impl<'a> CwCroncat<'a> {
             }
 
             // Parse interval into a future timestamp, then convert to a slot
-            let (next_id, slot_kind) = task.interval.next(env, task.boundary);
+            // One-shot tasks are done after their single run
+            let (mut next_id, slot_kind) = if task.interval == Interval::Once {
+                (0, SlotType::Block)
+            } else {
+                self.next_task_slot(deps.storage, &env, &task)?
+            };
 
             // If the next interval comes back 0, then this task should not schedule again
             if next_id == 0 {
-                let rt = self.remove_task(deps, task_hash.clone());
+                if let Some(resp) = self.spawn_successor(deps.branch(), &env, &task)? {
+                    return Ok(response
+                        .add_attributes(resp.attributes)
+                        .add_submessages(resp.messages)
+                        .add_events(resp.events));
+                }
+                let rt = self.remove_task(deps, env, task_hash.clone(), None);
                 if let Ok(..) = rt {
                     let resp = rt.unwrap();
                     response = response
@@ -270,34 +756,48 @@ impl<'a> CwCroncat<'a> {
                 return Ok(response);
             }
 
-            response = response.add_attribute("slot_id", next_id.to_string());
-            response = response.add_attribute("slot_kind", format!("{:?}", slot_kind));
-
-            // Get previous task hashes in slot, add as needed
-            let update_vec_data = |d: Option<Vec<Vec<u8>>>| -> StdResult<Vec<Vec<u8>>> {
-                match d {
-                    // has some data, simply push new hash
-                    Some(data) => {
-                        let mut s = data;
-                        s.push(task.to_hash_vec());
-                        Ok(s)
+            // Back off repeatedly failing tasks, doubling the gap to the next slot on every failure
+            if reply_submsg_failed {
+                let failure_streak = self
+                    .task_failure_streaks
+                    .may_load(deps.storage, task.to_hash_vec())?
+                    .unwrap_or_default()
+                    + 1;
+                if failure_streak > MAX_FAILURE_STREAK {
+                    let rt = self.remove_task(deps, env, task_hash.clone(), None);
+                    if let Ok(..) = rt {
+                        let resp = rt.unwrap();
+                        response = response
+                            .add_attributes(resp.attributes)
+                            .add_submessages(resp.messages)
+                            .add_events(resp.events);
                     }
-                    // No data, push new vec & hash
-                    None => Ok(vec![task.to_hash_vec()]),
+                    response = response.add_attribute("ended_task", task_hash);
+                    return Ok(response);
                 }
-            };
+                self.task_failure_streaks.save(
+                    deps.storage,
+                    task.to_hash_vec(),
+                    &failure_streak,
+                )?;
 
-            // Based on slot kind, put into block or cron slots
-            match slot_kind {
-                SlotType::Block => {
-                    self.block_slots
-                        .update(deps.storage, next_id, update_vec_data)?;
-                }
-                SlotType::Cron => {
-                    self.time_slots
-                        .update(deps.storage, next_id, update_vec_data)?;
-                }
+                let current_id = match slot_kind {
+                    SlotType::Block => env.block.height,
+                    SlotType::Cron => env.block.time.nanos(),
+                };
+                let gap = next_id.saturating_sub(current_id);
+                next_id = current_id.saturating_add(gap.saturating_mul(1 << failure_streak));
+                response = response.add_attribute("failure_streak", failure_streak.to_string());
+            } else {
+                self.task_failure_streaks
+                    .remove(deps.storage, task.to_hash_vec());
             }
+
+            response = response.add_attribute("slot_id", next_id.to_string());
+            response = response.add_attribute("slot_kind", format!("{:?}", slot_kind));
+
+            // Based on slot kind, put into block or cron slots
+            self.push_slot_item(deps.storage, &next_id, &slot_kind, task.to_hash_vec())?;
         } else {
             return Err(ContractError::NoTaskFound {});
         }
@@ -305,12 +805,140 @@ impl<'a> CwCroncat<'a> {
         Ok(response)
     }
 
+    /// Next slot of a task, 0 once it's past its boundary, be it height, time or executions
+    pub(crate) fn next_task_slot(
+        &self,
+        storage: &dyn Storage,
+        env: &Env,
+        task: &Task,
+    ) -> StdResult<(u64, SlotType)> {
+        let (next_id, slot_kind) = task.interval.next(env.clone(), task.boundary);
+        if task.boundary.max_executions.is_some() {
+            let executions = self
+                .task_executions
+                .may_load(storage, task.to_hash_vec())?
+                .unwrap_or_default();
+            if task.boundary.executions_reached(executions) {
+                return Ok((0, slot_kind));
+            }
+        }
+        Ok((next_id, slot_kind))
+    }
+
+    /// Moves a skipped task on to its next slot,
+    /// or ends it and refunds the owner once it's past its boundary
+    fn reschedule_or_end(
+        &self,
+        deps: DepsMut,
+        env: Env,
+        task: &Task,
+        response: Response,
+    ) -> Result<Response, ContractError> {
+        let (next_id, next_slot_kind) = self.next_task_slot(deps.storage, &env, task)?;
+        if next_id == 0 {
+            let task_hash = task.to_hash();
+            let resp = self.remove_task(deps, env, task_hash.clone(), None)?;
+            return Ok(response
+                .add_attributes(resp.attributes)
+                .add_submessages(resp.messages)
+                .add_events(resp.events)
+                .add_attribute("ended_task", task_hash));
+        }
+        self.push_slot_item(deps.storage, &next_id, &next_slot_kind, task.to_hash_vec())?;
+        Ok(response)
+    }
+
+    /// Ends a task that ran out of funds and refunds the owner,
+    /// or holds it for `removal_grace_slots` slots so the owner can refill it first
+    fn end_or_hold_exhausted(
+        &self,
+        deps: DepsMut,
+        env: Env,
+        task: &Task,
+        response: Response,
+    ) -> Result<Response, ContractError> {
+        let c: Config = self.config.load(deps.storage)?;
+        let task_hash = task.to_hash();
+        if c.removal_grace_slots == 0 {
+            let resp = self.remove_task(deps, env, task_hash.clone(), None)?;
+            return Ok(response
+                .add_attributes(resp.attributes)
+                .add_submessages(resp.messages)
+                .add_events(resp.events)
+                .add_attribute("ended_task", task_hash));
+        }
+        self.exhausted_tasks
+            .save(deps.storage, task.to_hash_vec(), &c.removal_grace_slots)?;
+        let response = response
+            .add_attribute("out_of_funds", task_hash)
+            .add_attribute("grace_slots", c.removal_grace_slots.to_string());
+        self.reschedule_or_end(deps, env, task, response)
+    }
+
+    /// Replaces an ended task with its successor, funded from the remaining task balance
+    /// Returns None if the task has no successor or its chain is too long, so it just ends
+    fn spawn_successor(
+        &self,
+        mut deps: DepsMut,
+        env: &Env,
+        task: &Task,
+    ) -> Result<Option<Response>, ContractError> {
+        let successor = match &task.successor {
+            Some(successor) if task.generation < MAX_TASK_GENERATION => *successor.clone(),
+            _ => return Ok(None),
+        };
+        let task_hash = task.to_hash();
+        let removed = self.remove_task(deps.branch(), env.clone(), task_hash.clone(), None)?;
+        // Only the native balance carries over, cw20 deposits still go back to the owner
+        let (cw20_refunds, _) = send_tokens(
+            &task.owner_id,
+            &GenericBalance {
+                native: vec![],
+                cw20: task.total_cw20_deposit.clone(),
+            },
+        )?;
+        let info = MessageInfo {
+            sender: task.owner_id.clone(),
+            funds: task.total_deposit.clone(),
+        };
+        let created = match self.create_task_generation(
+            deps.branch(),
+            info,
+            env.clone(),
+            successor,
+            task.generation + 1,
+        ) {
+            Ok(created) => created,
+            // Successor can't be created, refund the owner like any other ended task
+            Err(_) => {
+                return Ok(Some(
+                    removed
+                        .add_attribute("ended_task", task_hash)
+                        .add_attribute("successor", "failed"),
+                ))
+            }
+        };
+
+        // The remaining balance stays in the contract as the successor's deposit
+        Ok(Some(
+            Response::new()
+                .add_attributes(removed.attributes)
+                .add_events(removed.events)
+                .add_attribute("ended_task", task_hash)
+                .add_attributes(created.attributes)
+                .add_submessages(created.messages)
+                .add_events(created.events)
+                .add_submessages(cw20_refunds),
+        ))
+    }
+
     /// Internal management of agent reward
     /// Used in cases where there are empty slots or failed txns
     /// Keep the agent profitable, as this will be a business expense
     pub(crate) fn send_base_agent_reward(
         &self,
         storage: &mut dyn Storage,
+        env: &Env,
         mut agent: Agent,
         message: MessageInfo,
     ) {
@@ -321,7 +949,6 @@ impl<'a> CwCroncat<'a> {
         let add_native: Balance = Balance::from(coin);
 
         agent.balance.add_tokens(add_native.clone());
-        agent.total_tasks_executed = agent.total_tasks_executed.saturating_add(1);
         println!("{:?}", add_native);
         println!("{:?}", config.available_balance.native);
 
@@ -335,25 +962,125 @@ impl<'a> CwCroncat<'a> {
             .save(storage, &config)
             .expect("Could not save config");
 
+        // The new reward vests together with whatever is still locked,
+        // each accrual restarts the unlock period for the locked amount
+        if config.reward_vesting_seconds > 0 {
+            let locked = self
+                .locked_agent_reward(storage, &config, &message.sender, env.block.time)
+                .expect("Could not load reward vesting");
+            let vesting = RewardVesting {
+                locked: locked + agent_base_fee.amount,
+                start: env.block.time,
+            };
+            self.agent_reward_vesting
+                .save(storage, message.sender.clone(), &vesting)
+                .expect("Could not save reward vesting");
+        }
+
         // Reset missed slot, if any
         if agent.last_missed_slot != 0 {
             agent.last_missed_slot = 0;
         }
+        agent.last_executed_slot = env.block.height;
         self.agents.save(storage, message.sender, &agent).unwrap();
     }
 }
 
+/// Native amount a no-op run takes from the task balance: the agent fee and the gas of its actions
+pub(crate) fn no_op_run_cost(c: &Config, task: &Task) -> u128 {
+    task.task_balance_uses(&c.agent_fee, c.gas_base_fee, 0)
+}
+
+/// Everything a single run takes from the task balance, summed up per denom.
+/// Every run pays the protocol fee, no-op tasks their flat cost on top of it
+/// and other tasks the funds their actions send along, their agent fee and gas are paid by the contract
+pub(crate) fn task_run_charge(c: &Config, task: &Task) -> Vec<Coin> {
+    let run_cost = if task.is_no_op() {
+        vec![Coin::new(no_op_run_cost(c, task), &c.native_denom)]
+    } else {
+        task.funds_per_run()
+    };
+    let mut charge: Vec<Coin> = vec![];
+    for cost in std::iter::once(c.protocol_fee.clone()).chain(run_cost) {
+        if cost.amount.is_zero() {
+            continue;
+        }
+        match charge.iter_mut().find(|coin| coin.denom == cost.denom) {
+            Some(coin) => coin.amount += cost.amount,
+            None => charge.push(cost),
+        }
+    }
+    charge
+}
+
+/// Whether the task balance holds the protocol fee, along with the agent fee when they share a denom
+pub(crate) fn covers_protocol_fee(c: &Config, task: &Task) -> bool {
+    let fee_deposit = task
+        .total_deposit
+        .iter()
+        .find(|coin| coin.denom == c.protocol_fee.denom)
+        .map(|coin| coin.amount)
+        .unwrap_or_default();
+    let mut required = c.protocol_fee.amount;
+    if c.agent_fee.denom == c.protocol_fee.denom {
+        required += c.agent_fee.amount;
+    }
+    fee_deposit >= required
+}
+
+/// Draws the attached funds of the first action from `start_idx` the task balance covers,
+/// returns its index along with the actions skipped on the way.
+/// A task that stops on failure skips no further once one can't be covered
+pub(crate) fn draw_next_action_funds(
+    task: &mut Task,
+    start_idx: usize,
+) -> (Option<usize>, Vec<usize>) {
+    let mut skipped = vec![];
+    for (idx, action) in task.actions.iter().enumerate().skip(start_idx) {
+        let funds = action.funds();
+        if funds
+            .iter()
+            .all(|coin| has_coins(&task.total_deposit, coin))
+        {
+            for coin in funds {
+                if let Some(deposit) = task
+                    .total_deposit
+                    .iter_mut()
+                    .find(|deposit| deposit.denom == coin.denom)
+                {
+                    deposit.amount -= coin.amount;
+                }
+            }
+            return (Some(idx), skipped);
+        }
+        skipped.push(idx);
+        if task.stop_on_fail {
+            break;
+        }
+    }
+    (None, skipped)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
     use cosmwasm_std::{
-        coin, coins, to_binary, Addr, BlockInfo, CosmosMsg, Empty, StakingMsg, WasmMsg,
+        coin, coins, to_binary, Addr, BlockInfo, CosmosMsg, Empty, IbcMsg, IbcTimeout,
+        IbcTimeoutBlock, StakingMsg, SubMsgResponse, SubMsgResult, Timestamp, WasmMsg,
     };
-    use cw_multi_test::{App, AppBuilder, Contract, ContractWrapper, Executor};
+    use cw_multi_test::{App, AppBuilder, AppResponse, Contract, ContractWrapper, Executor};
     // use cw20::Balance;
+    use crate::helpers::test_helpers::mock_init;
     use crate::helpers::CwTemplateContract;
-    use cw_croncat_core::msg::{ExecuteMsg, InstantiateMsg, TaskRequest};
-    use cw_croncat_core::types::{Action, Boundary, Interval};
+    use cw_croncat_core::msg::{
+        ExecuteMsg, GetBalancesResponse, GetExecutionCostBreakdownResponse, GetSlotHashesResponse,
+        GetSlotIdsResponse, InstantiateMsg, QueryMsg, TaskRequest, TaskResponse,
+    };
+    use cw_croncat_core::types::{
+        Action, ActionOrder, AgentResponse, AgentStatus, Boundary, BoundaryValidated,
+        GenericBalance, Interval, Rule, Task,
+    };
 
     pub fn contract_template() -> Box<dyn Contract<Empty>> {
         let contract = ContractWrapper::new(
@@ -436,7 +1163,11 @@ mod tests {
     fn proxy_call_fail_cases() -> StdResult<()> {
         let (mut app, cw_template_contract) = proper_instantiate();
         let contract_addr = cw_template_contract.addr();
-        let proxy_call_msg = ExecuteMsg::ProxyCall {};
+        let proxy_call_msg = ExecuteMsg::ProxyCall {
+            reward_to: None,
+            agent_version: None,
+            task_hash: None,
+        };
         let validator = String::from("you");
         let amount = coin(3, NATIVE_DENOM);
         let stake = StakingMsg::Delegate { validator, amount };
@@ -455,6 +1186,9 @@ mod tests {
                     gas_limit: Some(150_000),
                 }],
                 rules: None,
+                metadata: None,
+                successor: None,
+                action_order: None,
             },
         };
         let task_id_str =
@@ -466,7 +1200,7 @@ mod tests {
                 Addr::unchecked(ANYONE),
                 contract_addr.clone(),
                 &proxy_call_msg,
-                &coins(300010, NATIVE_DENOM),
+                &coins(300016, NATIVE_DENOM),
             )
             .unwrap_err();
         assert_eq!(
@@ -479,14 +1213,37 @@ mod tests {
         // Create task paused
         let change_settings_msg = ExecuteMsg::UpdateSettings {
             paused: Some(true),
-            owner_id: None,
-            // treasury_id: None,
+            treasury_id: None,
             agent_fee: None,
             min_tasks_per_agent: None,
             agents_eject_threshold: None,
             gas_price: None,
             proxy_callback_gas: None,
             slot_granularity: None,
+            dead_task_bounty: None,
+            max_rules_per_task: None,
+            slash_destination: None,
+            low_balance_pause_threshold: None,
+            min_active_agents_for_execution: None,
+            max_submsgs_per_proxy_call: None,
+            protocol_fee: None,
+            native_denom: None,
+            task_creation_fee: None,
+            nomination_paused: None,
+            reward_vesting_seconds: None,
+            max_future_slot_seconds: None,
+            randomized_agent_selection: None,
+            max_tasks_per_owner: None,
+            dust_threshold: None,
+            dust_swap_contract: None,
+            min_active_agents: None,
+            min_agent_version: None,
+            removal_grace_slots: None,
+            eject_to_pending: None,
+            max_task_limit: None,
+            agent_allowlist_enabled: None,
+            max_task_gas: None,
+            min_task_balance: None,
         };
         app.execute_contract(
             Addr::unchecked(ADMIN),
@@ -515,14 +1272,37 @@ mod tests {
             contract_addr.clone(),
             &ExecuteMsg::UpdateSettings {
                 paused: Some(false),
-                owner_id: None,
-                // treasury_id: None,
+                treasury_id: None,
                 agent_fee: None,
                 min_tasks_per_agent: None,
                 agents_eject_threshold: None,
                 gas_price: None,
                 proxy_callback_gas: None,
                 slot_granularity: None,
+                dead_task_bounty: None,
+                max_rules_per_task: None,
+                slash_destination: None,
+                low_balance_pause_threshold: None,
+                min_active_agents_for_execution: None,
+                max_submsgs_per_proxy_call: None,
+                protocol_fee: None,
+                native_denom: None,
+                task_creation_fee: None,
+                nomination_paused: None,
+                reward_vesting_seconds: None,
+                max_future_slot_seconds: None,
+                randomized_agent_selection: None,
+                max_tasks_per_owner: None,
+                dust_threshold: None,
+                dust_swap_contract: None,
+                min_active_agents: None,
+                min_agent_version: None,
+                removal_grace_slots: None,
+                eject_to_pending: None,
+                max_task_limit: None,
+                agent_allowlist_enabled: None,
+                max_task_gas: None,
+                min_task_balance: None,
             },
             &vec![],
         )
@@ -555,7 +1335,7 @@ mod tests {
                 Addr::unchecked(ANYONE),
                 contract_addr.clone(),
                 &create_task_msg,
-                &coins(300010, NATIVE_DENOM),
+                &coins(300016, NATIVE_DENOM),
             )
             .unwrap();
         // Assert task hash is returned as part of event attributes
@@ -624,14 +1404,18 @@ mod tests {
     fn proxy_call_success() -> StdResult<()> {
         let (mut app, cw_template_contract) = proper_instantiate();
         let contract_addr = cw_template_contract.addr();
-        let proxy_call_msg = ExecuteMsg::ProxyCall {};
+        let proxy_call_msg = ExecuteMsg::ProxyCall {
+            reward_to: None,
+            agent_version: None,
+            task_hash: None,
+        };
         let task_id_str =
             "dcbe1820cda5783a78afd66b68df4609c3fbce8e07f1f22c9585ae1ae5cf3289".to_string();
 
         // Doing this msg since its the easiest to guarantee success in reply
         let msg = CosmosMsg::Wasm(WasmMsg::Execute {
             contract_addr: contract_addr.to_string(),
-            msg: to_binary(&ExecuteMsg::WithdrawReward {})?,
+            msg: to_binary(&ExecuteMsg::WithdrawReward { amount: None })?,
             funds: coins(1, NATIVE_DENOM),
         });
 
@@ -648,6 +1432,9 @@ mod tests {
                     gas_limit: Some(250_000),
                 }],
                 rules: None,
+                metadata: None,
+                successor: None,
+                action_order: None,
             },
         };
 
@@ -657,7 +1444,7 @@ mod tests {
                 Addr::unchecked(ADMIN),
                 contract_addr.clone(),
                 &create_task_msg,
-                &coins(500010, NATIVE_DENOM),
+                &coins(500016, NATIVE_DENOM),
             )
             .unwrap();
         // Assert task hash is returned as part of event attributes
@@ -757,7 +1544,11 @@ mod tests {
     fn proxy_callback_fail_cases() -> StdResult<()> {
         let (mut app, cw_template_contract) = proper_instantiate();
         let contract_addr = cw_template_contract.addr();
-        let proxy_call_msg = ExecuteMsg::ProxyCall {};
+        let proxy_call_msg = ExecuteMsg::ProxyCall {
+            reward_to: None,
+            agent_version: None,
+            task_hash: None,
+        };
         let task_id_str =
             "96003a7938c1ac9566fec1be9b0cfa97a56626a574940ef5968364ef4d30c15a".to_string();
 
@@ -780,6 +1571,9 @@ mod tests {
                     gas_limit: Some(250_000),
                 }],
                 rules: None,
+                metadata: None,
+                successor: None,
+                action_order: None,
             },
         };
 
@@ -789,7 +1583,7 @@ mod tests {
                 Addr::unchecked(ADMIN),
                 contract_addr.clone(),
                 &create_task_msg,
-                &coins(500010, NATIVE_DENOM),
+                &coins(500016, NATIVE_DENOM),
             )
             .unwrap();
         // Assert task hash is returned as part of event attributes
@@ -851,7 +1645,7 @@ mod tests {
                     }
                     if e.ty == "transfer"
                         && a.clone().key == "amount"
-                        && a.clone().value == "500010atom"
+                        && a.clone().value == "500016atom"
                     {
                         has_submsg_method = true;
                     }
@@ -903,6 +1697,9 @@ mod tests {
                     gas_limit: Some(250_000),
                 }],
                 rules: None,
+                metadata: None,
+                successor: None,
+                action_order: None,
             },
         };
 
@@ -911,7 +1708,7 @@ mod tests {
             Addr::unchecked(ADMIN),
             contract_addr.clone(),
             &create_task_msg,
-            &coins(500010, NATIVE_DENOM),
+            &coins(500016, NATIVE_DENOM),
         )
         .unwrap();
 
@@ -948,7 +1745,7 @@ mod tests {
                     }
                     if e.ty == "transfer"
                         && a.clone().key == "amount"
-                        && a.clone().value == "500010atom"
+                        && a.clone().value == "500016atom"
                     {
                         has_submsg_method = true;
                     }
@@ -985,14 +1782,18 @@ mod tests {
     fn proxy_callback_block_slots() -> StdResult<()> {
         let (mut app, cw_template_contract) = proper_instantiate();
         let contract_addr = cw_template_contract.addr();
-        let proxy_call_msg = ExecuteMsg::ProxyCall {};
+        let proxy_call_msg = ExecuteMsg::ProxyCall {
+            reward_to: None,
+            agent_version: None,
+            task_hash: None,
+        };
         let task_id_str =
             "dcbe1820cda5783a78afd66b68df4609c3fbce8e07f1f22c9585ae1ae5cf3289".to_string();
 
         // Doing this msg since its the easiest to guarantee success in reply
         let msg = CosmosMsg::Wasm(WasmMsg::Execute {
             contract_addr: contract_addr.to_string(),
-            msg: to_binary(&ExecuteMsg::WithdrawReward {})?,
+            msg: to_binary(&ExecuteMsg::WithdrawReward { amount: None })?,
             funds: coins(1, NATIVE_DENOM),
         });
 
@@ -1006,6 +1807,9 @@ mod tests {
                     gas_limit: Some(250_000),
                 }],
                 rules: None,
+                metadata: None,
+                successor: None,
+                action_order: None,
             },
         };
 
@@ -1015,7 +1819,7 @@ mod tests {
                 Addr::unchecked(ADMIN),
                 contract_addr.clone(),
                 &create_task_msg,
-                &coins(500010, NATIVE_DENOM),
+                &coins(500016, NATIVE_DENOM),
             )
             .unwrap();
         // Assert task hash is returned as part of event attributes
@@ -1114,14 +1918,18 @@ mod tests {
     fn proxy_callback_time_slots() -> StdResult<()> {
         let (mut app, cw_template_contract) = proper_instantiate();
         let contract_addr = cw_template_contract.addr();
-        let proxy_call_msg = ExecuteMsg::ProxyCall {};
+        let proxy_call_msg = ExecuteMsg::ProxyCall {
+            reward_to: None,
+            agent_version: None,
+            task_hash: None,
+        };
         let task_id_str =
             "c7905cb9e5d620ae61b06cae6fb2bf3afa0ba0b290c1d48da626d0b7f68c293c".to_string();
 
         // Doing this msg since its the easiest to guarantee success in reply
         let msg = CosmosMsg::Wasm(WasmMsg::Execute {
             contract_addr: contract_addr.to_string(),
-            msg: to_binary(&ExecuteMsg::WithdrawReward {})?,
+            msg: to_binary(&ExecuteMsg::WithdrawReward { amount: None })?,
             funds: coins(1, NATIVE_DENOM),
         });
 
@@ -1135,6 +1943,9 @@ mod tests {
                     gas_limit: Some(250_000),
                 }],
                 rules: None,
+                metadata: None,
+                successor: None,
+                action_order: None,
             },
         };
 
@@ -1144,7 +1955,7 @@ mod tests {
                 Addr::unchecked(ADMIN),
                 contract_addr.clone(),
                 &create_task_msg,
-                &coins(500010, NATIVE_DENOM),
+                &coins(500016, NATIVE_DENOM),
             )
             .unwrap();
         // Assert task hash is returned as part of event attributes
@@ -1243,24 +2054,28 @@ mod tests {
     fn proxy_call_several_tasks() -> StdResult<()> {
         let (mut app, cw_template_contract) = proper_instantiate();
         let contract_addr = cw_template_contract.addr();
-        let proxy_call_msg = ExecuteMsg::ProxyCall {};
+        let proxy_call_msg = ExecuteMsg::ProxyCall {
+            reward_to: None,
+            agent_version: None,
+            task_hash: None,
+        };
 
         // Doing this msg since its the easiest to guarantee success in reply
         let msg = CosmosMsg::Wasm(WasmMsg::Execute {
             contract_addr: contract_addr.to_string(),
-            msg: to_binary(&ExecuteMsg::WithdrawReward {})?,
+            msg: to_binary(&ExecuteMsg::WithdrawReward { amount: None })?,
             funds: coins(1, NATIVE_DENOM),
         });
 
         let msg2 = CosmosMsg::Wasm(WasmMsg::Execute {
             contract_addr: contract_addr.to_string(),
-            msg: to_binary(&ExecuteMsg::WithdrawReward {})?,
+            msg: to_binary(&ExecuteMsg::WithdrawReward { amount: None })?,
             funds: coins(2, NATIVE_DENOM),
         });
 
         let msg3 = CosmosMsg::Wasm(WasmMsg::Execute {
             contract_addr: contract_addr.to_string(),
-            msg: to_binary(&ExecuteMsg::WithdrawReward {})?,
+            msg: to_binary(&ExecuteMsg::WithdrawReward { amount: None })?,
             funds: coins(3, NATIVE_DENOM),
         });
 
@@ -1274,6 +2089,9 @@ mod tests {
                     gas_limit: Some(250_000),
                 }],
                 rules: None,
+                metadata: None,
+                successor: None,
+                action_order: None,
             },
         };
 
@@ -1287,6 +2105,9 @@ mod tests {
                     gas_limit: Some(250_000),
                 }],
                 rules: None,
+                metadata: None,
+                successor: None,
+                action_order: None,
             },
         };
 
@@ -1300,6 +2121,9 @@ mod tests {
                     gas_limit: Some(250_000),
                 }],
                 rules: None,
+                metadata: None,
+                successor: None,
+                action_order: None,
             },
         };
 
@@ -1308,7 +2132,7 @@ mod tests {
             Addr::unchecked(ADMIN),
             contract_addr.clone(),
             &create_task_msg,
-            &coins(500_010, NATIVE_DENOM),
+            &coins(500_016, NATIVE_DENOM),
         )
         .unwrap();
 
@@ -1316,7 +2140,7 @@ mod tests {
             Addr::unchecked(ADMIN),
             contract_addr.clone(),
             &create_task_msg2,
-            &coins(500_010, NATIVE_DENOM),
+            &coins(500_016, NATIVE_DENOM),
         )
         .unwrap();
 
@@ -1327,7 +2151,7 @@ mod tests {
             Addr::unchecked(ADMIN),
             contract_addr.clone(),
             &create_task_msg3,
-            &coins(500_010, NATIVE_DENOM),
+            &coins(500_016, NATIVE_DENOM),
         )
         .unwrap();
 
@@ -1348,7 +2172,15 @@ mod tests {
         // need block advancement
         app.update_block(add_little_time);
 
-        // execute proxy_call's
+        // execute proxy_call's, the first slot drains before moving on to the next one
+        let remaining_in_slot = |res: &AppResponse| {
+            res.events
+                .iter()
+                .filter(|e| e.ty == "wasm")
+                .flat_map(|e| e.attributes.iter())
+                .find(|a| a.key == "remaining_in_slot")
+                .map(|a| a.value.clone())
+        };
         let res = app.execute_contract(
             Addr::unchecked(AGENT0),
             contract_addr.clone(),
@@ -1356,6 +2188,7 @@ mod tests {
             &vec![],
         );
         assert!(res.is_ok());
+        assert_eq!(remaining_in_slot(&res.unwrap()), Some("1".to_string()));
 
         let res = app.execute_contract(
             Addr::unchecked(AGENT0),
@@ -1364,6 +2197,7 @@ mod tests {
             &vec![],
         );
         assert!(res.is_ok());
+        assert_eq!(remaining_in_slot(&res.unwrap()), Some("0".to_string()));
 
         let res = app.execute_contract(
             Addr::unchecked(AGENT0),
@@ -1372,6 +2206,2520 @@ mod tests {
             &vec![],
         );
         assert!(res.is_ok());
+        assert_eq!(remaining_in_slot(&res.unwrap()), Some("0".to_string()));
+        Ok(())
+    }
+
+    #[test]
+    fn proxy_callback_failure_backoff() -> StdResult<()> {
+        let mut deps = mock_dependencies();
+        let store = CwCroncat::default();
+        mock_init(&store, deps.as_mut())?;
+
+        let task = Task {
+            owner_id: Addr::unchecked(ANYONE),
+            interval: Interval::Immediate,
+            boundary: BoundaryValidated {
+                start: None,
+                end: None,
+                max_executions: None,
+            },
+            stop_on_fail: false,
+            total_deposit: coins(500_016, NATIVE_DENOM),
+            actions: vec![],
+            rules: None,
+            metadata: None,
+            successor: None,
+            generation: 0,
+            action_order: ActionOrder::Sequential,
+            total_cw20_deposit: vec![],
+        };
+        let task_hash = task.to_hash_vec();
+        store
+            .tasks
+            .save(&mut deps.storage, task_hash.clone(), &task)?;
+
+        let reply = |result: SubMsgResult| Reply { id: 1, result };
+        let failed = || reply(SubMsgResult::Err("Error executing WasmMsg".to_string()));
+        let next_slot_gap = |res: Response| -> u64 {
+            let slot_id = res
+                .attributes
+                .iter()
+                .find(|a| a.key == "slot_id")
+                .map(|a| a.value.parse::<u64>().unwrap())
+                .unwrap();
+            slot_id - mock_env().block.height
+        };
+
+        // Every consecutive failure doubles the gap to the next slot
+        let mut gaps = vec![];
+        for _ in 0..3 {
+            let res = store
+                .proxy_callback(deps.as_mut(), mock_env(), failed(), task_hash.clone())
+                .unwrap();
+            gaps.push(next_slot_gap(res));
+        }
+        assert_eq!(gaps, vec![2, 4, 8]);
+
+        // Success resets the backoff
+        let succeeded = reply(SubMsgResult::Ok(SubMsgResponse {
+            events: vec![],
+            data: None,
+        }));
+        let res = store
+            .proxy_callback(deps.as_mut(), mock_env(), succeeded, task_hash.clone())
+            .unwrap();
+        assert_eq!(next_slot_gap(res), 1);
+
+        // Past the cap, the task gets removed
+        for _ in 0..MAX_FAILURE_STREAK {
+            store
+                .proxy_callback(deps.as_mut(), mock_env(), failed(), task_hash.clone())
+                .unwrap();
+        }
+        let res = store
+            .proxy_callback(deps.as_mut(), mock_env(), failed(), task_hash.clone())
+            .unwrap();
+        assert!(res.attributes.iter().any(|a| a.key == "ended_task"));
+        assert!(store.tasks.may_load(&deps.storage, task_hash)?.is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn proxy_call_uses_prev_action_result() -> StdResult<()> {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+
+        // Action one reports the withdrawing account, action two makes it the payable account
+        let withdraw_msg = CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: contract_addr.to_string(),
+            msg: to_binary(&ExecuteMsg::WithdrawReward { amount: None })?,
+            funds: coins(1, NATIVE_DENOM),
+        });
+        let update_msg = CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: contract_addr.to_string(),
+            msg: to_binary(&ExecuteMsg::UpdateAgent {
+                payable_account_id: Addr::unchecked("{{prev:account_id}}"),
+            })?,
+            funds: vec![],
+        });
+        let create_task_msg = ExecuteMsg::CreateTask {
+            task: TaskRequest {
+                interval: Interval::Immediate,
+                boundary: None,
+                stop_on_fail: false,
+                actions: vec![
+                    Action {
+                        msg: withdraw_msg,
+                        gas_limit: Some(250_000),
+                    },
+                    Action {
+                        msg: update_msg,
+                        gas_limit: Some(250_000),
+                    },
+                ],
+                rules: None,
+                metadata: None,
+                successor: None,
+                action_order: None,
+            },
+        };
+        app.execute_contract(
+            Addr::unchecked(ADMIN),
+            contract_addr.clone(),
+            &create_task_msg,
+            &coins(1_000_022, NATIVE_DENOM),
+        )
+        .unwrap();
+
+        // quick agent register, the contract itself is an agent so the actions succeed
+        let msg = ExecuteMsg::RegisterAgent {
+            payable_account_id: Some(Addr::unchecked(AGENT1_BENEFICIARY)),
+        };
+        app.execute_contract(Addr::unchecked(AGENT0), contract_addr.clone(), &msg, &[])
+            .unwrap();
+        app.execute_contract(contract_addr.clone(), contract_addr.clone(), &msg, &[])
+            .unwrap();
+        app.update_block(add_little_time);
+
+        let res = app
+            .execute_contract(
+                Addr::unchecked(AGENT0),
+                contract_addr.clone(),
+                &ExecuteMsg::ProxyCall {
+                    reward_to: None,
+                    agent_version: None,
+                    task_hash: None,
+                },
+                &[],
+            )
+            .unwrap();
+        let has_attr = |key: &str, value: &str| {
+            res.events.iter().any(|e| {
+                e.ty == "wasm"
+                    && e.attributes
+                        .iter()
+                        .any(|a| a.key == key && a.value == value)
+            })
+        };
+        assert!(has_attr("method", "withdraw_agent_balance"));
+        assert!(has_attr("method", "proxy_next_action"));
+        assert!(has_attr("method", "update_agent"));
+        assert!(has_attr("method", "proxy_callback"));
+
+        let agent: Option<AgentResponse> = app.wrap().query_wasm_smart(
+            &contract_addr,
+            &QueryMsg::GetAgent {
+                account_id: contract_addr.clone(),
+            },
+        )?;
+        assert_eq!(agent.unwrap().payable_account_id, contract_addr);
+
+        Ok(())
+    }
+
+    #[test]
+    fn proxy_call_min_active_agents() -> StdResult<()> {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+        let settings_msg = |min_active_agents_for_execution| ExecuteMsg::UpdateSettings {
+            paused: None,
+            treasury_id: None,
+            agent_fee: None,
+            min_tasks_per_agent: None,
+            agents_eject_threshold: None,
+            gas_price: None,
+            proxy_callback_gas: None,
+            slot_granularity: None,
+            dead_task_bounty: None,
+            max_rules_per_task: None,
+            slash_destination: None,
+            low_balance_pause_threshold: None,
+            min_active_agents_for_execution: Some(min_active_agents_for_execution),
+            max_submsgs_per_proxy_call: None,
+            protocol_fee: None,
+            native_denom: None,
+            task_creation_fee: None,
+            nomination_paused: None,
+            reward_vesting_seconds: None,
+            max_future_slot_seconds: None,
+            randomized_agent_selection: None,
+            max_tasks_per_owner: None,
+            dust_threshold: None,
+            dust_swap_contract: None,
+            min_active_agents: None,
+            min_agent_version: None,
+            removal_grace_slots: None,
+            eject_to_pending: None,
+            max_task_limit: None,
+            agent_allowlist_enabled: None,
+            max_task_gas: None,
+            min_task_balance: None,
+        };
+        app.execute_contract(
+            Addr::unchecked(ADMIN),
+            contract_addr.clone(),
+            &settings_msg(2),
+            &[],
+        )
+        .unwrap();
+
+        let msg = CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: contract_addr.to_string(),
+            msg: to_binary(&ExecuteMsg::WithdrawReward { amount: None })?,
+            funds: coins(1, NATIVE_DENOM),
+        });
+        let create_task_msg = ExecuteMsg::CreateTask {
+            task: TaskRequest {
+                interval: Interval::Immediate,
+                boundary: None,
+                stop_on_fail: false,
+                actions: vec![Action {
+                    msg,
+                    gas_limit: Some(250_000),
+                }],
+                rules: None,
+                metadata: None,
+                successor: None,
+                action_order: None,
+            },
+        };
+        app.execute_contract(
+            Addr::unchecked(ADMIN),
+            contract_addr.clone(),
+            &create_task_msg,
+            &coins(500_016, NATIVE_DENOM),
+        )
+        .unwrap();
+
+        // a single active agent is below the minimum
+        let msg = ExecuteMsg::RegisterAgent {
+            payable_account_id: Some(Addr::unchecked(AGENT1_BENEFICIARY)),
+        };
+        app.execute_contract(Addr::unchecked(AGENT0), contract_addr.clone(), &msg, &[])
+            .unwrap();
+        app.update_block(add_little_time);
+
+        let res_err = app
+            .execute_contract(
+                Addr::unchecked(AGENT0),
+                contract_addr.clone(),
+                &ExecuteMsg::ProxyCall {
+                    reward_to: None,
+                    agent_version: None,
+                    task_hash: None,
+                },
+                &[],
+            )
+            .unwrap_err();
+        assert_eq!(
+            ContractError::CustomError {
+                val: "Not enough active agents to execute tasks: 1 of 2 required".to_string()
+            },
+            res_err.downcast().unwrap()
+        );
+
+        // lowering the minimum allows execution again
+        app.execute_contract(
+            Addr::unchecked(ADMIN),
+            contract_addr.clone(),
+            &settings_msg(1),
+            &[],
+        )
+        .unwrap();
+        app.execute_contract(
+            Addr::unchecked(AGENT0),
+            contract_addr.clone(),
+            &ExecuteMsg::ProxyCall {
+                reward_to: None,
+                agent_version: None,
+                task_hash: None,
+            },
+            &[],
+        )
+        .unwrap();
+
+        Ok(())
+    }
+
+    #[test]
+    fn proxy_call_skips_complex_task() -> StdResult<()> {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+        app.execute_contract(
+            Addr::unchecked(ADMIN),
+            contract_addr.clone(),
+            &ExecuteMsg::UpdateSettings {
+                paused: None,
+                treasury_id: None,
+                agent_fee: None,
+                min_tasks_per_agent: None,
+                agents_eject_threshold: None,
+                gas_price: None,
+                proxy_callback_gas: None,
+                slot_granularity: None,
+                dead_task_bounty: None,
+                max_rules_per_task: None,
+                slash_destination: None,
+                low_balance_pause_threshold: None,
+                min_active_agents_for_execution: None,
+                max_submsgs_per_proxy_call: Some(1),
+                protocol_fee: None,
+                native_denom: None,
+                task_creation_fee: None,
+                nomination_paused: None,
+                reward_vesting_seconds: None,
+                max_future_slot_seconds: None,
+                randomized_agent_selection: None,
+                max_tasks_per_owner: None,
+                dust_threshold: None,
+                dust_swap_contract: None,
+                min_active_agents: None,
+                min_agent_version: None,
+                removal_grace_slots: None,
+                eject_to_pending: None,
+                max_task_limit: None,
+                agent_allowlist_enabled: None,
+                max_task_gas: None,
+                min_task_balance: None,
+            },
+            &[],
+        )
+        .unwrap();
+
+        // two actions don't fit in a single proxy_call anymore
+        let action = |amount| Action {
+            msg: StakingMsg::Delegate {
+                validator: "you".to_string(),
+                amount: coin(amount, NATIVE_DENOM),
+            }
+            .into(),
+            gas_limit: Some(150_000),
+        };
+        let res = app
+            .execute_contract(
+                Addr::unchecked(ANYONE),
+                contract_addr.clone(),
+                &ExecuteMsg::CreateTask {
+                    task: TaskRequest {
+                        interval: Interval::Immediate,
+                        boundary: None,
+                        stop_on_fail: false,
+                        actions: vec![action(1), action(2)],
+                        rules: None,
+                        metadata: None,
+                        successor: None,
+                        action_order: None,
+                    },
+                },
+                &coins(600_022, NATIVE_DENOM),
+            )
+            .unwrap();
+        let task_hash = res
+            .events
+            .iter()
+            .flat_map(|e| e.attributes.iter())
+            .find(|a| a.key == "task_hash")
+            .map(|a| a.value.clone())
+            .unwrap();
+
+        let msg = ExecuteMsg::RegisterAgent {
+            payable_account_id: Some(Addr::unchecked(AGENT1_BENEFICIARY)),
+        };
+        app.execute_contract(Addr::unchecked(AGENT0), contract_addr.clone(), &msg, &[])
+            .unwrap();
+        app.update_block(add_little_time);
+
+        let res = app
+            .execute_contract(
+                Addr::unchecked(AGENT0),
+                contract_addr.clone(),
+                &ExecuteMsg::ProxyCall {
+                    reward_to: None,
+                    agent_version: None,
+                    task_hash: None,
+                },
+                &[],
+            )
+            .unwrap();
+        let attr = |key: &str| {
+            res.events
+                .iter()
+                .filter(|e| e.ty == "wasm")
+                .flat_map(|e| e.attributes.iter())
+                .find(|a| a.key == key)
+                .map(|a| a.value.clone())
+        };
+        assert_eq!(attr("skipped_task"), Some(task_hash.clone()));
+        assert_eq!(
+            attr("skip_reason"),
+            Some("max_submsgs_per_proxy_call".to_string())
+        );
+        // nothing got executed
+        assert!(!res.events.iter().any(|e| e.ty == "delegate"));
+
+        // the agent still got the base reward
+        let agent: Option<AgentResponse> = app.wrap().query_wasm_smart(
+            &contract_addr,
+            &QueryMsg::GetAgent {
+                account_id: Addr::unchecked(AGENT0),
+            },
+        )?;
+        let agent = agent.unwrap();
+        assert_eq!(agent.balance.native, coins(5, NATIVE_DENOM));
+        assert_eq!(agent.status, AgentStatus::Active);
+
+        // the task waits for its next slot
+        let task: Option<TaskResponse> = app
+            .wrap()
+            .query_wasm_smart(&contract_addr, &QueryMsg::GetTask { task_hash })?;
+        assert!(task.is_some());
+        let slots: GetSlotIdsResponse = app
+            .wrap()
+            .query_wasm_smart(&contract_addr, &QueryMsg::GetSlotIds {})?;
+        assert_eq!(slots.block_ids, vec![12347]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn proxy_callback_spawns_successor() -> StdResult<()> {
+        let mut deps = mock_dependencies();
+        let store = CwCroncat::default();
+        mock_init(&store, deps.as_mut())?;
+
+        let action = Action {
+            msg: CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: "alice".to_string(),
+                msg: to_binary("eat")?,
+                funds: vec![],
+            }),
+            gas_limit: Some(150_000),
+        };
+        let successor = TaskRequest {
+            interval: Interval::Immediate,
+            boundary: Some(Boundary::Height {
+                start: None,
+                end: Some(12_346u64.into()),
+            }),
+            stop_on_fail: false,
+            actions: vec![action.clone()],
+            rules: None,
+            metadata: None,
+            successor: None,
+            action_order: None,
+        };
+        // Boundary already passed, so the task ends on this callback
+        let task = Task {
+            owner_id: Addr::unchecked(ANYONE),
+            interval: Interval::Immediate,
+            boundary: BoundaryValidated {
+                start: None,
+                end: Some(12_000),
+                max_executions: None,
+            },
+            stop_on_fail: false,
+            total_deposit: coins(300_016, NATIVE_DENOM),
+            actions: vec![action],
+            rules: None,
+            metadata: None,
+            successor: Some(Box::new(successor)),
+            generation: 0,
+            action_order: ActionOrder::Sequential,
+            total_cw20_deposit: vec![],
+        };
+        let succeeded = || Reply {
+            id: 1,
+            result: SubMsgResult::Ok(SubMsgResponse {
+                events: vec![],
+                data: None,
+            }),
+        };
+        let tasks = |storage: &dyn Storage| -> Vec<Task> {
+            store
+                .tasks
+                .range(storage, None, None, cosmwasm_std::Order::Ascending)
+                .map(|res| res.unwrap().1)
+                .collect()
+        };
+
+        store
+            .tasks
+            .save(&mut deps.storage, task.to_hash_vec(), &task)?;
+        let res = store
+            .proxy_callback(deps.as_mut(), mock_env(), succeeded(), task.to_hash_vec())
+            .unwrap();
+        assert!(res
+            .attributes
+            .iter()
+            .any(|a| a.key == "ended_task" && a.value == task.to_hash()));
+        // The remaining balance moves over, nothing gets refunded
+        assert!(res.messages.is_empty());
+
+        let spawned = tasks(&deps.storage);
+        assert_eq!(spawned.len(), 1);
+        assert_eq!(spawned[0].generation, 1);
+        assert_eq!(spawned[0].total_deposit, coins(300_016, NATIVE_DENOM));
+        assert!(res
+            .attributes
+            .iter()
+            .any(|a| a.key == "task_hash" && a.value == spawned[0].to_hash()));
+
+        // The successor has no successor of its own, it just ends
+        let mut env = mock_env();
+        env.block.height += 2;
+        let res = store
+            .proxy_callback(deps.as_mut(), env, succeeded(), spawned[0].to_hash_vec())
+            .unwrap();
+        assert!(res
+            .attributes
+            .iter()
+            .any(|a| a.key == "ended_task" && a.value == spawned[0].to_hash()));
+        assert!(tasks(&deps.storage).is_empty());
+
+        // Chains stop growing at the generation cap
+        let capped = Task {
+            generation: MAX_TASK_GENERATION,
+            ..task
+        };
+        store
+            .tasks
+            .save(&mut deps.storage, capped.to_hash_vec(), &capped)?;
+        store
+            .proxy_callback(deps.as_mut(), mock_env(), succeeded(), capped.to_hash_vec())
+            .unwrap();
+        assert!(tasks(&deps.storage).is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn proxy_call_collects_protocol_fee() -> StdResult<()> {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+        app.execute_contract(
+            Addr::unchecked(ADMIN),
+            contract_addr.clone(),
+            &ExecuteMsg::UpdateSettings {
+                paused: None,
+                treasury_id: None,
+                agent_fee: None,
+                min_tasks_per_agent: None,
+                agents_eject_threshold: None,
+                gas_price: None,
+                proxy_callback_gas: None,
+                slot_granularity: None,
+                dead_task_bounty: None,
+                max_rules_per_task: None,
+                slash_destination: None,
+                low_balance_pause_threshold: None,
+                min_active_agents_for_execution: None,
+                max_submsgs_per_proxy_call: None,
+                protocol_fee: Some(coin(7, NATIVE_DENOM)),
+                native_denom: None,
+                task_creation_fee: None,
+                nomination_paused: None,
+                reward_vesting_seconds: None,
+                max_future_slot_seconds: None,
+                randomized_agent_selection: None,
+                max_tasks_per_owner: None,
+                dust_threshold: None,
+                dust_swap_contract: None,
+                min_active_agents: None,
+                min_agent_version: None,
+                removal_grace_slots: None,
+                eject_to_pending: None,
+                max_task_limit: None,
+                agent_allowlist_enabled: None,
+                max_task_gas: None,
+                min_task_balance: None,
+            },
+            &[],
+        )
+        .unwrap();
+
+        let msg = CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: contract_addr.to_string(),
+            msg: to_binary(&ExecuteMsg::WithdrawReward { amount: None })?,
+            funds: coins(1, NATIVE_DENOM),
+        });
+        let res = app
+            .execute_contract(
+                Addr::unchecked(ADMIN),
+                contract_addr.clone(),
+                &ExecuteMsg::CreateTask {
+                    task: TaskRequest {
+                        interval: Interval::Immediate,
+                        boundary: None,
+                        stop_on_fail: false,
+                        actions: vec![Action {
+                            msg,
+                            gas_limit: Some(250_000),
+                        }],
+                        rules: None,
+                        metadata: None,
+                        successor: None,
+                        action_order: None,
+                    },
+                },
+                &coins(500_016, NATIVE_DENOM),
+            )
+            .unwrap();
+        let task_hash = res
+            .events
+            .iter()
+            .flat_map(|e| e.attributes.iter())
+            .find(|a| a.key == "task_hash")
+            .map(|a| a.value.clone())
+            .unwrap();
+
+        let msg = ExecuteMsg::RegisterAgent {
+            payable_account_id: Some(Addr::unchecked(AGENT1_BENEFICIARY)),
+        };
+        app.execute_contract(Addr::unchecked(AGENT0), contract_addr.clone(), &msg, &[])
+            .unwrap();
+        app.execute_contract(contract_addr.clone(), contract_addr.clone(), &msg, &[])
+            .unwrap();
+
+        // Every execution takes the fee from the task and adds it to the collected total
+        let mut deposit_before = 500_016;
+        for run in 1..=2u128 {
+            app.update_block(add_little_time);
+            let res = app
+                .execute_contract(
+                    Addr::unchecked(AGENT0),
+                    contract_addr.clone(),
+                    &ExecuteMsg::ProxyCall {
+                        reward_to: None,
+                        agent_version: None,
+                        task_hash: None,
+                    },
+                    &[],
+                )
+                .unwrap();
+
+            let balances: GetBalancesResponse = app
+                .wrap()
+                .query_wasm_smart(&contract_addr, &QueryMsg::GetBalances {})?;
+            assert_eq!(
+                balances.protocol_fees_collected.native,
+                coins(7 * run, NATIVE_DENOM)
+            );
+            let task: Option<TaskResponse> = app.wrap().query_wasm_smart(
+                &contract_addr,
+                &QueryMsg::GetTask {
+                    task_hash: task_hash.clone(),
+                },
+            )?;
+            let deposit_after = task.unwrap().total_deposit;
+            // The action sends 1 along, that comes out of the task balance as well
+            assert_eq!(deposit_after, coins(500_016 - 8 * run, NATIVE_DENOM));
+
+            // The reported spend matches what the task balance went down by
+            let funds_spent = res
+                .events
+                .iter()
+                .flat_map(|e| e.attributes.iter())
+                .find(|a| a.key == "funds_spent")
+                .map(|a| a.value.clone())
+                .unwrap();
+            assert_eq!(
+                funds_spent,
+                coin(
+                    deposit_before - deposit_after[0].amount.u128(),
+                    NATIVE_DENOM
+                )
+                .to_string()
+            );
+            // And so does the cost breakdown
+            let breakdown: Option<GetExecutionCostBreakdownResponse> =
+                app.wrap().query_wasm_smart(
+                    &contract_addr,
+                    &QueryMsg::GetExecutionCostBreakdown {
+                        task_hash: task_hash.clone(),
+                    },
+                )?;
+            assert_eq!(
+                breakdown.unwrap().total,
+                coins(
+                    deposit_before - deposit_after[0].amount.u128(),
+                    NATIVE_DENOM
+                )
+            );
+            deposit_before = deposit_after[0].amount.u128();
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn proxy_call_action_order() -> StdResult<()> {
+        for (action_order, withdraws) in [(ActionOrder::Sequential, 1), (ActionOrder::Unordered, 2)]
+        {
+            let (mut app, cw_template_contract) = proper_instantiate();
+            let contract_addr = cw_template_contract.addr();
+
+            // The middle action calls a contract that doesn't exist
+            let withdraw = Action {
+                msg: CosmosMsg::Wasm(WasmMsg::Execute {
+                    contract_addr: contract_addr.to_string(),
+                    msg: to_binary(&ExecuteMsg::WithdrawReward { amount: None })?,
+                    funds: vec![],
+                }),
+                gas_limit: Some(150_000),
+            };
+            let failing = Action {
+                msg: CosmosMsg::Wasm(WasmMsg::Execute {
+                    contract_addr: "nonexistent".to_string(),
+                    msg: to_binary(&ExecuteMsg::WithdrawReward { amount: None })?,
+                    funds: vec![],
+                }),
+                gas_limit: Some(150_000),
+            };
+            app.execute_contract(
+                Addr::unchecked(ADMIN),
+                contract_addr.clone(),
+                &ExecuteMsg::CreateTask {
+                    task: TaskRequest {
+                        interval: Interval::Once,
+                        boundary: Some(Boundary::Height {
+                            start: Some(app.block_info().height.into()),
+                            end: None,
+                        }),
+                        stop_on_fail: false,
+                        actions: vec![withdraw.clone(), failing, withdraw],
+                        rules: None,
+                        metadata: None,
+                        successor: None,
+                        action_order: Some(action_order),
+                    },
+                },
+                &coins(1_000_000, NATIVE_DENOM),
+            )
+            .unwrap();
+
+            let msg = ExecuteMsg::RegisterAgent {
+                payable_account_id: Some(Addr::unchecked(AGENT1_BENEFICIARY)),
+            };
+            app.execute_contract(Addr::unchecked(AGENT0), contract_addr.clone(), &msg, &[])
+                .unwrap();
+            app.execute_contract(contract_addr.clone(), contract_addr.clone(), &msg, &[])
+                .unwrap();
+
+            app.update_block(add_little_time);
+            let res = app
+                .execute_contract(
+                    Addr::unchecked(AGENT0),
+                    contract_addr.clone(),
+                    &ExecuteMsg::ProxyCall {
+                        reward_to: None,
+                        agent_version: None,
+                        task_hash: None,
+                    },
+                    &[],
+                )
+                .unwrap();
+
+            // A sequential task stops at the failure, an unordered one runs the last action anyway
+            let attrs: Vec<_> = res
+                .events
+                .iter()
+                .flat_map(|e| e.attributes.iter())
+                .collect();
+            assert_eq!(
+                attrs
+                    .iter()
+                    .filter(|a| a.key == "method" && a.value == "withdraw_agent_balance")
+                    .count(),
+                withdraws
+            );
+            // Either way the task is done once, after its last reply
+            assert_eq!(
+                attrs
+                    .iter()
+                    .filter(|a| a.key == "method" && a.value == "proxy_callback")
+                    .count(),
+                1
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn proxy_call_ends_task_after_execution_count() -> StdResult<()> {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+
+        let res = app
+            .execute_contract(
+                Addr::unchecked(ADMIN),
+                contract_addr.clone(),
+                &ExecuteMsg::CreateTask {
+                    task: TaskRequest {
+                        interval: Interval::Immediate,
+                        boundary: Some(Boundary::Executions { count: 2u64.into() }),
+                        stop_on_fail: false,
+                        actions: vec![Action {
+                            msg: CosmosMsg::Wasm(WasmMsg::Execute {
+                                contract_addr: contract_addr.to_string(),
+                                msg: to_binary(&ExecuteMsg::WithdrawReward { amount: None })?,
+                                funds: vec![],
+                            }),
+                            gas_limit: Some(150_000),
+                        }],
+                        rules: None,
+                        metadata: None,
+                        successor: None,
+                        action_order: None,
+                    },
+                },
+                &coins(1_000_000, NATIVE_DENOM),
+            )
+            .unwrap();
+        let task_hash = res
+            .events
+            .iter()
+            .flat_map(|e| e.attributes.iter())
+            .find(|a| a.key == "task_hash")
+            .map(|a| a.value.clone())
+            .unwrap();
+
+        let msg = ExecuteMsg::RegisterAgent {
+            payable_account_id: Some(Addr::unchecked(AGENT1_BENEFICIARY)),
+        };
+        app.execute_contract(Addr::unchecked(AGENT0), contract_addr.clone(), &msg, &[])
+            .unwrap();
+        app.execute_contract(contract_addr.clone(), contract_addr.clone(), &msg, &[])
+            .unwrap();
+
+        let proxy_call = |app: &mut App| -> Vec<(String, String)> {
+            app.update_block(add_little_time);
+            app.execute_contract(
+                Addr::unchecked(AGENT0),
+                contract_addr.clone(),
+                &ExecuteMsg::ProxyCall {
+                    reward_to: None,
+                    agent_version: None,
+                    task_hash: None,
+                },
+                &[],
+            )
+            .unwrap()
+            .events
+            .iter()
+            .flat_map(|e| e.attributes.iter())
+            .map(|a| (a.key.clone(), a.value.clone()))
+            .collect()
+        };
+        let ended = |attrs: &[(String, String)]| attrs.iter().any(|(key, _)| key == "ended_task");
+
+        let executions_remaining = |app: &App| -> StdResult<Option<u64>> {
+            let task: Option<TaskResponse> = app.wrap().query_wasm_smart(
+                &contract_addr,
+                &QueryMsg::GetTask {
+                    task_hash: task_hash.clone(),
+                },
+            )?;
+            Ok(task.unwrap().executions_remaining)
+        };
+        assert_eq!(executions_remaining(&app)?, Some(2));
+
+        // The first run reschedules the task
+        assert!(!ended(&proxy_call(&mut app)));
+        assert_eq!(executions_remaining(&app)?, Some(1));
+        let owner_balance = app.wrap().query_balance(ADMIN, NATIVE_DENOM)?.amount;
+
+        // The second one ends it, the remaining balance goes back to the owner
+        assert!(ended(&proxy_call(&mut app)));
+        let task: Option<TaskResponse> = app
+            .wrap()
+            .query_wasm_smart(&contract_addr, &QueryMsg::GetTask { task_hash })?;
+        assert!(task.is_none());
+        assert!(app.wrap().query_balance(ADMIN, NATIVE_DENOM)?.amount > owner_balance);
+
+        Ok(())
+    }
+
+    #[test]
+    fn proxy_call_action_over_sends_task_balance() -> StdResult<()> {
+        for stop_on_fail in [false, true] {
+            let (mut app, cw_template_contract) = proper_instantiate();
+            let contract_addr = cw_template_contract.addr();
+
+            // The final action sends more than the task will ever hold
+            let action = |amount: u128| Action {
+                msg: CosmosMsg::Wasm(WasmMsg::Execute {
+                    contract_addr: contract_addr.to_string(),
+                    msg: to_binary(&ExecuteMsg::WithdrawReward { amount: None }).unwrap(),
+                    funds: coins(amount, NATIVE_DENOM),
+                }),
+                gas_limit: Some(150_000),
+            };
+            let res = app
+                .execute_contract(
+                    Addr::unchecked(ADMIN),
+                    contract_addr.clone(),
+                    &ExecuteMsg::CreateTask {
+                        task: TaskRequest {
+                            interval: Interval::Immediate,
+                            boundary: None,
+                            stop_on_fail,
+                            actions: vec![action(1), action(1_000_000)],
+                            rules: None,
+                            metadata: None,
+                            successor: None,
+                            action_order: None,
+                        },
+                    },
+                    &coins(600_022, NATIVE_DENOM),
+                )
+                .unwrap();
+            let task_hash = res
+                .events
+                .iter()
+                .flat_map(|e| e.attributes.iter())
+                .find(|a| a.key == "task_hash")
+                .map(|a| a.value.clone())
+                .unwrap();
+
+            let msg = ExecuteMsg::RegisterAgent {
+                payable_account_id: Some(Addr::unchecked(AGENT1_BENEFICIARY)),
+            };
+            app.execute_contract(Addr::unchecked(AGENT0), contract_addr.clone(), &msg, &[])
+                .unwrap();
+            app.execute_contract(contract_addr.clone(), contract_addr.clone(), &msg, &[])
+                .unwrap();
+
+            app.update_block(add_little_time);
+            let res = app
+                .execute_contract(
+                    Addr::unchecked(AGENT0),
+                    contract_addr.clone(),
+                    &ExecuteMsg::ProxyCall {
+                        reward_to: None,
+                        agent_version: None,
+                        task_hash: None,
+                    },
+                    &[],
+                )
+                .unwrap();
+            let attrs: Vec<_> = res
+                .events
+                .iter()
+                .flat_map(|e| e.attributes.iter())
+                .collect();
+            assert!(attrs
+                .iter()
+                .any(|a| a.key == "skipped_action" && a.value == "1"));
+
+            let task: Option<TaskResponse> = app.wrap().query_wasm_smart(
+                &contract_addr,
+                &QueryMsg::GetTask {
+                    task_hash: task_hash.clone(),
+                },
+            )?;
+            if stop_on_fail {
+                // The task can't run as defined anymore, so it ends with the rest refunded
+                assert!(task.is_none());
+                assert!(attrs
+                    .iter()
+                    .any(|a| a.key == "ended_task" && a.value == task_hash));
+            } else {
+                // Only the first action got paid for, the task keeps running
+                assert_eq!(task.unwrap().total_deposit, coins(600_021, NATIVE_DENOM));
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn proxy_call_while_nomination_paused() -> StdResult<()> {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+        let settings_msg = |nomination_paused: bool| ExecuteMsg::UpdateSettings {
+            paused: None,
+            treasury_id: None,
+            agent_fee: None,
+            min_tasks_per_agent: None,
+            agents_eject_threshold: None,
+            gas_price: None,
+            proxy_callback_gas: None,
+            slot_granularity: None,
+            dead_task_bounty: None,
+            max_rules_per_task: None,
+            slash_destination: None,
+            low_balance_pause_threshold: None,
+            min_active_agents_for_execution: None,
+            max_submsgs_per_proxy_call: None,
+            protocol_fee: None,
+            native_denom: None,
+            task_creation_fee: None,
+            nomination_paused: Some(nomination_paused),
+            reward_vesting_seconds: None,
+            max_future_slot_seconds: None,
+            randomized_agent_selection: None,
+            max_tasks_per_owner: None,
+            dust_threshold: None,
+            dust_swap_contract: None,
+            min_active_agents: None,
+            min_agent_version: None,
+            removal_grace_slots: None,
+            eject_to_pending: None,
+            max_task_limit: None,
+            agent_allowlist_enabled: None,
+            max_task_gas: None,
+            min_task_balance: None,
+        };
+
+        let msg = CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: contract_addr.to_string(),
+            msg: to_binary(&ExecuteMsg::WithdrawReward { amount: None })?,
+            funds: coins(1, NATIVE_DENOM),
+        });
+        app.execute_contract(
+            Addr::unchecked(ADMIN),
+            contract_addr.clone(),
+            &ExecuteMsg::CreateTask {
+                task: TaskRequest {
+                    interval: Interval::Immediate,
+                    boundary: None,
+                    stop_on_fail: false,
+                    actions: vec![Action {
+                        msg,
+                        gas_limit: Some(250_000),
+                    }],
+                    rules: None,
+                    metadata: None,
+                    successor: None,
+                    action_order: None,
+                },
+            },
+            &coins(500_016, NATIVE_DENOM),
+        )
+        .unwrap();
+
+        let msg = ExecuteMsg::RegisterAgent {
+            payable_account_id: Some(Addr::unchecked(AGENT1_BENEFICIARY)),
+        };
+        app.execute_contract(Addr::unchecked(AGENT0), contract_addr.clone(), &msg, &[])
+            .unwrap();
+        app.execute_contract(contract_addr.clone(), contract_addr.clone(), &msg, &[])
+            .unwrap();
+
+        app.execute_contract(
+            Addr::unchecked(ADMIN),
+            contract_addr.clone(),
+            &settings_msg(true),
+            &[],
+        )
+        .unwrap();
+
+        // Pending agents can't check in
+        let res_err = app
+            .execute_contract(
+                contract_addr.clone(),
+                contract_addr.clone(),
+                &ExecuteMsg::CheckInAgent {},
+                &[],
+            )
+            .unwrap_err();
+        assert_eq!(
+            ContractError::CustomError {
+                val: "Agent nomination paused".to_string()
+            },
+            res_err.downcast().unwrap()
+        );
+
+        // Active agents keep executing
+        app.update_block(add_little_time);
+        let res = app
+            .execute_contract(
+                Addr::unchecked(AGENT0),
+                contract_addr.clone(),
+                &ExecuteMsg::ProxyCall {
+                    reward_to: None,
+                    agent_version: None,
+                    task_hash: None,
+                },
+                &[],
+            )
+            .unwrap();
+        assert!(res
+            .events
+            .iter()
+            .flat_map(|e| e.attributes.iter())
+            .any(|a| a.key == "task_hash"));
+
+        // Unpausing goes back to the regular nomination rules
+        app.execute_contract(
+            Addr::unchecked(ADMIN),
+            contract_addr.clone(),
+            &settings_msg(false),
+            &[],
+        )
+        .unwrap();
+        let res_err = app
+            .execute_contract(
+                contract_addr.clone(),
+                contract_addr.clone(),
+                &ExecuteMsg::CheckInAgent {},
+                &[],
+            )
+            .unwrap_err();
+        assert_eq!(
+            ContractError::CustomError {
+                val: "Not accepting new agents".to_string()
+            },
+            res_err.downcast().unwrap()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn proxy_call_reward_to_agent() -> StdResult<()> {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+        let relayer = Addr::unchecked("relayer");
+
+        // a skipped task still pays the base reward
+        app.execute_contract(
+            Addr::unchecked(ADMIN),
+            contract_addr.clone(),
+            &ExecuteMsg::UpdateSettings {
+                paused: None,
+                treasury_id: None,
+                agent_fee: None,
+                min_tasks_per_agent: None,
+                agents_eject_threshold: None,
+                gas_price: None,
+                proxy_callback_gas: None,
+                slot_granularity: None,
+                dead_task_bounty: None,
+                max_rules_per_task: None,
+                slash_destination: None,
+                low_balance_pause_threshold: None,
+                min_active_agents_for_execution: None,
+                max_submsgs_per_proxy_call: Some(1),
+                protocol_fee: None,
+                native_denom: None,
+                task_creation_fee: None,
+                nomination_paused: None,
+                reward_vesting_seconds: None,
+                max_future_slot_seconds: None,
+                randomized_agent_selection: None,
+                max_tasks_per_owner: None,
+                dust_threshold: None,
+                dust_swap_contract: None,
+                min_active_agents: None,
+                min_agent_version: None,
+                removal_grace_slots: None,
+                eject_to_pending: None,
+                max_task_limit: None,
+                agent_allowlist_enabled: None,
+                max_task_gas: None,
+                min_task_balance: None,
+            },
+            &[],
+        )
+        .unwrap();
+        let action = |amount| Action {
+            msg: StakingMsg::Delegate {
+                validator: "you".to_string(),
+                amount: coin(amount, NATIVE_DENOM),
+            }
+            .into(),
+            gas_limit: Some(150_000),
+        };
+        app.execute_contract(
+            Addr::unchecked(ANYONE),
+            contract_addr.clone(),
+            &ExecuteMsg::CreateTask {
+                task: TaskRequest {
+                    interval: Interval::Immediate,
+                    boundary: None,
+                    stop_on_fail: false,
+                    actions: vec![action(1), action(2)],
+                    rules: None,
+                    metadata: None,
+                    successor: None,
+                    action_order: None,
+                },
+            },
+            &coins(600_022, NATIVE_DENOM),
+        )
+        .unwrap();
+        app.execute_contract(
+            Addr::unchecked(AGENT0),
+            contract_addr.clone(),
+            &ExecuteMsg::RegisterAgent {
+                payable_account_id: Some(Addr::unchecked(AGENT1_BENEFICIARY)),
+            },
+            &[],
+        )
+        .unwrap();
+        app.update_block(add_little_time);
+
+        let relayed_call = ExecuteMsg::ProxyCall {
+            reward_to: Some(Addr::unchecked(AGENT0)),
+            agent_version: None,
+            task_hash: None,
+        };
+
+        // the agent has to allow the relayer first
+        let res_err = app
+            .execute_contract(relayer.clone(), contract_addr.clone(), &relayed_call, &[])
+            .unwrap_err();
+        assert_eq!(ContractError::Unauthorized {}, res_err.downcast().unwrap());
+
+        app.execute_contract(
+            Addr::unchecked(AGENT0),
+            contract_addr.clone(),
+            &ExecuteMsg::SetAgentRelayer {
+                relayer: Some(relayer.clone()),
+            },
+            &[],
+        )
+        .unwrap();
+        let res = app
+            .execute_contract(relayer.clone(), contract_addr.clone(), &relayed_call, &[])
+            .unwrap();
+        assert!(res
+            .events
+            .iter()
+            .flat_map(|e| e.attributes.iter())
+            .any(|a| a.key == "agent" && a.value == AGENT0));
+
+        // the reward went to the agent, not the relayer
+        let agent: Option<AgentResponse> = app.wrap().query_wasm_smart(
+            &contract_addr,
+            &QueryMsg::GetAgent {
+                account_id: Addr::unchecked(AGENT0),
+            },
+        )?;
+        assert_eq!(agent.unwrap().balance.native, coins(5, NATIVE_DENOM));
+        let relayer_agent: Option<AgentResponse> = app.wrap().query_wasm_smart(
+            &contract_addr,
+            &QueryMsg::GetAgent {
+                account_id: relayer,
+            },
+        )?;
+        assert_eq!(relayer_agent, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn proxy_call_skips_task_when_rule_query_fails() -> StdResult<()> {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+
+        // The rule points at a contract that doesn't exist, so querying it errors
+        let res = app
+            .execute_contract(
+                Addr::unchecked(ADMIN),
+                contract_addr.clone(),
+                &ExecuteMsg::CreateTask {
+                    task: TaskRequest {
+                        interval: Interval::Immediate,
+                        boundary: None,
+                        stop_on_fail: false,
+                        actions: vec![Action {
+                            msg: CosmosMsg::Wasm(WasmMsg::Execute {
+                                contract_addr: contract_addr.to_string(),
+                                msg: to_binary(&ExecuteMsg::WithdrawReward { amount: None })?,
+                                funds: vec![],
+                            }),
+                            gas_limit: Some(150_000),
+                        }],
+                        rules: Some(vec![Rule::Query {
+                            contract_addr: Addr::unchecked("nonexistent"),
+                            msg: to_binary(&"ready")?,
+                        }]),
+                        metadata: None,
+                        successor: None,
+                        action_order: None,
+                    },
+                },
+                &coins(1_000_000, NATIVE_DENOM),
+            )
+            .unwrap();
+        let task_hash = res
+            .events
+            .iter()
+            .flat_map(|e| e.attributes.iter())
+            .find(|a| a.key == "task_hash")
+            .map(|a| a.value.clone())
+            .unwrap();
+
+        let msg = ExecuteMsg::RegisterAgent {
+            payable_account_id: Some(Addr::unchecked(AGENT1_BENEFICIARY)),
+        };
+        app.execute_contract(Addr::unchecked(AGENT0), contract_addr.clone(), &msg, &[])
+            .unwrap();
+
+        // The agent's call goes through, the task is skipped without touching its balance
+        app.update_block(add_little_time);
+        let res = app
+            .execute_contract(
+                Addr::unchecked(AGENT0),
+                contract_addr.clone(),
+                &ExecuteMsg::ProxyCall {
+                    reward_to: None,
+                    agent_version: None,
+                    task_hash: None,
+                },
+                &[],
+            )
+            .unwrap();
+        let attrs: Vec<_> = res
+            .events
+            .iter()
+            .flat_map(|e| e.attributes.iter())
+            .collect();
+        assert!(attrs
+            .iter()
+            .any(|a| a.key == "skipped_task" && a.value == task_hash));
+        assert!(attrs
+            .iter()
+            .any(|a| a.key == "skip_reason" && a.value == "rule_query_failed"));
+        assert!(attrs
+            .iter()
+            .any(|a| a.key == "rule_index" && a.value == "0"));
+
+        let task: Option<TaskResponse> = app
+            .wrap()
+            .query_wasm_smart(&contract_addr, &QueryMsg::GetTask { task_hash })?;
+        assert_eq!(task.unwrap().total_deposit, coins(1_000_000, NATIVE_DENOM));
+
+        Ok(())
+    }
+
+    #[test]
+    fn proxy_call_checks_native_balance_rule() -> StdResult<()> {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+
+        // ANYONE starts out with 500_000, short of what the rule asks for
+        let res = app
+            .execute_contract(
+                Addr::unchecked(ADMIN),
+                contract_addr.clone(),
+                &ExecuteMsg::CreateTask {
+                    task: TaskRequest {
+                        interval: Interval::Immediate,
+                        boundary: None,
+                        stop_on_fail: false,
+                        actions: vec![Action {
+                            msg: CosmosMsg::Wasm(WasmMsg::Execute {
+                                contract_addr: contract_addr.to_string(),
+                                msg: to_binary(&ExecuteMsg::WithdrawReward { amount: None })?,
+                                funds: vec![],
+                            }),
+                            gas_limit: Some(150_000),
+                        }],
+                        rules: Some(vec![Rule::NativeBalanceGte {
+                            address: Addr::unchecked(ANYONE),
+                            required: coin(600_000, NATIVE_DENOM),
+                        }]),
+                        metadata: None,
+                        successor: None,
+                        action_order: None,
+                    },
+                },
+                &coins(1_000_000, NATIVE_DENOM),
+            )
+            .unwrap();
+        let task_hash = res
+            .events
+            .iter()
+            .flat_map(|e| e.attributes.iter())
+            .find(|a| a.key == "task_hash")
+            .map(|a| a.value.clone())
+            .unwrap();
+
+        let msg = ExecuteMsg::RegisterAgent {
+            payable_account_id: Some(Addr::unchecked(AGENT1_BENEFICIARY)),
+        };
+        app.execute_contract(Addr::unchecked(AGENT0), contract_addr.clone(), &msg, &[])
+            .unwrap();
+        app.execute_contract(contract_addr.clone(), contract_addr.clone(), &msg, &[])
+            .unwrap();
+
+        let proxy_call = |app: &mut App| -> Vec<(String, String)> {
+            app.update_block(add_little_time);
+            app.execute_contract(
+                Addr::unchecked(AGENT0),
+                contract_addr.clone(),
+                &ExecuteMsg::ProxyCall {
+                    reward_to: None,
+                    agent_version: None,
+                    task_hash: None,
+                },
+                &[],
+            )
+            .unwrap()
+            .events
+            .iter()
+            .flat_map(|e| e.attributes.iter())
+            .map(|a| (a.key.clone(), a.value.clone()))
+            .collect()
+        };
+        let get_deposit = |app: &App| -> StdResult<Vec<Coin>> {
+            let task: Option<TaskResponse> = app.wrap().query_wasm_smart(
+                &contract_addr,
+                &QueryMsg::GetTask {
+                    task_hash: task_hash.clone(),
+                },
+            )?;
+            Ok(task.unwrap().total_deposit)
+        };
+
+        // Skipped without charging the task
+        let attrs = proxy_call(&mut app);
+        assert!(attrs.contains(&("skipped_task".to_string(), task_hash.clone())));
+        assert!(attrs.contains(&("skip_reason".to_string(), "rule_not_satisfied".to_string())));
+        assert_eq!(get_deposit(&app)?, coins(1_000_000, NATIVE_DENOM));
+
+        // Once the balance is there, the task runs
+        app.send_tokens(
+            Addr::unchecked(ADMIN),
+            Addr::unchecked(ANYONE),
+            &coins(100_000, NATIVE_DENOM),
+        )
+        .unwrap();
+        let attrs = proxy_call(&mut app);
+        assert!(attrs.contains(&("task_hash".to_string(), task_hash.clone())));
+        assert!(attrs.iter().all(|(key, _)| key != "skip_reason"));
+        assert_ne!(get_deposit(&app)?, coins(1_000_000, NATIVE_DENOM));
+
+        Ok(())
+    }
+
+    #[test]
+    fn proxy_call_sends_ibc_transfer() -> StdResult<()> {
+        let mut deps = mock_dependencies();
+        let mut store = CwCroncat::default();
+        mock_init(&store, deps.as_mut())?;
+
+        let agent = Addr::unchecked(AGENT0);
+        store.agents.save(
+            &mut deps.storage,
+            agent.clone(),
+            &Agent {
+                payable_account_id: Addr::unchecked(AGENT1_BENEFICIARY),
+                balance: GenericBalance::default(),
+                total_tasks_executed: 0,
+                last_missed_slot: 0,
+                register_start: mock_env().block.time,
+                last_executed_slot: 0,
+            },
+        )?;
+        store
+            .agent_active_queue
+            .save(&mut deps.storage, &vec![agent.clone()])?;
+
+        // Timeouts are relative: 20 blocks and 10 minutes after the transfer runs
+        let transfer = |timeout: IbcTimeout| {
+            CosmosMsg::Ibc(IbcMsg::Transfer {
+                channel_id: "channel-0".to_string(),
+                to_address: "cosmos1receiver".to_string(),
+                amount: coin(1_000, NATIVE_DENOM),
+                timeout,
+            })
+        };
+        let relative_timeout = IbcTimeout::with_both(
+            IbcTimeoutBlock {
+                revision: 1,
+                height: 20,
+            },
+            Timestamp::from_seconds(600),
+        );
+        store
+            .execute(
+                deps.as_mut(),
+                mock_env(),
+                mock_info(ANYONE, &coins(400_000, NATIVE_DENOM)),
+                ExecuteMsg::CreateTask {
+                    task: TaskRequest {
+                        interval: Interval::Immediate,
+                        boundary: None,
+                        stop_on_fail: false,
+                        actions: vec![Action {
+                            msg: transfer(relative_timeout),
+                            gas_limit: Some(150_000),
+                        }],
+                        rules: None,
+                        metadata: None,
+                        successor: None,
+                        action_order: None,
+                    },
+                },
+            )
+            .unwrap();
+
+        let mut env = mock_env();
+        env.block.height += 1;
+        env.block.time = env.block.time.plus_seconds(6);
+        let res = store
+            .execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info(AGENT0, &[]),
+                ExecuteMsg::ProxyCall {
+                    reward_to: None,
+                    agent_version: None,
+                    task_hash: None,
+                },
+            )
+            .unwrap();
+        assert_eq!(res.messages.len(), 1);
+        assert_eq!(
+            res.messages[0].msg,
+            transfer(IbcTimeout::with_both(
+                IbcTimeoutBlock {
+                    revision: 1,
+                    height: env.block.height + 20,
+                },
+                env.block.time.plus_seconds(600),
+            ))
+        );
+        assert_eq!(res.messages[0].gas_limit, Some(150_000));
+
+        Ok(())
+    }
+
+    #[test]
+    fn proxy_call_rejects_outdated_agent() -> StdResult<()> {
+        let mut deps = mock_dependencies();
+        let mut store = CwCroncat::default();
+        mock_init(&store, deps.as_mut())?;
+        store
+            .config
+            .update(&mut deps.storage, |mut c| -> StdResult<_> {
+                c.min_agent_version = Some("1.2.0".to_string());
+                Ok(c)
+            })?;
+
+        let agent = Addr::unchecked(AGENT0);
+        store.agents.save(
+            &mut deps.storage,
+            agent.clone(),
+            &Agent {
+                payable_account_id: agent.clone(),
+                balance: GenericBalance::default(),
+                total_tasks_executed: 0,
+                last_missed_slot: 0,
+                register_start: mock_env().block.time,
+                last_executed_slot: 0,
+            },
+        )?;
+        store
+            .agent_active_queue
+            .save(&mut deps.storage, &vec![agent])?;
+        store
+            .execute(
+                deps.as_mut(),
+                mock_env(),
+                mock_info(ANYONE, &coins(400_000, NATIVE_DENOM)),
+                ExecuteMsg::CreateTask {
+                    task: TaskRequest {
+                        interval: Interval::Immediate,
+                        boundary: None,
+                        stop_on_fail: false,
+                        actions: vec![Action {
+                            msg: StakingMsg::Delegate {
+                                validator: "you".to_string(),
+                                amount: coin(1, NATIVE_DENOM),
+                            }
+                            .into(),
+                            gas_limit: Some(150_000),
+                        }],
+                        rules: None,
+                        metadata: None,
+                        successor: None,
+                        action_order: None,
+                    },
+                },
+            )
+            .unwrap();
+
+        let mut env = mock_env();
+        env.block.height += 1;
+        let mut proxy_call = |agent_version: Option<&str>| {
+            store.execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info(AGENT0, &[]),
+                ExecuteMsg::ProxyCall {
+                    reward_to: None,
+                    agent_version: agent_version.map(ToString::to_string),
+                    task_hash: None,
+                },
+            )
+        };
+
+        assert_eq!(
+            proxy_call(Some("1.1.9")).unwrap_err(),
+            ContractError::CustomError {
+                val: "Agent version 1.1.9 is outdated, please upgrade to 1.2.0 or newer"
+                    .to_string()
+            }
+        );
+        assert_eq!(
+            proxy_call(None).unwrap_err(),
+            ContractError::CustomError {
+                val: "Agent version unknown is outdated, please upgrade to 1.2.0 or newer"
+                    .to_string()
+            }
+        );
+        let res = proxy_call(Some("1.10.0")).unwrap();
+        assert_eq!(res.messages.len(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn proxy_call_holds_exhausted_task_for_grace_slots() -> StdResult<()> {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+        app.execute_contract(
+            Addr::unchecked(ADMIN),
+            contract_addr.clone(),
+            &ExecuteMsg::UpdateSettings {
+                paused: None,
+                treasury_id: None,
+                agent_fee: None,
+                min_tasks_per_agent: None,
+                agents_eject_threshold: None,
+                gas_price: None,
+                proxy_callback_gas: None,
+                slot_granularity: None,
+                dead_task_bounty: None,
+                max_rules_per_task: None,
+                slash_destination: None,
+                low_balance_pause_threshold: None,
+                min_active_agents_for_execution: None,
+                max_submsgs_per_proxy_call: None,
+                protocol_fee: None,
+                native_denom: None,
+                task_creation_fee: None,
+                nomination_paused: None,
+                reward_vesting_seconds: None,
+                max_future_slot_seconds: None,
+                randomized_agent_selection: None,
+                max_tasks_per_owner: None,
+                dust_threshold: None,
+                dust_swap_contract: None,
+                min_active_agents: None,
+                min_agent_version: None,
+                removal_grace_slots: Some(2),
+                eject_to_pending: None,
+                max_task_limit: None,
+                agent_allowlist_enabled: None,
+                max_task_gas: None,
+                min_task_balance: None,
+            },
+            &[],
+        )
+        .unwrap();
+
+        // Every run sends more than the task was created with
+        let res = app
+            .execute_contract(
+                Addr::unchecked(ADMIN),
+                contract_addr.clone(),
+                &ExecuteMsg::CreateTask {
+                    task: TaskRequest {
+                        interval: Interval::Block(1),
+                        boundary: None,
+                        stop_on_fail: true,
+                        actions: vec![Action {
+                            msg: CosmosMsg::Wasm(WasmMsg::Execute {
+                                contract_addr: contract_addr.to_string(),
+                                msg: to_binary(&ExecuteMsg::WithdrawReward { amount: None })?,
+                                funds: coins(400_000, NATIVE_DENOM),
+                            }),
+                            gas_limit: Some(150_000),
+                        }],
+                        rules: None,
+                        metadata: None,
+                        successor: None,
+                        action_order: None,
+                    },
+                },
+                &coins(300_016, NATIVE_DENOM),
+            )
+            .unwrap();
+        let task_hash = res
+            .events
+            .iter()
+            .flat_map(|e| e.attributes.iter())
+            .find(|a| a.key == "task_hash")
+            .map(|a| a.value.clone())
+            .unwrap();
+
+        let msg = ExecuteMsg::RegisterAgent {
+            payable_account_id: Some(Addr::unchecked(AGENT1_BENEFICIARY)),
+        };
+        app.execute_contract(Addr::unchecked(AGENT0), contract_addr.clone(), &msg, &[])
+            .unwrap();
+        app.execute_contract(contract_addr.clone(), contract_addr.clone(), &msg, &[])
+            .unwrap();
+
+        let proxy_call = |app: &mut App| -> Vec<(String, String)> {
+            app.update_block(add_little_time);
+            let res = app
+                .execute_contract(
+                    Addr::unchecked(AGENT0),
+                    contract_addr.clone(),
+                    &ExecuteMsg::ProxyCall {
+                        reward_to: None,
+                        agent_version: None,
+                        task_hash: None,
+                    },
+                    &[],
+                )
+                .unwrap();
+            res.events
+                .into_iter()
+                .flat_map(|e| e.attributes)
+                .map(|a| (a.key, a.value))
+                .collect()
+        };
+        let has_attr = |attrs: &[(String, String)], key: &str, value: &str| {
+            attrs.iter().any(|(k, v)| k == key && v == value)
+        };
+        let task_exists = |app: &App| -> bool {
+            let task: Option<TaskResponse> = app
+                .wrap()
+                .query_wasm_smart(
+                    &contract_addr,
+                    &QueryMsg::GetTask {
+                        task_hash: task_hash.clone(),
+                    },
+                )
+                .unwrap();
+            task.is_some()
+        };
+
+        // Running out of funds holds the task instead of removing it
+        let attrs = proxy_call(&mut app);
+        assert!(has_attr(&attrs, "out_of_funds", &task_hash));
+        assert!(has_attr(&attrs, "grace_slots", "2"));
+        assert!(task_exists(&app));
+
+        // Held tasks don't run
+        let attrs = proxy_call(&mut app);
+        assert!(has_attr(&attrs, "skip_reason", "out_of_funds"));
+        assert!(has_attr(&attrs, "grace_slots", "1"));
+
+        // A refill that still can't cover a run keeps the task held
+        let refill = |app: &mut App, amount: u128| {
+            app.execute_contract(
+                Addr::unchecked(ADMIN),
+                contract_addr.clone(),
+                &ExecuteMsg::RefillTaskBalance {
+                    task_hash: task_hash.clone(),
+                },
+                &coins(amount, NATIVE_DENOM),
+            )
+            .unwrap();
+        };
+        refill(&mut app, 1);
+        let attrs = proxy_call(&mut app);
+        assert!(has_attr(&attrs, "skip_reason", "out_of_funds"));
+        assert!(has_attr(&attrs, "grace_slots", "0"));
+
+        // A refill within the grace lets the task run again
+        refill(&mut app, 300_000);
+        let attrs = proxy_call(&mut app);
+        assert!(has_attr(&attrs, "task_hash", &task_hash));
+        assert!(!attrs.iter().any(|(k, _)| k == "skip_reason"));
+
+        // Without a refill the task is removed once its grace slots are used up
+        let attrs = proxy_call(&mut app);
+        assert!(has_attr(&attrs, "out_of_funds", &task_hash));
+        for grace_slots in ["1", "0"] {
+            let attrs = proxy_call(&mut app);
+            assert!(has_attr(&attrs, "grace_slots", grace_slots));
+            assert!(task_exists(&app));
+        }
+        let attrs = proxy_call(&mut app);
+        assert!(has_attr(&attrs, "ended_task", &task_hash));
+        assert!(!task_exists(&app));
+
+        Ok(())
+    }
+
+    #[test]
+    fn proxy_call_no_op_task() -> StdResult<()> {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+
+        let res = app
+            .execute_contract(
+                Addr::unchecked(ADMIN),
+                contract_addr.clone(),
+                &ExecuteMsg::CreateTask {
+                    task: TaskRequest {
+                        interval: Interval::Block(1),
+                        boundary: None,
+                        stop_on_fail: false,
+                        actions: vec![Action::no_op(Some(100_000))],
+                        rules: None,
+                        metadata: None,
+                        successor: None,
+                        action_order: None,
+                    },
+                },
+                &coins(300_000, NATIVE_DENOM),
+            )
+            .unwrap();
+        let task_hash = res
+            .events
+            .iter()
+            .flat_map(|e| e.attributes.iter())
+            .find(|a| a.key == "task_hash")
+            .map(|a| a.value.clone())
+            .unwrap();
+        app.execute_contract(
+            Addr::unchecked(AGENT0),
+            contract_addr.clone(),
+            &ExecuteMsg::RegisterAgent {
+                payable_account_id: Some(Addr::unchecked(AGENT1_BENEFICIARY)),
+            },
+            &[],
+        )
+        .unwrap();
+
+        app.update_block(add_little_time);
+        let res = app
+            .execute_contract(
+                Addr::unchecked(AGENT0),
+                contract_addr.clone(),
+                &ExecuteMsg::ProxyCall {
+                    reward_to: None,
+                    agent_version: None,
+                    task_hash: None,
+                },
+                &[],
+            )
+            .unwrap();
+        let attrs: Vec<_> = res
+            .events
+            .iter()
+            .flat_map(|e| e.attributes.iter())
+            .collect();
+        assert!(attrs.iter().any(|a| a.key == "no_op" && a.value == "true"));
+        assert!(attrs
+            .iter()
+            .any(|a| a.key == "funds_spent" && a.value == "100005atom"));
+        // Nothing got dispatched
+        assert_eq!(res.events.iter().filter(|e| e.ty == "reply").count(), 0);
+
+        // Only the agent fee and the gas came out of the task balance
+        let task: Option<TaskResponse> = app.wrap().query_wasm_smart(
+            &contract_addr,
+            &QueryMsg::GetTask {
+                task_hash: task_hash.clone(),
+            },
+        )?;
+        assert_eq!(task.unwrap().total_deposit, coins(199_995, NATIVE_DENOM));
+        let breakdown: Option<GetExecutionCostBreakdownResponse> = app.wrap().query_wasm_smart(
+            &contract_addr,
+            &QueryMsg::GetExecutionCostBreakdown {
+                task_hash: task_hash.clone(),
+            },
+        )?;
+        assert_eq!(breakdown.unwrap().total, coins(100_005, NATIVE_DENOM));
+        let agent: Option<AgentResponse> = app.wrap().query_wasm_smart(
+            &contract_addr,
+            &QueryMsg::GetAgent {
+                account_id: Addr::unchecked(AGENT0),
+            },
+        )?;
+        assert_eq!(agent.unwrap().balance.native, coins(5, NATIVE_DENOM));
+
+        // It's scheduled again like any successful run
+        let slots: GetSlotIdsResponse = app
+            .wrap()
+            .query_wasm_smart(&contract_addr, &QueryMsg::GetSlotIds {})?;
+        assert_eq!(slots.block_ids, vec![app.block_info().height + 1]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn proxy_call_ends_once_task() -> StdResult<()> {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+
+        let res = app
+            .execute_contract(
+                Addr::unchecked(ADMIN),
+                contract_addr.clone(),
+                &ExecuteMsg::CreateTask {
+                    task: TaskRequest {
+                        interval: Interval::Once,
+                        boundary: Some(Boundary::Height {
+                            start: Some(app.block_info().height.into()),
+                            end: None,
+                        }),
+                        stop_on_fail: false,
+                        actions: vec![Action {
+                            msg: CosmosMsg::Wasm(WasmMsg::Execute {
+                                contract_addr: contract_addr.to_string(),
+                                msg: to_binary(&ExecuteMsg::WithdrawReward { amount: None })?,
+                                funds: vec![],
+                            }),
+                            gas_limit: Some(150_000),
+                        }],
+                        rules: None,
+                        metadata: None,
+                        successor: None,
+                        action_order: None,
+                    },
+                },
+                &coins(1_000_000, NATIVE_DENOM),
+            )
+            .unwrap();
+        let task_hash = res
+            .events
+            .iter()
+            .flat_map(|e| e.attributes.iter())
+            .find(|a| a.key == "task_hash")
+            .map(|a| a.value.clone())
+            .unwrap();
+
+        let msg = ExecuteMsg::RegisterAgent {
+            payable_account_id: Some(Addr::unchecked(AGENT1_BENEFICIARY)),
+        };
+        app.execute_contract(Addr::unchecked(AGENT0), contract_addr.clone(), &msg, &[])
+            .unwrap();
+        app.execute_contract(contract_addr.clone(), contract_addr.clone(), &msg, &[])
+            .unwrap();
+
+        app.update_block(add_little_time);
+        let res = app
+            .execute_contract(
+                Addr::unchecked(AGENT0),
+                contract_addr.clone(),
+                &ExecuteMsg::ProxyCall {
+                    reward_to: None,
+                    agent_version: None,
+                    task_hash: None,
+                },
+                &[],
+            )
+            .unwrap();
+        assert!(res
+            .events
+            .iter()
+            .flat_map(|e| e.attributes.iter())
+            .any(|a| a.key == "ended_task" && a.value == task_hash));
+
+        // Nothing is left to run
+        let task: Option<TaskResponse> = app
+            .wrap()
+            .query_wasm_smart(&contract_addr, &QueryMsg::GetTask { task_hash })?;
+        assert!(task.is_none());
+        let tasks: Vec<TaskResponse> = app.wrap().query_wasm_smart(
+            &contract_addr,
+            &QueryMsg::GetTasks {
+                from_index: None,
+                limit: None,
+                include_status: None,
+            },
+        )?;
+        assert!(tasks.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn proxy_call_ends_expired_task() -> StdResult<()> {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+
+        // Runs every minute, but only for the next 90 seconds
+        let end = app.block_info().time.plus_seconds(90);
+        let res = app
+            .execute_contract(
+                Addr::unchecked(ADMIN),
+                contract_addr.clone(),
+                &ExecuteMsg::CreateTask {
+                    task: TaskRequest {
+                        interval: Interval::Cron("0 * * * * *".to_string()),
+                        boundary: Some(Boundary::Time {
+                            start: None,
+                            end: Some(end),
+                        }),
+                        stop_on_fail: false,
+                        actions: vec![Action {
+                            msg: CosmosMsg::Wasm(WasmMsg::Execute {
+                                contract_addr: contract_addr.to_string(),
+                                msg: to_binary(&ExecuteMsg::WithdrawReward { amount: None })?,
+                                funds: vec![],
+                            }),
+                            gas_limit: Some(150_000),
+                        }],
+                        rules: None,
+                        metadata: None,
+                        successor: None,
+                        action_order: None,
+                    },
+                },
+                &coins(1_000_000, NATIVE_DENOM),
+            )
+            .unwrap();
+        let task_hash = res
+            .events
+            .iter()
+            .flat_map(|e| e.attributes.iter())
+            .find(|a| a.key == "task_hash")
+            .map(|a| a.value.clone())
+            .unwrap();
+
+        let msg = ExecuteMsg::RegisterAgent {
+            payable_account_id: Some(Addr::unchecked(AGENT1_BENEFICIARY)),
+        };
+        app.execute_contract(Addr::unchecked(AGENT0), contract_addr.clone(), &msg, &[])
+            .unwrap();
+
+        // The agent only shows up after the boundary end
+        app.update_block(|block| {
+            block.time = block.time.plus_seconds(3600);
+            block.height += 1;
+        });
+        let owner_balance = app.wrap().query_balance(ADMIN, NATIVE_DENOM)?.amount;
+        let res = app
+            .execute_contract(
+                Addr::unchecked(AGENT0),
+                contract_addr.clone(),
+                &ExecuteMsg::ProxyCall {
+                    reward_to: None,
+                    agent_version: None,
+                    task_hash: None,
+                },
+                &[],
+            )
+            .unwrap();
+        let attrs: Vec<_> = res
+            .events
+            .iter()
+            .flat_map(|e| e.attributes.iter())
+            .collect();
+        assert!(attrs
+            .iter()
+            .any(|a| a.key == "skip_reason" && a.value == "expired"));
+        assert!(attrs
+            .iter()
+            .any(|a| a.key == "ended_task" && a.value == task_hash));
+        assert!(!attrs
+            .iter()
+            .any(|a| a.key == "method" && a.value == "withdraw_agent_balance"));
+
+        // The task is gone and its deposit went back to the owner
+        let task: Option<TaskResponse> = app
+            .wrap()
+            .query_wasm_smart(&contract_addr, &QueryMsg::GetTask { task_hash })?;
+        assert!(task.is_none());
+        assert_eq!(
+            app.wrap().query_balance(ADMIN, NATIVE_DENOM)?.amount,
+            owner_balance + Uint128::new(1_000_000)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn proxy_call_targets_task_hash() -> StdResult<()> {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+
+        let create_task = |app: &mut App, interval: Interval, gas: u64| -> String {
+            let boundary = (interval == Interval::Once).then(|| Boundary::Height {
+                start: Some(app.block_info().height.into()),
+                end: None,
+            });
+            app.execute_contract(
+                Addr::unchecked(ADMIN),
+                contract_addr.clone(),
+                &ExecuteMsg::CreateTask {
+                    task: TaskRequest {
+                        interval,
+                        boundary,
+                        stop_on_fail: false,
+                        actions: vec![Action {
+                            msg: CosmosMsg::Wasm(WasmMsg::Execute {
+                                contract_addr: contract_addr.to_string(),
+                                msg: to_binary(&ExecuteMsg::WithdrawReward { amount: None })
+                                    .unwrap(),
+                                funds: vec![],
+                            }),
+                            gas_limit: Some(gas),
+                        }],
+                        rules: None,
+                        metadata: None,
+                        successor: None,
+                        action_order: None,
+                    },
+                },
+                &coins(1_000_000, NATIVE_DENOM),
+            )
+            .unwrap()
+            .events
+            .iter()
+            .flat_map(|e| e.attributes.iter())
+            .find(|a| a.key == "task_hash")
+            .map(|a| a.value.clone())
+            .unwrap()
+        };
+        let first = create_task(&mut app, Interval::Once, 150_000);
+        create_task(&mut app, Interval::Once, 160_000);
+        let hourly = create_task(&mut app, Interval::Cron("0 0 * * * *".to_string()), 170_000);
+
+        let msg = ExecuteMsg::RegisterAgent {
+            payable_account_id: Some(Addr::unchecked(AGENT1_BENEFICIARY)),
+        };
+        app.execute_contract(Addr::unchecked(AGENT0), contract_addr.clone(), &msg, &[])
+            .unwrap();
+        app.execute_contract(contract_addr.clone(), contract_addr.clone(), &msg, &[])
+            .unwrap();
+        app.update_block(add_little_time);
+
+        let proxy_call = |app: &mut App, task_hash: &str| {
+            app.execute_contract(
+                Addr::unchecked(AGENT0),
+                contract_addr.clone(),
+                &ExecuteMsg::ProxyCall {
+                    reward_to: None,
+                    agent_version: None,
+                    task_hash: Some(task_hash.to_string()),
+                },
+                &[],
+            )
+        };
+
+        // Unknown hashes and tasks scheduled for later are rejected
+        let err = proxy_call(&mut app, "not_a_task").unwrap_err();
+        assert_eq!(ContractError::NoTaskFound {}, err.downcast().unwrap());
+        let err = proxy_call(&mut app, &hourly).unwrap_err();
+        assert_eq!(
+            ContractError::CustomError {
+                val: "Task is not due yet".to_string()
+            },
+            err.downcast().unwrap()
+        );
+
+        // The targeted task runs, even though the slot would hand out the other one first
+        let res = proxy_call(&mut app, &first).unwrap();
+        let executed: Vec<_> = res
+            .events
+            .iter()
+            .flat_map(|e| e.attributes.iter())
+            .filter(|a| a.key == "task_hash")
+            .map(|a| a.value.clone())
+            .collect();
+        assert!(!executed.is_empty());
+        assert!(executed.iter().all(|hash| hash == &first));
+
+        // It left the slot, the other one is still waiting
+        let slots: GetSlotHashesResponse = app.wrap().query_wasm_smart(
+            &contract_addr,
+            &QueryMsg::GetSlotHashes {
+                slot: None,
+                limit: None,
+            },
+        )?;
+        assert_eq!(slots.block_task_hash.len(), 1);
+        assert!(!slots.block_task_hash.contains(&first));
+
+        Ok(())
+    }
+
+    #[test]
+    fn proxy_call_rejects_reentrant_execution() -> StdResult<()> {
+        let mut deps = mock_dependencies();
+        let mut store = CwCroncat::default();
+        mock_init(&store, deps.as_mut())?;
+
+        let agent = Addr::unchecked(AGENT0);
+        store.agents.save(
+            &mut deps.storage,
+            agent.clone(),
+            &Agent {
+                payable_account_id: agent.clone(),
+                balance: GenericBalance::default(),
+                total_tasks_executed: 0,
+                last_missed_slot: 0,
+                register_start: mock_env().block.time,
+                last_executed_slot: 0,
+            },
+        )?;
+        store
+            .agent_active_queue
+            .save(&mut deps.storage, &vec![agent])?;
+        let res = store
+            .execute(
+                deps.as_mut(),
+                mock_env(),
+                mock_info(ANYONE, &coins(400_000, NATIVE_DENOM)),
+                ExecuteMsg::CreateTask {
+                    task: TaskRequest {
+                        interval: Interval::Immediate,
+                        boundary: None,
+                        stop_on_fail: false,
+                        actions: vec![Action {
+                            msg: StakingMsg::Delegate {
+                                validator: "you".to_string(),
+                                amount: coin(1, NATIVE_DENOM),
+                            }
+                            .into(),
+                            gas_limit: Some(150_000),
+                        }],
+                        rules: None,
+                        metadata: None,
+                        successor: None,
+                        action_order: None,
+                    },
+                },
+            )
+            .unwrap();
+        let task_hash = res
+            .attributes
+            .iter()
+            .find(|a| a.key == "task_hash")
+            .map(|a| a.value.clone())
+            .unwrap();
+
+        let mut env = mock_env();
+        env.block.height += 1;
+        let mut proxy_call = |env: &Env, task_hash: Option<String>| {
+            store.execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info(AGENT0, &[]),
+                ExecuteMsg::ProxyCall {
+                    reward_to: None,
+                    agent_version: None,
+                    task_hash,
+                },
+            )
+        };
+        let res = proxy_call(&env, None).unwrap();
+        let reply_id = res.messages[0].id;
+
+        // The action calls back in before it replied, the task must not run twice
+        assert_eq!(
+            proxy_call(&env, Some(task_hash.clone())).unwrap_err(),
+            ContractError::CustomError {
+                val: "Task is already executing".to_string()
+            }
+        );
+
+        // The reply releases the task for its next slot
+        store
+            .reply(
+                deps.as_mut(),
+                env.clone(),
+                Reply {
+                    id: reply_id,
+                    result: SubMsgResult::Ok(SubMsgResponse {
+                        events: vec![],
+                        data: None,
+                    }),
+                },
+            )
+            .unwrap();
+        assert!(!store
+            .executing_tasks
+            .has(&deps.storage, task_hash.clone().into_bytes()));
+        env.block.height += 1;
+        store
+            .execute(
+                deps.as_mut(),
+                env,
+                mock_info(AGENT0, &[]),
+                ExecuteMsg::ProxyCall {
+                    reward_to: None,
+                    agent_version: None,
+                    task_hash: Some(task_hash),
+                },
+            )
+            .unwrap();
+
+        Ok(())
+    }
+
+    #[test]
+    fn proxy_next_action_releases_task_when_stopping_on_fail() -> StdResult<()> {
+        let mut deps = mock_dependencies();
+        let mut store = CwCroncat::default();
+        mock_init(&store, deps.as_mut())?;
+        store
+            .config
+            .update(&mut deps.storage, |mut c| -> StdResult<_> {
+                c.removal_grace_slots = 2;
+                Ok(c)
+            })?;
+
+        let agent = Addr::unchecked(AGENT0);
+        store.agents.save(
+            &mut deps.storage,
+            agent.clone(),
+            &Agent {
+                payable_account_id: agent.clone(),
+                balance: GenericBalance::default(),
+                total_tasks_executed: 0,
+                last_missed_slot: 0,
+                register_start: mock_env().block.time,
+                last_executed_slot: 0,
+            },
+        )?;
+        store
+            .agent_active_queue
+            .save(&mut deps.storage, &vec![agent])?;
+        let action = Action {
+            msg: CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: "some_contract".to_string(),
+                msg: to_binary(&"{}")?,
+                funds: coins(10, NATIVE_DENOM),
+            }),
+            gas_limit: Some(150_000),
+        };
+        let res = store
+            .execute(
+                deps.as_mut(),
+                mock_env(),
+                mock_info(ANYONE, &coins(1_000_000, NATIVE_DENOM)),
+                ExecuteMsg::CreateTask {
+                    task: TaskRequest {
+                        interval: Interval::Immediate,
+                        boundary: None,
+                        stop_on_fail: true,
+                        actions: vec![action.clone(), action],
+                        rules: None,
+                        metadata: None,
+                        successor: None,
+                        action_order: None,
+                    },
+                },
+            )
+            .unwrap();
+        let task_hash = res
+            .attributes
+            .iter()
+            .find(|a| a.key == "task_hash")
+            .map(|a| a.value.clone())
+            .unwrap();
+
+        // Enough left for the first action only
+        let mut task = store
+            .tasks
+            .load(&deps.storage, task_hash.clone().into_bytes())?;
+        task.total_deposit = coins(15, NATIVE_DENOM);
+        store
+            .tasks
+            .save(&mut deps.storage, task_hash.clone().into_bytes(), &task)?;
+
+        let mut env = mock_env();
+        env.block.height += 1;
+        let res = store
+            .execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info(AGENT0, &[]),
+                ExecuteMsg::ProxyCall {
+                    reward_to: None,
+                    agent_version: None,
+                    task_hash: None,
+                },
+            )
+            .unwrap();
+        assert!(store
+            .executing_tasks
+            .has(&deps.storage, task_hash.clone().into_bytes()));
+
+        // The second action can't be paid for, the task is held as exhausted and released
+        let res = store
+            .reply(
+                deps.as_mut(),
+                env,
+                Reply {
+                    id: res.messages[0].id,
+                    result: SubMsgResult::Ok(SubMsgResponse {
+                        events: vec![],
+                        data: None,
+                    }),
+                },
+            )
+            .unwrap();
+        assert!(res
+            .attributes
+            .iter()
+            .any(|a| a.key == "skip_reason" && a.value == "insufficient_task_balance"));
+        assert!(store
+            .exhausted_tasks
+            .has(&deps.storage, task_hash.clone().into_bytes()));
+        assert!(!store
+            .executing_tasks
+            .has(&deps.storage, task_hash.into_bytes()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn proxy_call_counts_completed_tasks() -> StdResult<()> {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+
+        for _ in 0..2 {
+            app.execute_contract(
+                Addr::unchecked(ADMIN),
+                contract_addr.clone(),
+                &ExecuteMsg::CreateTask {
+                    task: TaskRequest {
+                        interval: Interval::Once,
+                        boundary: Some(Boundary::Height {
+                            start: Some(app.block_info().height.into()),
+                            end: None,
+                        }),
+                        stop_on_fail: false,
+                        actions: vec![Action {
+                            msg: CosmosMsg::Wasm(WasmMsg::Execute {
+                                contract_addr: contract_addr.to_string(),
+                                msg: to_binary(&ExecuteMsg::WithdrawReward { amount: None })?,
+                                funds: vec![],
+                            }),
+                            gas_limit: Some(150_000),
+                        }],
+                        rules: None,
+                        metadata: None,
+                        successor: None,
+                        action_order: None,
+                    },
+                },
+                &coins(1_000_000, NATIVE_DENOM),
+            )
+            .unwrap();
+        }
+
+        let msg = ExecuteMsg::RegisterAgent {
+            payable_account_id: Some(Addr::unchecked(AGENT1_BENEFICIARY)),
+        };
+        app.execute_contract(Addr::unchecked(AGENT0), contract_addr.clone(), &msg, &[])
+            .unwrap();
+        app.execute_contract(contract_addr.clone(), contract_addr.clone(), &msg, &[])
+            .unwrap();
+
+        let get_agent = |app: &App| -> StdResult<AgentResponse> {
+            let agent: Option<AgentResponse> = app.wrap().query_wasm_smart(
+                &contract_addr,
+                &QueryMsg::GetAgent {
+                    account_id: Addr::unchecked(AGENT0),
+                },
+            )?;
+            Ok(agent.unwrap())
+        };
+        let agent = get_agent(&app)?;
+        assert_eq!(agent.total_tasks_executed, 0);
+        assert_eq!(agent.last_executed_slot, app.block_info().height);
+
+        let proxy_call_msg = ExecuteMsg::ProxyCall {
+            reward_to: None,
+            agent_version: None,
+            task_hash: None,
+        };
+        app.update_block(add_little_time);
+        for executed in 1..=2 {
+            app.execute_contract(
+                Addr::unchecked(AGENT0),
+                contract_addr.clone(),
+                &proxy_call_msg,
+                &[],
+            )
+            .unwrap();
+            let agent = get_agent(&app)?;
+            assert_eq!(agent.total_tasks_executed, executed);
+            assert_eq!(agent.last_executed_slot, app.block_info().height);
+        }
+
+        // Nothing left to run, the stats stay as they are
+        app.update_block(add_little_time);
+        app.execute_contract(
+            Addr::unchecked(AGENT0),
+            contract_addr.clone(),
+            &proxy_call_msg,
+            &[],
+        )
+        .unwrap_err();
+        assert_eq!(get_agent(&app)?.total_tasks_executed, 2);
+
         Ok(())
     }
 }
@@ -1,12 +1,16 @@
 use crate::error::ContractError;
-use crate::helpers::has_cw_coins;
+use crate::helpers::{has_cw_coins, parse_agent_version, GenericBalance};
 use crate::state::{Config, CwCroncat};
 use cosmwasm_std::{
-    has_coins, to_binary, Addr, BankMsg, Coin, Deps, DepsMut, Env, MessageInfo, Response,
-    StdResult, SubMsg, WasmMsg,
+    has_coins, to_binary, Addr, BankMsg, Coin, Deps, DepsMut, Env, MessageInfo, Order, Response,
+    StdResult, Storage, SubMsg, Uint128, WasmMsg,
 };
-use cw20::{Balance, Cw20ExecuteMsg};
-use cw_croncat_core::msg::{ExecuteMsg, GetBalancesResponse, GetConfigResponse};
+use cw20::{Balance, Cw20CoinVerified, Cw20ExecuteMsg};
+use cw_croncat_core::msg::{
+    BalanceReconciliation, ExecuteMsg, GetBalanceReconciliationResponse, GetBalancesResponse,
+    GetConfigResponse, GetPausedResponse,
+};
+use std::collections::BTreeMap;
 
 impl<'a> CwCroncat<'a> {
     pub(crate) fn query_config(&self, deps: Deps) -> StdResult<GetConfigResponse> {
@@ -14,7 +18,7 @@ impl<'a> CwCroncat<'a> {
         Ok(GetConfigResponse {
             paused: c.paused,
             owner_id: c.owner_id,
-            // treasury_id: c.treasury_id,
+            treasury_id: c.treasury_id,
             min_tasks_per_agent: c.min_tasks_per_agent,
             agent_active_indices: c.agent_active_indices,
             agents_eject_threshold: c.agents_eject_threshold,
@@ -23,19 +27,88 @@ impl<'a> CwCroncat<'a> {
             gas_price: c.gas_price,
             proxy_callback_gas: c.proxy_callback_gas,
             slot_granularity: c.slot_granularity,
+            dead_task_bounty: c.dead_task_bounty,
+            max_rules_per_task: c.max_rules_per_task,
+            slash_destination: c.slash_destination,
+            low_balance_pause_threshold: c.low_balance_pause_threshold,
+            min_active_agents_for_execution: c.min_active_agents_for_execution,
+            max_submsgs_per_proxy_call: c.max_submsgs_per_proxy_call,
+            protocol_fee: c.protocol_fee,
+            task_creation_fee: c.task_creation_fee,
+            nomination_paused: c.nomination_paused,
+            reward_vesting_seconds: c.reward_vesting_seconds,
+            task_creator_whitelist: c.task_creator_whitelist,
+            max_future_slot_seconds: c.max_future_slot_seconds,
+            randomized_agent_selection: c.randomized_agent_selection,
+            max_tasks_per_owner: c.max_tasks_per_owner,
+            dust_swap_contract: c.dust_swap_contract,
+            dust_threshold: c.dust_threshold,
+            min_active_agents: c.min_active_agents,
+            min_agent_version: c.min_agent_version,
+            removal_grace_slots: c.removal_grace_slots,
+            eject_to_pending: c.eject_to_pending,
+            max_task_limit: c.max_task_limit,
+            agent_allowlist_enabled: c.agent_allowlist_enabled,
+            max_task_gas: c.max_task_gas,
+            min_task_balance: c.min_task_balance,
         })
     }
 
+    /// The config is a single item, so it still gets loaded, only the response is smaller
+    pub(crate) fn query_paused(&self, deps: Deps) -> StdResult<GetPausedResponse> {
+        let c: Config = self.config.load(deps.storage)?;
+        Ok(GetPausedResponse { paused: c.paused })
+    }
+
     pub(crate) fn query_balances(&self, deps: Deps) -> StdResult<GetBalancesResponse> {
         let c: Config = self.config.load(deps.storage)?;
         Ok(GetBalancesResponse {
             native_denom: c.native_denom,
             available_balance: c.available_balance,
             staked_balance: c.staked_balance,
+            protocol_fees_collected: c.protocol_fees_collected,
             cw20_whitelist: c.cw20_whitelist,
         })
     }
 
+    /// Compares the tracked available balance to the contract's actual native balances,
+    /// any difference per denom points to an accounting bug
+    pub(crate) fn query_balance_reconciliation(
+        &self,
+        deps: Deps,
+        env: Env,
+    ) -> StdResult<GetBalanceReconciliationResponse> {
+        let c: Config = self.config.load(deps.storage)?;
+        let actual = deps.querier.query_all_balances(env.contract.address)?;
+
+        // (tracked, actual) per denom, sorted by denom
+        let mut denoms: BTreeMap<String, (Uint128, Uint128)> = BTreeMap::new();
+        // Staked funds are set aside in the ledger but still held by the contract
+        for coin in c
+            .available_balance
+            .native
+            .into_iter()
+            .chain(c.staked_balance.native)
+        {
+            denoms.entry(coin.denom).or_default().0 += coin.amount;
+        }
+        for coin in actual {
+            denoms.entry(coin.denom).or_default().1 += coin.amount;
+        }
+
+        let balances = denoms
+            .into_iter()
+            .map(|(denom, (tracked, actual))| BalanceReconciliation {
+                denom,
+                tracked,
+                actual,
+                surplus: actual.saturating_sub(tracked),
+                shortfall: tracked.saturating_sub(actual),
+            })
+            .collect();
+        Ok(GetBalanceReconciliationResponse { balances })
+    }
+
     /// Changes core configurations
     /// Should only be updated by owner -- in best case DAO based :)
     pub fn update_settings(
@@ -51,7 +124,6 @@ impl<'a> CwCroncat<'a> {
         }
         match payload {
             ExecuteMsg::UpdateSettings {
-                owner_id,
                 slot_granularity,
                 paused,
                 agent_fee,
@@ -59,20 +131,64 @@ impl<'a> CwCroncat<'a> {
                 proxy_callback_gas,
                 min_tasks_per_agent,
                 agents_eject_threshold,
-                // treasury_id,
+                dead_task_bounty,
+                max_rules_per_task,
+                slash_destination,
+                low_balance_pause_threshold,
+                min_active_agents_for_execution,
+                max_submsgs_per_proxy_call,
+                protocol_fee,
+                native_denom,
+                task_creation_fee,
+                nomination_paused,
+                reward_vesting_seconds,
+                max_future_slot_seconds,
+                randomized_agent_selection,
+                max_tasks_per_owner,
+                dust_threshold,
+                dust_swap_contract,
+                min_active_agents,
+                min_agent_version,
+                removal_grace_slots,
+                eject_to_pending,
+                max_task_limit,
+                agent_allowlist_enabled,
+                max_task_gas,
+                min_task_balance,
+                treasury_id,
             } => {
+                if let Some(version) = min_agent_version.as_deref() {
+                    if !version.is_empty() && parse_agent_version(version).is_none() {
+                        return Err(ContractError::CustomError {
+                            val: "Invalid min_agent_version, expected major.minor.patch"
+                                .to_string(),
+                        });
+                    }
+                }
+                // Accounting is kept in the native denom, so it can only change once drained
+                if let Some(native_denom) = &native_denom {
+                    let c: Config = self.config.load(deps.storage)?;
+                    if native_denom != &c.native_denom
+                        && self.holds_native_denom(deps.storage, &c)?
+                    {
+                        return Err(ContractError::CustomError {
+                            val: format!(
+                                "Cannot change native_denom while {} balances are held",
+                                c.native_denom
+                            ),
+                        });
+                    }
+                }
                 self.config
                     .update(deps.storage, |mut config| -> Result<_, ContractError> {
                         if info.sender != config.owner_id {
                             return Err(ContractError::Unauthorized {});
                         }
 
-                        if let Some(owner_id) = owner_id {
-                            config.owner_id = owner_id;
+                        if let Some(treasury_id) = treasury_id {
+                            config.treasury_id =
+                                Some(treasury_id).filter(|addr| !addr.as_str().is_empty());
                         }
-                        // if let Some(treasury_id) = treasury_id {
-                        //     config.treasury_id = Some(treasury_id);
-                        // }
 
                         if let Some(slot_granularity) = slot_granularity {
                             config.slot_granularity = slot_granularity;
@@ -95,6 +211,85 @@ impl<'a> CwCroncat<'a> {
                         if let Some(agents_eject_threshold) = agents_eject_threshold {
                             config.agents_eject_threshold = agents_eject_threshold;
                         }
+                        if let Some(dead_task_bounty) = dead_task_bounty {
+                            config.dead_task_bounty = dead_task_bounty;
+                        }
+                        if let Some(max_rules_per_task) = max_rules_per_task {
+                            config.max_rules_per_task = max_rules_per_task;
+                        }
+                        if let Some(slash_destination) = slash_destination {
+                            config.slash_destination = slash_destination;
+                        }
+                        if let Some(low_balance_pause_threshold) = low_balance_pause_threshold {
+                            config.low_balance_pause_threshold = low_balance_pause_threshold;
+                        }
+                        if let Some(min_active_agents_for_execution) =
+                            min_active_agents_for_execution
+                        {
+                            config.min_active_agents_for_execution =
+                                min_active_agents_for_execution;
+                        }
+                        if let Some(max_submsgs_per_proxy_call) = max_submsgs_per_proxy_call {
+                            config.max_submsgs_per_proxy_call = max_submsgs_per_proxy_call;
+                        }
+                        if let Some(protocol_fee) = protocol_fee {
+                            config.protocol_fee = protocol_fee;
+                        }
+                        if let Some(native_denom) = native_denom {
+                            config.native_denom = native_denom;
+                        }
+                        if let Some(task_creation_fee) = task_creation_fee {
+                            config.task_creation_fee =
+                                Some(task_creation_fee).filter(|fee| !fee.amount.is_zero());
+                        }
+                        if let Some(nomination_paused) = nomination_paused {
+                            config.nomination_paused = nomination_paused;
+                        }
+                        if let Some(reward_vesting_seconds) = reward_vesting_seconds {
+                            config.reward_vesting_seconds = reward_vesting_seconds;
+                        }
+                        if let Some(max_future_slot_seconds) = max_future_slot_seconds {
+                            config.max_future_slot_seconds = max_future_slot_seconds;
+                        }
+                        if let Some(randomized_agent_selection) = randomized_agent_selection {
+                            config.randomized_agent_selection = randomized_agent_selection;
+                        }
+                        if let Some(max_tasks_per_owner) = max_tasks_per_owner {
+                            config.max_tasks_per_owner =
+                                Some(max_tasks_per_owner).filter(|max| *max != 0);
+                        }
+                        if let Some(dust_threshold) = dust_threshold {
+                            config.dust_threshold = dust_threshold;
+                        }
+                        if let Some(dust_swap_contract) = dust_swap_contract {
+                            config.dust_swap_contract =
+                                Some(dust_swap_contract).filter(|addr| !addr.as_str().is_empty());
+                        }
+                        if let Some(min_active_agents) = min_active_agents {
+                            config.min_active_agents = min_active_agents;
+                        }
+                        if let Some(min_agent_version) = min_agent_version {
+                            config.min_agent_version =
+                                Some(min_agent_version).filter(|v| !v.is_empty());
+                        }
+                        if let Some(removal_grace_slots) = removal_grace_slots {
+                            config.removal_grace_slots = removal_grace_slots;
+                        }
+                        if let Some(eject_to_pending) = eject_to_pending {
+                            config.eject_to_pending = eject_to_pending;
+                        }
+                        if let Some(max_task_limit) = max_task_limit {
+                            config.max_task_limit = max_task_limit;
+                        }
+                        if let Some(agent_allowlist_enabled) = agent_allowlist_enabled {
+                            config.agent_allowlist_enabled = agent_allowlist_enabled;
+                        }
+                        if let Some(max_task_gas) = max_task_gas {
+                            config.max_task_gas = max_task_gas;
+                        }
+                        if let Some(min_task_balance) = min_task_balance {
+                            config.min_task_balance = min_task_balance;
+                        }
                         Ok(config)
                     })?;
             }
@@ -105,12 +300,12 @@ impl<'a> CwCroncat<'a> {
             .add_attribute("method", "update_settings")
             .add_attribute("paused", c.paused.to_string())
             .add_attribute("owner_id", c.owner_id.to_string())
-            // .add_attribute(
-            //     "treasury_id",
-            //     c.treasury_id
-            //         .unwrap_or_else(|| Addr::unchecked(""))
-            //         .to_string(),
-            // )
+            .add_attribute(
+                "treasury_id",
+                c.treasury_id
+                    .unwrap_or_else(|| Addr::unchecked(""))
+                    .to_string(),
+            )
             .add_attribute("min_tasks_per_agent", c.min_tasks_per_agent.to_string())
             .add_attribute(
                 "agent_active_indices",
@@ -127,11 +322,232 @@ impl<'a> CwCroncat<'a> {
             .add_attribute("agent_fee", c.agent_fee.to_string())
             .add_attribute("gas_price", c.gas_price.to_string())
             .add_attribute("proxy_callback_gas", c.proxy_callback_gas.to_string())
-            .add_attribute("slot_granularity", c.slot_granularity.to_string()))
+            .add_attribute("slot_granularity", c.slot_granularity.to_string())
+            .add_attribute("dead_task_bounty", c.dead_task_bounty.to_string())
+            .add_attribute("slash_destination", format!("{:?}", c.slash_destination)))
+    }
+
+    /// Propose a new owner, the transfer completes once the new owner accepts
+    /// Replaces any previous pending proposal
+    pub fn propose_new_owner(
+        &self,
+        deps: DepsMut,
+        info: MessageInfo,
+        addr: Addr,
+    ) -> Result<Response, ContractError> {
+        let config = self.config.load(deps.storage)?;
+        if info.sender != config.owner_id {
+            return Err(ContractError::Unauthorized {});
+        }
+        let addr = deps.api.addr_validate(addr.as_str())?;
+        self.pending_owner.save(deps.storage, &addr)?;
+
+        Ok(Response::new()
+            .add_attribute("method", "propose_new_owner")
+            .add_attribute("pending_owner", addr.to_string()))
+    }
+
+    /// Pending owner accepts the proposal and becomes the owner
+    pub fn accept_ownership(
+        &self,
+        deps: DepsMut,
+        info: MessageInfo,
+    ) -> Result<Response, ContractError> {
+        let pending_owner = self.pending_owner.may_load(deps.storage)?;
+        if pending_owner != Some(info.sender.clone()) {
+            return Err(ContractError::Unauthorized {});
+        }
+        self.config
+            .update(deps.storage, |mut config| -> StdResult<_> {
+                config.owner_id = info.sender.clone();
+                Ok(config)
+            })?;
+        self.pending_owner.remove(deps.storage);
+
+        Ok(Response::new()
+            .add_attribute("method", "accept_ownership")
+            .add_attribute("owner_id", info.sender.to_string()))
+    }
+
+    /// Owner withdraws a pending ownership proposal
+    pub fn cancel_ownership_proposal(
+        &self,
+        deps: DepsMut,
+        info: MessageInfo,
+    ) -> Result<Response, ContractError> {
+        let config = self.config.load(deps.storage)?;
+        if info.sender != config.owner_id {
+            return Err(ContractError::Unauthorized {});
+        }
+        if self.pending_owner.may_load(deps.storage)?.is_none() {
+            return Err(ContractError::CustomError {
+                val: "No pending owner".to_string(),
+            });
+        }
+        self.pending_owner.remove(deps.storage);
+
+        Ok(Response::new().add_attribute("method", "cancel_ownership_proposal"))
+    }
+
+    /// Allow more addresses to create tasks, this closes task creation to everyone else
+    pub fn add_task_creators(
+        &self,
+        deps: DepsMut,
+        info: MessageInfo,
+        addrs: Vec<Addr>,
+    ) -> Result<Response, ContractError> {
+        let mut config = self.config.load(deps.storage)?;
+        if info.sender != config.owner_id {
+            return Err(ContractError::Unauthorized {});
+        }
+        let mut whitelist = config.task_creator_whitelist.unwrap_or_default();
+        for addr in addrs {
+            let addr = deps.api.addr_validate(addr.as_str())?;
+            if !whitelist.contains(&addr) {
+                whitelist.push(addr);
+            }
+        }
+        config.task_creator_whitelist = Some(whitelist);
+        self.config.save(deps.storage, &config)?;
+
+        Ok(Response::new().add_attribute("method", "add_task_creators"))
+    }
+
+    /// Remove addresses from the task creators, an emptied whitelist opens task creation again
+    pub fn remove_task_creators(
+        &self,
+        deps: DepsMut,
+        info: MessageInfo,
+        addrs: Vec<Addr>,
+    ) -> Result<Response, ContractError> {
+        let mut config = self.config.load(deps.storage)?;
+        if info.sender != config.owner_id {
+            return Err(ContractError::Unauthorized {});
+        }
+        let mut whitelist = config.task_creator_whitelist.unwrap_or_default();
+        whitelist.retain(|addr| !addrs.contains(addr));
+        config.task_creator_whitelist = Some(whitelist).filter(|w| !w.is_empty());
+        self.config.save(deps.storage, &config)?;
+
+        Ok(Response::new().add_attribute("method", "remove_task_creators"))
+    }
+
+    /// Allow an agent to register while the agent allowlist is enabled
+    pub fn add_agent_to_allowlist(
+        &self,
+        deps: DepsMut,
+        info: MessageInfo,
+        agent_id: Addr,
+    ) -> Result<Response, ContractError> {
+        let config = self.config.load(deps.storage)?;
+        if info.sender != config.owner_id {
+            return Err(ContractError::Unauthorized {});
+        }
+        let agent_id = deps.api.addr_validate(agent_id.as_str())?;
+        let mut allowlist = self
+            .agent_allowlist
+            .may_load(deps.storage)?
+            .unwrap_or_default();
+        if !allowlist.contains(&agent_id) {
+            allowlist.push(agent_id.clone());
+        }
+        self.agent_allowlist.save(deps.storage, &allowlist)?;
+
+        Ok(Response::new()
+            .add_attribute("method", "add_agent_to_allowlist")
+            .add_attribute("agent_id", agent_id))
+    }
+
+    /// Take an agent off the allowlist, it stays registered if it already is
+    pub fn remove_agent_from_allowlist(
+        &self,
+        deps: DepsMut,
+        info: MessageInfo,
+        agent_id: Addr,
+    ) -> Result<Response, ContractError> {
+        let config = self.config.load(deps.storage)?;
+        if info.sender != config.owner_id {
+            return Err(ContractError::Unauthorized {});
+        }
+        let mut allowlist = self
+            .agent_allowlist
+            .may_load(deps.storage)?
+            .unwrap_or_default();
+        allowlist.retain(|addr| addr != &agent_id);
+        self.agent_allowlist.save(deps.storage, &allowlist)?;
+
+        Ok(Response::new()
+            .add_attribute("method", "remove_agent_from_allowlist")
+            .add_attribute("agent_id", agent_id))
+    }
+
+    /// Funds that must stay in the contract: rewards owed to agents and deposits of existing tasks
+    pub(crate) fn get_reserved_balance(&self, storage: &dyn Storage) -> StdResult<GenericBalance> {
+        let mut reserved = GenericBalance::default();
+        for agent in self.agents.range(storage, None, None, Order::Ascending) {
+            let (_, agent) = agent?;
+            reserved.add_tokens(Balance::from(agent.balance.native));
+            for token in agent.balance.cw20 {
+                reserved.add_tokens(Balance::Cw20(token));
+            }
+        }
+        for task in self.tasks.range(storage, None, None, Order::Ascending) {
+            let (_, task) = task?;
+            reserved.add_tokens(Balance::from(task.total_deposit));
+            for token in task.total_cw20_deposit {
+                reserved.add_tokens(Balance::Cw20(token));
+            }
+        }
+        Ok(reserved)
+    }
+
+    /// Checks if any available balance, task deposit or agent reward is in the native denom
+    pub(crate) fn holds_native_denom(&self, storage: &dyn Storage, c: &Config) -> StdResult<bool> {
+        let holds = |coins: &[Coin]| {
+            coins
+                .iter()
+                .any(|coin| coin.denom == c.native_denom && !coin.amount.is_zero())
+        };
+        if holds(&c.available_balance.native) {
+            return Ok(true);
+        }
+        for task in self.tasks.range(storage, None, None, Order::Ascending) {
+            if holds(&task?.1.total_deposit) {
+                return Ok(true);
+            }
+        }
+        for agent in self.agents.range(storage, None, None, Order::Ascending) {
+            if holds(&agent?.1.balance.native) {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Checks if the native available balance, minus task deposits, fell below the pause threshold
+    /// Scans every task, so it only runs when a threshold is set
+    pub(crate) fn is_balance_low(&self, storage: &dyn Storage, c: &Config) -> StdResult<bool> {
+        if c.low_balance_pause_threshold.is_zero() {
+            return Ok(false);
+        }
+        let native_amount = |coins: &[Coin]| -> Uint128 {
+            coins
+                .iter()
+                .find(|coin| coin.denom == c.native_denom)
+                .map(|coin| coin.amount)
+                .unwrap_or_default()
+        };
+        let mut committed = Uint128::zero();
+        for task in self.tasks.range(storage, None, None, Order::Ascending) {
+            let (_, task) = task?;
+            committed += native_amount(&task.total_deposit);
+        }
+        let uncommitted = native_amount(&c.available_balance.native).saturating_sub(committed);
+        Ok(uncommitted < c.low_balance_pause_threshold)
     }
 
     /// Move Balance
-    /// Allows owner to move balance to DAO or to let treasury transfer to itself only.
+    /// Allows owner or treasury to move balance between the two of them.
     /// This is a restricted method for moving funds utilized in growth management strategies.
     pub fn move_balances(
         &self,
@@ -143,23 +559,14 @@ impl<'a> CwCroncat<'a> {
     ) -> Result<Response, ContractError> {
         let mut config = self.config.load(deps.storage)?;
 
-        // // Check if is owner OR the treasury account making the transfer request
-        // if let Some(treasury_id) = config.treasury_id.clone() {
-        //     if treasury_id != info.sender && config.owner_id != info.sender {
-        //         return Err(ContractError::Unauthorized {});
-        //     }
-        // } else
-        if info.sender != config.owner_id {
+        // Check if is owner OR the treasury account making the transfer request
+        let is_treasury = |addr: &Addr| config.treasury_id.as_ref() == Some(addr);
+        if info.sender != config.owner_id && !is_treasury(&info.sender) {
             return Err(ContractError::Unauthorized {});
         }
 
-        // for now, only allow movement of funds between owner and treasury
-        // let check_account = config
-        //     .treasury_id
-        //     .clone()
-        //     .unwrap_or_else(|| config.owner_id.clone());
-        let check_account = config.owner_id.clone();
-        if check_account != account_id && config.owner_id != account_id {
+        // only allow movement of funds between owner and treasury
+        if account_id != config.owner_id && !is_treasury(&account_id) {
             return Err(ContractError::CustomError {
                 val: "Cannot move funds to this account".to_string(),
             });
@@ -170,14 +577,49 @@ impl<'a> CwCroncat<'a> {
         let state_balances = deps.querier.query_all_balances(&env.contract.address)?;
         let mut has_fund_err = false;
 
+        // Funds owed to agents and tasks can't be moved, neither can staked funds
+        let mut reserved = self.get_reserved_balance(deps.storage)?;
+        reserved.add_tokens(Balance::from(config.staked_balance.native.clone()));
+        for token in config.staked_balance.cw20.iter() {
+            reserved.add_tokens(Balance::Cw20(token.clone()));
+        }
+        // Staking takes cw20 out of the available balance, so count it back in as held
+        let mut held_cw20 = config.available_balance.clone();
+        for token in config.staked_balance.cw20.iter() {
+            held_cw20.add_tokens(Balance::Cw20(token.clone()));
+        }
+        let reserved_native = |denom: &str| -> Uint128 {
+            reserved
+                .native
+                .iter()
+                .find(|c| c.denom == denom)
+                .map(|c| c.amount)
+                .unwrap_or_default()
+        };
+        let reserved_cw20 = |address: &Addr| -> Uint128 {
+            reserved
+                .cw20
+                .iter()
+                .find(|c| &c.address == address)
+                .map(|c| c.amount)
+                .unwrap_or_default()
+        };
+
         let messages: Result<Vec<SubMsg>, ContractError> = balances
             .iter()
             .map(|balance| -> Result<SubMsg<_>, ContractError> {
                 match balance {
                     Balance::Native(balance) => {
-                        // check has enough
+                        // check has enough of every denom, on top of the reserve
                         let bal = balance.clone().into_vec();
-                        let has_c = has_coins(&state_balances, &bal[0]);
+                        let has_c = !bal.is_empty()
+                            && bal.iter().all(|coin| {
+                                let required = Coin {
+                                    denom: coin.denom.clone(),
+                                    amount: coin.amount + reserved_native(&coin.denom),
+                                };
+                                has_coins(&state_balances, &required)
+                            });
                         if !has_c {
                             has_fund_err = true;
                             // TODO: refactor to not need
@@ -187,19 +629,28 @@ impl<'a> CwCroncat<'a> {
                             }));
                         }
 
-                        // Update internal registry balance
-                        config
-                            .available_balance
-                            .minus_tokens(Balance::from(bal.clone()));
+                        // Update internal registry balance, per coin
+                        for coin in bal.iter() {
+                            config
+                                .available_balance
+                                .minus_tokens(Balance::from(vec![coin.clone()]));
+                        }
                         Ok(SubMsg::new(BankMsg::Send {
                             to_address: account_id.clone().into(),
                             amount: bal,
                         }))
                     }
                     Balance::Cw20(token) => {
-                        // check has enough
+                        if !config.cw20_whitelist.contains(&token.address) {
+                            return Err(ContractError::NotInWhitelist {});
+                        }
+                        // check has enough, on top of the reserve
                         let bal = token.clone();
-                        if !has_cw_coins(&config.available_balance.cw20, &bal) {
+                        let required = Cw20CoinVerified {
+                            address: bal.address.clone(),
+                            amount: bal.amount + reserved_cw20(&bal.address),
+                        };
+                        if !has_cw_coins(&held_cw20.cw20, &required) {
                             has_fund_err = true;
                             // TODO: refactor to not need
                             return Ok(SubMsg::new(BankMsg::Send {
@@ -212,6 +663,7 @@ impl<'a> CwCroncat<'a> {
                         config
                             .available_balance
                             .minus_tokens(Balance::from(bal.clone()));
+                        held_cw20.minus_tokens(Balance::from(bal.clone()));
 
                         let msg = Cw20ExecuteMsg::Transfer {
                             recipient: account_id.clone().into(),
@@ -226,6 +678,7 @@ impl<'a> CwCroncat<'a> {
                 }
             })
             .collect();
+        let messages = messages?;
 
         // failed
         if has_fund_err {
@@ -237,11 +690,84 @@ impl<'a> CwCroncat<'a> {
         // Update balances in config
         self.config.save(deps.storage, &config)?;
 
-        Ok(Response::new()
+        let mut response = Response::new()
             .add_attribute("method", "move_balance")
             .add_attribute("account_id", account_id.to_string())
-            .add_submessages(messages.unwrap()))
+            .add_submessages(messages);
+        if self.is_balance_low(deps.storage, &config)? {
+            response = response.add_attribute("warning", "low_balance_task_creation_paused");
+        }
+        Ok(response)
+    }
+
+    /// Stake Balances
+    /// Sets aside part of the available balance (native or CW20) as staked, so it can't be moved
+    /// or spent until it is unstaked again.
+    pub fn stake_balances(
+        &self,
+        deps: DepsMut,
+        info: MessageInfo,
+        balances: Vec<Balance>,
+    ) -> Result<Response, ContractError> {
+        let mut config = self.config.load(deps.storage)?;
+        if info.sender != config.owner_id {
+            return Err(ContractError::Unauthorized {});
+        }
+        shift_balances(
+            &mut config.available_balance,
+            &mut config.staked_balance,
+            balances,
+        )?;
+        self.config.save(deps.storage, &config)?;
+
+        Ok(Response::new().add_attribute("method", "stake_balances"))
+    }
+
+    /// Unstake Balances
+    /// Returns staked funds back to the available balance.
+    pub fn unstake_balances(
+        &self,
+        deps: DepsMut,
+        info: MessageInfo,
+        balances: Vec<Balance>,
+    ) -> Result<Response, ContractError> {
+        let mut config = self.config.load(deps.storage)?;
+        if info.sender != config.owner_id {
+            return Err(ContractError::Unauthorized {});
+        }
+        shift_balances(
+            &mut config.staked_balance,
+            &mut config.available_balance,
+            balances,
+        )?;
+        self.config.save(deps.storage, &config)?;
+
+        Ok(Response::new().add_attribute("method", "unstake_balances"))
+    }
+}
+
+/// Moves balances from one ledger to the other, failing if `from` doesn't cover all of them
+fn shift_balances(
+    from: &mut GenericBalance,
+    to: &mut GenericBalance,
+    balances: Vec<Balance>,
+) -> Result<(), ContractError> {
+    for balance in balances {
+        let covered = match &balance {
+            Balance::Native(native) => {
+                !native.0.is_empty() && native.0.iter().all(|coin| has_coins(&from.native, coin))
+            }
+            Balance::Cw20(token) => has_cw_coins(&from.cw20, token),
+        };
+        if !covered {
+            return Err(ContractError::CustomError {
+                val: "Not enough funds".to_string(),
+            });
+        }
+        from.minus_tokens(balance.clone());
+        to.add_tokens(balance);
     }
+    Ok(())
 }
 
 #[cfg(test)]
@@ -249,10 +775,17 @@ mod tests {
     use crate::error::ContractError;
     use crate::state::CwCroncat;
     use cosmwasm_std::testing::{mock_dependencies_with_balance, mock_env, mock_info};
-    use cosmwasm_std::{coin, coins, from_binary, Addr, MessageInfo};
-    use cw20::Balance;
+    use cosmwasm_std::{
+        coin, coins, from_binary, from_slice, to_binary, Addr, BankMsg, Deps, MessageInfo,
+        StakingMsg, StdResult, SubMsg, Uint128, WasmMsg,
+    };
+    use cw20::{Balance, Cw20CoinVerified, Cw20ExecuteMsg};
     use cw_croncat_core::msg::{
-        ExecuteMsg, GetBalancesResponse, GetConfigResponse, InstantiateMsg, QueryMsg,
+        BalanceReconciliation, ExecuteMsg, GetBalanceReconciliationResponse, GetBalancesResponse,
+        GetConfigResponse, GetPausedResponse, InstantiateMsg, QueryMsg, TaskRequest,
+    };
+    use cw_croncat_core::types::{
+        Action, ActionOrder, Agent, BoundaryValidated, GenericBalance, Interval, Task,
     };
 
     #[test]
@@ -278,14 +811,37 @@ mod tests {
 
         let payload = ExecuteMsg::UpdateSettings {
             paused: Some(true),
-            owner_id: None,
-            // treasury_id: None,
+            treasury_id: None,
             agent_fee: None,
             min_tasks_per_agent: None,
             agents_eject_threshold: None,
             gas_price: None,
             proxy_callback_gas: None,
             slot_granularity: None,
+            dead_task_bounty: None,
+            max_rules_per_task: None,
+            slash_destination: None,
+            low_balance_pause_threshold: None,
+            min_active_agents_for_execution: None,
+            max_submsgs_per_proxy_call: None,
+            protocol_fee: None,
+            native_denom: None,
+            task_creation_fee: None,
+            nomination_paused: None,
+            reward_vesting_seconds: None,
+            max_future_slot_seconds: None,
+            randomized_agent_selection: None,
+            max_tasks_per_owner: None,
+            dust_threshold: None,
+            dust_swap_contract: None,
+            min_active_agents: None,
+            min_agent_version: None,
+            removal_grace_slots: None,
+            eject_to_pending: None,
+            max_task_limit: None,
+            agent_allowlist_enabled: None,
+            max_task_gas: None,
+            min_task_balance: None,
         };
 
         // non-owner fails
@@ -312,9 +868,27 @@ mod tests {
             _ => panic!("Must return deposit error"),
         }
 
+        // the native denom too, it would never be credited to the available balance
+        let res_fail = store.execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("owner_id", &coins(100, "atom")),
+            payload.clone(),
+        );
+        assert!(matches!(res_fail, Err(ContractError::AttachedDeposit {})));
+        let res = store
+            .query(deps.as_ref(), mock_env(), QueryMsg::GetBalances {})
+            .unwrap();
+        let balances: GetBalancesResponse = from_binary(&res).unwrap();
+        assert!(!balances
+            .available_balance
+            .native
+            .iter()
+            .any(|coin| coin.denom == "atom"));
+
         // do the right thing
         let res_exec = store
-            .execute(deps.as_mut(), mock_env(), info.clone(), payload)
+            .execute(deps.as_mut(), mock_env(), info.clone(), payload.clone())
             .unwrap();
         assert_eq!(0, res_exec.messages.len());
 
@@ -325,48 +899,351 @@ mod tests {
         let value: GetConfigResponse = from_binary(&res).unwrap();
         assert_eq!(true, value.paused);
         assert_eq!(info.sender, value.owner_id);
+        // settings left out keep their value
+        assert_eq!(600, value.agents_eject_threshold);
+        assert_eq!(3, value.min_tasks_per_agent);
+
+        // the cheap pause query follows along
+        let res = store
+            .query(deps.as_ref(), mock_env(), QueryMsg::GetPaused {})
+            .unwrap();
+        assert_eq!(
+            GetPausedResponse { paused: true },
+            from_binary(&res).unwrap()
+        );
+        let mut unpause = payload;
+        if let ExecuteMsg::UpdateSettings { paused, .. } = &mut unpause {
+            *paused = Some(false);
+        }
+        store
+            .execute(deps.as_mut(), mock_env(), info, unpause)
+            .unwrap();
+        let res = store
+            .query(deps.as_ref(), mock_env(), QueryMsg::GetPaused {})
+            .unwrap();
+        assert_eq!(
+            GetPausedResponse { paused: false },
+            from_binary(&res).unwrap()
+        );
     }
 
     #[test]
-    fn move_balances_auth_checks() {
-        let mut deps = mock_dependencies_with_balance(&coins(200000000, "atom"));
+    fn update_settings_cannot_transfer_ownership() {
+        let mut deps = mock_dependencies_with_balance(&coins(200, ""));
         let mut store = CwCroncat::default();
-        let info = mock_info("owner_id", &coins(1000, "meow"));
-        let unauth_info = mock_info("michael_scott", &coins(2, "shrute_bucks"));
-        let exist_bal = vec![Balance::from(coins(2, "atom"))];
-        let non_exist_bal = vec![Balance::from(coins(2, "shrute_bucks"))];
-
-        // instantiate with owner, then add treasury
+        let info = mock_info("creator", &[]);
         let msg = InstantiateMsg {
             denom: "atom".to_string(),
             owner_id: None,
             gas_base_fee: None,
             agent_nomination_duration: Some(360),
         };
-        let res_init = store
+        store
             .instantiate(deps.as_mut(), mock_env(), info.clone(), msg)
             .unwrap();
-        assert!(res_init.messages.is_empty());
 
-        let payload = ExecuteMsg::UpdateSettings {
-            paused: None,
-            owner_id: None,
-            // treasury_id: Some(Addr::unchecked("money_bags")),
-            agent_fee: None,
-            min_tasks_per_agent: None,
-            agents_eject_threshold: None,
-            gas_price: None,
-            proxy_callback_gas: None,
-            slot_granularity: None,
-        };
-        let info_setting = mock_info("owner_id", &coins(0, "meow"));
-        let res_exec = store
-            .execute(deps.as_mut(), mock_env(), info_setting, payload)
+        // an owner_id in the settings is dropped, the owner stays put
+        let payload: ExecuteMsg =
+            from_slice(br#"{"update_settings":{"owner_id":"typo_owner","paused":true}}"#).unwrap();
+        store
+            .execute(deps.as_mut(), mock_env(), info.clone(), payload)
             .unwrap();
-        assert!(res_exec.messages.is_empty());
+        let res = store
+            .query(deps.as_ref(), mock_env(), QueryMsg::GetConfig {})
+            .unwrap();
+        let value: GetConfigResponse = from_binary(&res).unwrap();
+        assert!(value.paused);
+        assert_eq!(info.sender, value.owner_id);
+        assert!(store
+            .pending_owner
+            .may_load(&deps.storage)
+            .unwrap()
+            .is_none());
+    }
 
-        // try to move funds as non-owner
-        let msg_move_1 = ExecuteMsg::MoveBalances {
+    #[test]
+    fn propose_and_accept_ownership() {
+        let mut deps = mock_dependencies_with_balance(&coins(200, ""));
+        let mut store = CwCroncat::default();
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg {
+            denom: "atom".to_string(),
+            owner_id: None,
+            gas_base_fee: None,
+            agent_nomination_duration: Some(360),
+        };
+        store
+            .instantiate(deps.as_mut(), mock_env(), info.clone(), msg)
+            .unwrap();
+
+        // accepting without a proposal fails
+        let new_owner_info = mock_info("new_owner", &[]);
+        let res_fail = store.execute(
+            deps.as_mut(),
+            mock_env(),
+            new_owner_info.clone(),
+            ExecuteMsg::AcceptOwnership {},
+        );
+        assert_eq!(res_fail.unwrap_err(), ContractError::Unauthorized {});
+
+        // only the owner can propose
+        let propose_msg = ExecuteMsg::ProposeNewOwner {
+            addr: Addr::unchecked("new_owner"),
+        };
+        let res_fail = store.execute(
+            deps.as_mut(),
+            mock_env(),
+            new_owner_info.clone(),
+            propose_msg.clone(),
+        );
+        assert_eq!(res_fail.unwrap_err(), ContractError::Unauthorized {});
+        store
+            .execute(deps.as_mut(), mock_env(), info.clone(), propose_msg)
+            .unwrap();
+
+        // owner is unchanged until the proposal is accepted
+        let res = store
+            .query(deps.as_ref(), mock_env(), QueryMsg::GetConfig {})
+            .unwrap();
+        let value: GetConfigResponse = from_binary(&res).unwrap();
+        assert_eq!(info.sender, value.owner_id);
+
+        // someone else can't accept
+        let res_fail = store.execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("michael_scott", &[]),
+            ExecuteMsg::AcceptOwnership {},
+        );
+        assert_eq!(res_fail.unwrap_err(), ContractError::Unauthorized {});
+
+        store
+            .execute(
+                deps.as_mut(),
+                mock_env(),
+                new_owner_info.clone(),
+                ExecuteMsg::AcceptOwnership {},
+            )
+            .unwrap();
+        let res = store
+            .query(deps.as_ref(), mock_env(), QueryMsg::GetConfig {})
+            .unwrap();
+        let value: GetConfigResponse = from_binary(&res).unwrap();
+        assert_eq!(new_owner_info.sender, value.owner_id);
+        assert!(store
+            .pending_owner
+            .may_load(&deps.storage)
+            .unwrap()
+            .is_none());
+
+        // old owner lost its rights
+        let res_fail = store.execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::ProposeNewOwner {
+                addr: Addr::unchecked("creator"),
+            },
+        );
+        assert_eq!(res_fail.unwrap_err(), ContractError::Unauthorized {});
+    }
+
+    #[test]
+    fn propose_overwrites_pending_owner() {
+        let mut deps = mock_dependencies_with_balance(&coins(200, ""));
+        let mut store = CwCroncat::default();
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg {
+            denom: "atom".to_string(),
+            owner_id: None,
+            gas_base_fee: None,
+            agent_nomination_duration: Some(360),
+        };
+        store
+            .instantiate(deps.as_mut(), mock_env(), info.clone(), msg)
+            .unwrap();
+
+        store
+            .execute(
+                deps.as_mut(),
+                mock_env(),
+                info.clone(),
+                ExecuteMsg::ProposeNewOwner {
+                    addr: Addr::unchecked("typo_owner"),
+                },
+            )
+            .unwrap();
+        // the proposed address can also be sent as new_owner
+        let payload: ExecuteMsg =
+            from_slice(br#"{"propose_new_owner":{"new_owner":"new_owner"}}"#).unwrap();
+        store
+            .execute(deps.as_mut(), mock_env(), info.clone(), payload)
+            .unwrap();
+        assert_eq!(
+            store.pending_owner.load(&deps.storage).unwrap(),
+            Addr::unchecked("new_owner")
+        );
+
+        // the replaced proposal can't be accepted anymore
+        let res_fail = store.execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("typo_owner", &[]),
+            ExecuteMsg::AcceptOwnership {},
+        );
+        assert_eq!(res_fail.unwrap_err(), ContractError::Unauthorized {});
+
+        store
+            .execute(
+                deps.as_mut(),
+                mock_env(),
+                mock_info("new_owner", &[]),
+                ExecuteMsg::AcceptOwnership {},
+            )
+            .unwrap();
+        let res = store
+            .query(deps.as_ref(), mock_env(), QueryMsg::GetConfig {})
+            .unwrap();
+        let value: GetConfigResponse = from_binary(&res).unwrap();
+        assert_eq!(Addr::unchecked("new_owner"), value.owner_id);
+    }
+
+    #[test]
+    fn cancel_ownership_proposal() {
+        let mut deps = mock_dependencies_with_balance(&coins(200, ""));
+        let mut store = CwCroncat::default();
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg {
+            denom: "atom".to_string(),
+            owner_id: None,
+            gas_base_fee: None,
+            agent_nomination_duration: Some(360),
+        };
+        store
+            .instantiate(deps.as_mut(), mock_env(), info.clone(), msg)
+            .unwrap();
+
+        // nothing to cancel yet
+        let res_fail = store.execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            ExecuteMsg::CancelOwnershipProposal {},
+        );
+        assert_eq!(
+            res_fail.unwrap_err(),
+            ContractError::CustomError {
+                val: "No pending owner".to_string()
+            }
+        );
+
+        store
+            .execute(
+                deps.as_mut(),
+                mock_env(),
+                info.clone(),
+                ExecuteMsg::ProposeNewOwner {
+                    addr: Addr::unchecked("typo_owner"),
+                },
+            )
+            .unwrap();
+
+        // only the owner can cancel
+        let res_fail = store.execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("typo_owner", &[]),
+            ExecuteMsg::CancelOwnershipProposal {},
+        );
+        assert_eq!(res_fail.unwrap_err(), ContractError::Unauthorized {});
+
+        store
+            .execute(
+                deps.as_mut(),
+                mock_env(),
+                info.clone(),
+                ExecuteMsg::CancelOwnershipProposal {},
+            )
+            .unwrap();
+
+        // cancelled proposal can't be accepted
+        let res_fail = store.execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("typo_owner", &[]),
+            ExecuteMsg::AcceptOwnership {},
+        );
+        assert_eq!(res_fail.unwrap_err(), ContractError::Unauthorized {});
+        let res = store
+            .query(deps.as_ref(), mock_env(), QueryMsg::GetConfig {})
+            .unwrap();
+        let value: GetConfigResponse = from_binary(&res).unwrap();
+        assert_eq!(info.sender, value.owner_id);
+    }
+
+    #[test]
+    fn move_balances_auth_checks() {
+        let mut deps = mock_dependencies_with_balance(&coins(200000000, "atom"));
+        let mut store = CwCroncat::default();
+        let info = mock_info("owner_id", &coins(1000, "meow"));
+        let unauth_info = mock_info("michael_scott", &coins(2, "shrute_bucks"));
+        let exist_bal = vec![Balance::from(coins(2, "atom"))];
+        let non_exist_bal = vec![Balance::from(coins(2, "shrute_bucks"))];
+
+        // instantiate with owner, then add treasury
+        let msg = InstantiateMsg {
+            denom: "atom".to_string(),
+            owner_id: None,
+            gas_base_fee: None,
+            agent_nomination_duration: Some(360),
+        };
+        let res_init = store
+            .instantiate(deps.as_mut(), mock_env(), info.clone(), msg)
+            .unwrap();
+        assert!(res_init.messages.is_empty());
+
+        let payload = ExecuteMsg::UpdateSettings {
+            paused: None,
+            treasury_id: Some(Addr::unchecked("money_bags")),
+            agent_fee: None,
+            min_tasks_per_agent: None,
+            agents_eject_threshold: None,
+            gas_price: None,
+            proxy_callback_gas: None,
+            slot_granularity: None,
+            dead_task_bounty: None,
+            max_rules_per_task: None,
+            slash_destination: None,
+            low_balance_pause_threshold: None,
+            min_active_agents_for_execution: None,
+            max_submsgs_per_proxy_call: None,
+            protocol_fee: None,
+            native_denom: None,
+            task_creation_fee: None,
+            nomination_paused: None,
+            reward_vesting_seconds: None,
+            max_future_slot_seconds: None,
+            randomized_agent_selection: None,
+            max_tasks_per_owner: None,
+            dust_threshold: None,
+            dust_swap_contract: None,
+            min_active_agents: None,
+            min_agent_version: None,
+            removal_grace_slots: None,
+            eject_to_pending: None,
+            max_task_limit: None,
+            agent_allowlist_enabled: None,
+            max_task_gas: None,
+            min_task_balance: None,
+        };
+        let info_setting = mock_info("owner_id", &coins(0, "meow"));
+        let res_exec = store
+            .execute(deps.as_mut(), mock_env(), info_setting, payload)
+            .unwrap();
+        assert!(res_exec.messages.is_empty());
+
+        // try to move funds as non-owner
+        let msg_move_1 = ExecuteMsg::MoveBalances {
             balances: non_exist_bal,
             account_id: Addr::unchecked("scammer"),
         };
@@ -388,6 +1265,146 @@ mod tests {
         }
     }
 
+    #[test]
+    fn move_balances_treasury() {
+        let mut deps = mock_dependencies_with_balance(&coins(200000000, "atom"));
+        let mut store = CwCroncat::default();
+        let info = mock_info("owner_id", &coins(1000, "meow"));
+        let treasury = Addr::unchecked("money_bags");
+        let treasury_info = mock_info(treasury.as_str(), &[]);
+        let exist_bal = vec![Balance::from(coins(2, "atom"))];
+
+        let msg = InstantiateMsg {
+            denom: "atom".to_string(),
+            owner_id: None,
+            gas_base_fee: None,
+            agent_nomination_duration: Some(360),
+        };
+        store
+            .instantiate(deps.as_mut(), mock_env(), info.clone(), msg)
+            .unwrap();
+
+        // Without a treasury set, it's just another account
+        let msg_move = ExecuteMsg::MoveBalances {
+            balances: exist_bal.clone(),
+            account_id: treasury.clone(),
+        };
+        let res_err = store.execute(deps.as_mut(), mock_env(), treasury_info.clone(), msg_move);
+        assert!(matches!(res_err, Err(ContractError::Unauthorized {})));
+
+        let mut payload = ExecuteMsg::UpdateSettings {
+            paused: None,
+            treasury_id: Some(treasury.clone()),
+            agent_fee: None,
+            min_tasks_per_agent: None,
+            agents_eject_threshold: None,
+            gas_price: None,
+            proxy_callback_gas: None,
+            slot_granularity: None,
+            dead_task_bounty: None,
+            max_rules_per_task: None,
+            slash_destination: None,
+            low_balance_pause_threshold: None,
+            min_active_agents_for_execution: None,
+            max_submsgs_per_proxy_call: None,
+            protocol_fee: None,
+            native_denom: None,
+            task_creation_fee: None,
+            nomination_paused: None,
+            reward_vesting_seconds: None,
+            max_future_slot_seconds: None,
+            randomized_agent_selection: None,
+            max_tasks_per_owner: None,
+            dust_threshold: None,
+            dust_swap_contract: None,
+            min_active_agents: None,
+            min_agent_version: None,
+            removal_grace_slots: None,
+            eject_to_pending: None,
+            max_task_limit: None,
+            agent_allowlist_enabled: None,
+            max_task_gas: None,
+            min_task_balance: None,
+        };
+        store
+            .execute(
+                deps.as_mut(),
+                mock_env(),
+                mock_info("owner_id", &[]),
+                payload.clone(),
+            )
+            .unwrap();
+        let res = store
+            .query(deps.as_ref(), mock_env(), QueryMsg::GetConfig {})
+            .unwrap();
+        let config: GetConfigResponse = from_binary(&res).unwrap();
+        assert_eq!(config.treasury_id, Some(treasury.clone()));
+
+        // The treasury can move funds to itself
+        let msg_move = ExecuteMsg::MoveBalances {
+            balances: exist_bal.clone(),
+            account_id: treasury.clone(),
+        };
+        let res = store
+            .execute(deps.as_mut(), mock_env(), treasury_info.clone(), msg_move)
+            .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: treasury.to_string(),
+                amount: coins(2, "atom"),
+            })]
+        );
+
+        // and to the owner, but nowhere else
+        let msg_move = ExecuteMsg::MoveBalances {
+            balances: exist_bal.clone(),
+            account_id: Addr::unchecked("owner_id"),
+        };
+        store
+            .execute(deps.as_mut(), mock_env(), treasury_info.clone(), msg_move)
+            .unwrap();
+        let msg_move = ExecuteMsg::MoveBalances {
+            balances: exist_bal.clone(),
+            account_id: Addr::unchecked("scammer"),
+        };
+        let res_err = store.execute(deps.as_mut(), mock_env(), treasury_info, msg_move);
+        assert!(matches!(res_err, Err(ContractError::CustomError { .. })));
+
+        // Third parties still can't move anything, even to the treasury
+        let msg_move = ExecuteMsg::MoveBalances {
+            balances: exist_bal.clone(),
+            account_id: treasury.clone(),
+        };
+        let res_err = store.execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("michael_scott", &[]),
+            msg_move.clone(),
+        );
+        assert!(matches!(res_err, Err(ContractError::Unauthorized {})));
+
+        // An empty address removes the treasury, along with its rights
+        if let ExecuteMsg::UpdateSettings { treasury_id, .. } = &mut payload {
+            *treasury_id = Some(Addr::unchecked(""));
+        }
+        store
+            .execute(
+                deps.as_mut(),
+                mock_env(),
+                mock_info("owner_id", &[]),
+                payload,
+            )
+            .unwrap();
+        let res = store
+            .query(deps.as_ref(), mock_env(), QueryMsg::GetConfig {})
+            .unwrap();
+        let config: GetConfigResponse = from_binary(&res).unwrap();
+        assert_eq!(config.treasury_id, None);
+        let res_err = store.execute(deps.as_mut(), mock_env(), treasury_info, msg_move);
+        assert!(matches!(res_err, Err(ContractError::Unauthorized {})));
+    }
+
     #[test]
     fn move_balances_native() {
         let mut deps = mock_dependencies_with_balance(&coins(200000000, "atom"));
@@ -411,14 +1428,37 @@ mod tests {
 
         let payload = ExecuteMsg::UpdateSettings {
             paused: None,
-            owner_id: None,
-            // treasury_id: Some(money_bags.clone()),
+            treasury_id: Some(money_bags.clone()),
             agent_fee: None,
             min_tasks_per_agent: None,
             agents_eject_threshold: None,
             gas_price: None,
             proxy_callback_gas: None,
             slot_granularity: None,
+            dead_task_bounty: None,
+            max_rules_per_task: None,
+            slash_destination: None,
+            low_balance_pause_threshold: None,
+            min_active_agents_for_execution: None,
+            max_submsgs_per_proxy_call: None,
+            protocol_fee: None,
+            native_denom: None,
+            task_creation_fee: None,
+            nomination_paused: None,
+            reward_vesting_seconds: None,
+            max_future_slot_seconds: None,
+            randomized_agent_selection: None,
+            max_tasks_per_owner: None,
+            dust_threshold: None,
+            dust_swap_contract: None,
+            min_active_agents: None,
+            min_agent_version: None,
+            removal_grace_slots: None,
+            eject_to_pending: None,
+            max_task_limit: None,
+            agent_allowlist_enabled: None,
+            max_task_gas: None,
+            min_task_balance: None,
         };
         let info_settings = mock_info("owner_id", &coins(0, "meow"));
         let res_exec = store
@@ -458,53 +1498,720 @@ mod tests {
         );
     }
 
-    // // TODO: Setup CW20 logic / balances!
-    // #[test]
-    // fn move_balances_cw() {
-    //     let mut deps = mock_dependencies_with_balance(&coins(200000000, "atom"));
-    //     let info = mock_info("owner_id", &vec![Balance::Cw20(1000, "meow")]);
-    //     let money_bags = Addr::unchecked("money_bags");
-    //     let exist_bal = vec![Balance::from(coins(2, "atom"))];
-    //     let spensive_bal = vec![Balance::from(coins(2000000000000, "atom"))];
-    //     let non_exist_bal = vec![Balance::from(coins(2, "shrute_bucks"))];
-
-    //     // instantiate with owner, then add treasury
-    //     let msg = InstantiateMsg { owner_id: None };
-    //     let res_init = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
-    //     assert!(res_init.messages.is_empty());
-
-    //     let payload = ExecuteMsg::UpdateSettings {
-    //         paused: None,
-    //         owner_id: None,
-    //         treasury_id: Some(money_bags.clone()),
-    //         agent_fee: None,
-    //         agent_task_ratio: None,
-    //         agents_eject_threshold: None,
-    //         gas_price: None,
-    //         proxy_callback_gas: None,
-    //         slot_granularity: None,
-    //     };
-    //     let res_exec = execute(deps.as_mut(), mock_env(), info.clone(), payload).unwrap();
-    //     assert!(res_exec.messages.is_empty());
-
-    //     // try to move funds with greater amount than cw available
-    //     let msg_move_fail = ExecuteMsg::MoveBalances { balances: spensive_bal, account_id: money_bags.clone() };
-    //     let res_fail = execute(deps.as_mut(), mock_env(), info.clone(), msg_move_fail);
-    //     match res_fail {
-    //         Err(ContractError::CustomError { .. }) => {}
-    //         _ => panic!("Must return custom not enough funds error"),
-    //     }
-
-    //     // try to move cw available funds
-    //     // // do the right thing
-    //     // let res_exec = execute(deps.as_mut(), mock_env(), info.clone(), payload).unwrap();
-    //     // assert!(!res_exec.messages.is_empty());
-
-    //     // // it worked, let's query the state
-    //     // let res = query(deps.as_ref(), mock_env(), QueryMsg::GetConfig {}).unwrap();
-    //     // let value: ConfigResponse = from_binary(&res).unwrap();
-    //     // println!("CONFIG {:?}", value);
-    //     // assert_eq!(true, value.paused);
-    //     // assert_eq!(info.sender, value.owner_id);
-    // }
+    #[test]
+    fn move_balances_respects_reserve() {
+        let mut deps = mock_dependencies_with_balance(&coins(1000, "atom"));
+        let mut store = CwCroncat::default();
+        let info = mock_info("owner_id", &[]);
+        let money_bags = Addr::unchecked("owner_id");
+
+        let msg = InstantiateMsg {
+            denom: "atom".to_string(),
+            owner_id: None,
+            gas_base_fee: None,
+            agent_nomination_duration: Some(360),
+        };
+        store
+            .instantiate(deps.as_mut(), mock_env(), info.clone(), msg)
+            .unwrap();
+
+        // 300 owed to an agent, 500 committed to a task
+        let agent = Agent {
+            payable_account_id: Addr::unchecked("agent"),
+            balance: GenericBalance {
+                native: coins(300, "atom"),
+                cw20: vec![],
+            },
+            total_tasks_executed: 0,
+            last_missed_slot: 0,
+            register_start: mock_env().block.time,
+            last_executed_slot: 0,
+        };
+        store
+            .agents
+            .save(&mut deps.storage, Addr::unchecked("agent"), &agent)
+            .unwrap();
+        let task = Task {
+            owner_id: Addr::unchecked("task_owner"),
+            interval: Interval::Immediate,
+            boundary: BoundaryValidated {
+                start: None,
+                end: None,
+                max_executions: None,
+            },
+            stop_on_fail: false,
+            total_deposit: coins(500, "atom"),
+            actions: vec![],
+            rules: None,
+            metadata: None,
+            successor: None,
+            generation: 0,
+            action_order: ActionOrder::Sequential,
+            total_cw20_deposit: vec![],
+        };
+        store
+            .tasks
+            .save(&mut deps.storage, task.to_hash_vec(), &task)
+            .unwrap();
+
+        // dipping into the reserve is rejected
+        let msg_move_fail = ExecuteMsg::MoveBalances {
+            balances: vec![Balance::from(coins(201, "atom"))],
+            account_id: money_bags.clone(),
+        };
+        let res_fail = store.execute(deps.as_mut(), mock_env(), info.clone(), msg_move_fail);
+        assert_eq!(
+            res_fail.unwrap_err(),
+            ContractError::CustomError {
+                val: "Not enough funds".to_string()
+            }
+        );
+
+        // everything above the reserve can move
+        let msg_move = ExecuteMsg::MoveBalances {
+            balances: vec![Balance::from(coins(200, "atom"))],
+            account_id: money_bags.clone(),
+        };
+        let res_exec = store
+            .execute(deps.as_mut(), mock_env(), info, msg_move)
+            .unwrap();
+        assert_eq!(
+            res_exec.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: money_bags.to_string(),
+                amount: coins(200, "atom"),
+            })]
+        );
+    }
+
+    #[test]
+    fn low_balance_pauses_task_creation() {
+        let mut deps = mock_dependencies_with_balance(&coins(2_000_000, "atom"));
+        let mut store = CwCroncat::default();
+        let info = mock_info("owner_id", &[]);
+        let msg = InstantiateMsg {
+            denom: "atom".to_string(),
+            owner_id: None,
+            gas_base_fee: None,
+            agent_nomination_duration: Some(360),
+        };
+        store
+            .instantiate(deps.as_mut(), mock_env(), info.clone(), msg)
+            .unwrap();
+
+        let settings = ExecuteMsg::UpdateSettings {
+            paused: None,
+            treasury_id: None,
+            agent_fee: None,
+            min_tasks_per_agent: None,
+            agents_eject_threshold: None,
+            gas_price: None,
+            proxy_callback_gas: None,
+            slot_granularity: None,
+            dead_task_bounty: None,
+            max_rules_per_task: None,
+            slash_destination: None,
+            low_balance_pause_threshold: Some(Uint128::new(1_000_000)),
+            min_active_agents_for_execution: None,
+            max_submsgs_per_proxy_call: None,
+            protocol_fee: None,
+            native_denom: None,
+            task_creation_fee: None,
+            nomination_paused: None,
+            reward_vesting_seconds: None,
+            max_future_slot_seconds: None,
+            randomized_agent_selection: None,
+            max_tasks_per_owner: None,
+            dust_threshold: None,
+            dust_swap_contract: None,
+            min_active_agents: None,
+            min_agent_version: None,
+            removal_grace_slots: None,
+            eject_to_pending: None,
+            max_task_limit: None,
+            agent_allowlist_enabled: None,
+            max_task_gas: None,
+            min_task_balance: None,
+        };
+        store
+            .execute(deps.as_mut(), mock_env(), info.clone(), settings)
+            .unwrap();
+
+        let create_task_msg = |amount: u128| ExecuteMsg::CreateTask {
+            task: TaskRequest {
+                interval: Interval::Immediate,
+                boundary: None,
+                stop_on_fail: false,
+                actions: vec![Action {
+                    msg: StakingMsg::Delegate {
+                        validator: "you".to_string(),
+                        amount: coin(amount, "atom"),
+                    }
+                    .into(),
+                    gas_limit: Some(150_000),
+                }],
+                rules: None,
+                metadata: None,
+                successor: None,
+                action_order: None,
+            },
+        };
+        let task_owner = mock_info("task_owner", &coins(300_016, "atom"));
+
+        // plenty of uncommitted balance
+        store
+            .execute(
+                deps.as_mut(),
+                mock_env(),
+                task_owner.clone(),
+                create_task_msg(1),
+            )
+            .unwrap();
+
+        // draining the balance warns about it
+        let res = store
+            .execute(
+                deps.as_mut(),
+                mock_env(),
+                info.clone(),
+                ExecuteMsg::MoveBalances {
+                    balances: vec![Balance::from(coins(1_500_000, "atom"))],
+                    account_id: Addr::unchecked("owner_id"),
+                },
+            )
+            .unwrap();
+        assert!(res
+            .attributes
+            .iter()
+            .any(|a| a.key == "warning" && a.value == "low_balance_task_creation_paused"));
+
+        // and blocks task creation
+        let res_fail = store.execute(
+            deps.as_mut(),
+            mock_env(),
+            task_owner.clone(),
+            create_task_msg(2),
+        );
+        assert_eq!(
+            res_fail.unwrap_err(),
+            ContractError::CustomError {
+                val: "Create task paused, contract balance is low".to_string()
+            }
+        );
+
+        // lowering the threshold under the uncommitted balance lifts it
+        let settings = ExecuteMsg::UpdateSettings {
+            paused: None,
+            treasury_id: None,
+            agent_fee: None,
+            min_tasks_per_agent: None,
+            agents_eject_threshold: None,
+            gas_price: None,
+            proxy_callback_gas: None,
+            slot_granularity: None,
+            dead_task_bounty: None,
+            max_rules_per_task: None,
+            slash_destination: None,
+            low_balance_pause_threshold: Some(Uint128::new(500_000)),
+            min_active_agents_for_execution: None,
+            max_submsgs_per_proxy_call: None,
+            protocol_fee: None,
+            native_denom: None,
+            task_creation_fee: None,
+            nomination_paused: None,
+            reward_vesting_seconds: None,
+            max_future_slot_seconds: None,
+            randomized_agent_selection: None,
+            max_tasks_per_owner: None,
+            dust_threshold: None,
+            dust_swap_contract: None,
+            min_active_agents: None,
+            min_agent_version: None,
+            removal_grace_slots: None,
+            eject_to_pending: None,
+            max_task_limit: None,
+            agent_allowlist_enabled: None,
+            max_task_gas: None,
+            min_task_balance: None,
+        };
+        store
+            .execute(deps.as_mut(), mock_env(), info, settings)
+            .unwrap();
+        store
+            .execute(deps.as_mut(), mock_env(), task_owner, create_task_msg(2))
+            .unwrap();
+    }
+
+    #[test]
+    fn move_balances_native_multiple_denoms() {
+        let mut deps =
+            mock_dependencies_with_balance(&[coin(200000000, "atom"), coin(1000, "meow")]);
+        let mut store = CwCroncat::default();
+        let info = mock_info("owner_id", &[]);
+        let money_bags = Addr::unchecked("owner_id");
+
+        let msg = InstantiateMsg {
+            denom: "atom".to_string(),
+            owner_id: None,
+            gas_base_fee: None,
+            agent_nomination_duration: Some(360),
+        };
+        store
+            .instantiate(deps.as_mut(), mock_env(), info.clone(), msg)
+            .unwrap();
+
+        // second denom is more than available, nothing should move
+        let msg_move_fail = ExecuteMsg::MoveBalances {
+            balances: vec![Balance::from(vec![coin(2, "atom"), coin(1001, "meow")])],
+            account_id: money_bags.clone(),
+        };
+        let res_fail = store.execute(deps.as_mut(), mock_env(), info.clone(), msg_move_fail);
+        match res_fail {
+            Err(ContractError::CustomError { .. }) => {}
+            _ => panic!("Must return custom not enough funds error"),
+        }
+
+        // both denoms are available
+        let both = vec![coin(2, "atom"), coin(10, "meow")];
+        let msg_move = ExecuteMsg::MoveBalances {
+            balances: vec![Balance::from(both.clone())],
+            account_id: money_bags.clone(),
+        };
+        let res_exec = store
+            .execute(deps.as_mut(), mock_env(), info.clone(), msg_move)
+            .unwrap();
+        assert_eq!(
+            res_exec.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: money_bags.to_string(),
+                amount: both,
+            })]
+        );
+
+        // registry got decremented for every denom
+        let res_bal = store
+            .query(deps.as_ref(), mock_env(), QueryMsg::GetBalances {})
+            .unwrap();
+        let balances: GetBalancesResponse = from_binary(&res_bal).unwrap();
+        assert_eq!(
+            vec![coin(199999998, "atom"), coin(990, "meow")],
+            balances.available_balance.native
+        );
+    }
+
+    #[test]
+    fn native_denom_change_requires_drain() {
+        let settings = ExecuteMsg::UpdateSettings {
+            paused: None,
+            treasury_id: None,
+            agent_fee: None,
+            min_tasks_per_agent: None,
+            agents_eject_threshold: None,
+            gas_price: None,
+            proxy_callback_gas: None,
+            slot_granularity: None,
+            dead_task_bounty: None,
+            max_rules_per_task: None,
+            slash_destination: None,
+            low_balance_pause_threshold: None,
+            min_active_agents_for_execution: None,
+            max_submsgs_per_proxy_call: None,
+            protocol_fee: None,
+            native_denom: Some("ujuno".to_string()),
+            task_creation_fee: None,
+            nomination_paused: None,
+            reward_vesting_seconds: None,
+            max_future_slot_seconds: None,
+            randomized_agent_selection: None,
+            max_tasks_per_owner: None,
+            dust_threshold: None,
+            dust_swap_contract: None,
+            min_active_agents: None,
+            min_agent_version: None,
+            removal_grace_slots: None,
+            eject_to_pending: None,
+            max_task_limit: None,
+            agent_allowlist_enabled: None,
+            max_task_gas: None,
+            min_task_balance: None,
+        };
+        let info = mock_info("owner_id", &[]);
+        let msg = InstantiateMsg {
+            denom: "atom".to_string(),
+            owner_id: None,
+            gas_base_fee: None,
+            agent_nomination_duration: Some(360),
+        };
+
+        // The contract holds atom, the denom can't change
+        let mut deps = mock_dependencies_with_balance(&coins(2_000_000, "atom"));
+        let mut store = CwCroncat::default();
+        store
+            .instantiate(deps.as_mut(), mock_env(), info.clone(), msg.clone())
+            .unwrap();
+        let err = store
+            .execute(deps.as_mut(), mock_env(), info.clone(), settings.clone())
+            .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::CustomError {
+                val: "Cannot change native_denom while atom balances are held".to_string()
+            }
+        );
+        let config = store.query_config(deps.as_ref()).unwrap();
+        assert_eq!(config.native_denom, "atom");
+
+        // Nothing held, so it can change
+        let mut deps = mock_dependencies_with_balance(&[]);
+        let mut store = CwCroncat::default();
+        store
+            .instantiate(deps.as_mut(), mock_env(), info.clone(), msg)
+            .unwrap();
+        store
+            .execute(deps.as_mut(), mock_env(), info, settings)
+            .unwrap();
+        let config = store.query_config(deps.as_ref()).unwrap();
+        assert_eq!(config.native_denom, "ujuno");
+    }
+
+    #[test]
+    fn balance_reconciliation() {
+        let mut deps = mock_dependencies_with_balance(&[coin(1000, "meow"), coin(7, "juno")]);
+        let mut store = CwCroncat::default();
+        let msg = InstantiateMsg {
+            denom: "atom".to_string(),
+            owner_id: None,
+            gas_base_fee: None,
+            agent_nomination_duration: None,
+        };
+        store
+            .instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg)
+            .unwrap();
+
+        // Track an atom balance the contract doesn't hold, and miss the juno one
+        store
+            .config
+            .update(&mut deps.storage, |mut c| -> StdResult<_> {
+                c.available_balance = GenericBalance {
+                    native: vec![coin(1000, "meow"), coin(50, "atom")],
+                    cw20: vec![],
+                };
+                Ok(c)
+            })
+            .unwrap();
+
+        let res = store
+            .query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::GetBalanceReconciliation {},
+            )
+            .unwrap();
+        let res: GetBalanceReconciliationResponse = from_binary(&res).unwrap();
+        assert_eq!(
+            res.balances,
+            vec![
+                BalanceReconciliation {
+                    denom: "atom".to_string(),
+                    tracked: Uint128::new(50),
+                    actual: Uint128::zero(),
+                    surplus: Uint128::zero(),
+                    shortfall: Uint128::new(50),
+                },
+                BalanceReconciliation {
+                    denom: "juno".to_string(),
+                    tracked: Uint128::zero(),
+                    actual: Uint128::new(7),
+                    surplus: Uint128::new(7),
+                    shortfall: Uint128::zero(),
+                },
+                BalanceReconciliation {
+                    denom: "meow".to_string(),
+                    tracked: Uint128::new(1000),
+                    actual: Uint128::new(1000),
+                    surplus: Uint128::zero(),
+                    shortfall: Uint128::zero(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn move_balances_respects_stake() {
+        let mut deps = mock_dependencies_with_balance(&coins(1000, "atom"));
+        let mut store = CwCroncat::default();
+        let info = mock_info("owner_id", &[]);
+        let money_bags = Addr::unchecked("owner_id");
+        let msg = InstantiateMsg {
+            denom: "atom".to_string(),
+            owner_id: None,
+            gas_base_fee: None,
+            agent_nomination_duration: Some(360),
+        };
+        store
+            .instantiate(deps.as_mut(), mock_env(), info.clone(), msg)
+            .unwrap();
+        store
+            .config
+            .update(&mut deps.storage, |mut c| -> StdResult<_> {
+                c.cw20_whitelist = vec![Addr::unchecked("cw20")];
+                c.available_balance
+                    .add_tokens(Balance::Cw20(Cw20CoinVerified {
+                        address: Addr::unchecked("cw20"),
+                        amount: Uint128::new(300),
+                    }));
+                Ok(c)
+            })
+            .unwrap();
+
+        // 400 native and 100 cw20 staked
+        let cw20 = |amount: u128| {
+            Balance::Cw20(Cw20CoinVerified {
+                address: Addr::unchecked("cw20"),
+                amount: Uint128::new(amount),
+            })
+        };
+        store
+            .execute(
+                deps.as_mut(),
+                mock_env(),
+                info.clone(),
+                ExecuteMsg::StakeBalances {
+                    balances: vec![Balance::from(coins(400, "atom")), cw20(100)],
+                },
+            )
+            .unwrap();
+
+        // staked funds can't be moved
+        for balance in [Balance::from(coins(601, "atom")), cw20(201)] {
+            let res_fail = store.execute(
+                deps.as_mut(),
+                mock_env(),
+                info.clone(),
+                ExecuteMsg::MoveBalances {
+                    balances: vec![balance],
+                    account_id: money_bags.clone(),
+                },
+            );
+            assert_eq!(
+                res_fail.unwrap_err(),
+                ContractError::CustomError {
+                    val: "Not enough funds".to_string()
+                }
+            );
+        }
+
+        // the rest can
+        store
+            .execute(
+                deps.as_mut(),
+                mock_env(),
+                info,
+                ExecuteMsg::MoveBalances {
+                    balances: vec![Balance::from(coins(600, "atom")), cw20(200)],
+                    account_id: money_bags,
+                },
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn stake_and_unstake_balances() {
+        let mut deps = mock_dependencies_with_balance(&coins(1000, "meow"));
+        let mut store = CwCroncat::default();
+        let msg = InstantiateMsg {
+            denom: "atom".to_string(),
+            owner_id: None,
+            gas_base_fee: None,
+            agent_nomination_duration: None,
+        };
+        store
+            .instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg)
+            .unwrap();
+
+        let cw20_token = Cw20CoinVerified {
+            address: Addr::unchecked("cw20"),
+            amount: Uint128::new(300),
+        };
+        store
+            .config
+            .update(&mut deps.storage, |mut c| -> StdResult<_> {
+                c.available_balance = GenericBalance {
+                    native: coins(1000, "meow"),
+                    cw20: vec![cw20_token.clone()],
+                };
+                Ok(c)
+            })
+            .unwrap();
+
+        let stake_native = Balance::from(coins(400, "meow"));
+        let stake_cw20 = Balance::Cw20(Cw20CoinVerified {
+            address: Addr::unchecked("cw20"),
+            amount: Uint128::new(100),
+        });
+
+        // Only the owner can stake
+        let res_err = store
+            .execute(
+                deps.as_mut(),
+                mock_env(),
+                mock_info("alice", &[]),
+                ExecuteMsg::StakeBalances {
+                    balances: vec![stake_native.clone()],
+                },
+            )
+            .unwrap_err();
+        assert_eq!(ContractError::Unauthorized {}, res_err);
+
+        // Can't stake more than is available
+        let res_err = store
+            .execute(
+                deps.as_mut(),
+                mock_env(),
+                mock_info("creator", &[]),
+                ExecuteMsg::StakeBalances {
+                    balances: vec![Balance::from(coins(1001, "meow"))],
+                },
+            )
+            .unwrap_err();
+        assert_eq!(
+            ContractError::CustomError {
+                val: "Not enough funds".to_string()
+            },
+            res_err
+        );
+
+        store
+            .execute(
+                deps.as_mut(),
+                mock_env(),
+                mock_info("creator", &[]),
+                ExecuteMsg::StakeBalances {
+                    balances: vec![stake_native.clone(), stake_cw20.clone()],
+                },
+            )
+            .unwrap();
+        let res = store
+            .query(deps.as_ref(), mock_env(), QueryMsg::GetBalances {})
+            .unwrap();
+        let balances: GetBalancesResponse = from_binary(&res).unwrap();
+        assert_eq!(balances.available_balance.native, coins(600, "meow"));
+        assert_eq!(balances.available_balance.cw20[0].amount, Uint128::new(200));
+        assert_eq!(balances.staked_balance.native, coins(400, "meow"));
+        assert_eq!(balances.staked_balance.cw20[0].amount, Uint128::new(100));
+
+        // Can't unstake more than was staked
+        let res_err = store
+            .execute(
+                deps.as_mut(),
+                mock_env(),
+                mock_info("creator", &[]),
+                ExecuteMsg::UnstakeBalances {
+                    balances: vec![Balance::from(coins(401, "meow"))],
+                },
+            )
+            .unwrap_err();
+        assert_eq!(
+            ContractError::CustomError {
+                val: "Not enough funds".to_string()
+            },
+            res_err
+        );
+
+        store
+            .execute(
+                deps.as_mut(),
+                mock_env(),
+                mock_info("creator", &[]),
+                ExecuteMsg::UnstakeBalances {
+                    balances: vec![stake_native, stake_cw20],
+                },
+            )
+            .unwrap();
+        let res = store
+            .query(deps.as_ref(), mock_env(), QueryMsg::GetBalances {})
+            .unwrap();
+        let balances: GetBalancesResponse = from_binary(&res).unwrap();
+        assert_eq!(balances.available_balance.native, coins(1000, "meow"));
+        assert_eq!(balances.available_balance.cw20, vec![cw20_token]);
+        assert_eq!(balances.staked_balance.native, coins(0, "meow"));
+        assert_eq!(balances.staked_balance.cw20[0].amount, Uint128::zero());
+    }
+
+    #[test]
+    fn move_balances_cw() {
+        let mut deps = mock_dependencies_with_balance(&coins(1000, "atom"));
+        let mut store = CwCroncat::default();
+        let msg = InstantiateMsg {
+            denom: "atom".to_string(),
+            owner_id: None,
+            gas_base_fee: None,
+            agent_nomination_duration: None,
+        };
+        store
+            .instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg)
+            .unwrap();
+
+        let cw20 = Addr::unchecked("cw20");
+        store
+            .config
+            .update(&mut deps.storage, |mut c| -> StdResult<_> {
+                c.cw20_whitelist = vec![cw20.clone()];
+                c.available_balance.cw20 = vec![
+                    Cw20CoinVerified {
+                        address: cw20.clone(),
+                        amount: Uint128::new(300),
+                    },
+                    Cw20CoinVerified {
+                        address: Addr::unchecked("shrute_bucks"),
+                        amount: Uint128::new(300),
+                    },
+                ];
+                Ok(c)
+            })
+            .unwrap();
+        let mut move_cw20 = |address: &Addr, amount: u128| {
+            store.execute(
+                deps.as_mut(),
+                mock_env(),
+                mock_info("creator", &[]),
+                ExecuteMsg::MoveBalances {
+                    balances: vec![Balance::Cw20(Cw20CoinVerified {
+                        address: address.clone(),
+                        amount: Uint128::new(amount),
+                    })],
+                    account_id: Addr::unchecked("creator"),
+                },
+            )
+        };
+
+        // Tokens outside the whitelist can't be moved
+        let res_err = move_cw20(&Addr::unchecked("shrute_bucks"), 100).unwrap_err();
+        assert_eq!(ContractError::NotInWhitelist {}, res_err);
+
+        // Nor more than the contract holds
+        let res_err = move_cw20(&cw20, 301).unwrap_err();
+        assert_eq!(
+            ContractError::CustomError {
+                val: "Not enough funds".to_string()
+            },
+            res_err
+        );
+
+        let res = move_cw20(&cw20, 100).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(WasmMsg::Execute {
+                contract_addr: cw20.to_string(),
+                msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                    recipient: "creator".to_string(),
+                    amount: Uint128::new(100),
+                })
+                .unwrap(),
+                funds: vec![],
+            })]
+        );
+        let config = store.config.load(&deps.storage).unwrap();
+        assert_eq!(config.available_balance.cw20[0].amount, Uint128::new(200));
+    }
 }
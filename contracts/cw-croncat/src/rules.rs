@@ -0,0 +1,263 @@
+use cosmwasm_std::{from_slice, Binary, QuerierWrapper, QueryRequest, WasmQuery};
+use cw_croncat_core::types::{Rule, RuleExpr, RuleResponse};
+use serde_cw_value::Value;
+
+/// Rule expressions nest at most this deep, every level can fan out into more queries
+pub const MAX_RULE_DEPTH: usize = 4;
+
+/// Queries a task rule, a rule query that errors counts as not satisfied
+/// Returns the reason the rule doesn't pass
+pub(crate) fn check_rule(querier: &QuerierWrapper, rule: &Rule) -> Result<(), &'static str> {
+    check_rule_at(querier, rule, 0)
+}
+
+fn check_rule_at(querier: &QuerierWrapper, rule: &Rule, depth: usize) -> Result<(), &'static str> {
+    let satisfied = match rule {
+        Rule::Query { contract_addr, msg } => querier
+            .query::<RuleResponse<Option<Binary>>>(&QueryRequest::Wasm(WasmQuery::Smart {
+                contract_addr: contract_addr.to_string(),
+                msg: msg.clone(),
+            }))
+            .map(|(pass, _)| pass),
+        Rule::NativeBalanceGte { address, required } => querier
+            .query_balance(address, &required.denom)
+            .map(|balance| balance.amount >= required.amount),
+        Rule::SmartQueryEquals {
+            contract_addr,
+            msg,
+            path,
+            value,
+        } => {
+            let expected: Value = from_slice(value).map_err(|_| "rule_value_invalid")?;
+            querier
+                .query::<Value>(&QueryRequest::Wasm(WasmQuery::Smart {
+                    contract_addr: contract_addr.to_string(),
+                    msg: msg.clone(),
+                }))
+                .map(|res| value_at_path(res, path).as_ref() == Some(&expected))
+        }
+        Rule::Expr(expr) => return check_rule_expr(querier, expr, depth + 1),
+    };
+    match satisfied {
+        Ok(true) => Ok(()),
+        Ok(false) => Err("rule_not_satisfied"),
+        Err(_) => Err("rule_query_failed"),
+    }
+}
+
+/// Short-circuits like boolean logic, NOT only flips rules that weren't satisfied,
+/// a failing query stays a failure
+fn check_rule_expr(
+    querier: &QuerierWrapper,
+    expr: &RuleExpr,
+    depth: usize,
+) -> Result<(), &'static str> {
+    if depth > MAX_RULE_DEPTH {
+        return Err("rule_too_deep");
+    }
+    match expr {
+        RuleExpr::Leaf(rule) => check_rule_at(querier, rule, depth),
+        RuleExpr::And(exprs) => exprs
+            .iter()
+            .try_for_each(|expr| check_rule_expr(querier, expr, depth + 1)),
+        RuleExpr::Or(exprs) => {
+            let mut reason = "rule_not_satisfied";
+            for expr in exprs {
+                match check_rule_expr(querier, expr, depth + 1) {
+                    Ok(()) => return Ok(()),
+                    Err("rule_too_deep") => return Err("rule_too_deep"),
+                    Err(err) => reason = err,
+                }
+            }
+            Err(reason)
+        }
+        RuleExpr::Not(expr) => match check_rule_expr(querier, expr, depth + 1) {
+            Ok(()) => Err("rule_not_satisfied"),
+            Err("rule_not_satisfied") => Ok(()),
+            Err(err) => Err(err),
+        },
+    }
+}
+
+/// Follows a dot separated path into a JSON value, numbers index into arrays
+fn value_at_path(value: Value, path: &str) -> Option<Value> {
+    if path.is_empty() {
+        return Some(value);
+    }
+    path.split('.').try_fold(value, |value, key| match value {
+        Value::Map(mut map) => map.remove(&Value::String(key.to_string())),
+        Value::Seq(seq) => seq.into_iter().nth(key.parse().ok()?),
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmwasm_std::testing::MockQuerier;
+    use cosmwasm_std::{coin, to_binary, Addr, ContractResult, Empty, SystemError, SystemResult};
+
+    const PROPOSAL: &str = r#"{"proposal":{"status":"passed","votes":[3,1]}}"#;
+
+    fn mock_querier() -> MockQuerier<Empty> {
+        let mut querier = MockQuerier::new(&[("alice", &[coin(10, "atom")])]);
+        querier.update_wasm(|query| match query {
+            WasmQuery::Smart { contract_addr, .. } if contract_addr == "dao" => {
+                SystemResult::Ok(ContractResult::Ok(Binary::from(PROPOSAL.as_bytes())))
+            }
+            _ => SystemResult::Err(SystemError::Unknown {}),
+        });
+        querier
+    }
+
+    fn smart_query(contract_addr: &str, path: &str, value: Binary) -> Rule {
+        Rule::SmartQueryEquals {
+            contract_addr: Addr::unchecked(contract_addr),
+            msg: to_binary(&"proposal").unwrap(),
+            path: path.to_string(),
+            value,
+        }
+    }
+
+    #[test]
+    fn check_smart_query_equals() {
+        let querier = mock_querier();
+        let querier = QuerierWrapper::<Empty>::new(&querier);
+        let check = |rule: Rule| check_rule(&querier, &rule);
+
+        // Matching responses
+        assert_eq!(
+            check(smart_query(
+                "dao",
+                "proposal.status",
+                to_binary("passed").unwrap()
+            )),
+            Ok(())
+        );
+        assert_eq!(
+            check(smart_query(
+                "dao",
+                "proposal.votes.1",
+                to_binary(&1u64).unwrap()
+            )),
+            Ok(())
+        );
+        assert_eq!(
+            check(smart_query("dao", "", Binary::from(PROPOSAL.as_bytes()))),
+            Ok(())
+        );
+
+        // Non-matching responses, including paths that aren't there
+        assert_eq!(
+            check(smart_query(
+                "dao",
+                "proposal.status",
+                to_binary("open").unwrap()
+            )),
+            Err("rule_not_satisfied")
+        );
+        assert_eq!(
+            check(smart_query(
+                "dao",
+                "proposal.votes.2",
+                to_binary(&1u64).unwrap()
+            )),
+            Err("rule_not_satisfied")
+        );
+        assert_eq!(
+            check(smart_query(
+                "dao",
+                "proposal.result",
+                to_binary("passed").unwrap()
+            )),
+            Err("rule_not_satisfied")
+        );
+
+        // Broken queries and values
+        assert_eq!(
+            check(smart_query(
+                "nonexistent",
+                "proposal.status",
+                to_binary("passed").unwrap()
+            )),
+            Err("rule_query_failed")
+        );
+        assert_eq!(
+            check(smart_query(
+                "dao",
+                "proposal.status",
+                Binary::from(b"{".to_vec())
+            )),
+            Err("rule_value_invalid")
+        );
+    }
+
+    #[test]
+    fn check_native_balance_gte() {
+        let querier = mock_querier();
+        let querier = QuerierWrapper::<Empty>::new(&querier);
+        let rule = |amount: u128| Rule::NativeBalanceGte {
+            address: Addr::unchecked("alice"),
+            required: coin(amount, "atom"),
+        };
+
+        assert_eq!(check_rule(&querier, &rule(10)), Ok(()));
+        assert_eq!(check_rule(&querier, &rule(11)), Err("rule_not_satisfied"));
+    }
+
+    #[test]
+    fn check_rule_expressions() {
+        let querier = mock_querier();
+        let querier = QuerierWrapper::<Empty>::new(&querier);
+        let balance = |amount: u128| {
+            RuleExpr::Leaf(Rule::NativeBalanceGte {
+                address: Addr::unchecked("alice"),
+                required: coin(amount, "atom"),
+            })
+        };
+        let (pass, fail) = (balance(10), balance(11));
+        let broken = RuleExpr::Leaf(smart_query("nonexistent", "", to_binary(&1u64).unwrap()));
+        let not = |expr: RuleExpr| RuleExpr::Not(Box::new(expr));
+        let check = |expr: RuleExpr| check_rule(&querier, &Rule::Expr(Box::new(expr)));
+
+        assert_eq!(
+            check(RuleExpr::Or(vec![fail.clone(), pass.clone()])),
+            Ok(())
+        );
+        assert_eq!(
+            check(RuleExpr::And(vec![pass.clone(), fail.clone()])),
+            Err("rule_not_satisfied")
+        );
+        assert_eq!(
+            check(RuleExpr::And(vec![
+                pass.clone(),
+                RuleExpr::Or(vec![fail.clone(), not(fail.clone())]),
+            ])),
+            Ok(())
+        );
+        assert_eq!(
+            check(RuleExpr::Or(vec![
+                RuleExpr::And(vec![pass.clone(), fail.clone()]),
+                not(pass.clone()),
+            ])),
+            Err("rule_not_satisfied")
+        );
+        // A query that fails isn't turned into a pass
+        assert_eq!(check(not(broken)), Err("rule_query_failed"));
+
+        let nest =
+            |levels: usize| (1..levels).fold(pass.clone(), |expr, _| RuleExpr::And(vec![expr]));
+        assert_eq!(nest(MAX_RULE_DEPTH).depth(), MAX_RULE_DEPTH);
+        assert_eq!(check(nest(MAX_RULE_DEPTH)), Ok(()));
+        assert_eq!(check(nest(MAX_RULE_DEPTH + 1)), Err("rule_too_deep"));
+
+        // Expressions nested in leaf rules count towards the depth too
+        let nested = RuleExpr::Leaf(Rule::Expr(Box::new(RuleExpr::And(vec![]))));
+        assert_eq!(
+            check(RuleExpr::And(vec![RuleExpr::And(vec![RuleExpr::And(
+                vec![nested]
+            )])])),
+            Err("rule_too_deep")
+        );
+    }
+}
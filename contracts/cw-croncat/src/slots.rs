@@ -2,6 +2,7 @@ use crate::CwCroncat;
 use cosmwasm_std::{BlockInfo, Order, StdResult, Storage};
 pub use cw_croncat_core::types::Interval;
 use cw_croncat_core::types::SlotType;
+use cw_storage_plus::Bound;
 
 impl<'a> CwCroncat<'a> {
     /// Get the slot with lowest height/timestamp
@@ -61,6 +62,18 @@ impl<'a> CwCroncat<'a> {
         storage: &mut dyn Storage,
         slot: &u64,
         kind: &SlotType,
+    ) -> Option<Vec<u8>> {
+        self.pop_slot_item_at(storage, slot, kind, None)
+    }
+
+    /// Like `pop_slot_item`, but takes the hash at `index`, wrapping around the slot length
+    /// Takes the last hash without an index
+    pub(crate) fn pop_slot_item_at(
+        &mut self,
+        storage: &mut dyn Storage,
+        slot: &u64,
+        kind: &SlotType,
+        index: Option<usize>,
     ) -> Option<Vec<u8>> {
         let store = match kind {
             SlotType::Block => self.block_slots.clone(),
@@ -70,7 +83,10 @@ impl<'a> CwCroncat<'a> {
         let mut slot_data = store.may_load(storage, *slot).unwrap()?;
 
         // Get a single task hash, then retrieve task details
-        let hash = slot_data.pop();
+        let hash = match index {
+            Some(index) if !slot_data.is_empty() => Some(slot_data.remove(index % slot_data.len())),
+            _ => slot_data.pop(),
+        };
 
         // Need to remove this slot if no hash's left
         if slot_data.is_empty() {
@@ -82,6 +98,82 @@ impl<'a> CwCroncat<'a> {
         hash
     }
 
+    /// Takes a specific task hash out of the first due slot holding it
+    /// Returns None if the task isn't in any slot that is ready now
+    pub(crate) fn take_due_slot_item(
+        &mut self,
+        storage: &mut dyn Storage,
+        block: &BlockInfo,
+        hash: &[u8],
+    ) -> StdResult<Option<(u64, SlotType)>> {
+        let due = [
+            (SlotType::Block, block.height),
+            (SlotType::Cron, block.time.nanos()),
+        ];
+        for (kind, now) in due.iter() {
+            let store = match kind {
+                SlotType::Block => &self.block_slots,
+                SlotType::Cron => &self.time_slots,
+            };
+            let found = store
+                .range(
+                    storage,
+                    None,
+                    Some(Bound::inclusive(*now)),
+                    Order::Ascending,
+                )
+                .find_map(|slot| match slot {
+                    Ok((id, hashes)) if hashes.iter().any(|h| h == hash) => Some(Ok((id, hashes))),
+                    Ok(_) => None,
+                    Err(err) => Some(Err(err)),
+                })
+                .transpose()?;
+            if let Some((slot_id, mut hashes)) = found {
+                hashes.retain(|h| h != hash);
+                if hashes.is_empty() {
+                    self.clean_slot(storage, &slot_id, kind);
+                } else {
+                    store.save(storage, slot_id, &hashes)?;
+                }
+                return Ok(Some((slot_id, kind.clone())));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Adds a task hash to a slot, creating the slot if needed
+    /// Tasks in a slot stay ordered by creation sequence, tasks without one go last
+    pub(crate) fn push_slot_item(
+        &self,
+        storage: &mut dyn Storage,
+        slot: &u64,
+        kind: &SlotType,
+        hash: Vec<u8>,
+    ) -> StdResult<()> {
+        let store = match kind {
+            SlotType::Block => &self.block_slots,
+            SlotType::Cron => &self.time_slots,
+        };
+
+        let sequence = |storage: &dyn Storage, hash: &Vec<u8>| -> StdResult<u64> {
+            Ok(self
+                .task_sequence
+                .may_load(storage, hash.clone())?
+                .unwrap_or(u64::MAX))
+        };
+        let task_sequence = sequence(storage, &hash)?;
+        let mut slot_data = store.may_load(storage, *slot)?.unwrap_or_default();
+        let mut index = slot_data.len();
+        for (i, slot_hash) in slot_data.iter().enumerate() {
+            if sequence(storage, slot_hash)? > task_sequence {
+                index = i;
+                break;
+            }
+        }
+        slot_data.insert(index, hash);
+        store.save(storage, *slot, &slot_data)
+    }
+
     // TODO: TestCov
     /// Used in cases where there are empty slots or failed txns
     fn clean_slot(&mut self, storage: &mut dyn Storage, slot: &u64, kind: &SlotType) {
@@ -106,15 +198,15 @@ mod tests {
         // (input, input, outcome, outcome)
         let cases: Vec<(Interval, BoundaryValidated, u64, SlotType)> = vec![
             // Once cases
-            (Interval::Once, BoundaryValidated { start: None, end: None }, 12346, SlotType::Block),
-            (Interval::Once, BoundaryValidated { start: Some(12348), end: None }, 12348, SlotType::Block),
-            (Interval::Once, BoundaryValidated { start: None, end: Some(12346) }, 12346, SlotType::Block),
-            (Interval::Once, BoundaryValidated { start: None, end: Some(12340) }, 0, SlotType::Block),
+            (Interval::Once, BoundaryValidated { start: None, end: None, max_executions: None }, 12346, SlotType::Block),
+            (Interval::Once, BoundaryValidated { start: Some(12348), end: None, max_executions: None }, 12348, SlotType::Block),
+            (Interval::Once, BoundaryValidated { start: None, end: Some(12346), max_executions: None }, 12346, SlotType::Block),
+            (Interval::Once, BoundaryValidated { start: None, end: Some(12340), max_executions: None }, 0, SlotType::Block),
             // Immediate cases
-            (Interval::Immediate, BoundaryValidated { start: None, end: None }, 12346, SlotType::Block),
-            (Interval::Immediate, BoundaryValidated { start: Some(12348), end: None }, 12348, SlotType::Block),
-            (Interval::Immediate, BoundaryValidated { start: None, end: Some(12346) }, 12346, SlotType::Block),
-            (Interval::Immediate, BoundaryValidated { start: None, end: Some(12340) }, 0, SlotType::Block),
+            (Interval::Immediate, BoundaryValidated { start: None, end: None, max_executions: None }, 12346, SlotType::Block),
+            (Interval::Immediate, BoundaryValidated { start: Some(12348), end: None, max_executions: None }, 12348, SlotType::Block),
+            (Interval::Immediate, BoundaryValidated { start: None, end: Some(12346), max_executions: None }, 12346, SlotType::Block),
+            (Interval::Immediate, BoundaryValidated { start: None, end: Some(12340), max_executions: None }, 0, SlotType::Block),
         ];
         for (interval, boundary, outcome_block, outcome_slot_kind) in cases.iter() {
             let env = mock_env();
@@ -131,22 +223,22 @@ mod tests {
         // (input, input, outcome, outcome)
         let cases: Vec<(Interval, BoundaryValidated, u64, SlotType)> = vec![
             // strictly modulo cases
-            (Interval::Block(1), BoundaryValidated { start: None, end: None }, 12346, SlotType::Block),
-            (Interval::Block(10), BoundaryValidated { start: None, end: None }, 12350, SlotType::Block),
-            (Interval::Block(100), BoundaryValidated { start: None, end: None }, 12400, SlotType::Block),
-            (Interval::Block(1000), BoundaryValidated { start: None, end: None }, 13000, SlotType::Block),
-            (Interval::Block(10000), BoundaryValidated { start: None, end: None }, 20000, SlotType::Block),
-            (Interval::Block(100000), BoundaryValidated { start: None, end: None }, 100000, SlotType::Block),
+            (Interval::Block(1), BoundaryValidated { start: None, end: None, max_executions: None }, 12346, SlotType::Block),
+            (Interval::Block(10), BoundaryValidated { start: None, end: None, max_executions: None }, 12350, SlotType::Block),
+            (Interval::Block(100), BoundaryValidated { start: None, end: None, max_executions: None }, 12400, SlotType::Block),
+            (Interval::Block(1000), BoundaryValidated { start: None, end: None, max_executions: None }, 13000, SlotType::Block),
+            (Interval::Block(10000), BoundaryValidated { start: None, end: None, max_executions: None }, 20000, SlotType::Block),
+            (Interval::Block(100000), BoundaryValidated { start: None, end: None, max_executions: None }, 100000, SlotType::Block),
             // modulo + boundary start
-            (Interval::Block(1), BoundaryValidated { start: Some(12348), end: None }, 12348, SlotType::Block),
-            (Interval::Block(10), BoundaryValidated { start: Some(12360), end: None }, 12360, SlotType::Block),
-            (Interval::Block(10), BoundaryValidated { start: Some(12364), end: None }, 12370, SlotType::Block),
-            (Interval::Block(100), BoundaryValidated { start: Some(12364), end: None }, 12400, SlotType::Block),
+            (Interval::Block(1), BoundaryValidated { start: Some(12348), end: None, max_executions: None }, 12348, SlotType::Block),
+            (Interval::Block(10), BoundaryValidated { start: Some(12360), end: None, max_executions: None }, 12360, SlotType::Block),
+            (Interval::Block(10), BoundaryValidated { start: Some(12364), end: None, max_executions: None }, 12370, SlotType::Block),
+            (Interval::Block(100), BoundaryValidated { start: Some(12364), end: None, max_executions: None }, 12400, SlotType::Block),
             // modulo + boundary end
-            (Interval::Block(1), BoundaryValidated { start: None, end: Some(12345) }, 12345, SlotType::Block),
-            (Interval::Block(10), BoundaryValidated { start: None, end: Some(12355) }, 12350, SlotType::Block),
-            (Interval::Block(100), BoundaryValidated { start: None, end: Some(12355) }, 12300, SlotType::Block),
-            (Interval::Block(100), BoundaryValidated { start: None, end: Some(12300) }, 0, SlotType::Block),
+            (Interval::Block(1), BoundaryValidated { start: None, end: Some(12345), max_executions: None }, 12345, SlotType::Block),
+            (Interval::Block(10), BoundaryValidated { start: None, end: Some(12355), max_executions: None }, 12350, SlotType::Block),
+            (Interval::Block(100), BoundaryValidated { start: None, end: Some(12355), max_executions: None }, 12300, SlotType::Block),
+            (Interval::Block(100), BoundaryValidated { start: None, end: Some(12300), max_executions: None }, 0, SlotType::Block),
         ];
         for (interval, boundary, outcome_block, outcome_slot_kind) in cases.iter() {
             let env = mock_env();
@@ -157,6 +249,31 @@ mod tests {
         }
     }
 
+    #[test]
+    fn interval_get_next_cron() {
+        // mock_env time is Wednesday 2019-10-23 02:23:39 UTC
+        // (input, input, outcome, outcome)
+        let cases: Vec<(Interval, BoundaryValidated, u64, SlotType)> = vec![
+            // every minute
+            (Interval::Cron("0 * * * * *".to_string()), BoundaryValidated { start: None, end: None, max_executions: None }, 1571797440000000000, SlotType::Cron),
+            // hourly
+            (Interval::Cron("0 0 * * * *".to_string()), BoundaryValidated { start: None, end: None, max_executions: None }, 1571799600000000000, SlotType::Cron),
+            // every Monday at midnight
+            (Interval::Cron("0 0 0 * * Mon".to_string()), BoundaryValidated { start: None, end: None, max_executions: None }, 1572220800000000000, SlotType::Cron),
+            // boundary start
+            (Interval::Cron("0 * * * * *".to_string()), BoundaryValidated { start: Some(1571797800000000000), end: None, max_executions: None }, 1571797860000000000, SlotType::Cron),
+            // boundary end
+            (Interval::Cron("0 * * * * *".to_string()), BoundaryValidated { start: None, end: Some(1571797800000000000), max_executions: None }, 1571797440000000000, SlotType::Cron),
+            (Interval::Cron("0 0 * * * *".to_string()), BoundaryValidated { start: None, end: Some(1571797800000000000), max_executions: None }, 0, SlotType::Cron),
+        ];
+        for (interval, boundary, outcome_time, outcome_slot_kind) in cases.iter() {
+            let env = mock_env();
+            let (next_id, slot_kind) = interval.next(env, boundary.clone());
+            assert_eq!(outcome_time, &next_id);
+            assert_eq!(outcome_slot_kind, &slot_kind);
+        }
+    }
+
     #[test]
     fn slot_items_get_current() {
         let mut deps = mock_dependencies_with_balance(&coins(200, ""));
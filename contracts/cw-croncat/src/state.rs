@@ -1,10 +1,12 @@
-use cosmwasm_std::{Addr, Coin, StdResult, Storage, Timestamp};
+use cosmwasm_std::{Addr, Coin, StdResult, Storage, Timestamp, Uint128};
 use cw_storage_plus::{Index, IndexList, IndexedMap, Item, Map, MultiIndex};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use crate::helpers::Task;
-use cw_croncat_core::types::{Agent, GenericBalance, SlotType};
+use cw_croncat_core::types::{
+    Agent, FundingEvent, GenericBalance, Interval, SlashDestination, SlotType,
+};
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct Config {
@@ -35,13 +37,62 @@ pub struct Config {
     pub gas_base_fee: u64,
     pub proxy_callback_gas: u32,
     pub slot_granularity: u64,
+    // Paid to whoever reaps a task that can never execute again
+    pub dead_task_bounty: Coin,
+    // How many rules a single task can have, as each rule is a query on execution
+    pub max_rules_per_task: u64,
+    // Where slashed agent funds go
+    pub slash_destination: SlashDestination,
+    // Task creation is rejected while uncommitted native balance is below this, zero disables
+    pub low_balance_pause_threshold: Uint128,
+    // Proxy calls are refused while fewer agents are active
+    pub min_active_agents_for_execution: u64,
+    // Tasks needing more actions and rule queries than this are skipped by proxy_call
+    pub max_submsgs_per_proxy_call: u64,
+    // Taken from the task balance on every execution, left to the owner, zero disables
+    pub protocol_fee: Coin,
+    // optional fee taken from the deposit on create_task
+    pub task_creation_fee: Option<Coin>,
+    // freezes the agent set, active agents keep executing
+    pub nomination_paused: bool,
+    // agent rewards unlock linearly over this many seconds, 0 disables vesting
+    pub reward_vesting_seconds: u64,
+    // only these addresses can create tasks, None or empty leaves it open
+    pub task_creator_whitelist: Option<Vec<Addr>>,
+    // the first slot of a new task must be within this many seconds from now
+    pub max_future_slot_seconds: u64,
+    // pick agents in a block-seeded rotation instead of strict activation order
+    pub randomized_agent_selection: bool,
+    // how many tasks a single owner can have at once, None is unbounded
+    pub max_tasks_per_owner: Option<u64>,
+    // non-native rewards below dust_threshold get swapped to native_denom here on withdraw
+    pub dust_swap_contract: Option<Addr>,
+    // zero disables dust consolidation
+    pub dust_threshold: Uint128,
+    // ejecting agents stops once the active queue is down to this many
+    pub min_active_agents: u64,
+    // Agents calling with an older version are rejected, None accepts any
+    pub min_agent_version: Option<String>,
+    // Slots an out of funds task is held for, so its owner can refill it before removal
+    pub removal_grace_slots: u64,
+    // Ejected agents go back to the pending queue, keeping their registration and balance
+    pub eject_to_pending: bool,
+    // create_task is refused once the contract holds this many tasks
+    pub max_task_limit: u64,
+    // only agents in the allowlist can register
+    pub agent_allowlist_enabled: bool,
+    // tasks whose actions add up to more gas than this are rejected
+    pub max_task_gas: u64,
+    // least native deposit a new task needs, on top of covering its first runs
+    pub min_task_balance: Coin,
 
     // Treasury
-    // pub treasury_id: Option<Addr>,
+    pub treasury_id: Option<Addr>,
     pub cw20_whitelist: Vec<Addr>, // TODO: Consider fee structure for whitelisted CW20s
     pub native_denom: String,
     pub available_balance: GenericBalance, // tasks + rewards balances
     pub staked_balance: GenericBalance, // surplus that is temporary staking (to be used in conjunction with external treasury)
+    pub protocol_fees_collected: GenericBalance, // lifetime total of protocol fees taken from tasks
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
@@ -52,6 +103,35 @@ pub struct QueueItem {
     // could help for IBC non-block bound txns
     pub prev_idx: Option<u64>,
     pub task_hash: Option<Vec<u8>>,
+    /// Index of the task action this reply belongs to, actions are chained one after another
+    pub action_idx: Option<u64>,
+}
+
+/// Agent rewards that haven't unlocked yet, they unlock linearly from `start`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct RewardVesting {
+    pub locked: Uint128,
+    pub start: Timestamp,
+}
+
+impl RewardVesting {
+    /// Amount still locked at the given time, for a vesting period of `duration` seconds
+    pub fn locked_at(&self, time: Timestamp, duration: u64) -> Uint128 {
+        let elapsed = time.seconds().saturating_sub(self.start.seconds());
+        if elapsed >= duration {
+            Uint128::zero()
+        } else {
+            self.locked.multiply_ratio(duration - elapsed, duration)
+        }
+    }
+}
+
+/// Agent lifecycle events counted within one churn bucket
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq, JsonSchema)]
+pub struct AgentChurn {
+    pub registered: u64,
+    pub unregistered: u64,
+    pub ejected: u64,
 }
 
 pub struct TaskIndexes<'a> {
@@ -69,6 +149,16 @@ pub fn token_owner_idx(d: &Task) -> Addr {
     d.owner_id.clone()
 }
 
+/// Key of the task counter for an interval kind
+pub(crate) fn interval_kind(interval: &Interval) -> &'static str {
+    match interval {
+        Interval::Once => "once",
+        Interval::Immediate => "immediate",
+        Interval::Block(_) => "block",
+        Interval::Cron(_) => "cron",
+    }
+}
+
 /// ----------------------------------------------------------------
 /// Tasks Storage
 /// ----------------------------------------------------------------
@@ -79,6 +169,12 @@ pub struct CwCroncat<'a> {
     // TODO: Assess if diff store structure is needed for these:
     pub agent_active_queue: Item<'a, Vec<Addr>>,
     pub agent_pending_queue: Item<'a, Vec<Addr>>,
+    /// Rewards still vesting per agent, only used when reward vesting is enabled
+    pub agent_reward_vesting: Map<'a, Addr, RewardVesting>,
+    /// Relayer allowed to call proxy_call on behalf of an agent
+    pub agent_relayers: Map<'a, Addr, Addr>,
+    /// Agents allowed to register while the allowlist is enabled
+    pub agent_allowlist: Item<'a, Vec<Addr>>,
 
     // REF: https://github.com/CosmWasm/cw-plus/tree/main/packages/storage-plus#indexedmap
     pub tasks: IndexedMap<'a, Vec<u8>, Task, TaskIndexes<'a>>,
@@ -99,6 +195,46 @@ pub struct CwCroncat<'a> {
     // the agent/task ratio allows for another agent to join.
     // Once an agent joins, fulfilling the need, this value changes to None
     pub agent_nomination_begin_time: Item<'a, Option<Timestamp>>,
+
+    /// Last task hash scanned by the dead task reaper, so the next call continues from there
+    pub reap_cursor: Item<'a, Vec<u8>>,
+
+    /// Deposits, refills and withdrawals per task hash, oldest events get dropped
+    pub task_funding_history: Map<'a, Vec<u8>, Vec<FundingEvent>>,
+
+    /// Consecutive failed executions per task hash, used to back off the next slot
+    pub task_failure_streaks: Map<'a, Vec<u8>, u32>,
+
+    /// Creation sequence per task hash, tasks within a slot are kept in this order
+    pub task_sequence: Map<'a, Vec<u8>, u64>,
+    pub task_sequence_next: Item<'a, u64>,
+    /// Task hashes per owner by creation sequence, to look up an owner's tasks in creation order
+    pub owner_task_sequence: Map<'a, (Addr, u64), Vec<u8>>,
+
+    /// Executions per task hash, only kept for tasks bounded by an execution count
+    pub task_executions: Map<'a, Vec<u8>, u64>,
+
+    /// Grace slots left per out of funds task, it's removed once they're used up
+    pub exhausted_tasks: Map<'a, Vec<u8>, u64>,
+
+    /// Tasks with actions in flight, by the height they started at
+    /// Cleared in the proxy callback, guards against a reentrant proxy call running them again
+    pub executing_tasks: Map<'a, Vec<u8>, u64>,
+
+    /// Creation time per task hash
+    pub task_created_at: Map<'a, Vec<u8>, Timestamp>,
+
+    /// Task counts per interval kind, see `interval_kind`
+    pub task_interval_counts: Map<'a, &'a str, u64>,
+
+    /// Owner proposed by the current owner, becomes owner once it accepts
+    pub pending_owner: Item<'a, Addr>,
+
+    /// Dust sent for swapping per reply id, paid out as-is if the swap fails
+    pub dust_swaps: Map<'a, u64, (Addr, Vec<Coin>)>,
+
+    /// Agent churn per hour since epoch, buckets past the churn window get dropped
+    pub agent_churn: Map<'a, u64, AgentChurn>,
 }
 
 impl Default for CwCroncat<'static> {
@@ -117,6 +253,9 @@ impl<'a> CwCroncat<'a> {
             agents: Map::new("agents"),
             agent_active_queue: Item::new("agent_active_queue"),
             agent_pending_queue: Item::new("agent_pending_queue"),
+            agent_reward_vesting: Map::new("agent_reward_vesting"),
+            agent_relayers: Map::new("agent_relayers"),
+            agent_allowlist: Item::new("agent_allowlist"),
             tasks: IndexedMap::new(tasks_key, indexes),
             task_total: Item::new("task_total"),
             time_slots: Map::new("time_slots"),
@@ -124,6 +263,20 @@ impl<'a> CwCroncat<'a> {
             reply_queue: Map::new("reply_queue"),
             reply_index: Item::new("reply_index"),
             agent_nomination_begin_time: Item::new("agent_nomination_begin_time"),
+            reap_cursor: Item::new("reap_cursor"),
+            task_funding_history: Map::new("task_funding_history"),
+            task_failure_streaks: Map::new("task_failure_streaks"),
+            task_sequence: Map::new("task_sequence"),
+            task_sequence_next: Item::new("task_sequence_next"),
+            owner_task_sequence: Map::new("owner_task_sequence"),
+            task_created_at: Map::new("task_created_at"),
+            task_executions: Map::new("task_executions"),
+            exhausted_tasks: Map::new("exhausted_tasks"),
+            executing_tasks: Map::new("executing_tasks"),
+            task_interval_counts: Map::new("task_interval_counts"),
+            pending_owner: Item::new("pending_owner"),
+            dust_swaps: Map::new("dust_swaps"),
+            agent_churn: Map::new("agent_churn"),
         }
     }
 
@@ -138,11 +291,32 @@ impl<'a> CwCroncat<'a> {
     }
 
     pub fn decrement_tasks(&self, storage: &mut dyn Storage) -> StdResult<u64> {
-        let val = self.task_total(storage)? - 1;
+        let val = self.task_total(storage)?.saturating_sub(1);
         self.task_total.save(storage, &val)?;
         Ok(val)
     }
 
+    /// Counts a task in or out of the counter for its interval kind
+    pub(crate) fn count_task_interval(
+        &self,
+        storage: &mut dyn Storage,
+        interval: &Interval,
+        added: bool,
+    ) -> StdResult<u64> {
+        self.task_interval_counts.update(
+            storage,
+            interval_kind(interval),
+            |count| -> StdResult<_> {
+                let count = count.unwrap_or_default();
+                Ok(if added {
+                    count + 1
+                } else {
+                    count.saturating_sub(1)
+                })
+            },
+        )
+    }
+
     pub(crate) fn rq_next_id(&self, storage: &dyn Storage) -> StdResult<u64> {
         Ok(self.reply_index.load(storage)? + 1)
     }
@@ -167,7 +341,7 @@ mod tests {
     use crate::helpers::Task;
     use cosmwasm_std::testing::MockStorage;
     use cosmwasm_std::{coins, BankMsg, CosmosMsg, Order, StdResult};
-    use cw_croncat_core::types::{Action, BoundaryValidated, Interval};
+    use cw_croncat_core::types::{Action, ActionOrder, BoundaryValidated, Interval};
     use cw_storage_plus::Bound;
 
     #[test]
@@ -186,6 +360,7 @@ mod tests {
             boundary: BoundaryValidated {
                 start: None,
                 end: None,
+                max_executions: None,
             },
             stop_on_fail: false,
             total_deposit: vec![],
@@ -194,6 +369,11 @@ mod tests {
                 gas_limit: Some(150_000),
             }],
             rules: None,
+            metadata: None,
+            successor: None,
+            generation: 0,
+            action_order: ActionOrder::Sequential,
+            total_cw20_deposit: vec![],
         };
         let task_id_str = "69217dd2b6334abe2544a12fcb89588f9cc5c62a298b8720706d9befa3d736d3";
         let task_id = task_id_str.to_string().into_bytes();
@@ -1,60 +1,415 @@
 use crate::error::ContractError;
+use crate::helpers::{send_tokens, GenericBalance};
+use crate::manager::{
+    covers_protocol_fee, draw_next_action_funds, no_op_run_cost, task_run_charge,
+};
+use crate::rules::{check_rule, MAX_RULE_DEPTH};
 use crate::slots::Interval;
-use crate::state::{Config, CwCroncat};
+use crate::state::{interval_kind, Config, CwCroncat};
 use cosmwasm_std::{
-    coin, Addr, BankMsg, Coin, Deps, DepsMut, Env, MessageInfo, Order, Response, StdResult, SubMsg,
+    coin, from_binary, Addr, BankMsg, Coin, Deps, DepsMut, Env, Event, MessageInfo, Order,
+    Response, StdError, StdResult, Storage, SubMsg, Timestamp, Uint128,
+};
+use cw20::{Balance, Cw20CoinVerified, Cw20ReceiveMsg};
+use cw_croncat_core::msg::{
+    CheckTaskFundingResponse, CheckTaskRulesResponse, GetExecutionCostBreakdownResponse,
+    GetNextExecutionResponse, GetNextSlotRewardsResponse, GetOldestOverdueSlotResponse,
+    GetSlotCountsResponse, GetSlotHashesResponse, GetSlotIdsResponse, GetTaskCountResponse,
+    GetTaskTypeBreakdownResponse, IneligibleTask, OverdueSlot, ReceiveMsg, RuleCheck,
+    SimulateCreateTaskResponse, SlotHashes, TaskRequest, TaskResponse, TaskStatus, UpcomingSlot,
+    ValidateIntervalResponse,
 };
-use cw20::Balance;
-use cw_croncat_core::msg::{GetSlotHashesResponse, GetSlotIdsResponse, TaskRequest, TaskResponse};
 use cw_croncat_core::traits::Intervals;
-use cw_croncat_core::types::{BoundaryValidated, SlotType, Task};
+use cw_croncat_core::types::{
+    Action, Boundary, BoundaryValidated, FundingEvent, FundingEventKind, Rule, SlotType, Task,
+};
+use cw_storage_plus::{Bound, Map};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::HashSet;
+
+// How many tasks the dead task reaper scans per call
+const DEFAULT_REAP_LIMIT: u64 = 50;
+const MAX_REAP_LIMIT: u64 = 200;
+
+// How many funding events are kept per task
+const MAX_FUNDING_HISTORY: usize = 50;
+
+// Largest metadata blob a task can carry, in bytes
+const MAX_TASK_METADATA_SIZE: usize = 1024;
+
+// Shortest expected block time, converts block slots to seconds for the lookahead
+const MIN_BLOCK_SECONDS: u64 = 5;
+
+// How many upcoming slots of each kind GetSlotHashes lists
+const DEFAULT_SLOT_HASHES_LIMIT: u64 = 10;
+const MAX_SLOT_HASHES_LIMIT: u64 = 100;
+
+// Rejection reason for a boundary that ends before it starts
+const BOUNDARY_REVERSED: &str = "Boundary start is after its end";
+
+// Event emitted on task creation and removal, for indexers to subscribe to
+const TASK_EVENT: &str = "croncat_task";
 
 impl<'a> CwCroncat<'a> {
     /// Returns task data
     /// Used by the frontend for viewing tasks
+    /// When `include_status` is set, every task's due slot and rules are evaluated,
+    /// which scans the ready slots and queries each rule contract (extra gas)
     pub(crate) fn query_get_tasks(
         &self,
         deps: Deps,
+        env: Env,
         from_index: Option<u64>,
         limit: Option<u64>,
+        include_status: Option<bool>,
     ) -> StdResult<Vec<TaskResponse>> {
         let size: u64 = self.task_total.load(deps.storage)?.min(1000);
         let from_index = from_index.unwrap_or_default();
         let limit = limit.unwrap_or(100).min(size);
-        self.tasks
+        let tasks = self
+            .tasks
             .range(deps.storage, None, None, Order::Ascending)
             .skip(from_index as usize)
             .take(limit as usize)
-            .map(|res| res.map(|(_k, task)| task.into()))
+            .collect::<StdResult<Vec<_>>>()?;
+        if !include_status.unwrap_or(false) {
+            return Ok(tasks.into_iter().map(|(_k, task)| task.into()).collect());
+        }
+
+        let due_hashes: HashSet<Vec<u8>> = self.get_due_hashes(deps, &env)?.into_iter().collect();
+        tasks
+            .into_iter()
+            .map(|(hash, task)| {
+                let status = TaskStatus {
+                    due: due_hashes.contains(&hash),
+                    rules_pass: self.check_rules_pass(deps, &task),
+                    grace_slots: self.exhausted_tasks.may_load(deps.storage, hash)?,
+                };
+                let mut res: TaskResponse = task.into();
+                res.status = Some(status);
+                Ok(res)
+            })
             .collect()
     }
 
-    /// Returns task data for a specific owner
+    /// Collects every task hash in a block or time slot that is ready now, in slot order
+    fn get_due_hashes(&self, deps: Deps, env: &Env) -> StdResult<Vec<Vec<u8>>> {
+        let mut due_hashes = vec![];
+        for slot in self.block_slots.range(
+            deps.storage,
+            None,
+            Some(Bound::inclusive(env.block.height)),
+            Order::Ascending,
+        ) {
+            due_hashes.extend(slot?.1);
+        }
+        for slot in self.time_slots.range(
+            deps.storage,
+            None,
+            Some(Bound::inclusive(env.block.time.nanos())),
+            Order::Ascending,
+        ) {
+            due_hashes.extend(slot?.1);
+        }
+        Ok(due_hashes)
+    }
+
+    /// Due tasks a proxy call of the agent would skip, checked in the order proxy_call does.
+    /// Which agent gets a task is decided by the balancer on execution, that isn't covered here.
+    pub(crate) fn query_get_ineligible_tasks(
+        &self,
+        deps: Deps,
+        env: Env,
+        agent_id: Addr,
+        limit: Option<u64>,
+    ) -> StdResult<Vec<IneligibleTask>> {
+        let c: Config = self.config.load(deps.storage)?;
+        let limit = limit.unwrap_or(100).min(1000) as usize;
+        let agent_active = self
+            .agent_active_queue
+            .load(deps.storage)?
+            .contains(&agent_id);
+
+        let mut ineligible = vec![];
+        for hash in self.get_due_hashes(deps, &env)? {
+            if ineligible.len() >= limit {
+                break;
+            }
+            let task = match self.tasks.may_load(deps.storage, hash.clone())? {
+                Some(task) => task,
+                None => continue,
+            };
+            let submsg_count = task.actions.len() + task.rules.as_ref().map_or(0, Vec::len);
+            let reason = if !agent_active {
+                "agent_not_active"
+            } else if self.exhausted_tasks.has(deps.storage, hash.clone()) {
+                "out_of_funds"
+            } else if submsg_count as u64 > c.max_submsgs_per_proxy_call {
+                "max_submsgs_per_proxy_call"
+            } else if self.check_rules_pass(deps, &task) == Some(false) {
+                "rule_not_satisfied"
+            } else if !covers_run(&c, task.clone()) {
+                "insufficient_task_balance"
+            } else {
+                continue;
+            };
+            ineligible.push(IneligibleTask {
+                task_hash: task.to_hash(),
+                reason: reason.to_string(),
+            });
+        }
+        Ok(ineligible)
+    }
+
+    /// Queries every rule of the task, a failing query counts as false
+    fn check_rules_pass(&self, deps: Deps, task: &Task) -> Option<bool> {
+        let rules = task.rules.as_ref()?;
+        Some(
+            rules
+                .iter()
+                .all(|rule| check_rule(&deps.querier, rule).is_ok()),
+        )
+    }
+
+    /// Checks each rule of a task the way proxy calls do, None if the task doesn't exist
+    pub(crate) fn query_check_task_rules(
+        &self,
+        deps: Deps,
+        task_hash: String,
+    ) -> StdResult<Option<CheckTaskRulesResponse>> {
+        let task = match self.tasks.may_load(deps.storage, task_hash.into_bytes())? {
+            Some(task) => task,
+            None => return Ok(None),
+        };
+        let rules: Vec<RuleCheck> = task
+            .rules
+            .iter()
+            .flatten()
+            .map(|rule| {
+                let reason = check_rule(&deps.querier, rule).err();
+                RuleCheck {
+                    contract_addr: rule.addr().cloned(),
+                    pass: reason.is_none(),
+                    reason: reason.map(str::to_string),
+                }
+            })
+            .collect();
+        let pass = rules.iter().all(|rule| rule.pass);
+        Ok(Some(CheckTaskRulesResponse { rules, pass }))
+    }
+
+    /// Returns task data for a specific owner, paginated like `query_get_tasks`
     pub(crate) fn query_get_tasks_by_owner(
         &self,
         deps: Deps,
         owner_id: Addr,
+        from_index: Option<u64>,
+        limit: Option<u64>,
     ) -> StdResult<Vec<TaskResponse>> {
+        let from_index = from_index.unwrap_or_default();
+        let limit = limit.unwrap_or(100).min(1000);
         self.tasks
             .idx
             .owner
             .prefix(owner_id)
             .range(deps.storage, None, None, Order::Ascending)
+            .skip(from_index as usize)
+            .take(limit as usize)
             .map(|x| x.map(|(_, task)| task.into()))
             .collect::<StdResult<Vec<_>>>()
     }
 
+    /// Gets the owner's task at `index`, ordered by creation sequence
+    pub(crate) fn query_get_owner_task_by_index(
+        &self,
+        deps: Deps,
+        owner_id: Addr,
+        index: u64,
+    ) -> StdResult<Option<TaskResponse>> {
+        let hash = self
+            .owner_task_sequence
+            .prefix(owner_id)
+            .range(deps.storage, None, None, Order::Ascending)
+            .nth(index as usize)
+            .transpose()?;
+        match hash {
+            Some((_, hash)) => Ok(self
+                .tasks
+                .may_load(deps.storage, hash)?
+                .map(|task| task.into())),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the total number of tasks from the task counter
+    pub(crate) fn query_task_count(&self, deps: Deps) -> StdResult<GetTaskCountResponse> {
+        Ok(GetTaskCountResponse {
+            total: self.task_total(deps.storage)?,
+        })
+    }
+
+    /// Returns how many tasks there are of each interval kind
+    pub(crate) fn query_task_type_breakdown(
+        &self,
+        deps: Deps,
+    ) -> StdResult<GetTaskTypeBreakdownResponse> {
+        let count = |kind: &Interval| -> StdResult<u64> {
+            Ok(self
+                .task_interval_counts
+                .may_load(deps.storage, interval_kind(kind))?
+                .unwrap_or_default())
+        };
+        Ok(GetTaskTypeBreakdownResponse {
+            once: count(&Interval::Once)?,
+            immediate: count(&Interval::Immediate)?,
+            block: count(&Interval::Block(1))?,
+            cron: count(&Interval::Cron(String::new()))?,
+        })
+    }
+
+    /// Returns tasks whose time boundary ends within `within_seconds` from now
+    /// Only cron tasks have time boundaries, block based ones are never returned
+    pub(crate) fn query_get_expiring_tasks(
+        &self,
+        deps: Deps,
+        env: Env,
+        within_seconds: u64,
+        limit: Option<u64>,
+    ) -> StdResult<Vec<TaskResponse>> {
+        let limit = limit.unwrap_or(100).min(1000);
+        let now = env.block.time.nanos();
+        let window_end = env.block.time.plus_seconds(within_seconds).nanos();
+        self.tasks
+            .range(deps.storage, None, None, Order::Ascending)
+            .filter(|res| match res {
+                Ok((_, task)) => match (&task.interval, task.boundary.end) {
+                    (Interval::Cron(_), Some(end)) => now <= end && end <= window_end,
+                    _ => false,
+                },
+                Err(_) => true,
+            })
+            .take(limit as usize)
+            .map(|x| x.map(|(_, task)| task.into()))
+            .collect()
+    }
+
+    /// Returns tasks whose native balance can't pay for one more execution
+    pub(crate) fn query_get_underfunded_tasks(
+        &self,
+        deps: Deps,
+        limit: Option<u64>,
+    ) -> StdResult<Vec<TaskResponse>> {
+        let c: Config = self.config.load(deps.storage)?;
+        let limit = limit.unwrap_or(100).min(1000);
+        self.tasks
+            .range(deps.storage, None, None, Order::Ascending)
+            .filter(|res| match res {
+                Ok((_, task)) => {
+                    let balance = task
+                        .total_deposit
+                        .iter()
+                        .find(|coin| coin.denom == c.native_denom)
+                        .map(|coin| coin.amount.u128())
+                        .unwrap_or_default();
+                    balance < self.task_execution_cost(&c, task)
+                }
+                Err(_) => true,
+            })
+            .take(limit as usize)
+            .map(|x| x.map(|(_, task)| task.into()))
+            .collect()
+    }
+
+    /// Returns tasks created between `start` and `end`, both inclusive
+    pub(crate) fn query_get_tasks_created_between(
+        &self,
+        deps: Deps,
+        start: Timestamp,
+        end: Timestamp,
+        from_index: Option<u64>,
+        limit: Option<u64>,
+    ) -> StdResult<Vec<TaskResponse>> {
+        let from_index = from_index.unwrap_or_default();
+        let limit = limit.unwrap_or(100).min(1000);
+        self.task_created_at
+            .range(deps.storage, None, None, Order::Ascending)
+            .filter(|res| match res {
+                Ok((_, created_at)) => *created_at >= start && *created_at <= end,
+                Err(_) => true,
+            })
+            .skip(from_index as usize)
+            .take(limit as usize)
+            .map(|res| {
+                let (hash, _) = res?;
+                Ok(self.tasks.load(deps.storage, hash)?.into())
+            })
+            .collect()
+    }
+
     /// Returns single task data
     pub(crate) fn query_get_task(
         &self,
         deps: Deps,
         task_hash: String,
     ) -> StdResult<Option<TaskResponse>> {
-        let res: Option<Task> = self
-            .tasks
-            .may_load(deps.storage, task_hash.as_bytes().to_vec())?;
+        let hash = task_hash.into_bytes();
+        let task = match self.tasks.may_load(deps.storage, hash.clone())? {
+            Some(task) => task,
+            None => return Ok(None),
+        };
+        let executions_remaining = match task.boundary.max_executions {
+            Some(max) => {
+                let executions = self
+                    .task_executions
+                    .may_load(deps.storage, hash)?
+                    .unwrap_or_default();
+                Some(max.saturating_sub(executions))
+            }
+            None => None,
+        };
+        let mut res: TaskResponse = task.into();
+        res.executions_remaining = executions_remaining;
+        Ok(Some(res))
+    }
+
+    /// Returns the deposits, refills and withdrawals of a task, oldest first
+    pub(crate) fn query_get_task_funding_history(
+        &self,
+        deps: Deps,
+        task_hash: String,
+    ) -> StdResult<Vec<FundingEvent>> {
+        Ok(self
+            .task_funding_history
+            .may_load(deps.storage, task_hash.into_bytes())?
+            .unwrap_or_default())
+    }
 
-        Ok(res.map(Into::into))
+    /// Appends to the task funding history, dropping the oldest events past the limit
+    pub(crate) fn push_funding_event(
+        &self,
+        storage: &mut dyn Storage,
+        env: &Env,
+        task_hash: Vec<u8>,
+        kind: FundingEventKind,
+        amount: Vec<Coin>,
+    ) -> StdResult<()> {
+        let event = FundingEvent {
+            kind,
+            amount,
+            block_height: env.block.height,
+            block_time: env.block.time,
+        };
+        self.task_funding_history
+            .update(storage, task_hash, |history| -> StdResult<_> {
+                let mut history = history.unwrap_or_default();
+                history.push(event);
+                if history.len() > MAX_FUNDING_HISTORY {
+                    history.drain(..history.len() - MAX_FUNDING_HISTORY);
+                }
+                Ok(history)
+            })?;
+        Ok(())
     }
 
     /// Returns a hash computed by the input task data
@@ -62,9 +417,285 @@ impl<'a> CwCroncat<'a> {
         Ok(task.to_hash())
     }
 
-    /// Check if interval params are valid by attempting to parse
-    pub(crate) fn query_validate_interval(&self, interval: Interval) -> StdResult<bool> {
-        Ok(interval.is_valid())
+    /// Check if interval params are valid by attempting to parse,
+    /// and that the boundary kind fits the interval
+    pub(crate) fn query_validate_interval(
+        &self,
+        interval: Interval,
+        boundary: Option<Boundary>,
+    ) -> StdResult<ValidateIntervalResponse> {
+        let reason = match BoundaryValidated::validate_boundary(boundary, &interval) {
+            Ok(boundary) => interval.invalid_reason(&boundary).or_else(|| {
+                boundary
+                    .is_reversed()
+                    .then(|| BOUNDARY_REVERSED.to_string())
+            }),
+            Err(_) => Some(match interval {
+                Interval::Cron(_) => "Cron interval needs a time boundary".to_string(),
+                _ => "Block based interval needs a height boundary".to_string(),
+            }),
+        };
+        Ok(ValidateIntervalResponse {
+            valid: reason.is_none(),
+            reason,
+        })
+    }
+
+    /// Costs of a single run of the task, None if the task doesn't exist
+    /// The total is what proxy_call takes from the task balance per run
+    pub(crate) fn query_get_execution_cost_breakdown(
+        &self,
+        deps: Deps,
+        task_hash: String,
+    ) -> StdResult<Option<GetExecutionCostBreakdownResponse>> {
+        let task = match self.tasks.may_load(deps.storage, task_hash.into_bytes())? {
+            Some(task) => task,
+            None => return Ok(None),
+        };
+        let c: Config = self.config.load(deps.storage)?;
+        let gas_price = u128::from(c.gas_price);
+        let action_gas = task.actions.iter().fold(0u64, |sum, action| {
+            sum.saturating_add(action.gas_limit.unwrap_or(c.gas_base_fee))
+        });
+        let gas_cost = coin(u128::from(action_gas) * gas_price, &c.native_denom);
+        // No-op runs don't dispatch anything, so there's no callback
+        let callback_gas = if task.is_no_op() {
+            0
+        } else {
+            task.to_callback_gas(c.proxy_callback_gas)
+        };
+        let proxy_callback_cost = coin(u128::from(callback_gas) * gas_price, &c.native_denom);
+        Ok(Some(GetExecutionCostBreakdownResponse {
+            gas_cost,
+            agent_fee: c.agent_fee.clone(),
+            proxy_callback_cost,
+            protocol_fee: c.protocol_fee.clone(),
+            action_funds: task.funds_per_run(),
+            total: task_run_charge(&c, &task),
+        }))
+    }
+
+    /// Checks a deposit against a task request the same way create_task does,
+    /// and counts how many executions it pays for
+    pub(crate) fn query_check_task_funding(
+        &self,
+        deps: Deps,
+        env: Env,
+        task: TaskRequest,
+        deposit: Vec<Coin>,
+    ) -> StdResult<CheckTaskFundingResponse> {
+        let c: Config = self.config.load(deps.storage)?;
+        let boundary = BoundaryValidated::validate_boundary(task.boundary, &task.interval)
+            .map_err(|err| StdError::generic_err(err.to_string()))?;
+        let task_deposit = self.deduct_task_creation_fee(&c, &deposit);
+        let item = Task {
+            owner_id: env.contract.address,
+            interval: task.interval,
+            boundary,
+            stop_on_fail: task.stop_on_fail,
+            total_deposit: task_deposit.clone().unwrap_or_default(),
+            actions: task.actions,
+            rules: task.rules,
+            metadata: task.metadata,
+            successor: task.successor,
+            generation: 0,
+            action_order: task.action_order.unwrap_or_default(),
+            total_cw20_deposit: vec![],
+        };
+        let min_balance_needed = self.task_required_deposit(&c, &item);
+        let attached_native = item
+            .total_deposit
+            .iter()
+            .find(|coin| coin.denom == c.native_denom)
+            .map(|coin| coin.amount.u128())
+            .unwrap_or_default();
+        let sufficient = task_deposit.is_some()
+            && attached_native >= min_balance_needed
+            && self.action_funds_shortfalls(&c, &item).is_empty();
+
+        // Runs that cost nothing can go on forever
+        let executions = attached_native
+            .checked_div(self.task_execution_cost(&c, &item))
+            .map_or(u64::MAX, |runs| runs.min(u128::from(u64::MAX)) as u64);
+
+        Ok(CheckTaskFundingResponse {
+            sufficient,
+            min_deposit: coin(min_balance_needed, c.native_denom),
+            task_creation_fee: c.task_creation_fee,
+            executions,
+        })
+    }
+
+    /// Runs the checks create_task does on a request without needing funds,
+    /// collecting every problem instead of stopping at the first one
+    pub(crate) fn query_simulate_create_task(
+        &self,
+        deps: Deps,
+        env: Env,
+        owner_id: Addr,
+        task: TaskRequest,
+    ) -> StdResult<SimulateCreateTaskResponse> {
+        let c: Config = self.config.load(deps.storage)?;
+        let owner_id = deps.api.addr_validate(owner_id.as_str())?;
+        let mut errors: Vec<String> = vec![];
+
+        if task.metadata.as_ref().map_or(0, |m| m.len()) > MAX_TASK_METADATA_SIZE {
+            errors.push(format!(
+                "Task metadata exceeds {} bytes",
+                MAX_TASK_METADATA_SIZE
+            ));
+        }
+        let boundary = match BoundaryValidated::validate_boundary(task.boundary, &task.interval) {
+            Ok(boundary) if boundary.is_reversed() => {
+                errors.push(BOUNDARY_REVERSED.to_string());
+                Some(boundary)
+            }
+            Ok(boundary) => Some(boundary),
+            Err(err) => {
+                errors.push(err.to_string());
+                None
+            }
+        };
+        let item = Task {
+            owner_id: owner_id.clone(),
+            interval: task.interval,
+            boundary: boundary.unwrap_or(BoundaryValidated {
+                start: None,
+                end: None,
+                max_executions: None,
+            }),
+            stop_on_fail: task.stop_on_fail,
+            total_deposit: vec![],
+            actions: task.actions,
+            rules: task.rules,
+            metadata: task.metadata,
+            successor: task.successor,
+            generation: 0,
+            action_order: task.action_order.unwrap_or_default(),
+            total_cw20_deposit: vec![],
+        };
+
+        if !item.is_valid_msg(&env.contract.address, &owner_id, &c.owner_id) {
+            errors.push("Actions Message Unsupported".to_string());
+        }
+        if !item.interval.is_valid(&item.boundary) {
+            errors.push("Interval invalid".to_string());
+        } else if boundary.is_some() && item.interval.next(env, item.boundary).0 == 0 {
+            errors.push("Task ended".to_string());
+        }
+        if item.to_gas_total() > c.max_task_gas {
+            errors.push(format!("Task gas exceeds max allowed: {}", c.max_task_gas));
+        }
+        let num_rules = item.rules.as_ref().map(Vec::len).unwrap_or_default() as u64;
+        if num_rules > c.max_rules_per_task {
+            errors.push(format!(
+                "Too many rules, max allowed: {}",
+                c.max_rules_per_task
+            ));
+        }
+        if item
+            .rules
+            .iter()
+            .flatten()
+            .any(|rule| rule.depth() > MAX_RULE_DEPTH)
+        {
+            errors.push(format!(
+                "Rules nest too deep, max allowed: {}",
+                MAX_RULE_DEPTH
+            ));
+        }
+
+        let min_balance_needed = self.task_required_deposit(&c, &item);
+        Ok(SimulateCreateTaskResponse {
+            task_hash: boundary.map(|_| item.to_hash()),
+            gas_total: item.to_gas_total(),
+            min_deposit: coin(min_balance_needed, c.native_denom),
+            errors,
+        })
+    }
+
+    /// Attached funds left for the task once the creation fee is taken, None if they can't cover it
+    fn deduct_task_creation_fee(&self, c: &Config, funds: &[Coin]) -> Option<Vec<Coin>> {
+        let mut total_deposit = funds.to_vec();
+        if let Some(fee) = &c.task_creation_fee {
+            let fee_deposit = total_deposit
+                .iter_mut()
+                .find(|coin| coin.denom == fee.denom)?;
+            if fee_deposit.amount < fee.amount {
+                return None;
+            }
+            fee_deposit.amount -= fee.amount;
+            total_deposit.retain(|coin| !coin.amount.is_zero());
+        }
+        Some(total_deposit)
+    }
+
+    /// Native amount a single run of the task costs,
+    /// the protocol fee is taken on every run on top of the execution costs
+    fn task_execution_cost(&self, c: &Config, task: &Task) -> u128 {
+        let mut execution_cost =
+            task.task_balance_uses(&c.agent_fee, c.gas_base_fee, c.proxy_callback_gas);
+        if c.protocol_fee.denom == c.native_denom {
+            execution_cost += c.protocol_fee.amount.u128();
+        }
+        execution_cost
+    }
+
+    /// Whether the native deposit covers the execution cost and the native funds the actions send in a run
+    fn covers_full_run(&self, c: &Config, task: &Task) -> bool {
+        let native_amount = |coins: &[Coin]| {
+            coins
+                .iter()
+                .find(|coin| coin.denom == c.native_denom)
+                .map_or(0, |coin| coin.amount.u128())
+        };
+        native_amount(&task.total_deposit)
+            >= self.task_execution_cost(c, task) + native_amount(&task.funds_per_run())
+    }
+
+    /// Denoms other than the native one that the deposit can't cover a single run of the actions in
+    /// Those only get spent by the actions, so each has to cover a run up front.
+    /// The native one is covered by the required deposit and actions it can't pay are skipped on execution
+    fn action_funds_shortfalls(&self, c: &Config, task: &Task) -> Vec<String> {
+        task.funds_per_run()
+            .into_iter()
+            .filter(|coin| coin.denom != c.native_denom)
+            .filter_map(|coin| {
+                let attached = task
+                    .total_deposit
+                    .iter()
+                    .find(|d| d.denom == coin.denom)
+                    .map(|d| d.amount)
+                    .unwrap_or_default();
+                (attached < coin.amount).then(|| {
+                    format!(
+                        "{} (need {}, attached {})",
+                        coin.denom, coin.amount, attached
+                    )
+                })
+            })
+            .collect()
+    }
+
+    /// Native balance a new task needs, enough for two runs unless it only runs once
+    fn task_min_balance(&self, c: &Config, task: &Task) -> u128 {
+        let call_balance_used =
+            task.task_balance_uses(&c.agent_fee, c.gas_base_fee, c.proxy_callback_gas);
+        if task.interval != Interval::Once {
+            call_balance_used * 2
+        } else {
+            call_balance_used
+        }
+    }
+
+    /// Native deposit a task needs on create and update, the configured minimum when it's above what the first runs cost
+    fn task_required_deposit(&self, c: &Config, task: &Task) -> u128 {
+        let min_task_balance = if c.min_task_balance.denom == c.native_denom {
+            c.min_task_balance.amount.u128()
+        } else {
+            0
+        };
+        self.task_min_balance(c, task).max(min_task_balance)
     }
 
     /// Gets a set of tasks.
@@ -73,17 +704,22 @@ impl<'a> CwCroncat<'a> {
     /// Optional Parameters:
     /// "offset" - An unsigned integer specifying how far in the future to check for tasks that are slotted.
     ///
+    /// "limit" - How many upcoming non-empty slots of each kind to list when no slot is given.
+    ///
     /// Result:
-    /// (block id, block task hash's, time id, time task hash's)
+    /// (block id, block task hash's, time id, time task hash's, upcoming block slots, upcoming time slots)
     pub(crate) fn query_slot_tasks(
         &self,
         deps: Deps,
         slot: Option<u64>,
+        limit: Option<u64>,
     ) -> StdResult<GetSlotHashesResponse> {
         let mut block_id: u64 = 0;
         let mut block_hashes: Vec<Vec<u8>> = Vec::new();
         let mut time_id: u64 = 0;
         let mut time_hashes: Vec<Vec<u8>> = Vec::new();
+        let mut block_slots: Vec<SlotHashes> = Vec::new();
+        let mut time_slots: Vec<SlotHashes> = Vec::new();
 
         // Check if slot was supplied, otherwise get the next slots for block and time
         if let Some(id) = slot {
@@ -95,7 +731,7 @@ impl<'a> CwCroncat<'a> {
                 block_id = id;
             }
             time_hashes = self
-                .block_slots
+                .time_slots
                 .may_load(deps.storage, id)?
                 .unwrap_or_default();
             if !time_hashes.is_empty() {
@@ -127,23 +763,34 @@ impl<'a> CwCroncat<'a> {
                 block_id = slot.0;
                 block_hashes = slot.1;
             }
-        }
 
-        // Generate strings for all hashes
-        let block_task_hash: Vec<_> = block_hashes
-            .iter()
-            .map(|b| String::from_utf8(b.to_vec()).unwrap_or_else(|_| "".to_string()))
-            .collect();
-        let time_task_hash: Vec<_> = time_hashes
-            .iter()
-            .map(|t| String::from_utf8(t.to_vec()).unwrap_or_else(|_| "".to_string()))
-            .collect();
+            let limit = limit
+                .unwrap_or(DEFAULT_SLOT_HASHES_LIMIT)
+                .min(MAX_SLOT_HASHES_LIMIT);
+            let upcoming = |slots: &Map<u64, Vec<Vec<u8>>>| -> StdResult<Vec<SlotHashes>> {
+                slots
+                    .range(deps.storage, None, None, Order::Ascending)
+                    .filter(|res| res.as_ref().map_or(true, |(_, hashes)| !hashes.is_empty()))
+                    .take(limit as usize)
+                    .map(|res| {
+                        res.map(|(slot_id, hashes)| SlotHashes {
+                            slot_id,
+                            task_hashes: hashes_to_strings(&hashes),
+                        })
+                    })
+                    .collect()
+            };
+            block_slots = upcoming(&self.block_slots)?;
+            time_slots = upcoming(&self.time_slots)?;
+        }
 
         Ok(GetSlotHashesResponse {
             block_id,
-            block_task_hash,
+            block_task_hash: hashes_to_strings(&block_hashes),
             time_id,
-            time_task_hash,
+            time_task_hash: hashes_to_strings(&time_hashes),
+            block_slots,
+            time_slots,
         })
     }
 
@@ -164,38 +811,240 @@ impl<'a> CwCroncat<'a> {
         })
     }
 
-    /// Allows any user or contract to pay for future txns based on a specific schedule
-    /// contract, function id & other settings. When the task runs out of balance
-    /// the task is no longer executed, any additional funds will be returned to task owner.
-    pub fn create_task(
+    /// Gets the occupied slot ids paired with their task count, for time and block slots
+    /// Pagination applies to both slot kinds separately
+    pub(crate) fn query_slot_counts(
         &self,
-        deps: DepsMut,
-        info: MessageInfo,
+        deps: Deps,
+        from_index: Option<u64>,
+        limit: Option<u64>,
+    ) -> StdResult<GetSlotCountsResponse> {
+        let from_index = from_index.unwrap_or_default() as usize;
+        let limit = limit.unwrap_or(100).min(1000) as usize;
+        let count_slots = |slots: &Map<u64, Vec<Vec<u8>>>| -> StdResult<Vec<(u64, u64)>> {
+            slots
+                .range(deps.storage, None, None, Order::Ascending)
+                .skip(from_index)
+                .take(limit)
+                .map(|res| res.map(|(id, hashes)| (id, hashes.len() as u64)))
+                .collect()
+        };
+        Ok(GetSlotCountsResponse {
+            time_counts: count_slots(&self.time_slots)?,
+            block_counts: count_slots(&self.block_slots)?,
+        })
+    }
+
+    /// Gets the oldest block and time slots that still hold tasks after they were due
+    /// Helps operators see how far behind agents are
+    pub(crate) fn query_oldest_overdue_slot(
+        &self,
+        deps: Deps,
         env: Env,
-        task: TaskRequest,
-    ) -> Result<Response, ContractError> {
-        if info.funds.is_empty() {
-            return Err(ContractError::CustomError {
-                val: "Must attach funds".to_string(),
-            });
-        }
-        let c: Config = self.config.load(deps.storage)?;
-        if c.paused {
-            return Err(ContractError::CustomError {
-                val: "Create task paused".to_string(),
-            });
+    ) -> StdResult<Option<GetOldestOverdueSlotResponse>> {
+        let oldest_overdue =
+            |slots: &Map<u64, Vec<Vec<u8>>>, current: u64| -> StdResult<Option<OverdueSlot>> {
+                for slot in slots.range(
+                    deps.storage,
+                    None,
+                    Some(Bound::exclusive(current)),
+                    Order::Ascending,
+                ) {
+                    let (slot_id, hashes) = slot?;
+                    if !hashes.is_empty() {
+                        return Ok(Some(OverdueSlot {
+                            slot_id,
+                            overdue_by: current - slot_id,
+                        }));
+                    }
+                }
+                Ok(None)
+            };
+        let block_slot = oldest_overdue(&self.block_slots, env.block.height)?;
+        let time_slot = oldest_overdue(&self.time_slots, env.block.time.nanos())?;
+        if block_slot.is_none() && time_slot.is_none() {
+            return Ok(None);
         }
+        Ok(Some(GetOldestOverdueSlotResponse {
+            block_slot,
+            time_slot,
+        }))
+    }
+
+    /// Gets the earliest non-empty block and time slots with the blocks or seconds until they're due
+    pub(crate) fn query_next_execution(
+        &self,
+        deps: Deps,
+        env: Env,
+    ) -> StdResult<Option<GetNextExecutionResponse>> {
+        let earliest = |slots: &Map<u64, Vec<Vec<u8>>>| -> StdResult<Option<u64>> {
+            for slot in slots.range(deps.storage, None, None, Order::Ascending) {
+                let (slot_id, hashes) = slot?;
+                if !hashes.is_empty() {
+                    return Ok(Some(slot_id));
+                }
+            }
+            Ok(None)
+        };
+        let block_slot = earliest(&self.block_slots)?.map(|slot_id| UpcomingSlot {
+            slot_id,
+            due_in: slot_id.saturating_sub(env.block.height),
+        });
+        let time_slot = earliest(&self.time_slots)?.map(|slot_id| UpcomingSlot {
+            slot_id,
+            due_in: slot_id.saturating_sub(env.block.time.nanos()) / 1_000_000_000,
+        });
+        if block_slot.is_none() && time_slot.is_none() {
+            return Ok(None);
+        }
+        Ok(Some(GetNextExecutionResponse {
+            block_slot,
+            time_slot,
+        }))
+    }
+
+    /// Agent fees paid out for the tasks of whichever of the nearest block and time slot
+    /// comes up first, blocks are counted as `MIN_BLOCK_SECONDS` each.
+    /// Ties go to the block slot, same as agents prefer it on execution.
+    pub(crate) fn query_next_slot_rewards(
+        &self,
+        deps: Deps,
+        env: Env,
+    ) -> StdResult<Option<GetNextSlotRewardsResponse>> {
+        let next = match self.query_next_execution(deps, env)? {
+            Some(next) => next,
+            None => return Ok(None),
+        };
+        let (slot_id, slot_kind) = match (next.block_slot, next.time_slot) {
+            (Some(block), Some(time))
+                if block.due_in.saturating_mul(MIN_BLOCK_SECONDS) <= time.due_in =>
+            {
+                (block.slot_id, SlotType::Block)
+            }
+            (Some(block), None) => (block.slot_id, SlotType::Block),
+            (_, Some(time)) => (time.slot_id, SlotType::Cron),
+            (None, None) => return Ok(None),
+        };
+        let slots = match slot_kind {
+            SlotType::Block => &self.block_slots,
+            SlotType::Cron => &self.time_slots,
+        };
+        let tasks = slots.load(deps.storage, slot_id)?.len() as u64;
+        let c: Config = self.config.load(deps.storage)?;
+        Ok(Some(GetNextSlotRewardsResponse {
+            slot_id,
+            slot_kind,
+            tasks,
+            total_reward: Coin::new(
+                c.agent_fee.amount.u128() * u128::from(tasks),
+                c.agent_fee.denom,
+            ),
+        }))
+    }
+
+    /// Allows any user or contract to pay for future txns based on a specific schedule
+    /// contract, function id & other settings. When the task runs out of balance
+    /// the task is no longer executed, any additional funds will be returned to task owner.
+    pub fn create_task(
+        &self,
+        deps: DepsMut,
+        info: MessageInfo,
+        env: Env,
+        task: TaskRequest,
+    ) -> Result<Response, ContractError> {
+        self.create_task_generation(deps, info, env, task, 0)
+    }
+
+    /// Creates a task, successors of ended tasks count up the generation of their predecessor
+    pub(crate) fn create_task_generation(
+        &self,
+        deps: DepsMut,
+        info: MessageInfo,
+        env: Env,
+        task: TaskRequest,
+        generation: u64,
+    ) -> Result<Response, ContractError> {
+        if info.funds.is_empty() {
+            return Err(ContractError::CustomError {
+                val: "Must attach funds".to_string(),
+            });
+        }
+        let c: Config = self.config.load(deps.storage)?;
+        if c.paused {
+            return Err(ContractError::CustomError {
+                val: "Create task paused".to_string(),
+            });
+        }
+        // Private deployments only let whitelisted addresses schedule tasks
+        if let Some(whitelist) = &c.task_creator_whitelist {
+            if !whitelist.is_empty() && !whitelist.contains(&info.sender) {
+                return Err(ContractError::Unauthorized {});
+            }
+        }
+        if self.task_total(deps.storage)? >= c.max_task_limit {
+            return Err(ContractError::TaskLimitReached {
+                max: c.max_task_limit,
+            });
+        }
+        if let Some(max_tasks) = c.max_tasks_per_owner {
+            let owner_tasks = self
+                .tasks
+                .idx
+                .owner
+                .prefix(info.sender.clone())
+                .keys(deps.storage, None, None, Order::Ascending)
+                .count() as u64;
+            if owner_tasks >= max_tasks {
+                return Err(ContractError::CustomError {
+                    val: format!("Too many tasks for owner, max allowed: {}", max_tasks),
+                });
+            }
+        }
+        if self.is_balance_low(deps.storage, &c)? {
+            return Err(ContractError::CustomError {
+                val: "Create task paused, contract balance is low".to_string(),
+            });
+        }
+
+        if task.metadata.as_ref().map_or(0, |m| m.len()) > MAX_TASK_METADATA_SIZE {
+            return Err(ContractError::CustomError {
+                val: format!("Task metadata exceeds {} bytes", MAX_TASK_METADATA_SIZE),
+            });
+        }
+
+        // Creation fee is taken out of the attached funds, the rest funds the task
+        let total_deposit = match self.deduct_task_creation_fee(&c, &info.funds) {
+            Some(total_deposit) => total_deposit,
+            None => {
+                return Err(ContractError::CustomError {
+                    val: format!(
+                        "Must attach task creation fee of {}",
+                        c.task_creation_fee.as_ref().unwrap()
+                    ),
+                })
+            }
+        };
 
         let owner_id = info.sender;
         let boundary = BoundaryValidated::validate_boundary(task.boundary, &task.interval)?;
+        if boundary.is_reversed() {
+            return Err(ContractError::CustomError {
+                val: BOUNDARY_REVERSED.to_string(),
+            });
+        }
         let item = Task {
             owner_id: owner_id.clone(),
             interval: task.interval,
             boundary,
             stop_on_fail: task.stop_on_fail,
-            total_deposit: info.funds.clone(),
+            total_deposit,
             actions: task.actions,
             rules: task.rules,
+            metadata: task.metadata,
+            successor: task.successor,
+            generation,
+            action_order: task.action_order.unwrap_or_default(),
+            total_cw20_deposit: vec![],
         };
 
         if !item.is_valid_msg(&env.contract.address, &owner_id, &c.owner_id) {
@@ -204,29 +1053,56 @@ impl<'a> CwCroncat<'a> {
             });
         }
 
-        if !item.interval.is_valid() {
+        if !item.interval.is_valid(&item.boundary) {
             return Err(ContractError::CustomError {
                 val: "Interval invalid".to_string(),
             });
         }
 
-        // // Check that balance is sufficient for 1 execution minimum
-        let call_balance_used = item.task_balance_uses(&c.agent_fee, c.gas_base_fee);
-        let min_balance_needed: u128 = if item.interval != Interval::Once {
-            call_balance_used * 2
-        } else {
-            call_balance_used
-        };
+        if item.to_gas_total() > c.max_task_gas {
+            return Err(ContractError::CustomError {
+                val: format!("Task gas exceeds max allowed: {}", c.max_task_gas),
+            });
+        }
+
+        // Each rule is queried on execution, so keep it bounded
+        let num_rules = item.rules.as_ref().map(Vec::len).unwrap_or_default() as u64;
+        if num_rules > c.max_rules_per_task {
+            return Err(ContractError::CustomError {
+                val: format!("Too many rules, max allowed: {}", c.max_rules_per_task),
+            });
+        }
+        if item
+            .rules
+            .iter()
+            .flatten()
+            .any(|rule| rule.depth() > MAX_RULE_DEPTH)
+        {
+            return Err(ContractError::CustomError {
+                val: format!("Rules nest too deep, max allowed: {}", MAX_RULE_DEPTH),
+            });
+        }
+
+        // Check that the balance covers the first runs and the configured minimum
         let attached_native = item
             .total_deposit
             .iter()
             .find(|coin| coin.denom == c.native_denom)
             .map(|c| c.amount.u128())
             .unwrap_or_default();
-        if attached_native < min_balance_needed {
+        let required_deposit = self.task_required_deposit(&c, &item);
+        if attached_native < required_deposit {
+            return Err(ContractError::InsufficientTaskBalance {
+                required: coin(required_deposit, &c.native_denom),
+            });
+        }
+
+        let shortfalls = self.action_funds_shortfalls(&c, &item);
+        if !shortfalls.is_empty() {
             return Err(ContractError::CustomError {
                 val: format!(
-                    "Not enough task balance to execute job, need at least {min_balance_needed}, attached: {attached_native}",
+                    "Not enough task balance for actions in: {}",
+                    shortfalls.join(", ")
                 ),
             });
         }
@@ -243,6 +1119,22 @@ impl<'a> CwCroncat<'a> {
             });
         }
 
+        // Don't keep storage around for tasks that won't run for ages
+        let lookahead = match slot_kind {
+            SlotType::Block => next_id
+                .saturating_sub(env.block.height)
+                .saturating_mul(MIN_BLOCK_SECONDS),
+            SlotType::Cron => next_id.saturating_sub(env.block.time.nanos()) / 1_000_000_000,
+        };
+        if lookahead > c.max_future_slot_seconds {
+            return Err(ContractError::CustomError {
+                val: format!(
+                    "First slot is too far in the future, max {} seconds ahead",
+                    c.max_future_slot_seconds
+                ),
+            });
+        }
+
         // Add task to catalog
         self.tasks
             .update(deps.storage, item.to_hash_vec(), |old| match old {
@@ -251,6 +1143,13 @@ impl<'a> CwCroncat<'a> {
                 }),
                 None => Ok(item.clone()),
             })?;
+        self.push_funding_event(
+            deps.storage,
+            &env,
+            item.to_hash_vec(),
+            FundingEventKind::Deposit,
+            item.total_deposit.clone(),
+        )?;
 
         // Increment task totals
         let size_res = self.increment_tasks(deps.storage);
@@ -260,42 +1159,42 @@ impl<'a> CwCroncat<'a> {
             });
         }
         let size = size_res.unwrap();
+        self.count_task_interval(deps.storage, &item.interval, true)?;
 
-        // Get previous task hashes in slot, add as needed
-        let update_vec_data = |d: Option<Vec<Vec<u8>>>| -> StdResult<Vec<Vec<u8>>> {
-            match d {
-                // has some data, simply push new hash
-                Some(data) => {
-                    let mut s = data;
-                    s.push(item.to_hash_vec());
-                    Ok(s)
-                }
-                // No data, push new vec & hash
-                None => Ok(vec![item.to_hash_vec()]),
-            }
-        };
+        // Creation sequence keeps the order of tasks sharing a slot stable
+        let sequence = self
+            .task_sequence_next
+            .may_load(deps.storage)?
+            .unwrap_or_default();
+        self.task_sequence_next
+            .save(deps.storage, &(sequence + 1))?;
+        self.task_sequence
+            .save(deps.storage, item.to_hash_vec(), &sequence)?;
+        self.owner_task_sequence.save(
+            deps.storage,
+            (owner_id.clone(), sequence),
+            &item.to_hash_vec(),
+        )?;
+        self.task_created_at
+            .save(deps.storage, item.to_hash_vec(), &env.block.time)?;
 
         // Based on slot kind, put into block or cron slots
-        match slot_kind {
-            SlotType::Block => {
-                self.block_slots
-                    .update(deps.storage, next_id, update_vec_data)?;
-            }
-            SlotType::Cron => {
-                self.time_slots
-                    .update(deps.storage, next_id, update_vec_data)?;
-            }
-        }
+        self.push_slot_item(deps.storage, &next_id, &slot_kind, item.to_hash_vec())?;
 
         // Add the attached balance into available_balance
         let mut c: Config = c;
         c.available_balance.add_tokens(Balance::from(info.funds));
+        if let Some(fee) = &c.task_creation_fee {
+            c.protocol_fees_collected
+                .add_tokens(Balance::from(vec![fee.clone()]));
+        }
 
         // If the creation of this task means we'd like another agent, update config
         let min_tasks_per_agent = c.min_tasks_per_agent;
         let num_active_agents = self.agent_active_queue.load(deps.storage)?.len() as u64;
         let num_agents_to_accept =
             self.agents_to_let_in(&min_tasks_per_agent, &num_active_agents, &size);
+        let mut nomination_attrs: Vec<(&str, String)> = vec![];
         // If we should allow a new agent to take over
         if num_agents_to_accept != 0 {
             // Don't wipe out an older timestamp
@@ -303,20 +1202,81 @@ impl<'a> CwCroncat<'a> {
             if begin.is_none() {
                 self.agent_nomination_begin_time
                     .save(deps.storage, &Some(env.block.time))?;
+                nomination_attrs.push((
+                    "agent_nomination_begin_time",
+                    env.block.time.nanos().to_string(),
+                ));
+            }
+            // This task asked for one more agent, the next one in the pending queue is up
+            let num_agents_before =
+                self.agents_to_let_in(&min_tasks_per_agent, &num_active_agents, &(size - 1));
+            if num_agents_to_accept > num_agents_before {
+                let position = num_agents_to_accept - 1;
+                let pending = self.agent_pending_queue.load(deps.storage)?;
+                if let Some(agent) = pending.get(position as usize) {
+                    nomination_attrs.push(("nominated_agent", agent.to_string()));
+                    nomination_attrs.push(("nominated_agent_position", position.to_string()));
+                }
             }
+        } else {
+            // No demand for agents, so a stale nomination timer must not linger
+            self.agent_nomination_begin_time.save(deps.storage, &None)?;
         }
 
         self.config.save(deps.storage, &c)?;
 
+        let task_event = Event::new(TASK_EVENT)
+            .add_attribute("action", "create_task")
+            .add_attribute("task_hash", hash.clone())
+            .add_attribute("owner_id", owner_id.to_string())
+            .add_attribute("interval", format!("{:?}", item.interval))
+            .add_attribute("boundary_start", optional_to_string(item.boundary.start))
+            .add_attribute("boundary_end", optional_to_string(item.boundary.end))
+            .add_attribute("with_rules", item.rules.is_some().to_string());
+
         Ok(Response::new()
             .add_attribute("method", "create_task")
             .add_attribute("slot_id", next_id.to_string())
             .add_attribute("slot_kind", format!("{:?}", slot_kind))
-            .add_attribute("task_hash", hash))
+            .add_attribute("task_hash", hash)
+            .add_attributes(nomination_attrs)
+            .add_event(task_event))
+    }
+
+    /// Remove a task on request, only the task owner can send the refund to another address
+    pub fn remove_task_with_refund(
+        &self,
+        deps: DepsMut,
+        info: MessageInfo,
+        env: Env,
+        task_hash: String,
+        refund_to: Option<Addr>,
+    ) -> Result<Response, ContractError> {
+        let refund_to = match refund_to {
+            Some(refund_to) => {
+                let task = self
+                    .tasks
+                    .may_load(deps.storage, task_hash.clone().into_bytes())?;
+                if let Some(task) = task {
+                    if task.owner_id != info.sender {
+                        return Err(ContractError::Unauthorized {});
+                    }
+                }
+                Some(deps.api.addr_validate(refund_to.as_str())?)
+            }
+            None => None,
+        };
+        self.remove_task(deps, env, task_hash, refund_to)
     }
 
     /// Deletes a task in its entirety, returning any remaining balance to task owner.
-    pub fn remove_task(&self, deps: DepsMut, task_hash: String) -> Result<Response, ContractError> {
+    pub fn remove_task(
+        &self,
+        deps: DepsMut,
+        env: Env,
+        task_hash: String,
+        refund_to: Option<Addr>,
+    ) -> Result<Response, ContractError> {
         let hash_vec = task_hash.clone().into_bytes();
         let task_raw = self.tasks.may_load(deps.storage, hash_vec.clone())?;
         if task_raw.is_none() {
@@ -326,7 +1286,25 @@ impl<'a> CwCroncat<'a> {
         }
 
         // Remove all the thangs
-        self.tasks.remove(deps.storage, hash_vec)?;
+        self.tasks.remove(deps.storage, hash_vec.clone())?;
+        let interval = &task_raw.as_ref().unwrap().interval;
+        self.count_task_interval(deps.storage, interval, false)?;
+        let size = self.decrement_tasks(deps.storage)?;
+        self.task_failure_streaks
+            .remove(deps.storage, hash_vec.clone());
+        if let Some(sequence) = self
+            .task_sequence
+            .may_load(deps.storage, hash_vec.clone())?
+        {
+            let owner_id = task_raw.as_ref().unwrap().owner_id.clone();
+            self.owner_task_sequence
+                .remove(deps.storage, (owner_id, sequence));
+            self.task_sequence.remove(deps.storage, hash_vec.clone());
+        }
+        self.task_created_at.remove(deps.storage, hash_vec.clone());
+        self.task_executions.remove(deps.storage, hash_vec.clone());
+        self.exhausted_tasks.remove(deps.storage, hash_vec.clone());
+        self.executing_tasks.remove(deps.storage, hash_vec);
 
         // find any scheduled things and remove them!
         // check which type of slot it would be in, then iterate to remove
@@ -375,22 +1353,68 @@ impl<'a> CwCroncat<'a> {
             }
         }
 
-        // setup sub-msgs for returning any remaining total_deposit to the owner
+        // setup sub-msgs for returning any remaining total_deposit to the owner, or where they asked
         let task = task_raw.unwrap();
-        let submsgs = SubMsg::new(BankMsg::Send {
-            to_address: task.clone().owner_id.into(),
-            amount: task.clone().total_deposit,
-        });
+        let refund_to = refund_to.unwrap_or_else(|| task.owner_id.clone());
+        // Drained deposits hold zero coins, which a bank send would reject
+        let refund: Vec<Coin> = task
+            .total_deposit
+            .iter()
+            .filter(|coin| !coin.amount.is_zero())
+            .cloned()
+            .collect();
+        let submsgs: Vec<SubMsg> = if refund.is_empty() {
+            vec![]
+        } else {
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: refund_to.to_string(),
+                amount: refund,
+            })]
+        };
+        let (cw20_submsgs, _) = send_tokens(
+            &refund_to,
+            &GenericBalance {
+                native: vec![],
+                cw20: task.total_cw20_deposit.clone(),
+            },
+        )?;
 
         // remove from the total available_balance
         let mut c: Config = self.config.load(deps.storage)?;
         c.available_balance
-            .minus_tokens(Balance::from(task.total_deposit));
+            .minus_tokens(Balance::from(task.total_deposit.clone()));
+        for token in task.total_cw20_deposit.iter() {
+            c.available_balance
+                .minus_tokens(Balance::Cw20(token.clone()));
+        }
         self.config.save(deps.storage, &c)?;
 
+        // Fewer tasks may mean no more agents are needed, stop nominating if so
+        let num_active_agents = self.agent_active_queue.load(deps.storage)?.len() as u64;
+        if self.agents_to_let_in(&c.min_tasks_per_agent, &num_active_agents, &size) == 0 {
+            self.agent_nomination_begin_time.save(deps.storage, &None)?;
+        }
+        let task_event = Event::new(TASK_EVENT)
+            .add_attribute("action", "remove_task")
+            .add_attribute("task_hash", task_hash.clone())
+            .add_attribute("owner_id", task.owner_id.to_string())
+            .add_attribute(
+                "refund_amount",
+                refund_to_string(&task.total_deposit, &task.total_cw20_deposit),
+            );
+        self.push_funding_event(
+            deps.storage,
+            &env,
+            task_hash.into_bytes(),
+            FundingEventKind::Withdraw,
+            task.total_deposit,
+        )?;
+
         Ok(Response::new()
             .add_attribute("method", "remove_task")
-            .add_submessage(submsgs))
+            .add_event(task_event)
+            .add_submessages(submsgs)
+            .add_submessages(cw20_submsgs))
     }
 
     /// Refill a task with more balance to continue its execution
@@ -399,6 +1423,7 @@ impl<'a> CwCroncat<'a> {
         &self,
         deps: DepsMut,
         info: MessageInfo,
+        env: Env,
         task_hash: String,
     ) -> Result<Response, ContractError> {
         let hash_vec = task_hash.into_bytes();
@@ -433,14 +1458,27 @@ impl<'a> CwCroncat<'a> {
             }
         }
         task.total_deposit = total_balance;
+        // A refill within the grace slots lets an out of funds task run again,
+        // once it covers a full run
+        if self.covers_full_run(&c, &task) {
+            self.exhausted_tasks.remove(deps.storage, hash_vec.clone());
+        }
 
         // update the task
-        self.tasks.update(deps.storage, hash_vec, |old| match old {
-            Some(_) => Ok(task.clone()),
-            None => Err(ContractError::CustomError {
-                val: "Task doesnt exist".to_string(),
-            }),
-        })?;
+        self.tasks
+            .update(deps.storage, hash_vec.clone(), |old| match old {
+                Some(_) => Ok(task.clone()),
+                None => Err(ContractError::CustomError {
+                    val: "Task doesnt exist".to_string(),
+                }),
+            })?;
+        self.push_funding_event(
+            deps.storage,
+            &env,
+            hash_vec,
+            FundingEventKind::Refill,
+            info.funds,
+        )?;
 
         // return the task total
         let coins_total: String = task.total_deposit.iter().map(|a| a.to_string()).collect();
@@ -448,102 +1486,528 @@ impl<'a> CwCroncat<'a> {
             .add_attribute("method", "refill_task")
             .add_attribute("total_deposit", coins_total))
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// Handles cw20 tokens sent to the manager, the sending token contract is `info.sender`
+    pub fn receive_cw20(
+        &self,
+        deps: DepsMut,
+        info: MessageInfo,
+        msg: Cw20ReceiveMsg,
+    ) -> Result<Response, ContractError> {
+        if !info.funds.is_empty() {
+            return Err(ContractError::CustomError {
+                val: "Must not attach funds".to_string(),
+            });
+        }
+        let mut c: Config = self.config.load(deps.storage)?;
+        if !c.cw20_whitelist.contains(&info.sender) {
+            return Err(ContractError::NotInWhitelist {});
+        }
+        let sender = deps.api.addr_validate(&msg.sender)?;
+        let token = Cw20CoinVerified {
+            address: info.sender,
+            amount: msg.amount,
+        };
 
-    use std::convert::TryInto;
-    // use cosmwasm_std::testing::MockStorage;
-    use crate::contract::GAS_BASE_FEE_JUNO;
-    use cosmwasm_std::{
-        coin, coins, to_binary, Addr, BankMsg, CosmosMsg, Empty, StakingMsg, WasmMsg,
-    };
-    use cw_multi_test::{App, AppBuilder, Contract, ContractWrapper, Executor};
-    // use crate::error::ContractError;
-    use crate::helpers::CwTemplateContract;
-    use cw_croncat_core::msg::{ExecuteMsg, GetBalancesResponse, InstantiateMsg, QueryMsg};
-    use cw_croncat_core::types::{Action, Boundary};
+        match from_binary(&msg.msg)? {
+            ReceiveMsg::RefillTaskBalance { task_hash } => {
+                let hash_vec = task_hash.into_bytes();
+                let mut task = match self.tasks.may_load(deps.storage, hash_vec.clone())? {
+                    Some(task) => task,
+                    None => {
+                        return Err(ContractError::CustomError {
+                            val: "Task doesnt exist".to_string(),
+                        })
+                    }
+                };
+                if task.owner_id != sender {
+                    return Err(ContractError::CustomError {
+                        val: "Only owner can refill their task".to_string(),
+                    });
+                }
 
-    pub fn contract_template() -> Box<dyn Contract<Empty>> {
-        let contract = ContractWrapper::new(
-            crate::entry::execute,
-            crate::entry::instantiate,
-            crate::entry::query,
-        );
-        Box::new(contract)
-    }
+                c.available_balance.add_tokens(Balance::Cw20(token.clone()));
+                self.config.save(deps.storage, &c)?;
 
-    const ADMIN: &str = "cosmos1sjllsnramtg3ewxqwwrwjxfgc4n4ef9u0tvx7u";
-    const ANYONE: &str = "cosmos1t5u0jfg3ljsjrh2m9e47d4ny2hea7eehxrzdgd";
-    const VERY_RICH: &str = "cosmos1c3cy3wzzz3698ypklvh7shksvmefj69xhm89z2";
-    const NATIVE_DENOM: &str = "atom";
+                match task
+                    .total_cw20_deposit
+                    .iter_mut()
+                    .find(|deposit| deposit.address == token.address)
+                {
+                    Some(deposit) => deposit.amount += token.amount,
+                    None => task.total_cw20_deposit.push(token),
+                }
+                self.tasks.save(deps.storage, hash_vec, &task)?;
 
-    fn mock_app() -> App {
-        AppBuilder::new().build(|router, _, storage| {
-            let accounts: Vec<(u128, String)> = vec![
-                (100, ADMIN.to_string()),
-                (800_010, ANYONE.to_string()),
-                (u128::max_value(), VERY_RICH.to_string()),
-            ];
-            for (amt, address) in accounts.iter() {
-                router
-                    .bank
-                    .init_balance(
-                        storage,
-                        &Addr::unchecked(address),
-                        vec![coin(amt.clone(), NATIVE_DENOM.to_string())],
-                    )
-                    .unwrap();
+                let cw20_total: Vec<String> = task
+                    .total_cw20_deposit
+                    .iter()
+                    .map(|deposit| format!("{}{}", deposit.amount, deposit.address))
+                    .collect();
+                Ok(Response::new()
+                    .add_attribute("method", "refill_task_cw20")
+                    .add_attribute("total_cw20_deposit", cw20_total.join(",")))
             }
-        })
+        }
     }
 
-    fn proper_instantiate() -> (App, CwTemplateContract) {
-        let mut app = mock_app();
-        let cw_template_id = app.store_code(contract_template());
-        let owner_addr = Addr::unchecked(ADMIN);
-
-        let msg = InstantiateMsg {
-            denom: "atom".to_string(),
-            owner_id: Some(owner_addr.clone()),
-            gas_base_fee: None,
-            agent_nomination_duration: Some(360),
+    /// Edit the actions or rules of a task without recreating it
+    /// The hash changes with them, so everything stored under the old one is moved over
+    pub fn update_task(
+        &self,
+        deps: DepsMut,
+        info: MessageInfo,
+        env: Env,
+        task_hash: String,
+        actions: Option<Vec<Action>>,
+        rules: Option<Vec<Rule>>,
+    ) -> Result<Response, ContractError> {
+        if !info.funds.is_empty() {
+            return Err(ContractError::CustomError {
+                val: "Must not attach funds".to_string(),
+            });
+        }
+        let old_hash = task_hash.clone().into_bytes();
+        let mut task = match self.tasks.may_load(deps.storage, old_hash.clone())? {
+            Some(task) => task,
+            None => {
+                return Err(ContractError::CustomError {
+                    val: "Task doesnt exist".to_string(),
+                })
+            }
         };
-        let cw_template_contract_addr = app
-            .instantiate_contract(cw_template_id, owner_addr, &msg, &[], "Manager", None)
-            .unwrap();
-
-        let cw_template_contract = CwTemplateContract(cw_template_contract_addr);
-
-        (app, cw_template_contract)
-    }
-
-    #[test]
-    fn query_task_hash_success() {
-        let (app, cw_template_contract) = proper_instantiate();
-        let contract_addr = cw_template_contract.addr();
-
-        let to_address = String::from("you");
-        let amount = coins(1015, "earth");
-        let bank = BankMsg::Send { to_address, amount };
-        let msg: CosmosMsg = bank.clone().into();
+        if task.owner_id != info.sender {
+            return Err(ContractError::CustomError {
+                val: "Only owner can update their task".to_string(),
+            });
+        }
+        if let Some(actions) = actions {
+            task.actions = actions;
+        }
+        if let Some(rules) = rules {
+            task.rules = Some(rules);
+        }
 
-        let task = Task {
-            owner_id: Addr::unchecked("nobody".to_string()),
-            interval: Interval::Immediate,
-            boundary: BoundaryValidated {
-                start: None,
-                end: None,
-            },
-            stop_on_fail: false,
-            total_deposit: coins(37, "atom"),
-            actions: vec![Action {
-                msg,
+        let c: Config = self.config.load(deps.storage)?;
+        if !task.is_valid_msg(&env.contract.address, &info.sender, &c.owner_id) {
+            return Err(ContractError::CustomError {
+                val: "Actions Message Unsupported".to_string(),
+            });
+        }
+        if task.to_gas_total() > c.max_task_gas {
+            return Err(ContractError::CustomError {
+                val: format!("Task gas exceeds max allowed: {}", c.max_task_gas),
+            });
+        }
+        let num_rules = task.rules.as_ref().map(Vec::len).unwrap_or_default() as u64;
+        if num_rules > c.max_rules_per_task {
+            return Err(ContractError::CustomError {
+                val: format!("Too many rules, max allowed: {}", c.max_rules_per_task),
+            });
+        }
+        if task
+            .rules
+            .iter()
+            .flatten()
+            .any(|rule| rule.depth() > MAX_RULE_DEPTH)
+        {
+            return Err(ContractError::CustomError {
+                val: format!("Rules nest too deep, max allowed: {}", MAX_RULE_DEPTH),
+            });
+        }
+        let native_deposit = task
+            .total_deposit
+            .iter()
+            .find(|coin| coin.denom == c.native_denom)
+            .map(|c| c.amount.u128())
+            .unwrap_or_default();
+        let required_deposit = self.task_required_deposit(&c, &task);
+        if native_deposit < required_deposit {
+            return Err(ContractError::InsufficientTaskBalance {
+                required: coin(required_deposit, &c.native_denom),
+            });
+        }
+        let shortfalls = self.action_funds_shortfalls(&c, &task);
+        if !shortfalls.is_empty() {
+            return Err(ContractError::CustomError {
+                val: format!(
+                    "Not enough task balance for actions in: {}",
+                    shortfalls.join(", ")
+                ),
+            });
+        }
+
+        let new_hash = task.to_hash_vec();
+        if new_hash != old_hash {
+            if self.tasks.has(deps.storage, new_hash.clone()) {
+                return Err(ContractError::CustomError {
+                    val: "Task already exists".to_string(),
+                });
+            }
+            self.tasks.remove(deps.storage, old_hash.clone())?;
+            move_task_entry(
+                deps.storage,
+                &self.task_funding_history,
+                &old_hash,
+                &new_hash,
+            )?;
+            move_task_entry(
+                deps.storage,
+                &self.task_failure_streaks,
+                &old_hash,
+                &new_hash,
+            )?;
+            move_task_entry(deps.storage, &self.task_sequence, &old_hash, &new_hash)?;
+            if let Some(sequence) = self
+                .task_sequence
+                .may_load(deps.storage, new_hash.clone())?
+            {
+                self.owner_task_sequence.save(
+                    deps.storage,
+                    (task.owner_id.clone(), sequence),
+                    &new_hash,
+                )?;
+            }
+            move_task_entry(deps.storage, &self.task_executions, &old_hash, &new_hash)?;
+            move_task_entry(deps.storage, &self.exhausted_tasks, &old_hash, &new_hash)?;
+            move_task_entry(deps.storage, &self.task_created_at, &old_hash, &new_hash)?;
+
+            // Point the scheduled slots at the new hash
+            for slots in [&self.time_slots, &self.block_slots] {
+                let slot_ids: Vec<u64> = slots
+                    .keys(deps.storage, None, None, Order::Ascending)
+                    .collect::<StdResult<Vec<_>>>()?;
+                for slot_id in slot_ids {
+                    let mut hashes = slots.load(deps.storage, slot_id)?;
+                    if hashes.contains(&old_hash) {
+                        for h in hashes.iter_mut().filter(|h| **h == old_hash) {
+                            *h = new_hash.clone();
+                        }
+                        slots.save(deps.storage, slot_id, &hashes)?;
+                    }
+                }
+            }
+        }
+        self.tasks.save(deps.storage, new_hash, &task)?;
+
+        Ok(Response::new()
+            .add_attribute("method", "update_task")
+            .add_attribute("old_task_hash", task_hash)
+            .add_attribute("task_hash", task.to_hash()))
+    }
+
+    /// Check if a task can never be executed again:
+    /// - its boundary is exhausted, so it won't get scheduled anymore
+    /// - its native deposit can't cover a single execution, and its grace slots for a refill are used up
+    pub(crate) fn is_dead_task(
+        &self,
+        storage: &dyn Storage,
+        task: &Task,
+        env: &Env,
+        c: &Config,
+    ) -> StdResult<bool> {
+        let (next_id, _) = task.interval.next(env.clone(), task.boundary);
+        if next_id == 0 {
+            return Ok(true);
+        }
+        match self.exhausted_tasks.may_load(storage, task.to_hash_vec())? {
+            // Held for a refill until its grace slots are used up
+            Some(grace_slots) if grace_slots > 0 => return Ok(false),
+            // Not held yet, its next run starts the grace slots
+            None if c.removal_grace_slots > 0 => return Ok(false),
+            _ => (),
+        }
+
+        let native_deposit = task
+            .total_deposit
+            .iter()
+            .find(|coin| coin.denom == c.native_denom)
+            .map(|c| c.amount.u128())
+            .unwrap_or_default();
+        Ok(native_deposit
+            < task.task_balance_uses(&c.agent_fee, c.gas_base_fee, c.proxy_callback_gas))
+    }
+
+    /// Removes tasks that can never execute again, returning their remaining balance to the owners.
+    /// Anyone can call this, the caller gets paid the dead task bounty for every task reaped.
+    ///
+    /// Optional Parameters:
+    /// "limit" - How many tasks to scan in this call, continues from where the last call stopped.
+    pub fn reap_dead_tasks(
+        &self,
+        mut deps: DepsMut,
+        info: MessageInfo,
+        env: Env,
+        limit: Option<u64>,
+    ) -> Result<Response, ContractError> {
+        if !info.funds.is_empty() {
+            return Err(ContractError::CustomError {
+                val: "Must not attach funds".to_string(),
+            });
+        }
+        let c: Config = self.config.load(deps.storage)?;
+        if c.paused {
+            return Err(ContractError::ContractPaused {
+                val: "Reap dead tasks paused".to_string(),
+            });
+        }
+
+        // Bound the scan, so the reaper can't run out of gas
+        let limit = limit.unwrap_or(DEFAULT_REAP_LIMIT).min(MAX_REAP_LIMIT) as usize;
+        let cursor = self.reap_cursor.may_load(deps.storage)?;
+        let scanned: Vec<(Vec<u8>, Task)> = self
+            .tasks
+            .range(
+                deps.storage,
+                cursor.map(Bound::exclusive),
+                None,
+                Order::Ascending,
+            )
+            .take(limit)
+            .collect::<StdResult<Vec<_>>>()?;
+
+        // Start over from the beginning once we reached the end
+        match scanned.last() {
+            Some((hash, _)) if scanned.len() == limit => {
+                self.reap_cursor.save(deps.storage, hash)?
+            }
+            _ => self.reap_cursor.remove(deps.storage),
+        }
+
+        let mut dead_hashes: Vec<String> = vec![];
+        for (_, task) in scanned {
+            if self.is_dead_task(deps.storage, &task, &env, &c)? {
+                dead_hashes.push(task.to_hash());
+            }
+        }
+
+        let mut response = Response::new().add_attribute("method", "reap_dead_tasks");
+        for task_hash in dead_hashes.iter() {
+            // Removing refunds the remaining balance to the task owner
+            let resp = self.remove_task(deps.branch(), env.clone(), task_hash.clone(), None)?;
+            response = response
+                .add_attribute("reaped_task", task_hash)
+                .add_submessages(resp.messages)
+                .add_events(resp.events);
+        }
+
+        // Pay the bounty only from funds nobody has a claim on,
+        // task deposits, agent rewards and staked funds are left alone
+        let mut c: Config = self.config.load(deps.storage)?;
+        let bounty = coin(
+            c.dead_task_bounty.amount.u128() * dead_hashes.len() as u128,
+            c.dead_task_bounty.denom.clone(),
+        );
+        let has_bounty_balance = !bounty.amount.is_zero() && {
+            let amount_of = |coins: &[Coin]| -> Uint128 {
+                coins
+                    .iter()
+                    .find(|coin| coin.denom == bounty.denom)
+                    .map(|coin| coin.amount)
+                    .unwrap_or_default()
+            };
+            let reserved = self.get_reserved_balance(deps.storage)?;
+            let unreserved = amount_of(&c.available_balance.native)
+                .saturating_sub(amount_of(&reserved.native))
+                .saturating_sub(amount_of(&c.staked_balance.native));
+            unreserved >= bounty.amount
+        };
+        if has_bounty_balance {
+            c.available_balance
+                .minus_tokens(Balance::from(vec![bounty.clone()]));
+            self.config.save(deps.storage, &c)?;
+            response = response
+                .add_attribute("bounty", bounty.to_string())
+                .add_submessage(SubMsg::new(BankMsg::Send {
+                    to_address: info.sender.into(),
+                    amount: vec![bounty],
+                }));
+        }
+
+        Ok(response.add_attribute("reaped", dead_hashes.len().to_string()))
+    }
+}
+
+/// Moves whatever is stored under a task's old hash to its new one
+fn move_task_entry<T>(
+    storage: &mut dyn Storage,
+    map: &Map<Vec<u8>, T>,
+    old_hash: &[u8],
+    new_hash: &[u8],
+) -> StdResult<()>
+where
+    T: Serialize + DeserializeOwned,
+{
+    if let Some(value) = map.may_load(storage, old_hash.to_vec())? {
+        map.remove(storage, old_hash.to_vec());
+        map.save(storage, new_hash.to_vec(), &value)?;
+    }
+    Ok(())
+}
+
+/// Generate strings for all hashes
+fn hashes_to_strings(hashes: &[Vec<u8>]) -> Vec<String> {
+    hashes
+        .iter()
+        .map(|h| String::from_utf8(h.to_vec()).unwrap_or_else(|_| "".to_string()))
+        .collect()
+}
+
+/// Attribute values can't be empty, so missing values read as "none"
+fn optional_to_string(value: Option<u64>) -> String {
+    value.map_or_else(|| "none".to_string(), |v| v.to_string())
+}
+
+/// Comma separated refunded coins, cw20 tokens are keyed by their contract address
+fn refund_to_string(native: &[Coin], cw20: &[Cw20CoinVerified]) -> String {
+    let amounts: Vec<String> = native
+        .iter()
+        .map(|coin| coin.to_string())
+        .chain(
+            cw20.iter()
+                .map(|token| format!("{}{}", token.amount, token.address)),
+        )
+        .collect();
+    if amounts.is_empty() {
+        "0".to_string()
+    } else {
+        amounts.join(",")
+    }
+}
+
+/// Whether proxy_call can take what a run needs from the task balance,
+/// the protocol fee first, then the no-op cost or the funds of the first action
+fn covers_run(c: &Config, mut task: Task) -> bool {
+    if !c.protocol_fee.amount.is_zero() {
+        if !covers_protocol_fee(c, &task) {
+            return false;
+        }
+        for coin in task.total_deposit.iter_mut() {
+            if coin.denom == c.protocol_fee.denom {
+                coin.amount -= c.protocol_fee.amount;
+            }
+        }
+    }
+    if task.is_no_op() {
+        let native_deposit = task
+            .total_deposit
+            .iter()
+            .find(|coin| coin.denom == c.native_denom)
+            .map(|coin| coin.amount.u128())
+            .unwrap_or_default();
+        return native_deposit >= no_op_run_cost(c, &task);
+    }
+    let (action_idx, skipped_actions) = draw_next_action_funds(&mut task, 0);
+    action_idx.is_some() && !(task.stop_on_fail && !skipped_actions.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::convert::TryInto;
+    // use cosmwasm_std::testing::MockStorage;
+    use crate::contract::GAS_BASE_FEE_JUNO;
+    use cosmwasm_std::{
+        coin, coins, to_binary, Addr, Attribute, BankMsg, Binary, ContractResult, CosmosMsg, Empty,
+        StakingMsg, SystemError, SystemResult, Timestamp, Uint128, WasmMsg, WasmQuery,
+    };
+    use cw20::Cw20ExecuteMsg;
+    use cw_multi_test::{App, AppBuilder, Contract, ContractWrapper, Executor};
+    // use crate::error::ContractError;
+    use crate::helpers::test_helpers::mock_init;
+    use crate::helpers::CwTemplateContract;
+    use cosmwasm_std::from_binary;
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
+    use cw_croncat_core::msg::{
+        ExecuteMsg, GetBalancesResponse, GetConfigResponse, InstantiateMsg, QueryMsg,
+    };
+    use cw_croncat_core::types::{Action, ActionOrder, Boundary, Rule, RuleExpr};
+
+    pub fn contract_template() -> Box<dyn Contract<Empty>> {
+        let contract = ContractWrapper::new(
+            crate::entry::execute,
+            crate::entry::instantiate,
+            crate::entry::query,
+        );
+        Box::new(contract)
+    }
+
+    const ADMIN: &str = "cosmos1sjllsnramtg3ewxqwwrwjxfgc4n4ef9u0tvx7u";
+    const ANYONE: &str = "cosmos1t5u0jfg3ljsjrh2m9e47d4ny2hea7eehxrzdgd";
+    const VERY_RICH: &str = "cosmos1c3cy3wzzz3698ypklvh7shksvmefj69xhm89z2";
+    const NATIVE_DENOM: &str = "atom";
+
+    fn mock_app() -> App {
+        AppBuilder::new().build(|router, _, storage| {
+            let accounts: Vec<(u128, String)> = vec![
+                (100, ADMIN.to_string()),
+                (800_010, ANYONE.to_string()),
+                (u128::max_value(), VERY_RICH.to_string()),
+            ];
+            for (amt, address) in accounts.iter() {
+                router
+                    .bank
+                    .init_balance(
+                        storage,
+                        &Addr::unchecked(address),
+                        vec![coin(amt.clone(), NATIVE_DENOM.to_string())],
+                    )
+                    .unwrap();
+            }
+        })
+    }
+
+    fn proper_instantiate() -> (App, CwTemplateContract) {
+        let mut app = mock_app();
+        let cw_template_id = app.store_code(contract_template());
+        let owner_addr = Addr::unchecked(ADMIN);
+
+        let msg = InstantiateMsg {
+            denom: "atom".to_string(),
+            owner_id: Some(owner_addr.clone()),
+            gas_base_fee: None,
+            agent_nomination_duration: Some(360),
+        };
+        let cw_template_contract_addr = app
+            .instantiate_contract(cw_template_id, owner_addr, &msg, &[], "Manager", None)
+            .unwrap();
+
+        let cw_template_contract = CwTemplateContract(cw_template_contract_addr);
+
+        (app, cw_template_contract)
+    }
+
+    #[test]
+    fn query_task_hash_success() {
+        let (app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+
+        let to_address = String::from("you");
+        let amount = coins(1015, "earth");
+        let bank = BankMsg::Send { to_address, amount };
+        let msg: CosmosMsg = bank.clone().into();
+
+        let task = Task {
+            owner_id: Addr::unchecked("nobody".to_string()),
+            interval: Interval::Immediate,
+            boundary: BoundaryValidated {
+                start: None,
+                end: None,
+                max_executions: None,
+            },
+            stop_on_fail: false,
+            total_deposit: coins(37, "atom"),
+            actions: vec![Action {
+                msg,
                 gas_limit: Some(150_000),
             }],
             rules: None,
+            metadata: None,
+            successor: None,
+            generation: 0,
+            action_order: ActionOrder::Sequential,
+            total_cw20_deposit: vec![],
         };
 
         // HASH CHECK!
@@ -567,23 +2031,97 @@ mod tests {
         let (app, cw_template_contract) = proper_instantiate();
         let contract_addr = cw_template_contract.addr();
 
-        let intervals: Vec<Interval> = vec![
-            Interval::Once,
-            Interval::Immediate,
-            Interval::Block(12345),
-            Interval::Cron("0 0 * * * *".to_string()),
+        let once_boundary = Boundary::Height {
+            start: Some(12345u64.into()),
+            end: None,
+        };
+        let intervals: Vec<(Interval, Option<Boundary>)> = vec![
+            (Interval::Once, Some(once_boundary)),
+            (Interval::Immediate, None),
+            (Interval::Block(12345), None),
+            (Interval::Cron("0 0 * * * *".to_string()), None),
         ];
-        for i in intervals.iter() {
-            let valid: bool = app
+        for (interval, boundary) in intervals {
+            let res: ValidateIntervalResponse = app
                 .wrap()
                 .query_wasm_smart(
                     &contract_addr.clone(),
-                    &QueryMsg::ValidateInterval {
-                        interval: i.to_owned(),
-                    },
+                    &QueryMsg::ValidateInterval { interval, boundary },
+                )
+                .unwrap();
+            assert!(res.valid);
+            assert_eq!(res.reason, None);
+        }
+    }
+
+    #[test]
+    fn query_validate_interval_reasons() {
+        let (app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+
+        let cases = vec![
+            (
+                Interval::Block(0),
+                None,
+                "Block interval must be greater than zero",
+            ),
+            (Interval::Once, None, "Once interval needs a boundary start"),
+            (
+                Interval::Once,
+                Some(Boundary::Height {
+                    start: None,
+                    end: Some(12400u64.into()),
+                }),
+                "Once interval needs a boundary start",
+            ),
+            (
+                Interval::Cron("every day".to_string()),
+                None,
+                "Malformed cron expression: every day",
+            ),
+            (
+                Interval::Cron("0 0 * * * *".to_string()),
+                Some(Boundary::Height {
+                    start: None,
+                    end: Some(12400u64.into()),
+                }),
+                "Cron interval needs a time boundary",
+            ),
+            (
+                Interval::Block(5),
+                Some(Boundary::Time {
+                    start: None,
+                    end: Some(Timestamp::from_seconds(100)),
+                }),
+                "Block based interval needs a height boundary",
+            ),
+            (
+                Interval::Block(5),
+                Some(Boundary::Height {
+                    start: Some(12500u64.into()),
+                    end: Some(12400u64.into()),
+                }),
+                "Boundary start is after its end",
+            ),
+            (
+                Interval::Cron("0 0 * * * *".to_string()),
+                Some(Boundary::Time {
+                    start: Some(Timestamp::from_seconds(200)),
+                    end: Some(Timestamp::from_seconds(100)),
+                }),
+                "Boundary start is after its end",
+            ),
+        ];
+        for (interval, boundary, reason) in cases {
+            let res: ValidateIntervalResponse = app
+                .wrap()
+                .query_wasm_smart(
+                    &contract_addr,
+                    &QueryMsg::ValidateInterval { interval, boundary },
                 )
                 .unwrap();
-            assert!(valid);
+            assert!(!res.valid);
+            assert_eq!(res.reason, Some(reason.to_string()));
         }
     }
 
@@ -607,6 +2145,9 @@ mod tests {
                     gas_limit: Some(150_000),
                 }],
                 rules: None,
+                metadata: None,
+                successor: None,
+                action_order: None,
             },
         };
 
@@ -615,7 +2156,7 @@ mod tests {
             Addr::unchecked(ANYONE),
             contract_addr.clone(),
             &create_task_msg,
-            &coins(300010, "atom"),
+            &coins(300016, "atom"),
         )
         .unwrap();
 
@@ -627,6 +2168,7 @@ mod tests {
                 &QueryMsg::GetTasks {
                     from_index: None,
                     limit: None,
+                    include_status: None,
                 },
             )
             .unwrap();
@@ -638,6 +2180,8 @@ mod tests {
                 &contract_addr.clone(),
                 &QueryMsg::GetTasksByOwner {
                     owner_id: Addr::unchecked(ANYONE),
+                    from_index: None,
+                    limit: None,
                 },
             )
             .unwrap();
@@ -667,6 +2211,9 @@ mod tests {
                     gas_limit: Some(150_000),
                 }],
                 rules: None,
+                metadata: None,
+                successor: None,
+                action_order: None,
             },
         };
 
@@ -676,7 +2223,7 @@ mod tests {
                 Addr::unchecked(VERY_RICH),
                 contract_addr.clone(),
                 &new_msg(amount),
-                &coins(300010, "atom"),
+                &coins(300016, "atom"),
             )
             .unwrap();
         }
@@ -687,6 +2234,7 @@ mod tests {
                 &QueryMsg::GetTasks {
                     from_index: None,
                     limit: None,
+                    include_status: None,
                 },
             )
             .unwrap();
@@ -700,6 +2248,7 @@ mod tests {
                 &QueryMsg::GetTasks {
                     from_index: Some(from_index),
                     limit: None,
+                    include_status: None,
                 },
             )
             .unwrap();
@@ -734,6 +2283,7 @@ mod tests {
                 &QueryMsg::GetTasks {
                     from_index: Some(from_index),
                     limit: Some(limit),
+                    include_status: None,
                 },
             )
             .unwrap();
@@ -751,6 +2301,7 @@ mod tests {
                 &QueryMsg::GetTasks {
                     from_index: Some(from_index),
                     limit: None,
+                    include_status: None,
                 },
             )
             .unwrap();
@@ -765,6 +2316,7 @@ mod tests {
                 &QueryMsg::GetTasks {
                     from_index: Some(from_index),
                     limit: Some(tasks_amnt),
+                    include_status: None,
                 },
             )
             .unwrap();
@@ -779,6 +2331,7 @@ mod tests {
                 task_hash: all_tasks
                     .remove(removed_index) // We removed hash from original vector to match
                     .task_hash,
+                refund_to: None,
             },
             &vec![],
         )
@@ -790,6 +2343,7 @@ mod tests {
                 &QueryMsg::GetTasks {
                     from_index: None,
                     limit: None,
+                    include_status: None,
                 },
             )
             .unwrap();
@@ -816,6 +2370,9 @@ mod tests {
                     gas_limit: Some(150_000),
                 }],
                 rules: None,
+                metadata: None,
+                successor: None,
+                action_order: None,
             },
         };
         // let task_id_str = "95c916a53fa9d26deef094f7e1ee31c00a2d47b8bf474b2e06d39aebfb1fecc7".to_string();
@@ -840,14 +2397,37 @@ mod tests {
         // Create task paused
         let change_settings_msg = ExecuteMsg::UpdateSettings {
             paused: Some(true),
-            owner_id: None,
-            // treasury_id: None,
+            treasury_id: None,
             agent_fee: None,
             agents_eject_threshold: None,
             gas_price: None,
             proxy_callback_gas: None,
             slot_granularity: None,
             min_tasks_per_agent: None,
+            dead_task_bounty: None,
+            max_rules_per_task: None,
+            slash_destination: None,
+            low_balance_pause_threshold: None,
+            min_active_agents_for_execution: None,
+            max_submsgs_per_proxy_call: None,
+            protocol_fee: None,
+            native_denom: None,
+            task_creation_fee: None,
+            nomination_paused: None,
+            reward_vesting_seconds: None,
+            max_future_slot_seconds: None,
+            randomized_agent_selection: None,
+            max_tasks_per_owner: None,
+            dust_threshold: None,
+            dust_swap_contract: None,
+            min_active_agents: None,
+            min_agent_version: None,
+            removal_grace_slots: None,
+            eject_to_pending: None,
+            max_task_limit: None,
+            agent_allowlist_enabled: None,
+            max_task_gas: None,
+            min_task_balance: None,
         };
         app.execute_contract(
             Addr::unchecked(ADMIN),
@@ -861,7 +2441,7 @@ mod tests {
                 Addr::unchecked(ANYONE),
                 contract_addr.clone(),
                 &create_task_msg,
-                &coins(300010, "atom"),
+                &coins(300016, "atom"),
             )
             .unwrap_err();
         assert_eq!(
@@ -876,14 +2456,37 @@ mod tests {
             contract_addr.clone(),
             &ExecuteMsg::UpdateSettings {
                 paused: Some(false),
-                owner_id: None,
-                // treasury_id: None,
+                treasury_id: None,
                 agent_fee: None,
                 agents_eject_threshold: None,
                 gas_price: None,
                 proxy_callback_gas: None,
                 slot_granularity: None,
                 min_tasks_per_agent: None,
+                dead_task_bounty: None,
+                max_rules_per_task: None,
+                slash_destination: None,
+                low_balance_pause_threshold: None,
+                min_active_agents_for_execution: None,
+                max_submsgs_per_proxy_call: None,
+                protocol_fee: None,
+                native_denom: None,
+                task_creation_fee: None,
+                nomination_paused: None,
+                reward_vesting_seconds: None,
+                max_future_slot_seconds: None,
+                randomized_agent_selection: None,
+                max_tasks_per_owner: None,
+                dust_threshold: None,
+                dust_swap_contract: None,
+                min_active_agents: None,
+                min_agent_version: None,
+                removal_grace_slots: None,
+                eject_to_pending: None,
+                max_task_limit: None,
+                agent_allowlist_enabled: None,
+                max_task_gas: None,
+                min_task_balance: None,
             },
             &vec![],
         )
@@ -909,6 +2512,9 @@ mod tests {
                             gas_limit: Some(150_000),
                         }],
                         rules: None,
+                        metadata: None,
+                        successor: None,
+                        action_order: None,
                     },
                 },
                 &coins(13, "atom"),
@@ -936,6 +2542,9 @@ mod tests {
                             gas_limit: Some(150_000),
                         }],
                         rules: None,
+                        metadata: None,
+                        successor: None,
+                        action_order: None,
                     },
                 },
                 &coins(13, "atom"),
@@ -953,7 +2562,7 @@ mod tests {
             Addr::unchecked(ANYONE),
             contract_addr.clone(),
             &create_task_msg,
-            &coins(300010, "atom"),
+            &coins(300016, "atom"),
         )
         .unwrap();
         let res_err = app
@@ -961,7 +2570,7 @@ mod tests {
                 Addr::unchecked(ANYONE),
                 contract_addr.clone(),
                 &create_task_msg,
-                &coins(300010, "atom"),
+                &coins(300016, "atom"),
             )
             .unwrap_err();
         assert_eq!(
@@ -989,9 +2598,12 @@ mod tests {
                             gas_limit: Some(150_000),
                         }],
                         rules: None,
+                        metadata: None,
+                        successor: None,
+                        action_order: None,
                     },
                 },
-                &coins(300010, "atom"),
+                &coins(300016, "atom"),
             )
             .unwrap_err();
         assert_eq!(
@@ -1026,6 +2638,9 @@ mod tests {
                     gas_limit: Some(150_000),
                 }],
                 rules: None,
+                metadata: None,
+                successor: None,
+                action_order: None,
             },
         };
         let task_id_str =
@@ -1037,7 +2652,7 @@ mod tests {
                 Addr::unchecked(ANYONE),
                 contract_addr.clone(),
                 &create_task_msg,
-                &coins(300010, "atom"),
+                &coins(300016, "atom"),
             )
             .unwrap();
         // Assert task hash is returned as part of event attributes
@@ -1067,7 +2682,7 @@ mod tests {
             assert_eq!(Interval::Immediate, t.interval);
             assert_eq!(None, t.boundary);
             assert_eq!(false, t.stop_on_fail);
-            assert_eq!(coins(300010, "atom"), t.total_deposit);
+            assert_eq!(coins(300016, "atom"), t.total_deposit);
             assert_eq!(task_id_str.clone(), t.task_hash);
         }
 
@@ -1085,7 +2700,10 @@ mod tests {
             .wrap()
             .query_wasm_smart(
                 &contract_addr.clone(),
-                &QueryMsg::GetSlotHashes { slot: None },
+                &QueryMsg::GetSlotHashes {
+                    slot: None,
+                    limit: None,
+                },
             )
             .unwrap();
         let s_3: Vec<String> = Vec::new();
@@ -1117,6 +2735,9 @@ mod tests {
                     gas_limit: Some(150_000),
                 }],
                 rules: None,
+                metadata: None,
+                successor: None,
+                action_order: None,
             },
         };
         let task_id_str =
@@ -1127,7 +2748,7 @@ mod tests {
             Addr::unchecked(ANYONE),
             contract_addr.clone(),
             &create_task_msg,
-            &coins(300010, "atom"),
+            &coins(300016, "atom"),
         )
         .unwrap();
 
@@ -1158,6 +2779,7 @@ mod tests {
             contract_addr.clone(),
             &ExecuteMsg::RemoveTask {
                 task_hash: task_id_str.clone(),
+                refund_to: None,
             },
             &vec![],
         )
@@ -1214,6 +2836,9 @@ mod tests {
                     gas_limit: Some(150_000),
                 }],
                 rules: None,
+                metadata: None,
+                successor: None,
+                action_order: None,
             },
         };
         let task_id_str =
@@ -1224,7 +2849,7 @@ mod tests {
             Addr::unchecked(ANYONE),
             contract_addr.clone(),
             &create_task_msg,
-            &coins(300010, "atom"),
+            &coins(300016, "atom"),
         )
         .unwrap();
         // refill task
@@ -1242,7 +2867,7 @@ mod tests {
         let mut matches_new_totals: bool = false;
         for e in res.events {
             for a in e.attributes {
-                if a.key == "total_deposit" && a.value == "300013atom".to_string() {
+                if a.key == "total_deposit" && a.value == "300019atom".to_string() {
                     matches_new_totals = true;
                 }
             }
@@ -1263,7 +2888,7 @@ mod tests {
 
         if let Some(t) = new_task {
             assert_eq!(Addr::unchecked(ANYONE), t.owner_id);
-            assert_eq!(coins(300013, "atom"), t.total_deposit);
+            assert_eq!(coins(300019, "atom"), t.total_deposit);
         }
 
         // Check the balance has increased to include the new refilled total
@@ -1271,7 +2896,7 @@ mod tests {
             .wrap()
             .query_wasm_smart(&contract_addr.clone(), &QueryMsg::GetBalances {})
             .unwrap();
-        assert_eq!(coins(300013, "atom"), balances.available_balance.native);
+        assert_eq!(coins(300019, "atom"), balances.available_balance.native);
 
         Ok(())
     }
@@ -1298,17 +2923,21 @@ mod tests {
                     gas_limit: Some(gas_limit),
                 }],
                 rules: None,
+                metadata: None,
+                successor: None,
+                action_order: None,
             },
         };
         // create 1 token off task
-        let amount_for_one_task = gas_limit + agent_fee;
+        // proxy_callback_gas is 3 per action
+        let amount_for_one_task = gas_limit + agent_fee + 3;
         let res = app.execute_contract(
             Addr::unchecked(ANYONE),
             contract_addr.clone(),
             &create_task_msg,
             &coins(u128::from(amount_for_one_task * 2 - 1), "atom"),
         );
-        assert!(format!("{res:?}").contains("Not enough task balance to execute job"));
+        assert!(format!("{res:?}").contains("Task balance is below the minimum"));
 
         // create a task
         let res = app.execute_contract(
@@ -1342,17 +2971,21 @@ mod tests {
                     gas_limit: None,
                 }],
                 rules: None,
+                metadata: None,
+                successor: None,
+                action_order: None,
             },
         };
         // create 1 token off task
-        let amount_for_one_task = gas_limit + agent_fee;
+        // proxy_callback_gas is 3 per action
+        let amount_for_one_task = gas_limit + agent_fee + 3;
         let res = app.execute_contract(
             Addr::unchecked(ANYONE),
             contract_addr.clone(),
             &create_task_msg,
             &coins(u128::from(amount_for_one_task * 2 - 1), "atom"),
         );
-        assert!(format!("{res:?}").contains("Not enough task balance to execute job"));
+        assert!(format!("{res:?}").contains("Task balance is below the minimum"));
 
         // create a task
         let res = app.execute_contract(
@@ -1363,4 +2996,3280 @@ mod tests {
         );
         assert!(res.is_ok());
     }
+
+    #[test]
+    fn reap_dead_tasks() -> StdResult<()> {
+        let mut app = mock_app();
+        let cw_template_id = app.store_code(contract_template());
+        let msg = InstantiateMsg {
+            denom: NATIVE_DENOM.to_string(),
+            owner_id: Some(Addr::unchecked(ADMIN)),
+            gas_base_fee: None,
+            agent_nomination_duration: None,
+        };
+        // Fund the contract, the bounty is paid from the available balance
+        let contract_addr = app
+            .instantiate_contract(
+                cw_template_id,
+                Addr::unchecked(VERY_RICH),
+                &msg,
+                &coins(1_000, NATIVE_DENOM),
+                "Manager",
+                None,
+            )
+            .unwrap();
+
+        let new_msg = |end: Option<u64>| ExecuteMsg::CreateTask {
+            task: TaskRequest {
+                interval: Interval::Immediate,
+                boundary: Some(Boundary::Height {
+                    start: None,
+                    end: end.map(Into::into),
+                }),
+                stop_on_fail: false,
+                actions: vec![Action {
+                    msg: StakingMsg::Delegate {
+                        validator: "you".to_string(),
+                        amount: coin(3, NATIVE_DENOM),
+                    }
+                    .into(),
+                    gas_limit: Some(150_000),
+                }],
+                rules: None,
+                metadata: None,
+                successor: None,
+                action_order: None,
+            },
+        };
+
+        // a task with a boundary that ends soon, and one that runs forever
+        app.execute_contract(
+            Addr::unchecked(ANYONE),
+            contract_addr.clone(),
+            &new_msg(Some(12347)),
+            &coins(300016, NATIVE_DENOM),
+        )
+        .unwrap();
+        app.execute_contract(
+            Addr::unchecked(VERY_RICH),
+            contract_addr.clone(),
+            &new_msg(None),
+            &coins(300016, NATIVE_DENOM),
+        )
+        .unwrap();
+
+        // Nothing to reap yet
+        let res = app
+            .execute_contract(
+                Addr::unchecked(ADMIN),
+                contract_addr.clone(),
+                &ExecuteMsg::ReapDeadTasks { limit: None },
+                &[],
+            )
+            .unwrap();
+        assert!(res.events.iter().any(|e| e
+            .attributes
+            .iter()
+            .any(|a| a.key == "reaped" && a.value == "0")));
+
+        // Move past the boundary end, so the first task can't run anymore
+        app.update_block(|block| block.height += 3);
+        app.execute_contract(
+            Addr::unchecked(ADMIN),
+            contract_addr.clone(),
+            &ExecuteMsg::ReapDeadTasks { limit: None },
+            &[],
+        )
+        .unwrap();
+
+        let tasks: Vec<TaskResponse> = app.wrap().query_wasm_smart(
+            &contract_addr,
+            &QueryMsg::GetTasks {
+                from_index: None,
+                limit: None,
+                include_status: None,
+            },
+        )?;
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(tasks[0].owner_id, Addr::unchecked(VERY_RICH));
+
+        // Owner got refunded, reaper got the bounty
+        let owner_balance = app.wrap().query_balance(ANYONE, NATIVE_DENOM)?;
+        assert_eq!(owner_balance.amount.u128(), 800_010);
+        let reaper_balance = app.wrap().query_balance(ADMIN, NATIVE_DENOM)?;
+        assert_eq!(reaper_balance.amount.u128(), 105);
+
+        Ok(())
+    }
+
+    #[test]
+    fn reap_dead_tasks_bounty_needs_spare_funds() -> StdResult<()> {
+        // Nothing but task deposits in the contract
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+
+        let new_msg = |end: Option<u64>| ExecuteMsg::CreateTask {
+            task: TaskRequest {
+                interval: Interval::Immediate,
+                boundary: Some(Boundary::Height {
+                    start: None,
+                    end: end.map(Into::into),
+                }),
+                stop_on_fail: false,
+                actions: vec![Action {
+                    msg: StakingMsg::Delegate {
+                        validator: "you".to_string(),
+                        amount: coin(3, NATIVE_DENOM),
+                    }
+                    .into(),
+                    gas_limit: Some(150_000),
+                }],
+                rules: None,
+                metadata: None,
+                successor: None,
+                action_order: None,
+            },
+        };
+        app.execute_contract(
+            Addr::unchecked(ANYONE),
+            contract_addr.clone(),
+            &new_msg(Some(12347)),
+            &coins(300016, NATIVE_DENOM),
+        )
+        .unwrap();
+        app.execute_contract(
+            Addr::unchecked(VERY_RICH),
+            contract_addr.clone(),
+            &new_msg(None),
+            &coins(300016, NATIVE_DENOM),
+        )
+        .unwrap();
+
+        app.update_block(|block| block.height += 3);
+        let res = app
+            .execute_contract(
+                Addr::unchecked(ADMIN),
+                contract_addr.clone(),
+                &ExecuteMsg::ReapDeadTasks { limit: None },
+                &[],
+            )
+            .unwrap();
+        assert!(res.events.iter().any(|e| e
+            .attributes
+            .iter()
+            .any(|a| a.key == "reaped" && a.value == "1")));
+
+        // The owner is refunded, but the other task's deposit doesn't pay the bounty
+        let owner_balance = app.wrap().query_balance(ANYONE, NATIVE_DENOM)?;
+        assert_eq!(owner_balance.amount.u128(), 800_010);
+        let reaper_balance = app.wrap().query_balance(ADMIN, NATIVE_DENOM)?;
+        assert_eq!(reaper_balance.amount.u128(), 100);
+        let balances: GetBalancesResponse = app
+            .wrap()
+            .query_wasm_smart(&contract_addr, &QueryMsg::GetBalances {})?;
+        assert_eq!(
+            balances.available_balance.native,
+            coins(300016, NATIVE_DENOM)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn dead_task_respects_grace_slots() {
+        let mut deps = mock_dependencies();
+        let store = CwCroncat::default();
+        mock_init(&store, deps.as_mut()).unwrap();
+
+        // Can't cover a single run
+        let task = Task {
+            owner_id: Addr::unchecked("bob"),
+            interval: Interval::Immediate,
+            boundary: BoundaryValidated {
+                start: None,
+                end: None,
+                max_executions: None,
+            },
+            stop_on_fail: false,
+            total_deposit: coins(10, "atom"),
+            actions: vec![Action {
+                msg: StakingMsg::Delegate {
+                    validator: "you".to_string(),
+                    amount: coin(1, "atom"),
+                }
+                .into(),
+                gas_limit: Some(150_000),
+            }],
+            rules: None,
+            metadata: None,
+            successor: None,
+            generation: 0,
+            action_order: ActionOrder::Sequential,
+            total_cw20_deposit: vec![],
+        };
+        let is_dead = |deps: &cosmwasm_std::OwnedDeps<_, _, _>, grace_slots: u64| {
+            let mut c: Config = store.config.load(&deps.storage).unwrap();
+            c.removal_grace_slots = grace_slots;
+            store
+                .is_dead_task(&deps.storage, &task, &mock_env(), &c)
+                .unwrap()
+        };
+
+        // Without grace slots it's reaped right away, with them it waits for its next run
+        assert!(is_dead(&deps, 0));
+        assert!(!is_dead(&deps, 2));
+
+        // Held tasks are reaped once their grace slots are used up
+        store
+            .exhausted_tasks
+            .save(&mut deps.storage, task.to_hash_vec(), &1)
+            .unwrap();
+        assert!(!is_dead(&deps, 2));
+        store
+            .exhausted_tasks
+            .save(&mut deps.storage, task.to_hash_vec(), &0)
+            .unwrap();
+        assert!(is_dead(&deps, 2));
+    }
+    #[test]
+    fn check_max_rules_per_task() -> StdResult<()> {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+
+        let settings_msg = ExecuteMsg::UpdateSettings {
+            paused: None,
+            treasury_id: None,
+            agent_fee: None,
+            agents_eject_threshold: None,
+            gas_price: None,
+            proxy_callback_gas: None,
+            slot_granularity: None,
+            min_tasks_per_agent: None,
+            dead_task_bounty: None,
+            max_rules_per_task: Some(2),
+            slash_destination: None,
+            low_balance_pause_threshold: None,
+            min_active_agents_for_execution: None,
+            max_submsgs_per_proxy_call: None,
+            protocol_fee: None,
+            native_denom: None,
+            task_creation_fee: None,
+            nomination_paused: None,
+            reward_vesting_seconds: None,
+            max_future_slot_seconds: None,
+            randomized_agent_selection: None,
+            max_tasks_per_owner: None,
+            dust_threshold: None,
+            dust_swap_contract: None,
+            min_active_agents: None,
+            min_agent_version: None,
+            removal_grace_slots: None,
+            eject_to_pending: None,
+            max_task_limit: None,
+            agent_allowlist_enabled: None,
+            max_task_gas: None,
+            min_task_balance: None,
+        };
+        app.execute_contract(
+            Addr::unchecked(ADMIN),
+            contract_addr.clone(),
+            &settings_msg,
+            &vec![],
+        )
+        .unwrap();
+
+        let new_msg = |num_rules: usize| ExecuteMsg::CreateTask {
+            task: TaskRequest {
+                interval: Interval::Immediate,
+                boundary: None,
+                stop_on_fail: false,
+                actions: vec![Action {
+                    msg: StakingMsg::Delegate {
+                        validator: "you".to_string(),
+                        amount: coin(num_rules as u128, "atom"),
+                    }
+                    .into(),
+                    gas_limit: Some(150_000),
+                }],
+                rules: Some(vec![
+                    Rule::Query {
+                        contract_addr: Addr::unchecked("foo"),
+                        msg: Binary::from(b"bar".to_vec()),
+                    };
+                    num_rules
+                ]),
+                metadata: None,
+                successor: None,
+                action_order: None,
+            },
+        };
+
+        // At the limit is fine
+        app.execute_contract(
+            Addr::unchecked(ANYONE),
+            contract_addr.clone(),
+            &new_msg(2),
+            &coins(300016, "atom"),
+        )
+        .unwrap();
+
+        // Over the limit gets rejected
+        let res_err = app
+            .execute_contract(
+                Addr::unchecked(ANYONE),
+                contract_addr.clone(),
+                &new_msg(3),
+                &coins(300016, "atom"),
+            )
+            .unwrap_err();
+        assert_eq!(
+            ContractError::CustomError {
+                val: "Too many rules, max allowed: 2".to_string()
+            },
+            res_err.downcast().unwrap()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn check_max_rule_depth() -> StdResult<()> {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+
+        let new_msg = |levels: usize| {
+            let leaf = RuleExpr::Leaf(Rule::NativeBalanceGte {
+                address: Addr::unchecked(ANYONE),
+                required: coin(1, "atom"),
+            });
+            let expr = (1..levels).fold(leaf, |expr, _| RuleExpr::Or(vec![expr]));
+            ExecuteMsg::CreateTask {
+                task: TaskRequest {
+                    interval: Interval::Immediate,
+                    boundary: None,
+                    stop_on_fail: false,
+                    actions: vec![Action {
+                        msg: StakingMsg::Delegate {
+                            validator: "you".to_string(),
+                            amount: coin(levels as u128, "atom"),
+                        }
+                        .into(),
+                        gas_limit: Some(150_000),
+                    }],
+                    rules: Some(vec![Rule::Expr(Box::new(expr))]),
+                    metadata: None,
+                    successor: None,
+                    action_order: None,
+                },
+            }
+        };
+
+        app.execute_contract(
+            Addr::unchecked(ANYONE),
+            contract_addr.clone(),
+            &new_msg(MAX_RULE_DEPTH),
+            &coins(300016, "atom"),
+        )
+        .unwrap();
+
+        let res_err = app
+            .execute_contract(
+                Addr::unchecked(ANYONE),
+                contract_addr.clone(),
+                &new_msg(MAX_RULE_DEPTH + 1),
+                &coins(300016, "atom"),
+            )
+            .unwrap_err();
+        assert_eq!(
+            ContractError::CustomError {
+                val: format!("Rules nest too deep, max allowed: {}", MAX_RULE_DEPTH)
+            },
+            res_err.downcast().unwrap()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn query_get_tasks_include_status() -> StdResult<()> {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+
+        let new_msg = |rules: Option<Vec<Rule>>| ExecuteMsg::CreateTask {
+            task: TaskRequest {
+                interval: Interval::Immediate,
+                boundary: None,
+                stop_on_fail: false,
+                actions: vec![Action {
+                    msg: StakingMsg::Delegate {
+                        validator: "you".to_string(),
+                        amount: coin(rules.is_some() as u128 + 1, "atom"),
+                    }
+                    .into(),
+                    gas_limit: Some(150_000),
+                }],
+                rules,
+                metadata: None,
+                successor: None,
+                action_order: None,
+            },
+        };
+        app.execute_contract(
+            Addr::unchecked(ANYONE),
+            contract_addr.clone(),
+            &new_msg(None),
+            &coins(300016, "atom"),
+        )
+        .unwrap();
+        // Rule pointed at a non-contract, query fails
+        app.execute_contract(
+            Addr::unchecked(ANYONE),
+            contract_addr.clone(),
+            &new_msg(Some(vec![Rule::Query {
+                contract_addr: Addr::unchecked("foo"),
+                msg: Binary::from(b"bar".to_vec()),
+            }])),
+            &coins(300016, "atom"),
+        )
+        .unwrap();
+
+        let query_tasks = |app: &App, include_status| -> StdResult<Vec<TaskResponse>> {
+            app.wrap().query_wasm_smart(
+                contract_addr.clone(),
+                &QueryMsg::GetTasks {
+                    from_index: None,
+                    limit: None,
+                    include_status,
+                },
+            )
+        };
+
+        // No status unless asked for
+        let tasks = query_tasks(&app, None)?;
+        assert_eq!(tasks.len(), 2);
+        assert!(tasks.iter().all(|t| t.status.is_none()));
+
+        // Immediate tasks are slotted for the next block
+        let tasks = query_tasks(&app, Some(true))?;
+        for task in tasks.iter() {
+            let status = task.status.clone().unwrap();
+            assert!(!status.due);
+            assert_eq!(status.rules_pass, task.rules.as_ref().map(|_| false));
+        }
+
+        app.update_block(|block| block.height += 1);
+        let tasks = query_tasks(&app, Some(true))?;
+        assert!(tasks.iter().all(|t| t.status.clone().unwrap().due));
+
+        Ok(())
+    }
+
+    #[test]
+    fn query_expiring_tasks() -> StdResult<()> {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+        let now = app.block_info().time;
+
+        let new_msg =
+            |amount: u128, interval: Interval, boundary: Boundary| ExecuteMsg::CreateTask {
+                task: TaskRequest {
+                    interval,
+                    boundary: Some(boundary),
+                    stop_on_fail: false,
+                    actions: vec![Action {
+                        msg: StakingMsg::Delegate {
+                            validator: "you".to_string(),
+                            amount: coin(amount, "atom"),
+                        }
+                        .into(),
+                        gas_limit: Some(150_000),
+                    }],
+                    rules: None,
+                    metadata: None,
+                    successor: None,
+                    action_order: None,
+                },
+            };
+        let time_boundary = |end: Timestamp| Boundary::Time {
+            start: None,
+            end: Some(end),
+        };
+        let msgs = vec![
+            // ends in an hour
+            new_msg(
+                1,
+                Interval::Cron("0 * * * * *".to_string()),
+                time_boundary(now.plus_seconds(3600)),
+            ),
+            // ends in ten days
+            new_msg(
+                2,
+                Interval::Cron("0 * * * * *".to_string()),
+                time_boundary(now.plus_seconds(864_000)),
+            ),
+            // block boundaries don't count as expiring, even if the number looks close
+            new_msg(
+                3,
+                Interval::Immediate,
+                Boundary::Height {
+                    start: None,
+                    end: Some(now.plus_seconds(3600).nanos().into()),
+                },
+            ),
+        ];
+        for msg in msgs.iter() {
+            app.execute_contract(
+                Addr::unchecked(ANYONE),
+                contract_addr.clone(),
+                msg,
+                &coins(300016, "atom"),
+            )
+            .unwrap();
+        }
+
+        let query_expiring = |app: &App, within_seconds| -> StdResult<Vec<TaskResponse>> {
+            app.wrap().query_wasm_smart(
+                contract_addr.clone(),
+                &QueryMsg::GetExpiringTasks {
+                    within_seconds,
+                    limit: None,
+                },
+            )
+        };
+
+        let tasks = query_expiring(&app, 7200)?;
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(
+            tasks[0].boundary,
+            Some(time_boundary(now.plus_seconds(3600)))
+        );
+
+        // a wide enough window catches both
+        let tasks = query_expiring(&app, 1_000_000)?;
+        assert_eq!(tasks.len(), 2);
+
+        // already expired tasks are left out
+        app.update_block(|block| block.time = block.time.plus_seconds(7200));
+        let tasks = query_expiring(&app, 7200)?;
+        assert!(tasks.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn query_task_funding_history() -> StdResult<()> {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+
+        let create_task_msg = ExecuteMsg::CreateTask {
+            task: TaskRequest {
+                interval: Interval::Immediate,
+                boundary: None,
+                stop_on_fail: false,
+                actions: vec![Action {
+                    msg: StakingMsg::Delegate {
+                        validator: "you".to_string(),
+                        amount: coin(3, "atom"),
+                    }
+                    .into(),
+                    gas_limit: Some(150_000),
+                }],
+                rules: None,
+                metadata: None,
+                successor: None,
+                action_order: None,
+            },
+        };
+        let res = app
+            .execute_contract(
+                Addr::unchecked(ANYONE),
+                contract_addr.clone(),
+                &create_task_msg,
+                &coins(300016, "atom"),
+            )
+            .unwrap();
+        let task_hash = res
+            .events
+            .iter()
+            .flat_map(|e| e.attributes.iter())
+            .find(|a| a.key == "task_hash")
+            .map(|a| a.value.clone())
+            .unwrap();
+
+        app.update_block(|block| block.height += 1);
+        app.execute_contract(
+            Addr::unchecked(ANYONE),
+            contract_addr.clone(),
+            &ExecuteMsg::RefillTaskBalance {
+                task_hash: task_hash.clone(),
+            },
+            &coins(3, "atom"),
+        )
+        .unwrap();
+
+        app.update_block(|block| block.height += 1);
+        app.execute_contract(
+            Addr::unchecked(ANYONE),
+            contract_addr.clone(),
+            &ExecuteMsg::RemoveTask {
+                task_hash: task_hash.clone(),
+                refund_to: None,
+            },
+            &[],
+        )
+        .unwrap();
+
+        let history: Vec<FundingEvent> = app.wrap().query_wasm_smart(
+            &contract_addr,
+            &QueryMsg::GetTaskFundingHistory { task_hash },
+        )?;
+        let events: Vec<(FundingEventKind, Vec<Coin>)> = history
+            .iter()
+            .map(|e| (e.kind.clone(), e.amount.clone()))
+            .collect();
+        assert_eq!(
+            events,
+            vec![
+                (FundingEventKind::Deposit, coins(300016, "atom")),
+                (FundingEventKind::Refill, coins(3, "atom")),
+                (FundingEventKind::Withdraw, coins(300019, "atom")),
+            ]
+        );
+        assert!(history[0].block_height < history[1].block_height);
+        assert!(history[1].block_height < history[2].block_height);
+
+        Ok(())
+    }
+
+    #[test]
+    fn query_slot_counts() -> StdResult<()> {
+        let mut deps = mock_dependencies();
+        let mut store = CwCroncat::default();
+        mock_init(&store, deps.as_mut())?;
+
+        let hashes = |n: usize| -> Vec<Vec<u8>> {
+            (0..n).map(|i| format!("hash{}", i).into_bytes()).collect()
+        };
+        store.block_slots.save(&mut deps.storage, 10, &hashes(1))?;
+        store.block_slots.save(&mut deps.storage, 12, &hashes(3))?;
+        store.block_slots.save(&mut deps.storage, 15, &hashes(2))?;
+        store
+            .time_slots
+            .save(&mut deps.storage, 1_000, &hashes(4))?;
+        store
+            .time_slots
+            .save(&mut deps.storage, 2_000, &hashes(1))?;
+
+        let res: GetSlotCountsResponse = from_binary(&store.query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::GetSlotCounts {
+                from_index: None,
+                limit: None,
+            },
+        )?)?;
+        assert_eq!(res.block_counts, vec![(10, 1), (12, 3), (15, 2)]);
+        assert_eq!(res.time_counts, vec![(1_000, 4), (2_000, 1)]);
+
+        let res: GetSlotCountsResponse = from_binary(&store.query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::GetSlotCounts {
+                from_index: Some(1),
+                limit: Some(1),
+            },
+        )?)?;
+        assert_eq!(res.block_counts, vec![(12, 3)]);
+        assert_eq!(res.time_counts, vec![(2_000, 1)]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn query_upcoming_slot_hashes() -> StdResult<()> {
+        let mut deps = mock_dependencies();
+        let mut store = CwCroncat::default();
+        mock_init(&store, deps.as_mut())?;
+
+        let hashes = |names: &[&str]| -> Vec<Vec<u8>> {
+            names.iter().map(|name| name.as_bytes().to_vec()).collect()
+        };
+        store
+            .block_slots
+            .save(&mut deps.storage, 15, &hashes(&["c"]))?;
+        store
+            .block_slots
+            .save(&mut deps.storage, 10, &hashes(&["a", "b"]))?;
+        store.block_slots.save(&mut deps.storage, 12, &vec![])?;
+        store
+            .block_slots
+            .save(&mut deps.storage, 20, &hashes(&["d"]))?;
+        store
+            .time_slots
+            .save(&mut deps.storage, 2_000, &hashes(&["f"]))?;
+        store
+            .time_slots
+            .save(&mut deps.storage, 1_000, &hashes(&["e"]))?;
+
+        let mut slot_hashes = |slot: Option<u64>, limit: Option<u64>| -> GetSlotHashesResponse {
+            let res = store
+                .query(
+                    deps.as_ref(),
+                    mock_env(),
+                    QueryMsg::GetSlotHashes { slot, limit },
+                )
+                .unwrap();
+            from_binary(&res).unwrap()
+        };
+        let slot = |slot_id: u64, names: &[&str]| SlotHashes {
+            slot_id,
+            task_hashes: names.iter().map(|name| name.to_string()).collect(),
+        };
+
+        // Empty slots are skipped and the limit applies to each kind
+        let res = slot_hashes(None, Some(2));
+        assert_eq!(res.block_id, 10);
+        assert_eq!(res.time_id, 1_000);
+        assert_eq!(
+            res.block_slots,
+            vec![slot(10, &["a", "b"]), slot(15, &["c"])]
+        );
+        assert_eq!(
+            res.time_slots,
+            vec![slot(1_000, &["e"]), slot(2_000, &["f"])]
+        );
+
+        let res = slot_hashes(None, None);
+        assert_eq!(res.block_slots.len(), 3);
+        assert_eq!(res.block_slots[2], slot(20, &["d"]));
+
+        // A single slot is looked up in both kinds, without listing upcoming ones
+        let res = slot_hashes(Some(2_000), Some(2));
+        assert_eq!(res.time_id, 2_000);
+        assert_eq!(res.time_task_hash, vec!["f".to_string()]);
+        assert_eq!(res.block_id, 0);
+        assert!(res.block_slots.is_empty() && res.time_slots.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn remove_task_refund_to() -> StdResult<()> {
+        let mut deps = mock_dependencies();
+        let mut store = CwCroncat::default();
+        mock_init(&store, deps.as_mut())?;
+
+        let task = Task {
+            owner_id: Addr::unchecked("bob"),
+            interval: Interval::Immediate,
+            boundary: BoundaryValidated {
+                start: None,
+                end: None,
+                max_executions: None,
+            },
+            stop_on_fail: false,
+            total_deposit: coins(300, NATIVE_DENOM),
+            actions: vec![],
+            rules: None,
+            metadata: None,
+            successor: None,
+            generation: 0,
+            action_order: ActionOrder::Sequential,
+            total_cw20_deposit: vec![],
+        };
+        store
+            .tasks
+            .save(&mut deps.storage, task.to_hash_vec(), &task)?;
+        let remove_msg = ExecuteMsg::RemoveTask {
+            task_hash: task.to_hash(),
+            refund_to: Some(Addr::unchecked("dao")),
+        };
+
+        // Only the owner can redirect the refund
+        let err = store
+            .execute(
+                deps.as_mut(),
+                mock_env(),
+                mock_info("alice", &[]),
+                remove_msg.clone(),
+            )
+            .unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+
+        let res = store
+            .execute(deps.as_mut(), mock_env(), mock_info("bob", &[]), remove_msg)
+            .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "dao".to_string(),
+                amount: coins(300, NATIVE_DENOM),
+            })]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn remove_task_clears_stale_nomination() -> StdResult<()> {
+        let mut deps = mock_dependencies();
+        let mut store = CwCroncat::default();
+        mock_init(&store, deps.as_mut())?;
+        let env = mock_env();
+
+        // No active agents, so every task asks for one
+        let mut hashes = vec![];
+        for owner in ["bob", "alice"] {
+            let task = Task {
+                owner_id: Addr::unchecked(owner),
+                interval: Interval::Immediate,
+                boundary: BoundaryValidated {
+                    start: None,
+                    end: None,
+                    max_executions: None,
+                },
+                stop_on_fail: false,
+                total_deposit: vec![],
+                actions: vec![],
+                rules: None,
+                metadata: None,
+                successor: None,
+                generation: 0,
+                action_order: ActionOrder::Sequential,
+                total_cw20_deposit: vec![],
+            };
+            store
+                .tasks
+                .save(&mut deps.storage, task.to_hash_vec(), &task)?;
+            store.increment_tasks(&mut deps.storage)?;
+            hashes.push(task.to_hash());
+        }
+        store
+            .agent_nomination_begin_time
+            .save(&mut deps.storage, &Some(env.block.time))?;
+
+        // Still one task needing an agent, keep nominating
+        store
+            .execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("bob", &[]),
+                ExecuteMsg::RemoveTask {
+                    task_hash: hashes[0].clone(),
+                    refund_to: None,
+                },
+            )
+            .unwrap();
+        assert_eq!(
+            store.agent_nomination_begin_time.load(&deps.storage)?,
+            Some(env.block.time)
+        );
+
+        // Demand dropped to zero, timer is cleared
+        store
+            .execute(
+                deps.as_mut(),
+                env,
+                mock_info("alice", &[]),
+                ExecuteMsg::RemoveTask {
+                    task_hash: hashes[1].clone(),
+                    refund_to: None,
+                },
+            )
+            .unwrap();
+        assert_eq!(store.task_total(&deps.storage)?, 0);
+        assert_eq!(store.agent_nomination_begin_time.load(&deps.storage)?, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn query_oldest_overdue_slot() -> StdResult<()> {
+        let mut deps = mock_dependencies();
+        let store = CwCroncat::default();
+        mock_init(&store, deps.as_mut())?;
+        let env = mock_env();
+
+        // Slots due in the current block aren't overdue yet
+        let height = env.block.height;
+        let now = env.block.time.nanos();
+        store
+            .block_slots
+            .save(&mut deps.storage, height, &vec![b"hash".to_vec()])?;
+        store
+            .time_slots
+            .save(&mut deps.storage, now + 1, &vec![b"hash".to_vec()])?;
+        assert_eq!(
+            store.query_oldest_overdue_slot(deps.as_ref(), env.clone())?,
+            None
+        );
+
+        store
+            .block_slots
+            .save(&mut deps.storage, height - 5, &vec![b"hash".to_vec()])?;
+        store
+            .block_slots
+            .save(&mut deps.storage, height - 2, &vec![b"hash".to_vec()])?;
+        store
+            .time_slots
+            .save(&mut deps.storage, now - 3_000, &vec![b"hash".to_vec()])?;
+        assert_eq!(
+            store.query_oldest_overdue_slot(deps.as_ref(), env.clone())?,
+            Some(GetOldestOverdueSlotResponse {
+                block_slot: Some(OverdueSlot {
+                    slot_id: height - 5,
+                    overdue_by: 5,
+                }),
+                time_slot: Some(OverdueSlot {
+                    slot_id: now - 3_000,
+                    overdue_by: 3_000,
+                }),
+            })
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn task_metadata() -> StdResult<()> {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+
+        let new_msg = |metadata: Vec<u8>| ExecuteMsg::CreateTask {
+            task: TaskRequest {
+                interval: Interval::Immediate,
+                boundary: None,
+                stop_on_fail: false,
+                actions: vec![Action {
+                    msg: StakingMsg::Delegate {
+                        validator: "you".to_string(),
+                        amount: coin(1, "atom"),
+                    }
+                    .into(),
+                    gas_limit: Some(150_000),
+                }],
+                rules: None,
+                metadata: Some(Binary::from(metadata)),
+                successor: None,
+                action_order: None,
+            },
+        };
+
+        let res_err = app
+            .execute_contract(
+                Addr::unchecked(ANYONE),
+                contract_addr.clone(),
+                &new_msg(vec![1; 1025]),
+                &coins(300016, "atom"),
+            )
+            .unwrap_err();
+        assert_eq!(
+            ContractError::CustomError {
+                val: "Task metadata exceeds 1024 bytes".to_string()
+            },
+            res_err.downcast().unwrap()
+        );
+
+        let res = app
+            .execute_contract(
+                Addr::unchecked(ANYONE),
+                contract_addr.clone(),
+                &new_msg(b"proposal:42".to_vec()),
+                &coins(300016, "atom"),
+            )
+            .unwrap();
+        let task_hash = res
+            .events
+            .iter()
+            .flat_map(|e| e.attributes.iter())
+            .find(|a| a.key == "task_hash")
+            .map(|a| a.value.clone())
+            .unwrap();
+
+        let task: Option<TaskResponse> = app
+            .wrap()
+            .query_wasm_smart(&contract_addr, &QueryMsg::GetTask { task_hash })?;
+        assert_eq!(
+            task.unwrap().metadata,
+            Some(Binary::from(b"proposal:42".to_vec()))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn task_creation_fee() -> StdResult<()> {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+
+        let change_settings_msg = ExecuteMsg::UpdateSettings {
+            paused: None,
+            treasury_id: None,
+            agent_fee: None,
+            agents_eject_threshold: None,
+            gas_price: None,
+            proxy_callback_gas: None,
+            slot_granularity: None,
+            min_tasks_per_agent: None,
+            dead_task_bounty: None,
+            max_rules_per_task: None,
+            slash_destination: None,
+            low_balance_pause_threshold: None,
+            min_active_agents_for_execution: None,
+            max_submsgs_per_proxy_call: None,
+            protocol_fee: None,
+            native_denom: None,
+            task_creation_fee: Some(coin(100, "atom")),
+            nomination_paused: None,
+            reward_vesting_seconds: None,
+            max_future_slot_seconds: None,
+            randomized_agent_selection: None,
+            max_tasks_per_owner: None,
+            dust_threshold: None,
+            dust_swap_contract: None,
+            min_active_agents: None,
+            min_agent_version: None,
+            removal_grace_slots: None,
+            eject_to_pending: None,
+            max_task_limit: None,
+            agent_allowlist_enabled: None,
+            max_task_gas: None,
+            min_task_balance: None,
+        };
+        app.execute_contract(
+            Addr::unchecked(ADMIN),
+            contract_addr.clone(),
+            &change_settings_msg,
+            &vec![],
+        )
+        .unwrap();
+
+        let create_task_msg = ExecuteMsg::CreateTask {
+            task: TaskRequest {
+                interval: Interval::Immediate,
+                boundary: None,
+                stop_on_fail: false,
+                actions: vec![Action {
+                    msg: StakingMsg::Delegate {
+                        validator: "you".to_string(),
+                        amount: coin(1, "atom"),
+                    }
+                    .into(),
+                    gas_limit: Some(150_000),
+                }],
+                rules: None,
+                metadata: None,
+                successor: None,
+                action_order: None,
+            },
+        };
+
+        // Fee must be covered in its denom
+        let res_err = app
+            .execute_contract(
+                Addr::unchecked(ANYONE),
+                contract_addr.clone(),
+                &create_task_msg,
+                &coins(300016, "juno"),
+            )
+            .unwrap_err();
+        assert_eq!(
+            ContractError::CustomError {
+                val: "Must attach task creation fee of 100atom".to_string()
+            },
+            res_err.downcast().unwrap()
+        );
+
+        // Enough for the task alone, but not for the fee on top
+        let res_err = app
+            .execute_contract(
+                Addr::unchecked(ANYONE),
+                contract_addr.clone(),
+                &create_task_msg,
+                &coins(300016, "atom"),
+            )
+            .unwrap_err();
+        assert_eq!(
+            ContractError::InsufficientTaskBalance {
+                required: coin(300_016, "atom")
+            },
+            res_err.downcast().unwrap()
+        );
+
+        let res = app
+            .execute_contract(
+                Addr::unchecked(ANYONE),
+                contract_addr.clone(),
+                &create_task_msg,
+                &coins(300116, "atom"),
+            )
+            .unwrap();
+        let task_hash = res
+            .events
+            .iter()
+            .flat_map(|e| e.attributes.iter())
+            .find(|a| a.key == "task_hash")
+            .map(|a| a.value.clone())
+            .unwrap();
+
+        // Fee isn't part of the task balance
+        let task: Option<TaskResponse> = app
+            .wrap()
+            .query_wasm_smart(&contract_addr, &QueryMsg::GetTask { task_hash })?;
+        assert_eq!(task.unwrap().total_deposit, coins(300016, "atom"));
+
+        let balances: GetBalancesResponse = app
+            .wrap()
+            .query_wasm_smart(&contract_addr, &QueryMsg::GetBalances {})?;
+        assert_eq!(balances.protocol_fees_collected.native, coins(100, "atom"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn check_task_funding() {
+        let mut deps = mock_dependencies();
+        let mut store = CwCroncat::default();
+        mock_init(&store, deps.as_mut()).unwrap();
+
+        let task = TaskRequest {
+            interval: Interval::Immediate,
+            boundary: None,
+            stop_on_fail: false,
+            actions: vec![Action {
+                msg: StakingMsg::Delegate {
+                    validator: "you".to_string(),
+                    amount: coin(1, "atom"),
+                }
+                .into(),
+                gas_limit: Some(150_000),
+            }],
+            rules: None,
+            metadata: None,
+            successor: None,
+            action_order: None,
+        };
+        let mut check = |deposit: Vec<Coin>| -> CheckTaskFundingResponse {
+            let res = store
+                .query(
+                    deps.as_ref(),
+                    mock_env(),
+                    QueryMsg::CheckTaskFunding {
+                        task: task.clone(),
+                        deposit,
+                    },
+                )
+                .unwrap();
+            from_binary(&res).unwrap()
+        };
+
+        // Each run costs 150_008, two runs are needed upfront
+        assert_eq!(
+            check(coins(750_040, "atom")),
+            CheckTaskFundingResponse {
+                sufficient: true,
+                min_deposit: coin(300_016, "atom"),
+                task_creation_fee: None,
+                executions: 5,
+            }
+        );
+        let res = check(coins(300_015, "atom"));
+        assert!(!res.sufficient);
+        assert_eq!(res.executions, 1);
+        assert!(!check(coins(300_016, "juno")).sufficient);
+
+        // Creation fee comes on top of the minimum
+        store
+            .config
+            .update(&mut deps.storage, |mut c| -> StdResult<_> {
+                c.task_creation_fee = Some(coin(100, "atom"));
+                Ok(c)
+            })
+            .unwrap();
+        let res = store
+            .query_check_task_funding(
+                deps.as_ref(),
+                mock_env(),
+                task.clone(),
+                coins(300_016, "atom"),
+            )
+            .unwrap();
+        assert!(!res.sufficient);
+        assert_eq!(res.task_creation_fee, Some(coin(100, "atom")));
+        let res = store
+            .query_check_task_funding(
+                deps.as_ref(),
+                mock_env(),
+                task.clone(),
+                coins(300_116, "atom"),
+            )
+            .unwrap();
+        assert!(res.sufficient);
+        assert_eq!(res.executions, 2);
+
+        // Other denoms sent along by the actions have to be covered too
+        let mut paying_task = task.clone();
+        paying_task.actions[0].msg = CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "alice".to_string(),
+            msg: to_binary("hello").unwrap(),
+            funds: coins(10, "ujuno"),
+        });
+        let res = store
+            .query_check_task_funding(
+                deps.as_ref(),
+                mock_env(),
+                paying_task.clone(),
+                coins(400_000, "atom"),
+            )
+            .unwrap();
+        assert!(!res.sufficient);
+        let res = store
+            .query_check_task_funding(
+                deps.as_ref(),
+                mock_env(),
+                paying_task,
+                vec![coin(400_000, "atom"), coin(10, "ujuno")],
+            )
+            .unwrap();
+        assert!(res.sufficient);
+
+        // A run that costs nothing doesn't run out
+        store
+            .config
+            .update(&mut deps.storage, |mut c| -> StdResult<_> {
+                c.task_creation_fee = None;
+                c.agent_fee = coin(0, "atom");
+                c.proxy_callback_gas = 0;
+                Ok(c)
+            })
+            .unwrap();
+        let mut free_task = task;
+        free_task.actions[0].gas_limit = Some(0);
+        let res = store
+            .query_check_task_funding(deps.as_ref(), mock_env(), free_task, coins(1, "atom"))
+            .unwrap();
+        assert!(res.sufficient);
+        assert_eq!(res.executions, u64::MAX);
+    }
+
+    #[test]
+    fn slot_order_follows_creation_sequence() {
+        let mut deps = mock_dependencies();
+        let mut store = CwCroncat::default();
+        mock_init(&store, deps.as_mut()).unwrap();
+
+        let create_task = |store: &mut CwCroncat, deps: DepsMut, amount: u128| -> Vec<u8> {
+            let res = store
+                .execute(
+                    deps,
+                    mock_env(),
+                    mock_info("owner", &coins(300_016, "atom")),
+                    ExecuteMsg::CreateTask {
+                        task: TaskRequest {
+                            interval: Interval::Immediate,
+                            boundary: None,
+                            stop_on_fail: false,
+                            actions: vec![Action {
+                                msg: StakingMsg::Delegate {
+                                    validator: "you".to_string(),
+                                    amount: coin(amount, "atom"),
+                                }
+                                .into(),
+                                gas_limit: Some(150_000),
+                            }],
+                            rules: None,
+                            metadata: None,
+                            successor: None,
+                            action_order: None,
+                        },
+                    },
+                )
+                .unwrap();
+            res.attributes
+                .iter()
+                .find(|a| a.key == "task_hash")
+                .unwrap()
+                .value
+                .clone()
+                .into_bytes()
+        };
+        let first = create_task(&mut store, deps.as_mut(), 1);
+        let second = create_task(&mut store, deps.as_mut(), 2);
+        let third = create_task(&mut store, deps.as_mut(), 3);
+
+        store
+            .execute(
+                deps.as_mut(),
+                mock_env(),
+                mock_info("creator", &[]),
+                ExecuteMsg::UpdateSettings {
+                    paused: None,
+                    treasury_id: None,
+                    agent_fee: None,
+                    min_tasks_per_agent: None,
+                    agents_eject_threshold: None,
+                    gas_price: None,
+                    proxy_callback_gas: None,
+                    slot_granularity: Some(30_000_000_000),
+                    dead_task_bounty: None,
+                    max_rules_per_task: None,
+                    slash_destination: None,
+                    low_balance_pause_threshold: None,
+                    min_active_agents_for_execution: None,
+                    max_submsgs_per_proxy_call: None,
+                    protocol_fee: None,
+                    native_denom: None,
+                    task_creation_fee: None,
+                    nomination_paused: None,
+                    reward_vesting_seconds: None,
+                },
+            )
+            .unwrap();
+
+        // Tasks put back into their slot in any order end up in creation order
+        let slot = mock_env().block.height + 1;
+        for expected in [&third, &second] {
+            let popped = store
+                .pop_slot_item(&mut deps.storage, &slot, &SlotType::Block)
+                .unwrap();
+            assert_eq!(&popped, expected);
+        }
+        for hash in [third.clone(), second.clone()] {
+            store
+                .push_slot_item(&mut deps.storage, &slot, &SlotType::Block, hash)
+                .unwrap();
+        }
+        let fourth = create_task(&mut store, deps.as_mut(), 4);
+
+        assert_eq!(
+            store.block_slots.load(&deps.storage, slot).unwrap(),
+            vec![first, second, third, fourth]
+        );
+    }
+
+    #[test]
+    fn query_underfunded_tasks() {
+        let mut deps = mock_dependencies();
+        let mut store = CwCroncat::default();
+        mock_init(&store, deps.as_mut()).unwrap();
+
+        // A run of this task costs 150_008
+        let task = |amount: u128, deposit: u128| Task {
+            owner_id: Addr::unchecked("owner"),
+            interval: Interval::Immediate,
+            boundary: BoundaryValidated {
+                start: None,
+                end: None,
+                max_executions: None,
+            },
+            stop_on_fail: false,
+            total_deposit: coins(deposit, "atom"),
+            actions: vec![Action {
+                msg: StakingMsg::Delegate {
+                    validator: "you".to_string(),
+                    amount: coin(amount, "atom"),
+                }
+                .into(),
+                gas_limit: Some(150_000),
+            }],
+            rules: None,
+            metadata: None,
+            successor: None,
+            generation: 0,
+            action_order: ActionOrder::Sequential,
+            total_cw20_deposit: vec![],
+        };
+        let funded = task(1, 300_016);
+        let underfunded = task(2, 150_007);
+        for t in [&funded, &underfunded] {
+            store
+                .tasks
+                .save(&mut deps.storage, t.to_hash_vec(), t)
+                .unwrap();
+        }
+
+        let res = store
+            .query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::GetUnderfundedTasks { limit: None },
+            )
+            .unwrap();
+        let tasks: Vec<TaskResponse> = from_binary(&res).unwrap();
+        assert_eq!(tasks, vec![underfunded.into()]);
+    }
+
+    #[test]
+    fn task_creator_whitelist() {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+
+        let create_task_msg = ExecuteMsg::CreateTask {
+            task: TaskRequest {
+                interval: Interval::Immediate,
+                boundary: None,
+                stop_on_fail: false,
+                actions: vec![Action {
+                    msg: StakingMsg::Delegate {
+                        validator: "you".to_string(),
+                        amount: coin(1, "atom"),
+                    }
+                    .into(),
+                    gas_limit: Some(150_000),
+                }],
+                rules: None,
+                metadata: None,
+                successor: None,
+                action_order: None,
+            },
+        };
+        let whitelist_msg = ExecuteMsg::AddTaskCreators {
+            addrs: vec![Addr::unchecked(VERY_RICH)],
+        };
+
+        // Only the owner manages the whitelist
+        let res_err = app
+            .execute_contract(
+                Addr::unchecked(ANYONE),
+                contract_addr.clone(),
+                &whitelist_msg,
+                &[],
+            )
+            .unwrap_err();
+        assert_eq!(ContractError::Unauthorized {}, res_err.downcast().unwrap());
+        app.execute_contract(
+            Addr::unchecked(ADMIN),
+            contract_addr.clone(),
+            &whitelist_msg,
+            &[],
+        )
+        .unwrap();
+
+        let res_err = app
+            .execute_contract(
+                Addr::unchecked(ANYONE),
+                contract_addr.clone(),
+                &create_task_msg,
+                &coins(300016, "atom"),
+            )
+            .unwrap_err();
+        assert_eq!(ContractError::Unauthorized {}, res_err.downcast().unwrap());
+        app.execute_contract(
+            Addr::unchecked(VERY_RICH),
+            contract_addr.clone(),
+            &create_task_msg,
+            &coins(300016, "atom"),
+        )
+        .unwrap();
+
+        // Emptying the whitelist opens task creation again
+        app.execute_contract(
+            Addr::unchecked(ADMIN),
+            contract_addr.clone(),
+            &ExecuteMsg::RemoveTaskCreators {
+                addrs: vec![Addr::unchecked(VERY_RICH)],
+            },
+            &[],
+        )
+        .unwrap();
+        let config: GetConfigResponse = app
+            .wrap()
+            .query_wasm_smart(&contract_addr, &QueryMsg::GetConfig {})
+            .unwrap();
+        assert_eq!(config.task_creator_whitelist, None);
+        app.execute_contract(
+            Addr::unchecked(ANYONE),
+            contract_addr.clone(),
+            &create_task_msg,
+            &coins(300016, "atom"),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn create_task_beyond_lookahead() {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+
+        let create_task_msg = |start: u64| ExecuteMsg::CreateTask {
+            task: TaskRequest {
+                interval: Interval::Immediate,
+                boundary: Some(Boundary::Height {
+                    start: Some(start.into()),
+                    end: None,
+                }),
+                stop_on_fail: false,
+                actions: vec![Action {
+                    msg: StakingMsg::Delegate {
+                        validator: "you".to_string(),
+                        amount: coin(1, "atom"),
+                    }
+                    .into(),
+                    gas_limit: Some(150_000),
+                }],
+                rules: None,
+                metadata: None,
+                successor: None,
+                action_order: None,
+            },
+        };
+        let height = app.block_info().height;
+
+        // Roughly a century of blocks is way past the default lookahead
+        let res_err = app
+            .execute_contract(
+                Addr::unchecked(ANYONE),
+                contract_addr.clone(),
+                &create_task_msg(height + 700_000_000),
+                &coins(300016, "atom"),
+            )
+            .unwrap_err();
+        assert_eq!(
+            ContractError::CustomError {
+                val: "First slot is too far in the future, max 315360000 seconds ahead".to_string()
+            },
+            res_err.downcast().unwrap()
+        );
+
+        app.execute_contract(
+            Addr::unchecked(ANYONE),
+            contract_addr.clone(),
+            &create_task_msg(height + 1_000),
+            &coins(300016, "atom"),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn query_task_type_breakdown() {
+        let mut deps = mock_dependencies();
+        let mut store = CwCroncat::default();
+        mock_init(&store, deps.as_mut()).unwrap();
+
+        let mut create_task = |interval: Interval| -> String {
+            let boundary = (interval == Interval::Once).then(|| Boundary::Height {
+                start: Some(mock_env().block.height.into()),
+                end: None,
+            });
+            let task = TaskRequest {
+                interval,
+                boundary,
+                stop_on_fail: false,
+                actions: vec![Action {
+                    msg: StakingMsg::Delegate {
+                        validator: "you".to_string(),
+                        amount: coin(1, "atom"),
+                    }
+                    .into(),
+                    gas_limit: Some(150_000),
+                }],
+                rules: None,
+                metadata: None,
+                successor: None,
+                action_order: None,
+            };
+            let res = store
+                .execute(
+                    deps.as_mut(),
+                    mock_env(),
+                    mock_info("bob", &coins(300_016, "atom")),
+                    ExecuteMsg::CreateTask { task },
+                )
+                .unwrap();
+            res.attributes
+                .into_iter()
+                .find(|a| a.key == "task_hash")
+                .unwrap()
+                .value
+        };
+        create_task(Interval::Once);
+        create_task(Interval::Immediate);
+        create_task(Interval::Block(5));
+        create_task(Interval::Cron("0 * * * * *".to_string()));
+        let hourly = create_task(Interval::Cron("0 0 * * * *".to_string()));
+
+        store
+            .execute(
+                deps.as_mut(),
+                mock_env(),
+                mock_info("bob", &[]),
+                ExecuteMsg::RemoveTask {
+                    task_hash: hourly,
+                    refund_to: None,
+                },
+            )
+            .unwrap();
+
+        let res = store
+            .query(deps.as_ref(), mock_env(), QueryMsg::GetTaskTypeBreakdown {})
+            .unwrap();
+        let breakdown: GetTaskTypeBreakdownResponse = from_binary(&res).unwrap();
+        assert_eq!(
+            breakdown,
+            GetTaskTypeBreakdownResponse {
+                once: 1,
+                immediate: 1,
+                block: 1,
+                cron: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn query_owner_task_by_index() {
+        let mut deps = mock_dependencies();
+        let mut store = CwCroncat::default();
+        mock_init(&store, deps.as_mut()).unwrap();
+
+        let mut create_task = |owner: &str, interval: Interval| -> String {
+            let task = TaskRequest {
+                interval,
+                boundary: None,
+                stop_on_fail: false,
+                actions: vec![Action {
+                    msg: StakingMsg::Delegate {
+                        validator: "you".to_string(),
+                        amount: coin(1, "atom"),
+                    }
+                    .into(),
+                    gas_limit: Some(150_000),
+                }],
+                rules: None,
+                metadata: None,
+                successor: None,
+                action_order: None,
+            };
+            let res = store
+                .execute(
+                    deps.as_mut(),
+                    mock_env(),
+                    mock_info(owner, &coins(300_016, "atom")),
+                    ExecuteMsg::CreateTask { task },
+                )
+                .unwrap();
+            res.attributes
+                .into_iter()
+                .find(|a| a.key == "task_hash")
+                .unwrap()
+                .value
+        };
+        let bob_hashes = vec![
+            create_task("bob", Interval::Block(7)),
+            create_task("bob", Interval::Block(3)),
+            create_task("bob", Interval::Block(5)),
+        ];
+        create_task("alice", Interval::Block(4));
+
+        let owner_task = |index: u64| -> Option<TaskResponse> {
+            let res = store
+                .query(
+                    deps.as_ref(),
+                    mock_env(),
+                    QueryMsg::GetOwnerTaskByIndex {
+                        owner_id: Addr::unchecked("bob"),
+                        index,
+                    },
+                )
+                .unwrap();
+            from_binary(&res).unwrap()
+        };
+        for (index, hash) in bob_hashes.iter().enumerate() {
+            let task = owner_task(index as u64).unwrap();
+            assert_eq!(&task.task_hash, hash);
+        }
+        assert_eq!(owner_task(3), None);
+
+        // Removed tasks drop out, the rest move up
+        store
+            .execute(
+                deps.as_mut(),
+                mock_env(),
+                mock_info("bob", &[]),
+                ExecuteMsg::RemoveTask {
+                    task_hash: bob_hashes[0].clone(),
+                    refund_to: None,
+                },
+            )
+            .unwrap();
+        let owner_task = |index: u64| -> Option<TaskResponse> {
+            store
+                .query_get_owner_task_by_index(deps.as_ref(), Addr::unchecked("bob"), index)
+                .unwrap()
+        };
+        assert_eq!(owner_task(0).unwrap().task_hash, bob_hashes[1]);
+        assert_eq!(owner_task(1).unwrap().task_hash, bob_hashes[2]);
+        assert_eq!(owner_task(2), None);
+    }
+
+    #[test]
+    fn create_task_past_owner_limit() {
+        let mut deps = mock_dependencies();
+        let mut store = CwCroncat::default();
+        mock_init(&store, deps.as_mut()).unwrap();
+        store
+            .config
+            .update(&mut deps.storage, |mut c| -> StdResult<_> {
+                c.max_tasks_per_owner = Some(2);
+                Ok(c)
+            })
+            .unwrap();
+
+        let mut create_task = |owner: &str, blocks: u64| {
+            let task = TaskRequest {
+                interval: Interval::Block(blocks),
+                boundary: None,
+                stop_on_fail: false,
+                actions: vec![Action {
+                    msg: StakingMsg::Delegate {
+                        validator: "you".to_string(),
+                        amount: coin(1, "atom"),
+                    }
+                    .into(),
+                    gas_limit: Some(150_000),
+                }],
+                rules: None,
+                metadata: None,
+                successor: None,
+                action_order: None,
+            };
+            store.execute(
+                deps.as_mut(),
+                mock_env(),
+                mock_info(owner, &coins(300_016, "atom")),
+                ExecuteMsg::CreateTask { task },
+            )
+        };
+        create_task("bob", 1).unwrap();
+        create_task("bob", 2).unwrap();
+
+        let err = create_task("bob", 3).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::CustomError {
+                val: "Too many tasks for owner, max allowed: 2".to_string()
+            }
+        );
+
+        // Other owners have their own allowance
+        create_task("alice", 3).unwrap();
+    }
+
+    #[test]
+    fn create_task_past_task_limit() {
+        let mut deps = mock_dependencies();
+        let mut store = CwCroncat::default();
+        mock_init(&store, deps.as_mut()).unwrap();
+        store
+            .config
+            .update(&mut deps.storage, |mut c| -> StdResult<_> {
+                c.max_task_limit = 2;
+                Ok(c)
+            })
+            .unwrap();
+
+        let create_task = |store: &mut CwCroncat, deps: DepsMut, owner: &str, blocks: u64| {
+            let task = TaskRequest {
+                interval: Interval::Block(blocks),
+                boundary: None,
+                stop_on_fail: false,
+                actions: vec![Action {
+                    msg: StakingMsg::Delegate {
+                        validator: "you".to_string(),
+                        amount: coin(1, "atom"),
+                    }
+                    .into(),
+                    gas_limit: Some(150_000),
+                }],
+                rules: None,
+                metadata: None,
+                successor: None,
+                action_order: None,
+            };
+            store.execute(
+                deps,
+                mock_env(),
+                mock_info(owner, &coins(300_016, "atom")),
+                ExecuteMsg::CreateTask { task },
+            )
+        };
+        let res = create_task(&mut store, deps.as_mut(), "bob", 1).unwrap();
+        let task_hash = res
+            .attributes
+            .iter()
+            .find(|attr| attr.key == "task_hash")
+            .unwrap()
+            .value
+            .clone();
+        create_task(&mut store, deps.as_mut(), "alice", 2).unwrap();
+
+        let err = create_task(&mut store, deps.as_mut(), "carol", 3).unwrap_err();
+        assert_eq!(err, ContractError::TaskLimitReached { max: 2 });
+
+        // Removing a task frees up room for another one
+        store
+            .execute(
+                deps.as_mut(),
+                mock_env(),
+                mock_info("bob", &[]),
+                ExecuteMsg::RemoveTask {
+                    task_hash,
+                    refund_to: None,
+                },
+            )
+            .unwrap();
+        create_task(&mut store, deps.as_mut(), "carol", 3).unwrap();
+        assert_eq!(store.task_total(&deps.storage).unwrap(), 2);
+    }
+
+    #[test]
+    fn create_task_gas_scales_with_actions() {
+        let mut deps = mock_dependencies();
+        let mut store = CwCroncat::default();
+        mock_init(&store, deps.as_mut()).unwrap();
+
+        let task = |num_actions: usize| TaskRequest {
+            interval: Interval::Immediate,
+            boundary: None,
+            stop_on_fail: false,
+            actions: vec![
+                Action {
+                    msg: StakingMsg::Delegate {
+                        validator: "you".to_string(),
+                        amount: coin(1, "atom"),
+                    }
+                    .into(),
+                    gas_limit: Some(150_000),
+                };
+                num_actions
+            ],
+            rules: None,
+            metadata: None,
+            successor: None,
+            action_order: None,
+        };
+        let simulate = |store: &CwCroncat, deps: Deps, task: TaskRequest| {
+            store
+                .query_simulate_create_task(deps, mock_env(), Addr::unchecked("bob"), task)
+                .unwrap()
+        };
+
+        // Every action adds its gas and a proxy callback to each run, and two runs are needed
+        let single = simulate(&store, deps.as_ref(), task(1));
+        let triple = simulate(&store, deps.as_ref(), task(3));
+        assert_eq!(single.gas_total, 150_000);
+        assert_eq!(triple.gas_total, 450_000);
+        assert_eq!(single.min_deposit, coin(300_016, "atom"));
+        assert_eq!(triple.min_deposit, coin(900_028, "atom"));
+
+        let err = store
+            .execute(
+                deps.as_mut(),
+                mock_env(),
+                mock_info("bob", &coins(300_016, "atom")),
+                ExecuteMsg::CreateTask { task: task(3) },
+            )
+            .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::InsufficientTaskBalance {
+                required: coin(900_028, "atom")
+            }
+        );
+
+        // Tasks over the gas ceiling are rejected whatever they attach
+        store
+            .config
+            .update(&mut deps.storage, |mut c| -> StdResult<_> {
+                c.max_task_gas = 300_000;
+                Ok(c)
+            })
+            .unwrap();
+        assert_eq!(
+            simulate(&store, deps.as_ref(), task(3)).errors,
+            vec!["Task gas exceeds max allowed: 300000".to_string()]
+        );
+        let err = store
+            .execute(
+                deps.as_mut(),
+                mock_env(),
+                mock_info("bob", &coins(900_028, "atom")),
+                ExecuteMsg::CreateTask { task: task(3) },
+            )
+            .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::CustomError {
+                val: "Task gas exceeds max allowed: 300000".to_string()
+            }
+        );
+        let res = store
+            .execute(
+                deps.as_mut(),
+                mock_env(),
+                mock_info("bob", &coins(300_016, "atom")),
+                ExecuteMsg::CreateTask { task: task(1) },
+            )
+            .unwrap();
+        let task_hash = res
+            .attributes
+            .into_iter()
+            .find(|a| a.key == "task_hash")
+            .unwrap()
+            .value;
+
+        // Updating can't push a task over the ceiling either
+        let err = store
+            .execute(
+                deps.as_mut(),
+                mock_env(),
+                mock_info("bob", &[]),
+                ExecuteMsg::UpdateTask {
+                    task_hash,
+                    actions: Some(task(3).actions),
+                    rules: None,
+                },
+            )
+            .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::CustomError {
+                val: "Task gas exceeds max allowed: 300000".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn create_task_below_min_task_balance() {
+        let mut deps = mock_dependencies();
+        let mut store = CwCroncat::default();
+        mock_init(&store, deps.as_mut()).unwrap();
+        store
+            .config
+            .update(&mut deps.storage, |mut c| -> StdResult<_> {
+                c.min_task_balance = coin(500_000, "atom");
+                Ok(c)
+            })
+            .unwrap();
+
+        let task = |gas_limit: u64| TaskRequest {
+            interval: Interval::Immediate,
+            boundary: None,
+            stop_on_fail: false,
+            actions: vec![Action {
+                msg: StakingMsg::Delegate {
+                    validator: "you".to_string(),
+                    amount: coin(1, "atom"),
+                }
+                .into(),
+                gas_limit: Some(gas_limit),
+            }],
+            rules: None,
+            metadata: None,
+            successor: None,
+            action_order: None,
+        };
+        let mut create_task = |task: TaskRequest, amount: u128| {
+            store.execute(
+                deps.as_mut(),
+                mock_env(),
+                mock_info("bob", &coins(amount, "atom")),
+                ExecuteMsg::CreateTask { task },
+            )
+        };
+
+        // Enough for the first runs, but under the configured minimum
+        let err = create_task(task(150_000), 300_016).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::InsufficientTaskBalance {
+                required: coin(500_000, "atom")
+            }
+        );
+        let task_hash = create_task(task(150_000), 500_000)
+            .unwrap()
+            .attributes
+            .into_iter()
+            .find(|a| a.key == "task_hash")
+            .unwrap()
+            .value;
+
+        // Tasks costing more than the minimum still need their first runs covered
+        let err = create_task(task(400_000), 500_000).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::InsufficientTaskBalance {
+                required: coin(800_016, "atom")
+            }
+        );
+        create_task(task(400_000), 800_016).unwrap();
+
+        // Updates are held to a raised minimum too
+        store
+            .config
+            .update(&mut deps.storage, |mut c| -> StdResult<_> {
+                c.min_task_balance = coin(600_000, "atom");
+                Ok(c)
+            })
+            .unwrap();
+        let err = store
+            .execute(
+                deps.as_mut(),
+                mock_env(),
+                mock_info("bob", &[]),
+                ExecuteMsg::UpdateTask {
+                    task_hash,
+                    actions: Some(task(160_000).actions),
+                    rules: None,
+                },
+            )
+            .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::InsufficientTaskBalance {
+                required: coin(600_000, "atom")
+            }
+        );
+    }
+
+    #[test]
+    fn create_task_reversed_boundary() {
+        let mut deps = mock_dependencies();
+        let mut store = CwCroncat::default();
+        mock_init(&store, deps.as_mut()).unwrap();
+
+        let task = |start: u64, end: u64| TaskRequest {
+            interval: Interval::Block(5),
+            boundary: Some(Boundary::Height {
+                start: Some(start.into()),
+                end: Some(end.into()),
+            }),
+            stop_on_fail: false,
+            actions: vec![Action {
+                msg: StakingMsg::Delegate {
+                    validator: "you".to_string(),
+                    amount: coin(1, "atom"),
+                }
+                .into(),
+                gas_limit: Some(150_000),
+            }],
+            rules: None,
+            metadata: None,
+            successor: None,
+            action_order: None,
+        };
+        let mut create_task = |task: TaskRequest| {
+            store.execute(
+                deps.as_mut(),
+                mock_env(),
+                mock_info("bob", &coins(300_016, "atom")),
+                ExecuteMsg::CreateTask { task },
+            )
+        };
+        let err = create_task(task(12500, 12400)).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::CustomError {
+                val: "Boundary start is after its end".to_string()
+            }
+        );
+        create_task(task(12400, 12500)).unwrap();
+    }
+
+    #[test]
+    fn task_events() {
+        let mut deps = mock_dependencies();
+        let mut store = CwCroncat::default();
+        mock_init(&store, deps.as_mut()).unwrap();
+
+        let task = TaskRequest {
+            interval: Interval::Block(5),
+            boundary: Some(Boundary::Height {
+                start: Some(12350u64.into()),
+                end: Some(20000u64.into()),
+            }),
+            stop_on_fail: false,
+            actions: vec![Action {
+                msg: StakingMsg::Delegate {
+                    validator: "you".to_string(),
+                    amount: coin(1, "atom"),
+                }
+                .into(),
+                gas_limit: Some(150_000),
+            }],
+            rules: None,
+            metadata: None,
+            successor: None,
+            action_order: None,
+        };
+        let res = store
+            .execute(
+                deps.as_mut(),
+                mock_env(),
+                mock_info("bob", &coins(300_016, "atom")),
+                ExecuteMsg::CreateTask { task },
+            )
+            .unwrap();
+        let event = res
+            .events
+            .iter()
+            .find(|event| event.ty == "croncat_task")
+            .unwrap();
+        let task_hash = event.attributes[1].value.clone();
+        assert_eq!(
+            event.attributes,
+            vec![
+                Attribute::new("action", "create_task"),
+                Attribute::new("task_hash", task_hash.clone()),
+                Attribute::new("owner_id", "bob"),
+                Attribute::new("interval", "Block(5)"),
+                Attribute::new("boundary_start", "12350"),
+                Attribute::new("boundary_end", "20000"),
+                Attribute::new("with_rules", "false"),
+            ]
+        );
+
+        let res = store
+            .execute(
+                deps.as_mut(),
+                mock_env(),
+                mock_info("bob", &[]),
+                ExecuteMsg::RemoveTask {
+                    task_hash: task_hash.clone(),
+                    refund_to: None,
+                },
+            )
+            .unwrap();
+        let event = res
+            .events
+            .iter()
+            .find(|event| event.ty == "croncat_task")
+            .unwrap();
+        assert_eq!(
+            event.attributes,
+            vec![
+                Attribute::new("action", "remove_task"),
+                Attribute::new("task_hash", task_hash),
+                Attribute::new("owner_id", "bob"),
+                Attribute::new("refund_amount", "300016atom"),
+            ]
+        );
+    }
+
+    #[test]
+    fn query_next_execution() -> StdResult<()> {
+        let mut deps = mock_dependencies();
+        let store = CwCroncat::default();
+        mock_init(&store, deps.as_mut())?;
+        let env = mock_env();
+        assert_eq!(
+            store.query_next_execution(deps.as_ref(), env.clone())?,
+            None
+        );
+
+        // Empty slots don't count, the nearest slot holding tasks wins
+        let height = env.block.height;
+        let now = env.block.time.nanos();
+        store
+            .block_slots
+            .save(&mut deps.storage, height + 3, &vec![])?;
+        store
+            .block_slots
+            .save(&mut deps.storage, height + 7, &vec![b"hash".to_vec()])?;
+        store
+            .block_slots
+            .save(&mut deps.storage, height + 20, &vec![b"hash".to_vec()])?;
+        store.time_slots.save(
+            &mut deps.storage,
+            now + 90_000_000_000,
+            &vec![b"hash".to_vec()],
+        )?;
+        store.time_slots.save(
+            &mut deps.storage,
+            now + 30_000_000_000,
+            &vec![b"hash".to_vec()],
+        )?;
+        assert_eq!(
+            store.query_next_execution(deps.as_ref(), env)?,
+            Some(GetNextExecutionResponse {
+                block_slot: Some(UpcomingSlot {
+                    slot_id: height + 7,
+                    due_in: 7,
+                }),
+                time_slot: Some(UpcomingSlot {
+                    slot_id: now + 30_000_000_000,
+                    due_in: 30,
+                }),
+            })
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn create_task_reports_nominated_agent() {
+        let mut deps = mock_dependencies();
+        let mut store = CwCroncat::default();
+        mock_init(&store, deps.as_mut()).unwrap();
+        store
+            .agent_active_queue
+            .save(&mut deps.storage, &vec![Addr::unchecked("agent1")])
+            .unwrap();
+        store
+            .agent_pending_queue
+            .save(
+                &mut deps.storage,
+                &vec![Addr::unchecked("agent2"), Addr::unchecked("agent3")],
+            )
+            .unwrap();
+
+        let mut create_task = |blocks: u64| -> Response {
+            let task = TaskRequest {
+                interval: Interval::Block(blocks),
+                boundary: None,
+                stop_on_fail: false,
+                actions: vec![Action {
+                    msg: StakingMsg::Delegate {
+                        validator: "you".to_string(),
+                        amount: coin(1, "atom"),
+                    }
+                    .into(),
+                    gas_limit: Some(150_000),
+                }],
+                rules: None,
+                metadata: None,
+                successor: None,
+                action_order: None,
+            };
+            store
+                .execute(
+                    deps.as_mut(),
+                    mock_env(),
+                    mock_info("bob", &coins(300_016, "atom")),
+                    ExecuteMsg::CreateTask { task },
+                )
+                .unwrap()
+        };
+        let has_attr = |res: &Response, key: &str| res.attributes.iter().any(|a| a.key == key);
+
+        // The active agent covers the first three tasks
+        for blocks in 1..=3 {
+            let res = create_task(blocks);
+            assert!(!has_attr(&res, "nominated_agent"));
+            assert!(!has_attr(&res, "agent_nomination_begin_time"));
+        }
+
+        // The fourth one needs another agent, the first pending agent is up
+        let res = create_task(4);
+        let now = mock_env().block.time;
+        let attrs: Vec<(String, String)> = res
+            .attributes
+            .into_iter()
+            .skip(4)
+            .map(|a| (a.key, a.value))
+            .collect();
+        assert_eq!(
+            attrs,
+            vec![
+                (
+                    "agent_nomination_begin_time".to_string(),
+                    now.nanos().to_string()
+                ),
+                ("nominated_agent".to_string(), "agent2".to_string()),
+                ("nominated_agent_position".to_string(), "0".to_string()),
+            ]
+        );
+
+        // More tasks within the same demand don't nominate anyone else
+        let res = create_task(5);
+        assert!(!has_attr(&res, "nominated_agent"));
+        assert!(!has_attr(&res, "agent_nomination_begin_time"));
+        assert_eq!(
+            store
+                .agent_nomination_begin_time
+                .load(&deps.storage)
+                .unwrap(),
+            Some(now)
+        );
+    }
+
+    #[test]
+    fn query_tasks_created_between() {
+        let mut deps = mock_dependencies();
+        let mut store = CwCroncat::default();
+        mock_init(&store, deps.as_mut()).unwrap();
+        let start = mock_env().block.time;
+
+        // One task every 100 seconds
+        let mut hashes = vec![];
+        for i in 0..4u64 {
+            let mut env = mock_env();
+            env.block.time = start.plus_seconds(i * 100);
+            let task = TaskRequest {
+                interval: Interval::Block(i + 1),
+                boundary: None,
+                stop_on_fail: false,
+                actions: vec![Action {
+                    msg: StakingMsg::Delegate {
+                        validator: "you".to_string(),
+                        amount: coin(1, "atom"),
+                    }
+                    .into(),
+                    gas_limit: Some(150_000),
+                }],
+                rules: None,
+                metadata: None,
+                successor: None,
+                action_order: None,
+            };
+            let res = store
+                .execute(
+                    deps.as_mut(),
+                    env,
+                    mock_info("bob", &coins(300_016, "atom")),
+                    ExecuteMsg::CreateTask { task },
+                )
+                .unwrap();
+            hashes.push(
+                res.attributes
+                    .into_iter()
+                    .find(|a| a.key == "task_hash")
+                    .unwrap()
+                    .value,
+            );
+        }
+
+        let created_between = |from_index: Option<u64>, limit: Option<u64>| -> Vec<String> {
+            let res = store
+                .query(
+                    deps.as_ref(),
+                    mock_env(),
+                    QueryMsg::GetTasksCreatedBetween {
+                        start: start.plus_seconds(100),
+                        end: start.plus_seconds(200),
+                        from_index,
+                        limit,
+                    },
+                )
+                .unwrap();
+            let tasks: Vec<TaskResponse> = from_binary(&res).unwrap();
+            tasks.into_iter().map(|t| t.task_hash).collect()
+        };
+
+        // Only the second and third task fall in the range, bounds included
+        let mut found = created_between(None, None);
+        found.sort();
+        let mut expected = vec![hashes[1].clone(), hashes[2].clone()];
+        expected.sort();
+        assert_eq!(found, expected);
+
+        // Pages split the same set
+        let mut pages = created_between(None, Some(1));
+        assert_eq!(pages.len(), 1);
+        pages.extend(created_between(Some(1), Some(1)));
+        pages.sort();
+        assert_eq!(pages, expected);
+        assert!(created_between(Some(2), None).is_empty());
+    }
+
+    #[test]
+    fn create_task_partially_funded_denoms() {
+        let mut deps = mock_dependencies();
+        let mut store = CwCroncat::default();
+        mock_init(&store, deps.as_mut()).unwrap();
+
+        // Two actions, each sending along a different denom
+        let send = |denom: &str| Action {
+            msg: CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: "alice".to_string(),
+                msg: to_binary("hello").unwrap(),
+                funds: coins(10, denom),
+            }),
+            gas_limit: Some(150_000),
+        };
+        let mut create_task = |funds: Vec<Coin>| {
+            let task = TaskRequest {
+                interval: Interval::Once,
+                boundary: Some(Boundary::Height {
+                    start: Some(mock_env().block.height.into()),
+                    end: None,
+                }),
+                stop_on_fail: false,
+                actions: vec![send("ujuno"), send("uosmo")],
+                rules: None,
+                metadata: None,
+                successor: None,
+                action_order: None,
+            };
+            store.execute(
+                deps.as_mut(),
+                mock_env(),
+                mock_info("bob", &funds),
+                ExecuteMsg::CreateTask { task },
+            )
+        };
+
+        let err = create_task(vec![coin(1_000_000, "atom"), coin(10, "ujuno")]).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::CustomError {
+                val: "Not enough task balance for actions in: uosmo (need 10, attached 0)"
+                    .to_string()
+            }
+        );
+
+        create_task(vec![
+            coin(1_000_000, "atom"),
+            coin(10, "ujuno"),
+            coin(10, "uosmo"),
+        ])
+        .unwrap();
+    }
+
+    #[test]
+    fn query_next_slot_rewards() {
+        let mut deps = mock_dependencies();
+        let mut store = CwCroncat::default();
+        mock_init(&store, deps.as_mut()).unwrap();
+        assert_eq!(
+            store
+                .query_next_slot_rewards(deps.as_ref(), mock_env())
+                .unwrap(),
+            None
+        );
+
+        // Two tasks land in the next block slot
+        let mut create_task = |validator: &str| {
+            let task = TaskRequest {
+                interval: Interval::Immediate,
+                boundary: None,
+                stop_on_fail: false,
+                actions: vec![Action {
+                    msg: StakingMsg::Delegate {
+                        validator: validator.to_string(),
+                        amount: coin(1, "atom"),
+                    }
+                    .into(),
+                    gas_limit: Some(150_000),
+                }],
+                rules: None,
+                metadata: None,
+                successor: None,
+                action_order: None,
+            };
+            store
+                .execute(
+                    deps.as_mut(),
+                    mock_env(),
+                    mock_info("bob", &coins(300_016, "atom")),
+                    ExecuteMsg::CreateTask { task },
+                )
+                .unwrap();
+        };
+        create_task("alice");
+        create_task("carol");
+
+        let res: Option<GetNextSlotRewardsResponse> = from_binary(
+            &store
+                .query(deps.as_ref(), mock_env(), QueryMsg::GetNextSlotRewards {})
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            res,
+            Some(GetNextSlotRewardsResponse {
+                slot_id: mock_env().block.height + 1,
+                slot_kind: SlotType::Block,
+                tasks: 2,
+                total_reward: coin(10, "atom"),
+            })
+        );
+    }
+
+    #[test]
+    fn query_ineligible_tasks() -> StdResult<()> {
+        let mut deps = mock_dependencies();
+        let mut store = CwCroncat::default();
+        mock_init(&store, deps.as_mut())?;
+        store
+            .config
+            .update(&mut deps.storage, |mut c| -> StdResult<_> {
+                c.max_submsgs_per_proxy_call = 2;
+                c.protocol_fee = coin(7, "atom");
+                Ok(c)
+            })?;
+        store
+            .agent_active_queue
+            .save(&mut deps.storage, &vec![Addr::unchecked("alice")])?;
+
+        let delegate = |validator: &str| Action {
+            msg: StakingMsg::Delegate {
+                validator: validator.to_string(),
+                amount: coin(1, "atom"),
+            }
+            .into(),
+            gas_limit: Some(150_000),
+        };
+        let task = |actions: Vec<Action>, rules: Option<Vec<Rule>>, deposit: u128| Task {
+            owner_id: Addr::unchecked("bob"),
+            interval: Interval::Immediate,
+            boundary: BoundaryValidated {
+                start: None,
+                end: None,
+                max_executions: None,
+            },
+            stop_on_fail: false,
+            total_deposit: coins(deposit, "atom"),
+            actions,
+            rules,
+            metadata: None,
+            successor: None,
+            generation: 0,
+            action_order: ActionOrder::Sequential,
+            total_cw20_deposit: vec![],
+        };
+        let eligible = task(vec![delegate("a")], None, 300_016);
+        let too_big = task(
+            vec![delegate("a"), delegate("b"), delegate("c")],
+            None,
+            900_000,
+        );
+        // The mock querier has no contracts, so the rule query fails
+        let ruled = task(
+            vec![delegate("a")],
+            Some(vec![Rule::Query {
+                contract_addr: Addr::unchecked("rules"),
+                msg: to_binary("ready")?,
+            }]),
+            300_016,
+        );
+        // Short of the protocol fee along with the agent fee
+        let underfunded = task(vec![delegate("b")], None, 10);
+        // Covers the fees, but not the funds the action sends along
+        let send = Action {
+            msg: BankMsg::Send {
+                to_address: "alice".to_string(),
+                amount: coins(6, "atom"),
+            }
+            .into(),
+            gas_limit: Some(150_000),
+        };
+        let unfunded_action = task(vec![send], None, 12);
+
+        let height = mock_env().block.height;
+        let mut hashes = vec![];
+        for t in [&eligible, &too_big, &ruled, &underfunded, &unfunded_action] {
+            store.tasks.save(&mut deps.storage, t.to_hash_vec(), t)?;
+            hashes.push(t.to_hash_vec());
+        }
+        store.block_slots.save(&mut deps.storage, height, &hashes)?;
+
+        let mut ineligible = |agent_id: &str, limit: Option<u64>| -> Vec<IneligibleTask> {
+            let res = store
+                .query(
+                    deps.as_ref(),
+                    mock_env(),
+                    QueryMsg::GetIneligibleTasks {
+                        agent_id: Addr::unchecked(agent_id),
+                        limit,
+                    },
+                )
+                .unwrap();
+            from_binary(&res).unwrap()
+        };
+        let skipped = |t: &Task, reason: &str| IneligibleTask {
+            task_hash: t.to_hash(),
+            reason: reason.to_string(),
+        };
+        assert_eq!(
+            ineligible("alice", None),
+            vec![
+                skipped(&too_big, "max_submsgs_per_proxy_call"),
+                skipped(&ruled, "rule_not_satisfied"),
+                skipped(&underfunded, "insufficient_task_balance"),
+                skipped(&unfunded_action, "insufficient_task_balance"),
+            ]
+        );
+
+        // Nothing runs for an agent that isn't active
+        assert_eq!(
+            ineligible("carol", Some(2)),
+            vec![
+                skipped(&eligible, "agent_not_active"),
+                skipped(&too_big, "agent_not_active"),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn query_check_task_rules() -> StdResult<()> {
+        let mut deps = mock_dependencies();
+        let mut store = CwCroncat::default();
+        mock_init(&store, deps.as_mut())?;
+        deps.querier.update_wasm(|query| match query {
+            WasmQuery::Smart { contract_addr, .. } if contract_addr == "yes" => SystemResult::Ok(
+                ContractResult::Ok(to_binary(&(true, None::<Binary>)).unwrap()),
+            ),
+            WasmQuery::Smart { contract_addr, .. } if contract_addr == "no" => SystemResult::Ok(
+                ContractResult::Ok(to_binary(&(false, None::<Binary>)).unwrap()),
+            ),
+            _ => SystemResult::Err(SystemError::NoSuchContract {
+                addr: "gone".to_string(),
+            }),
+        });
+
+        let rule = |contract_addr: &str| Rule::Query {
+            contract_addr: Addr::unchecked(contract_addr),
+            msg: to_binary("ready").unwrap(),
+        };
+        let task = Task {
+            owner_id: Addr::unchecked("bob"),
+            interval: Interval::Immediate,
+            boundary: BoundaryValidated {
+                start: None,
+                end: None,
+                max_executions: None,
+            },
+            stop_on_fail: false,
+            total_deposit: coins(300_016, "atom"),
+            actions: vec![Action {
+                msg: StakingMsg::Delegate {
+                    validator: "a".to_string(),
+                    amount: coin(1, "atom"),
+                }
+                .into(),
+                gas_limit: Some(150_000),
+            }],
+            rules: Some(vec![rule("yes"), rule("no"), rule("gone")]),
+            metadata: None,
+            successor: None,
+            generation: 0,
+            action_order: ActionOrder::Sequential,
+            total_cw20_deposit: vec![],
+        };
+        store
+            .tasks
+            .save(&mut deps.storage, task.to_hash_vec(), &task)?;
+
+        let mut check_rules = |task_hash: String| -> Option<CheckTaskRulesResponse> {
+            let res = store
+                .query(
+                    deps.as_ref(),
+                    mock_env(),
+                    QueryMsg::CheckTaskRules { task_hash },
+                )
+                .unwrap();
+            from_binary(&res).unwrap()
+        };
+        let check = |contract_addr: &str, reason: Option<&str>| RuleCheck {
+            contract_addr: Some(Addr::unchecked(contract_addr)),
+            pass: reason.is_none(),
+            reason: reason.map(str::to_string),
+        };
+        assert_eq!(
+            check_rules(task.to_hash()),
+            Some(CheckTaskRulesResponse {
+                rules: vec![
+                    check("yes", None),
+                    check("no", Some("rule_not_satisfied")),
+                    check("gone", Some("rule_query_failed")),
+                ],
+                pass: false,
+            })
+        );
+        assert_eq!(check_rules("missing".to_string()), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn query_execution_cost_breakdown() -> StdResult<()> {
+        let mut deps = mock_dependencies();
+        let mut store = CwCroncat::default();
+        mock_init(&store, deps.as_mut())?;
+        let c = store
+            .config
+            .update(&mut deps.storage, |mut c| -> StdResult<_> {
+                c.protocol_fee = coin(7, "atom");
+                c.gas_price = 2;
+                Ok(c)
+            })?;
+
+        let task = Task {
+            owner_id: Addr::unchecked("bob"),
+            interval: Interval::Immediate,
+            boundary: BoundaryValidated {
+                start: None,
+                end: None,
+                max_executions: None,
+            },
+            stop_on_fail: false,
+            total_deposit: coins(1_000_000, "atom"),
+            actions: vec![
+                Action {
+                    msg: CosmosMsg::Wasm(WasmMsg::Execute {
+                        contract_addr: "alice".to_string(),
+                        msg: to_binary("ping")?,
+                        funds: vec![coin(10, "atom"), coin(20, "earth")],
+                    }),
+                    gas_limit: Some(150_000),
+                },
+                Action {
+                    msg: StakingMsg::Delegate {
+                        validator: "a".to_string(),
+                        amount: coin(1, "atom"),
+                    }
+                    .into(),
+                    gas_limit: None,
+                },
+            ],
+            rules: None,
+            metadata: None,
+            successor: None,
+            generation: 0,
+            action_order: ActionOrder::Sequential,
+            total_cw20_deposit: vec![],
+        };
+        store
+            .tasks
+            .save(&mut deps.storage, task.to_hash_vec(), &task)?;
+
+        let res = store.query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::GetExecutionCostBreakdown {
+                task_hash: task.to_hash(),
+            },
+        )?;
+        let breakdown: GetExecutionCostBreakdownResponse = from_binary::<Option<_>>(&res)?.unwrap();
+        assert_eq!(
+            breakdown.gas_cost,
+            coin(u128::from(150_000 + c.gas_base_fee) * 2, "atom")
+        );
+        assert_eq!(breakdown.agent_fee, coin(5, "atom"));
+        assert_eq!(breakdown.proxy_callback_cost, coin(12, "atom"));
+        assert_eq!(breakdown.protocol_fee, coin(7, "atom"));
+        assert_eq!(
+            breakdown.action_funds,
+            vec![coin(11, "atom"), coin(20, "earth")]
+        );
+
+        // A run only takes the protocol fee and the funds actions send along from the task
+        assert_eq!(breakdown.total, vec![coin(18, "atom"), coin(20, "earth")]);
+
+        let res = store.query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::GetExecutionCostBreakdown {
+                task_hash: "missing".to_string(),
+            },
+        )?;
+        assert_eq!(
+            from_binary::<Option<GetExecutionCostBreakdownResponse>>(&res)?,
+            None
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn update_task() {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+
+        let delegate = |amount: u128| -> Action {
+            Action {
+                msg: StakingMsg::Delegate {
+                    validator: "you".to_string(),
+                    amount: coin(amount, NATIVE_DENOM),
+                }
+                .into(),
+                gas_limit: Some(150_000),
+            }
+        };
+        let res = app
+            .execute_contract(
+                Addr::unchecked(ANYONE),
+                contract_addr.clone(),
+                &ExecuteMsg::CreateTask {
+                    task: TaskRequest {
+                        interval: Interval::Immediate,
+                        boundary: None,
+                        stop_on_fail: false,
+                        actions: vec![delegate(3)],
+                        rules: None,
+                        metadata: None,
+                        successor: None,
+                        action_order: None,
+                    },
+                },
+                &coins(300016, NATIVE_DENOM),
+            )
+            .unwrap();
+        let task_hash = res
+            .events
+            .iter()
+            .flat_map(|e| e.attributes.iter())
+            .find(|a| a.key == "task_hash")
+            .unwrap()
+            .value
+            .clone();
+
+        // Only the owner can edit
+        let err = app
+            .execute_contract(
+                Addr::unchecked(ADMIN),
+                contract_addr.clone(),
+                &ExecuteMsg::UpdateTask {
+                    task_hash: task_hash.clone(),
+                    actions: Some(vec![delegate(5)]),
+                    rules: None,
+                },
+                &[],
+            )
+            .unwrap_err();
+        assert_eq!(
+            ContractError::CustomError {
+                val: "Only owner can update their task".to_string()
+            },
+            err.downcast().unwrap()
+        );
+
+        // Edits still have to pass the message checks
+        let err = app
+            .execute_contract(
+                Addr::unchecked(ANYONE),
+                contract_addr.clone(),
+                &ExecuteMsg::UpdateTask {
+                    task_hash: task_hash.clone(),
+                    actions: Some(vec![Action {
+                        msg: BankMsg::Send {
+                            to_address: ANYONE.to_string(),
+                            amount: coins(1, NATIVE_DENOM),
+                        }
+                        .into(),
+                        gas_limit: None,
+                    }]),
+                    rules: None,
+                },
+                &[],
+            )
+            .unwrap_err();
+        assert_eq!(
+            ContractError::CustomError {
+                val: "Actions Message Unsupported".to_string()
+            },
+            err.downcast().unwrap()
+        );
+
+        // and the deposit has to cover other denoms the new actions send along
+        let err = app
+            .execute_contract(
+                Addr::unchecked(ANYONE),
+                contract_addr.clone(),
+                &ExecuteMsg::UpdateTask {
+                    task_hash: task_hash.clone(),
+                    actions: Some(vec![Action {
+                        msg: CosmosMsg::Wasm(WasmMsg::Execute {
+                            contract_addr: "alice".to_string(),
+                            msg: to_binary("hello").unwrap(),
+                            funds: coins(10, "ujuno"),
+                        }),
+                        gas_limit: Some(150_000),
+                    }]),
+                    rules: None,
+                },
+                &[],
+            )
+            .unwrap_err();
+        assert_eq!(
+            ContractError::CustomError {
+                val: "Not enough task balance for actions in: ujuno (need 10, attached 0)"
+                    .to_string()
+            },
+            err.downcast().unwrap()
+        );
+
+        let res = app
+            .execute_contract(
+                Addr::unchecked(ANYONE),
+                contract_addr.clone(),
+                &ExecuteMsg::UpdateTask {
+                    task_hash: task_hash.clone(),
+                    actions: Some(vec![delegate(5)]),
+                    rules: None,
+                },
+                &[],
+            )
+            .unwrap();
+        let new_hash = res
+            .events
+            .iter()
+            .flat_map(|e| e.attributes.iter())
+            .find(|a| a.key == "task_hash")
+            .unwrap()
+            .value
+            .clone();
+        assert_ne!(new_hash, task_hash);
+
+        // The task moved to the new hash with its balance and slot
+        let old_task: Option<TaskResponse> = app
+            .wrap()
+            .query_wasm_smart(
+                &contract_addr,
+                &QueryMsg::GetTask {
+                    task_hash: task_hash.clone(),
+                },
+            )
+            .unwrap();
+        assert!(old_task.is_none());
+        let new_task: TaskResponse = app
+            .wrap()
+            .query_wasm_smart::<Option<TaskResponse>>(
+                &contract_addr,
+                &QueryMsg::GetTask {
+                    task_hash: new_hash.clone(),
+                },
+            )
+            .unwrap()
+            .unwrap();
+        assert_eq!(new_task.actions, vec![delegate(5)]);
+        assert_eq!(new_task.total_deposit, coins(300016, NATIVE_DENOM));
+        let slots: GetSlotHashesResponse = app
+            .wrap()
+            .query_wasm_smart(
+                &contract_addr,
+                &QueryMsg::GetSlotHashes {
+                    slot: None,
+                    limit: None,
+                },
+            )
+            .unwrap();
+        assert_eq!(slots.block_task_hash, vec![new_hash.clone()]);
+        let history: Vec<FundingEvent> = app
+            .wrap()
+            .query_wasm_smart(
+                &contract_addr,
+                &QueryMsg::GetTaskFundingHistory {
+                    task_hash: new_hash,
+                },
+            )
+            .unwrap();
+        assert_eq!(history.len(), 1);
+    }
+
+    #[test]
+    fn query_get_tasks_by_owner_pagination() {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+
+        let new_msg = |amount| ExecuteMsg::CreateTask {
+            task: TaskRequest {
+                interval: Interval::Immediate,
+                boundary: None,
+                stop_on_fail: false,
+                actions: vec![Action {
+                    msg: StakingMsg::Delegate {
+                        validator: "you".to_string(),
+                        amount: coin(amount, "atom"),
+                    }
+                    .into(),
+                    gas_limit: Some(150_000),
+                }],
+                rules: None,
+                metadata: None,
+                successor: None,
+                action_order: None,
+            },
+        };
+        for amount in 1..=5 {
+            app.execute_contract(
+                Addr::unchecked(VERY_RICH),
+                contract_addr.clone(),
+                &new_msg(amount),
+                &coins(300016, "atom"),
+            )
+            .unwrap();
+        }
+        // Someone else's task never shows up
+        app.execute_contract(
+            Addr::unchecked(ANYONE),
+            contract_addr.clone(),
+            &new_msg(6),
+            &coins(300016, "atom"),
+        )
+        .unwrap();
+
+        let get_tasks = |from_index: Option<u64>, limit: Option<u64>| -> Vec<String> {
+            app.wrap()
+                .query_wasm_smart::<Vec<TaskResponse>>(
+                    &contract_addr,
+                    &QueryMsg::GetTasksByOwner {
+                        owner_id: Addr::unchecked(VERY_RICH),
+                        from_index,
+                        limit,
+                    },
+                )
+                .unwrap()
+                .into_iter()
+                .map(|t| t.task_hash)
+                .collect()
+        };
+
+        let all_tasks = get_tasks(None, None);
+        assert_eq!(all_tasks.len(), 5);
+        assert_eq!(get_tasks(Some(2), None), all_tasks[2..].to_vec());
+        assert_eq!(get_tasks(Some(1), Some(2)), all_tasks[1..3].to_vec());
+
+        // Asking for more than there is returns what's left
+        assert_eq!(get_tasks(Some(3), Some(u64::MAX)), all_tasks[3..].to_vec());
+        assert!(get_tasks(Some(5), None).is_empty());
+    }
+
+    #[test]
+    fn query_task_count() {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+
+        let new_msg = |amount| ExecuteMsg::CreateTask {
+            task: TaskRequest {
+                interval: Interval::Immediate,
+                boundary: None,
+                stop_on_fail: false,
+                actions: vec![Action {
+                    msg: StakingMsg::Delegate {
+                        validator: "you".to_string(),
+                        amount: coin(amount, "atom"),
+                    }
+                    .into(),
+                    gas_limit: Some(150_000),
+                }],
+                rules: None,
+                metadata: None,
+                successor: None,
+                action_order: None,
+            },
+        };
+        let mut task_hashes = vec![];
+        for amount in 1..=3 {
+            let res = app
+                .execute_contract(
+                    Addr::unchecked(ANYONE),
+                    contract_addr.clone(),
+                    &new_msg(amount),
+                    &coins(300016, "atom"),
+                )
+                .unwrap();
+            task_hashes.push(
+                res.events
+                    .iter()
+                    .flat_map(|e| e.attributes.iter())
+                    .find(|a| a.key == "task_hash")
+                    .map(|a| a.value.clone())
+                    .unwrap(),
+            );
+        }
+        let get_count = |app: &App| -> u64 {
+            app.wrap()
+                .query_wasm_smart::<GetTaskCountResponse>(
+                    &contract_addr,
+                    &QueryMsg::GetTaskCount {},
+                )
+                .unwrap()
+                .total
+        };
+        assert_eq!(get_count(&app), 3);
+
+        app.execute_contract(
+            Addr::unchecked(ANYONE),
+            contract_addr.clone(),
+            &ExecuteMsg::RemoveTask {
+                task_hash: task_hashes.remove(0),
+                refund_to: None,
+            },
+            &[],
+        )
+        .unwrap();
+        assert_eq!(get_count(&app), 2);
+    }
+
+    #[test]
+    fn refill_task_cw20() {
+        let mut deps = mock_dependencies();
+        let mut store = CwCroncat::default();
+        mock_init(&store, deps.as_mut()).unwrap();
+        let cw20 = Addr::unchecked("cw20");
+        store
+            .config
+            .update(&mut deps.storage, |mut c| -> StdResult<_> {
+                c.cw20_whitelist = vec![cw20.clone()];
+                Ok(c)
+            })
+            .unwrap();
+
+        let res = store
+            .execute(
+                deps.as_mut(),
+                mock_env(),
+                mock_info("owner", &coins(300_016, "atom")),
+                ExecuteMsg::CreateTask {
+                    task: TaskRequest {
+                        interval: Interval::Immediate,
+                        boundary: None,
+                        stop_on_fail: false,
+                        actions: vec![Action {
+                            msg: StakingMsg::Delegate {
+                                validator: "you".to_string(),
+                                amount: coin(3, "atom"),
+                            }
+                            .into(),
+                            gas_limit: Some(150_000),
+                        }],
+                        rules: None,
+                        metadata: None,
+                        successor: None,
+                        action_order: None,
+                    },
+                },
+            )
+            .unwrap();
+        let task_hash = res
+            .attributes
+            .iter()
+            .find(|a| a.key == "task_hash")
+            .unwrap()
+            .value
+            .clone();
+
+        let mut refill = |token: &str, sender: &str, amount: u128| {
+            store.execute(
+                deps.as_mut(),
+                mock_env(),
+                mock_info(token, &[]),
+                ExecuteMsg::Receive(Cw20ReceiveMsg {
+                    sender: sender.to_string(),
+                    amount: Uint128::new(amount),
+                    msg: to_binary(&ReceiveMsg::RefillTaskBalance {
+                        task_hash: task_hash.clone(),
+                    })
+                    .unwrap(),
+                }),
+            )
+        };
+
+        // Only whitelisted tokens, and only from the owner
+        assert_eq!(
+            refill("shrute_bucks", "owner", 10).unwrap_err(),
+            ContractError::NotInWhitelist {}
+        );
+        assert_eq!(
+            refill("cw20", "bob", 10).unwrap_err(),
+            ContractError::CustomError {
+                val: "Only owner can refill their task".to_string()
+            }
+        );
+
+        // Refills of the same token add up
+        refill("cw20", "owner", 10).unwrap();
+        refill("cw20", "owner", 5).unwrap();
+        let task = store
+            .tasks
+            .load(&deps.storage, task_hash.clone().into_bytes())
+            .unwrap();
+        let deposit = vec![Cw20CoinVerified {
+            address: cw20.clone(),
+            amount: Uint128::new(15),
+        }];
+        assert_eq!(task.total_cw20_deposit, deposit);
+        let c = store.config.load(&deps.storage).unwrap();
+        assert_eq!(c.available_balance.cw20, deposit);
+
+        // Removing the task refunds the cw20 deposit too
+        let res = store
+            .execute(
+                deps.as_mut(),
+                mock_env(),
+                mock_info("owner", &[]),
+                ExecuteMsg::RemoveTask {
+                    task_hash,
+                    refund_to: None,
+                },
+            )
+            .unwrap();
+        assert!(res.messages.contains(&SubMsg::new(WasmMsg::Execute {
+            contract_addr: cw20.to_string(),
+            msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                recipient: "owner".to_string(),
+                amount: Uint128::new(15),
+            })
+            .unwrap(),
+            funds: vec![],
+        })));
+        let c = store.config.load(&deps.storage).unwrap();
+        assert!(c.available_balance.cw20.iter().all(|t| t.amount.is_zero()));
+    }
+
+    #[test]
+    fn simulate_create_task() {
+        let mut deps = mock_dependencies();
+        let mut store = CwCroncat::default();
+        mock_init(&store, deps.as_mut()).unwrap();
+
+        let mut task = TaskRequest {
+            interval: Interval::Immediate,
+            boundary: None,
+            stop_on_fail: false,
+            actions: vec![Action {
+                msg: StakingMsg::Delegate {
+                    validator: "you".to_string(),
+                    amount: coin(1, "atom"),
+                }
+                .into(),
+                gas_limit: Some(150_000),
+            }],
+            rules: None,
+            metadata: None,
+            successor: None,
+            action_order: None,
+        };
+        let mut simulate = |task: TaskRequest| -> SimulateCreateTaskResponse {
+            let res = store
+                .query(
+                    deps.as_ref(),
+                    mock_env(),
+                    QueryMsg::SimulateCreateTask {
+                        owner_id: Addr::unchecked("bob"),
+                        task,
+                    },
+                )
+                .unwrap();
+            from_binary(&res).unwrap()
+        };
+        let res = simulate(task.clone());
+        assert!(res.errors.is_empty());
+        assert_eq!(res.gas_total, 150_000);
+        assert_eq!(res.min_deposit, coin(300_016, "atom"));
+        let task_hash = res.task_hash.unwrap();
+
+        // The previewed hash is the one the task gets on creation
+        let res = store
+            .execute(
+                deps.as_mut(),
+                mock_env(),
+                mock_info("bob", &coins(300_016, "atom")),
+                ExecuteMsg::CreateTask { task: task.clone() },
+            )
+            .unwrap();
+        assert!(res
+            .attributes
+            .contains(&Attribute::new("task_hash", task_hash)));
+
+        task.interval = Interval::Block(0);
+        let res = store
+            .query_simulate_create_task(deps.as_ref(), mock_env(), Addr::unchecked("bob"), task)
+            .unwrap();
+        assert_eq!(res.errors, vec!["Interval invalid".to_string()]);
+        assert!(res.task_hash.is_some());
+    }
 }
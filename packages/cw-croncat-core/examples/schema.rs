@@ -3,7 +3,7 @@ use std::fs::create_dir_all;
 
 use cosmwasm_schema::{export_schema, export_schema_with_title, remove_schemas, schema_for};
 use cw_croncat_core::{
-    msg::{Croncat, ExecuteMsg, InstantiateMsg, QueryMsg, TaskResponse},
+    msg::{Croncat, ExecuteMsg, InstantiateMsg, QueryMsg, TaskResponse, ValidateIntervalResponse},
     types::AgentResponse,
 };
 
@@ -19,7 +19,7 @@ fn main() {
     export_schema(&schema_for!(ExecuteMsg), &out_dir);
     export_schema(&schema_for!(QueryMsg), &out_dir);
     export_schema(&schema_for!(Croncat), &out_dir);
-    export_schema_with_title(&schema_for!(bool), &out_dir, "ValidateIntervalResponse");
+    export_schema(&schema_for!(ValidateIntervalResponse), &out_dir);
     export_schema_with_title(
         &schema_for!(Option<AgentResponse>),
         &out_dir,
@@ -1,9 +1,10 @@
 use crate::types::{
-    Action, AgentResponse, Boundary, BoundaryValidated, GenericBalance, Interval, Rule, Task,
+    Action, ActionOrder, AgentResponse, Boundary, BoundaryValidated, GenericBalance, Interval,
+    Rule, Task,
 };
-use crate::types::{Agent, SlotType};
-use cosmwasm_std::{Addr, Coin, Timestamp, Uint64};
-use cw20::Balance;
+use crate::types::{Agent, AgentStatus, FundingEvent, SlashDestination, SlotType};
+use cosmwasm_std::{Addr, Binary, Coin, Timestamp, Uint128, Uint64};
+use cw20::{Balance, Cw20CoinVerified, Cw20ReceiveMsg};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
@@ -36,19 +37,86 @@ pub struct Croncat {
     agent: Option<Agent>,
     task: Option<Task>,
     config_response: Option<GetConfigResponse>,
+    get_paused_response: Option<GetPausedResponse>,
     balance_response: Option<GetBalancesResponse>,
+    get_balance_reconciliation_response: Option<GetBalanceReconciliationResponse>,
     get_agent_ids_response: Option<GetAgentIdsResponse>,
     get_agent_tasks_response: Option<AgentTaskResponse>,
     task_request: Option<TaskRequest>,
     task_response: Option<TaskResponse>,
-    validate_interval_response: Option<bool>,
+    validate_interval_response: Option<ValidateIntervalResponse>,
+    check_task_funding_response: Option<CheckTaskFundingResponse>,
+    simulate_create_task_response: Option<SimulateCreateTaskResponse>,
+    check_task_rules_response: Option<Option<CheckTaskRulesResponse>>,
     get_agent_response: Option<Option<AgentResponse>>,
+    get_agent_status_response: Option<AgentStatus>,
+    get_agents_response: Option<Vec<AgentRecord>>,
     get_tasks_response: Option<Vec<TaskResponse>>,
+    get_expiring_tasks_response: Option<Vec<TaskResponse>>,
+    get_underfunded_tasks_response: Option<Vec<TaskResponse>>,
+    get_tasks_created_between_response: Option<Vec<TaskResponse>>,
     get_tasks_by_owner_response: Option<Vec<TaskResponse>>,
     get_task_response: Option<Option<TaskResponse>>,
+    get_owner_task_by_index_response: Option<Option<TaskResponse>>,
     get_task_hash_response: Option<String>,
+    get_task_funding_history_response: Option<Vec<FundingEvent>>,
+    get_execution_cost_breakdown_response: Option<Option<GetExecutionCostBreakdownResponse>>,
     get_slot_hashes_response: Option<GetSlotHashesResponse>,
     get_slot_ids_response: Option<GetSlotIdsResponse>,
+    get_slot_counts_response: Option<GetSlotCountsResponse>,
+    get_agent_next_slot_response: Option<Option<AgentNextSlotResponse>>,
+    get_agent_projected_earnings_response: Option<Option<AgentProjectedEarningsResponse>>,
+    get_agent_churn_response: Option<GetAgentChurnResponse>,
+    get_ineligible_tasks_response: Option<Vec<IneligibleTask>>,
+    get_oldest_overdue_slot_response: Option<Option<GetOldestOverdueSlotResponse>>,
+    get_task_type_breakdown_response: Option<GetTaskTypeBreakdownResponse>,
+    get_task_count_response: Option<GetTaskCountResponse>,
+    get_next_execution_response: Option<Option<GetNextExecutionResponse>>,
+    get_next_slot_rewards_response: Option<Option<GetNextSlotRewardsResponse>>,
+}
+
+impl QueryMsg {
+    /// Name of the `Croncat` schema field holding the response of this query
+    /// The match is exhaustive on purpose, a new query doesn't compile until it's mapped here
+    pub fn croncat_response_field(&self) -> &'static str {
+        match self {
+            QueryMsg::GetConfig {} => "ConfigResponse",
+            QueryMsg::GetPaused {} => "GetPausedResponse",
+            QueryMsg::GetBalances {} => "BalanceResponse",
+            QueryMsg::GetBalanceReconciliation {} => "GetBalanceReconciliationResponse",
+            QueryMsg::GetAgent { .. } => "GetAgentResponse",
+            QueryMsg::GetAgentStatus { .. } => "GetAgentStatusResponse",
+            QueryMsg::GetAgentIds {} => "GetAgentIdsResponse",
+            QueryMsg::GetAgents { .. } => "GetAgentsResponse",
+            QueryMsg::GetAgentTasks { .. } => "GetAgentTasksResponse",
+            QueryMsg::GetAgentNextSlot { .. } => "GetAgentNextSlotResponse",
+            QueryMsg::GetAgentProjectedEarnings { .. } => "GetAgentProjectedEarningsResponse",
+            QueryMsg::GetAgentChurn {} => "GetAgentChurnResponse",
+            QueryMsg::GetIneligibleTasks { .. } => "GetIneligibleTasksResponse",
+            QueryMsg::GetTasks { .. } => "GetTasksResponse",
+            QueryMsg::GetExpiringTasks { .. } => "GetExpiringTasksResponse",
+            QueryMsg::GetUnderfundedTasks { .. } => "GetUnderfundedTasksResponse",
+            QueryMsg::GetTasksCreatedBetween { .. } => "GetTasksCreatedBetweenResponse",
+            QueryMsg::GetTasksByOwner { .. } => "GetTasksByOwnerResponse",
+            QueryMsg::GetTask { .. } => "GetTaskResponse",
+            QueryMsg::GetOwnerTaskByIndex { .. } => "GetOwnerTaskByIndexResponse",
+            QueryMsg::GetTaskHash { .. } => "GetTaskHashResponse",
+            QueryMsg::GetTaskFundingHistory { .. } => "GetTaskFundingHistoryResponse",
+            QueryMsg::GetExecutionCostBreakdown { .. } => "GetExecutionCostBreakdownResponse",
+            QueryMsg::CheckTaskFunding { .. } => "CheckTaskFundingResponse",
+            QueryMsg::SimulateCreateTask { .. } => "SimulateCreateTaskResponse",
+            QueryMsg::CheckTaskRules { .. } => "CheckTaskRulesResponse",
+            QueryMsg::ValidateInterval { .. } => "ValidateIntervalResponse",
+            QueryMsg::GetSlotHashes { .. } => "GetSlotHashesResponse",
+            QueryMsg::GetSlotIds {} => "GetSlotIdsResponse",
+            QueryMsg::GetSlotCounts { .. } => "GetSlotCountsResponse",
+            QueryMsg::GetOldestOverdueSlot {} => "GetOldestOverdueSlotResponse",
+            QueryMsg::GetTaskTypeBreakdown {} => "GetTaskTypeBreakdownResponse",
+            QueryMsg::GetTaskCount {} => "GetTaskCountResponse",
+            QueryMsg::GetNextExecution {} => "GetNextExecutionResponse",
+            QueryMsg::GetNextSlotRewards {} => "GetNextSlotRewardsResponse",
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
@@ -64,7 +132,6 @@ pub struct InstantiateMsg {
 #[serde(rename_all = "snake_case")]
 pub enum ExecuteMsg {
     UpdateSettings {
-        owner_id: Option<Addr>,
         slot_granularity: Option<u64>,
         paused: Option<bool>,
         agent_fee: Option<Coin>,
@@ -72,12 +139,67 @@ pub enum ExecuteMsg {
         proxy_callback_gas: Option<u32>,
         min_tasks_per_agent: Option<u64>,
         agents_eject_threshold: Option<u64>,
-        // treasury_id: Option<Addr>,
+        dead_task_bounty: Option<Coin>,
+        max_rules_per_task: Option<u64>,
+        slash_destination: Option<SlashDestination>,
+        low_balance_pause_threshold: Option<Uint128>,
+        min_active_agents_for_execution: Option<u64>,
+        max_submsgs_per_proxy_call: Option<u64>,
+        protocol_fee: Option<Coin>,
+        /// Only allowed while no balances in the current native denom are held
+        native_denom: Option<String>,
+        /// Zero amount removes the fee
+        task_creation_fee: Option<Coin>,
+        nomination_paused: Option<bool>,
+        reward_vesting_seconds: Option<u64>,
+        max_future_slot_seconds: Option<u64>,
+        randomized_agent_selection: Option<bool>,
+        /// Zero removes the limit
+        max_tasks_per_owner: Option<u64>,
+        dust_threshold: Option<Uint128>,
+        /// Empty address removes the swap contract
+        dust_swap_contract: Option<Addr>,
+        min_active_agents: Option<u64>,
+        /// Empty string accepts agents of any version
+        min_agent_version: Option<String>,
+        removal_grace_slots: Option<u64>,
+        eject_to_pending: Option<bool>,
+        max_task_limit: Option<u64>,
+        agent_allowlist_enabled: Option<bool>,
+        max_task_gas: Option<u64>,
+        min_task_balance: Option<Coin>,
+        /// Empty address removes the treasury
+        treasury_id: Option<Addr>,
     },
     MoveBalances {
         balances: Vec<Balance>,
         account_id: Addr,
     },
+    StakeBalances {
+        balances: Vec<Balance>,
+    },
+    UnstakeBalances {
+        balances: Vec<Balance>,
+    },
+    /// Ownership only changes through here, once the proposed owner accepts
+    ProposeNewOwner {
+        #[serde(alias = "new_owner")]
+        addr: Addr,
+    },
+    AcceptOwnership {},
+    CancelOwnershipProposal {},
+    AddTaskCreators {
+        addrs: Vec<Addr>,
+    },
+    RemoveTaskCreators {
+        addrs: Vec<Addr>,
+    },
+    AddAgentToAllowlist {
+        agent_id: Addr,
+    },
+    RemoveAgentFromAllowlist {
+        agent_id: Addr,
+    },
 
     RegisterAgent {
         payable_account_id: Option<Addr>,
@@ -85,61 +207,203 @@ pub enum ExecuteMsg {
     UpdateAgent {
         payable_account_id: Addr,
     },
+    /// Allow another address to call proxy_call for this agent, None revokes it
+    SetAgentRelayer {
+        relayer: Option<Addr>,
+    },
     CheckInAgent {},
     UnregisterAgent {},
-    WithdrawReward {},
+    /// Eject active agents that haven't executed for `agents_eject_threshold` blocks, callable by anyone
+    EjectStaleAgents {},
+    /// Withdraws accrued rewards, only `amount` of them when set
+    WithdrawReward {
+        amount: Option<Coin>,
+    },
 
     CreateTask {
         task: TaskRequest,
     },
     RemoveTask {
         task_hash: String,
+        /// Send the remaining deposit here instead of the task owner
+        refund_to: Option<Addr>,
     },
     RefillTaskBalance {
         task_hash: String,
     },
-    ProxyCall {},
+    /// Cw20 tokens sent with `Cw20ExecuteMsg::Send`, `msg` is a `ReceiveMsg`
+    Receive(Cw20ReceiveMsg),
+    /// Replace a task's actions or rules in place, keeping its balance and schedule
+    UpdateTask {
+        task_hash: String,
+        actions: Option<Vec<Action>>,
+        rules: Option<Vec<Rule>>,
+    },
+    ProxyCall {
+        /// Agent the relayer calls for, it gets the rewards
+        reward_to: Option<Addr>,
+        /// Version of the calling agent software, as major.minor.patch
+        agent_version: Option<String>,
+        /// Run this task if it's due, instead of the next one in the current slot
+        task_hash: Option<String>,
+    },
+    ReapDeadTasks {
+        limit: Option<u64>,
+    },
+}
+
+/// Hook messages of cw20 tokens sent to the manager
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ReceiveMsg {
+    /// Add the sent tokens to the task's cw20 deposit, only its owner can refill it
+    RefillTaskBalance { task_hash: String },
+}
+
+/// Sent to the dust swap contract with the dust attached,
+/// it must pay the swapped `ask_denom` out to `recipient`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DustSwapMsg {
+    Swap { ask_denom: String, recipient: Addr },
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum QueryMsg {
     GetConfig {},
+    /// Just the pause state, cheaper for agents to poll than the whole config
+    GetPaused {},
     GetBalances {},
+    /// Tracked vs actual native balances per denom, to detect accounting drift
+    GetBalanceReconciliation {},
     GetAgent {
         account_id: Addr,
     },
+    /// Whether the agent is active, pending or nominated to check in, errors for unregistered accounts
+    GetAgentStatus {
+        account_id: Addr,
+    },
     GetAgentIds {},
+    /// Full agent records, active agents first and then pending ones
+    GetAgents {
+        from_index: Option<u64>,
+        limit: Option<u64>,
+    },
     GetAgentTasks {
         account_id: Addr,
     },
+    GetAgentNextSlot {
+        account_id: Addr,
+    },
+    /// Rough estimate of an active agent's daily rewards
+    GetAgentProjectedEarnings {
+        account_id: Addr,
+    },
+    /// Agent registrations, unregistrations and ejections within the recent window
+    GetAgentChurn {},
+    /// Due tasks the agent's proxy call would not run, with the reason why
+    GetIneligibleTasks {
+        agent_id: Addr,
+        limit: Option<u64>,
+    },
     GetTasks {
         from_index: Option<u64>,
         limit: Option<u64>,
+        /// Evaluate due slots and rules for each task, costs extra gas
+        include_status: Option<bool>,
+    },
+    /// Cron tasks whose boundary end falls within the window from the current block time
+    GetExpiringTasks {
+        within_seconds: u64,
+        limit: Option<u64>,
+    },
+    /// Tasks whose balance can't cover their next run
+    GetUnderfundedTasks {
+        limit: Option<u64>,
+    },
+    /// Tasks created between `start` and `end`, both inclusive
+    GetTasksCreatedBetween {
+        start: Timestamp,
+        end: Timestamp,
+        from_index: Option<u64>,
+        limit: Option<u64>,
     },
     GetTasksByOwner {
         owner_id: Addr,
+        from_index: Option<u64>,
+        limit: Option<u64>,
     },
     GetTask {
         task_hash: String,
     },
+    /// Owner's task at `index`, counting in creation order
+    GetOwnerTaskByIndex {
+        owner_id: Addr,
+        index: u64,
+    },
     GetTaskHash {
         task: Box<Task>,
     },
+    GetTaskFundingHistory {
+        task_hash: String,
+    },
+    /// What a single run of the task costs, component by component
+    GetExecutionCostBreakdown {
+        task_hash: String,
+    },
+    /// Whether a deposit covers creating the task, and how many runs it pays for
+    CheckTaskFunding {
+        task: TaskRequest,
+        deposit: Vec<Coin>,
+    },
+    /// Validates a task request the way create_task does, without funds.
+    /// The owner is needed as it is part of the task hash
+    SimulateCreateTask {
+        owner_id: Addr,
+        task: TaskRequest,
+    },
+    /// Dry-runs the rules of a task the way proxy calls evaluate them, without running any action
+    CheckTaskRules {
+        task_hash: String,
+    },
+    /// Checks the interval, and the boundary against the interval kind when given
     ValidateInterval {
         interval: Interval,
+        boundary: Option<Boundary>,
     },
+    /// Without a slot, also lists up to `limit` upcoming non-empty block and time slots
     GetSlotHashes {
         slot: Option<u64>,
+        limit: Option<u64>,
     },
     GetSlotIds {},
+    GetSlotCounts {
+        from_index: Option<u64>,
+        limit: Option<u64>,
+    },
+    /// Oldest non-empty slots behind the current block, None when agents are caught up
+    GetOldestOverdueSlot {},
+    /// How many tasks there are of each interval kind
+    GetTaskTypeBreakdown {},
+    /// Total number of tasks, without loading any of them
+    GetTaskCount {},
+    /// Earliest non-empty slots and how far away they are, None when nothing is scheduled
+    GetNextExecution {},
+    /// Agent rewards paid out for the tasks of the nearest slot, None when nothing is scheduled
+    GetNextSlotRewards {},
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct GetPausedResponse {
+    pub paused: bool,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct GetConfigResponse {
     pub paused: bool,
     pub owner_id: Addr,
-    // pub treasury_id: Option<Addr>,
+    pub treasury_id: Option<Addr>,
     pub min_tasks_per_agent: u64,
     pub agent_active_indices: Vec<(SlotType, u32, u32)>,
     pub agents_eject_threshold: u64,
@@ -148,6 +412,30 @@ pub struct GetConfigResponse {
     pub proxy_callback_gas: u32,
     pub slot_granularity: u64,
     pub native_denom: String,
+    pub dead_task_bounty: Coin,
+    pub max_rules_per_task: u64,
+    pub slash_destination: SlashDestination,
+    pub low_balance_pause_threshold: Uint128,
+    pub min_active_agents_for_execution: u64,
+    pub max_submsgs_per_proxy_call: u64,
+    pub protocol_fee: Coin,
+    pub task_creation_fee: Option<Coin>,
+    pub nomination_paused: bool,
+    pub reward_vesting_seconds: u64,
+    pub task_creator_whitelist: Option<Vec<Addr>>,
+    pub max_future_slot_seconds: u64,
+    pub randomized_agent_selection: bool,
+    pub max_tasks_per_owner: Option<u64>,
+    pub dust_swap_contract: Option<Addr>,
+    pub dust_threshold: Uint128,
+    pub min_active_agents: u64,
+    pub min_agent_version: Option<String>,
+    pub removal_grace_slots: u64,
+    pub eject_to_pending: bool,
+    pub max_task_limit: u64,
+    pub agent_allowlist_enabled: bool,
+    pub max_task_gas: u64,
+    pub min_task_balance: Coin,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -155,21 +443,132 @@ pub struct GetBalancesResponse {
     pub native_denom: String,
     pub available_balance: GenericBalance,
     pub staked_balance: GenericBalance,
+    pub protocol_fees_collected: GenericBalance,
     pub cw20_whitelist: Vec<Addr>,
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct BalanceReconciliation {
+    pub denom: String,
+    /// Part of the available balance
+    pub tracked: Uint128,
+    /// Held by the contract
+    pub actual: Uint128,
+    pub surplus: Uint128,
+    pub shortfall: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct GetBalanceReconciliationResponse {
+    pub balances: Vec<BalanceReconciliation>,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
 pub struct GetAgentIdsResponse {
     pub active: Vec<Addr>,
     pub pending: Vec<Addr>,
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AgentRecord {
+    pub account_id: Addr,
+    pub agent: AgentResponse,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
 pub struct AgentTaskResponse {
     pub num_block_tasks: Uint64,
     pub num_block_tasks_extra: Uint64,
     pub num_cron_tasks: Uint64,
     pub num_cron_tasks_extra: Uint64,
+    /// Block slot the agent should execute next, if any of its tasks are assigned
+    #[serde(default)]
+    pub block_slot: Option<u64>,
+    /// Time slot (in nanos) the agent should execute next, if any of its tasks are assigned
+    #[serde(default)]
+    pub cron_slot: Option<u64>,
+}
+
+/// A slot still holding tasks after its block or time passed
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct OverdueSlot {
+    pub slot_id: u64,
+    /// Blocks for block slots, nanoseconds for time slots
+    pub overdue_by: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct GetOldestOverdueSlotResponse {
+    pub block_slot: Option<OverdueSlot>,
+    pub time_slot: Option<OverdueSlot>,
+}
+
+/// The next slot holding tasks, overdue slots are due in 0
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct UpcomingSlot {
+    pub slot_id: u64,
+    /// Blocks for block slots, seconds for time slots
+    pub due_in: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct GetNextExecutionResponse {
+    pub block_slot: Option<UpcomingSlot>,
+    pub time_slot: Option<UpcomingSlot>,
+}
+
+/// Sum of the agent fees of every task in the slot
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct GetNextSlotRewardsResponse {
+    pub slot_id: u64,
+    pub slot_kind: SlotType,
+    pub tasks: u64,
+    pub total_reward: Coin,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq, JsonSchema)]
+pub struct GetTaskTypeBreakdownResponse {
+    pub once: u64,
+    pub immediate: u64,
+    pub block: u64,
+    pub cron: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct GetTaskCountResponse {
+    pub total: u64,
+}
+
+/// Estimated daily agent rewards, assuming every task runs once a day
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AgentProjectedEarningsResponse {
+    pub estimated_daily_reward: Coin,
+    pub total_tasks: u64,
+    pub active_agents: u64,
+}
+
+/// Due task that gets skipped, `reason` matches the `skip_reason` of proxy calls
+/// or is `agent_not_active`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct IneligibleTask {
+    pub task_hash: String,
+    pub reason: String,
+}
+
+/// Agent churn counted over the last `window_seconds`
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, JsonSchema)]
+pub struct GetAgentChurnResponse {
+    pub window_seconds: u64,
+    pub registered: u64,
+    pub unregistered: u64,
+    pub ejected: u64,
+}
+
+/// Earliest block and time slots the agent gets tasks from
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AgentNextSlotResponse {
+    pub block_slot: Option<u64>,
+    pub time_slot: Option<u64>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -179,6 +578,73 @@ pub struct TaskRequest {
     pub stop_on_fail: bool,
     pub actions: Vec<Action>,
     pub rules: Option<Vec<Rule>>,
+    /// Opaque context for integrations, stored with the task but never executed
+    pub metadata: Option<Binary>,
+    /// Task created once this one ends, funded from what's left of its balance
+    pub successor: Option<Box<TaskRequest>>,
+    /// Defaults to running the actions sequentially
+    pub action_order: Option<ActionOrder>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct ValidateIntervalResponse {
+    pub valid: bool,
+    /// Why the interval is invalid
+    pub reason: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct CheckTaskFundingResponse {
+    pub sufficient: bool,
+    /// Native deposit the task needs, not counting the creation fee
+    pub min_deposit: Coin,
+    pub task_creation_fee: Option<Coin>,
+    /// Runs the deposit pays for, including protocol fees, u64::MAX if a run costs nothing
+    pub executions: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SimulateCreateTaskResponse {
+    /// None when the boundary doesn't fit the interval
+    pub task_hash: Option<String>,
+    /// Gas limits of all actions added up
+    pub gas_total: u64,
+    /// Native deposit the task needs, not counting the creation fee
+    pub min_deposit: Coin,
+    /// Everything create_task would reject the request for, empty if it's valid
+    pub errors: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct GetExecutionCostBreakdownResponse {
+    /// Gas of every action at the gas price, falling back to the base fee for actions without a gas limit
+    pub gas_cost: Coin,
+    pub agent_fee: Coin,
+    pub proxy_callback_cost: Coin,
+    pub protocol_fee: Coin,
+    /// Funds the actions send along
+    pub action_funds: Vec<Coin>,
+    /// What a run takes from the task balance, summed up per denom: the protocol fee
+    /// plus the action funds, or plus the agent fee and action gas for no-op tasks.
+    /// Gas and the agent fee of other tasks are paid by the contract
+    pub total: Vec<Coin>,
+}
+
+/// Result of a single task rule, `reason` matches the `skip_reason` of proxy calls
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct RuleCheck {
+    /// None for rule expressions
+    pub contract_addr: Option<Addr>,
+    pub pass: bool,
+    pub reason: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct CheckTaskRulesResponse {
+    /// Every rule of the task, in order
+    pub rules: Vec<RuleCheck>,
+    /// Whether all rules pass, so the task would run
+    pub pass: bool,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -191,6 +657,23 @@ pub struct TaskResponse {
     pub total_deposit: Vec<Coin>,
     pub actions: Vec<Action>,
     pub rules: Option<Vec<Rule>>,
+    pub metadata: Option<Binary>,
+    pub status: Option<TaskStatus>,
+    pub action_order: ActionOrder,
+    pub total_cw20_deposit: Vec<Cw20CoinVerified>,
+    /// Runs left for a task with an executions boundary, only filled in by single task queries
+    #[serde(default)]
+    pub executions_remaining: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TaskStatus {
+    /// Task sits in a slot that is ready at the current block
+    pub due: bool,
+    /// Result of querying every rule, None if task has no rules
+    pub rules_pass: Option<bool>,
+    /// Slots left to refill an out of funds task before it's removed, None while it's funded
+    pub grace_slots: Option<u64>,
 }
 
 impl From<Task> for TaskResponse {
@@ -200,14 +683,24 @@ impl From<Task> for TaskResponse {
                 BoundaryValidated {
                     start: None,
                     end: None,
+                    max_executions: None,
                 },
                 _,
             ) => None,
-            (BoundaryValidated { start, end }, Interval::Cron(_)) => Some(Boundary::Time {
+            (
+                BoundaryValidated {
+                    max_executions: Some(count),
+                    ..
+                },
+                _,
+            ) => Some(Boundary::Executions {
+                count: count.into(),
+            }),
+            (BoundaryValidated { start, end, .. }, Interval::Cron(_)) => Some(Boundary::Time {
                 start: start.map(Timestamp::from_nanos),
                 end: end.map(Timestamp::from_nanos),
             }),
-            (BoundaryValidated { start, end }, _) => Some(Boundary::Height {
+            (BoundaryValidated { start, end, .. }, _) => Some(Boundary::Height {
                 start: start.map(Into::into),
                 end: end.map(Into::into),
             }),
@@ -221,6 +714,11 @@ impl From<Task> for TaskResponse {
             total_deposit: task.total_deposit,
             actions: task.actions,
             rules: task.rules,
+            metadata: task.metadata,
+            status: None,
+            action_order: task.action_order,
+            total_cw20_deposit: task.total_cw20_deposit,
+            executions_remaining: None,
         }
     }
 }
@@ -231,6 +729,18 @@ pub struct GetSlotHashesResponse {
     pub block_task_hash: Vec<String>,
     pub time_id: u64,
     pub time_task_hash: Vec<String>,
+    /// Upcoming non-empty block slots, in order, only listed when no slot was asked for
+    #[serde(default)]
+    pub block_slots: Vec<SlotHashes>,
+    /// Upcoming non-empty time slots, in order, only listed when no slot was asked for
+    #[serde(default)]
+    pub time_slots: Vec<SlotHashes>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SlotHashes {
+    pub slot_id: u64,
+    pub task_hashes: Vec<String>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -239,12 +749,19 @@ pub struct GetSlotIdsResponse {
     pub block_ids: Vec<u64>,
 }
 
+/// Slot ids paired with the number of tasks in them
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct GetSlotCountsResponse {
+    pub time_counts: Vec<(u64, u64)>,
+    pub block_counts: Vec<(u64, u64)>,
+}
+
 #[cfg(test)]
 mod tests {
     use cosmwasm_std::{coin, coins, BankMsg, CosmosMsg, Timestamp};
     use cw20::Cw20CoinVerified;
 
-    use crate::types::AgentStatus;
+    use crate::types::FundingEventKind;
 
     use super::*;
 
@@ -265,6 +782,7 @@ mod tests {
             total_tasks_executed: 0,
             last_missed_slot: 3,
             register_start: Timestamp::from_nanos(5),
+            last_executed_slot: 0,
         }
         .into();
 
@@ -280,6 +798,7 @@ mod tests {
             boundary: BoundaryValidated {
                 start: Some(54),
                 end: Some(44),
+                max_executions: None,
             },
             stop_on_fail: false,
             total_deposit: vec![],
@@ -288,12 +807,18 @@ mod tests {
                 gas_limit: Some(150_000),
             }],
             rules: None,
+            metadata: None,
+            successor: None,
+            generation: 0,
+            action_order: ActionOrder::Sequential,
+            total_cw20_deposit: vec![],
         }
         .into();
 
         let config_response = GetConfigResponse {
             paused: true,
             owner_id: Addr::unchecked("bob"),
+            treasury_id: Some(Addr::unchecked("treasury")),
             min_tasks_per_agent: 5,
             agent_active_indices: vec![(SlotType::Block, 10, 5)],
             agents_eject_threshold: 5,
@@ -302,15 +827,51 @@ mod tests {
             proxy_callback_gas: 3,
             slot_granularity: 1,
             native_denom: "juno".to_string(),
+            dead_task_bounty: coin(5, "juno"),
+            max_rules_per_task: 5,
+            slash_destination: SlashDestination::Burn,
+            low_balance_pause_threshold: Uint128::new(1000),
+            min_active_agents_for_execution: 3,
+            max_submsgs_per_proxy_call: 15,
+            protocol_fee: coin(1, "juno"),
+            task_creation_fee: Some(coin(3, "juno")),
+            nomination_paused: true,
+            reward_vesting_seconds: 3600,
+            task_creator_whitelist: Some(vec![Addr::unchecked("bob")]),
+            max_future_slot_seconds: 315_360_000,
+            randomized_agent_selection: false,
+            max_tasks_per_owner: Some(10),
+            dust_swap_contract: Some(Addr::unchecked("swapper")),
+            dust_threshold: Uint128::new(10),
+            min_active_agents: 2,
+            min_agent_version: Some("1.0.0".to_string()),
+            removal_grace_slots: 0,
+            eject_to_pending: false,
+            max_task_limit: 100_000,
+            agent_allowlist_enabled: false,
+            max_task_gas: 10_000_000,
+            min_task_balance: coin(0, "atom"),
         }
         .into();
+        let get_paused_response = GetPausedResponse { paused: true }.into();
         let balance_response = GetBalancesResponse {
             native_denom: "some".to_string(),
             available_balance: generic_balance.clone(),
             staked_balance: generic_balance.clone(),
+            protocol_fees_collected: generic_balance.clone(),
             cw20_whitelist: vec![Addr::unchecked("bob")],
         }
         .into();
+        let get_balance_reconciliation_response = GetBalanceReconciliationResponse {
+            balances: vec![BalanceReconciliation {
+                denom: "atom".to_string(),
+                tracked: Uint128::new(10),
+                actual: Uint128::new(7),
+                surplus: Uint128::zero(),
+                shortfall: Uint128::new(3),
+            }],
+        }
+        .into();
         let get_agent_ids_response = GetAgentIdsResponse {
             active: vec![Addr::unchecked("bob")],
             pending: vec![Addr::unchecked("bob")],
@@ -321,6 +882,8 @@ mod tests {
             num_block_tasks_extra: 2u64.into(),
             num_cron_tasks: 3u64.into(),
             num_cron_tasks_extra: 300u64.into(),
+            block_slot: Some(12345),
+            cron_slot: None,
         }
         .into();
         let task_request = TaskRequest {
@@ -332,6 +895,9 @@ mod tests {
             stop_on_fail: true,
             actions: vec![],
             rules: None, // TODO
+            metadata: None,
+            successor: None,
+            action_order: None,
         }
         .into();
         let task_response_raw = TaskResponse {
@@ -346,27 +912,93 @@ mod tests {
             total_deposit: vec![coin(5, "earth")],
             actions: vec![],
             rules: None,
+            status: Some(TaskStatus {
+                due: false,
+                rules_pass: None,
+                grace_slots: Some(2),
+            }),
+            metadata: None,
+            action_order: ActionOrder::Sequential,
+            total_cw20_deposit: vec![],
+            executions_remaining: Some(3),
         };
         let task_response = task_response_raw.clone().into();
-        let validate_interval_response = false.into();
-        let get_agent_response = Some(AgentResponse {
+        let validate_interval_response = ValidateIntervalResponse {
+            valid: false,
+            reason: Some("Block interval must be greater than zero".to_string()),
+        }
+        .into();
+        let check_task_funding_response = CheckTaskFundingResponse {
+            sufficient: true,
+            min_deposit: coin(10, "atom"),
+            task_creation_fee: Some(coin(1, "atom")),
+            executions: 2,
+        }
+        .into();
+        let simulate_create_task_response = SimulateCreateTaskResponse {
+            task_hash: None,
+            gas_total: 150_000,
+            min_deposit: coin(10, "atom"),
+            errors: vec!["Interval invalid".to_string()],
+        }
+        .into();
+        let check_task_rules_response = Some(Some(CheckTaskRulesResponse {
+            rules: vec![RuleCheck {
+                contract_addr: Some(Addr::unchecked("rules")),
+                pass: false,
+                reason: Some("rule_query_failed".to_string()),
+            }],
+            pass: false,
+        }));
+        let agent_response = AgentResponse {
             status: AgentStatus::Active,
             payable_account_id: Addr::unchecked("bob"),
             balance: generic_balance.clone(),
             total_tasks_executed: 2,
             last_missed_slot: 2,
             register_start: Timestamp::from_nanos(5),
-        })
+            last_executed_slot: 3,
+        };
+        let get_agent_response = Some(agent_response.clone()).into();
+        let get_agent_status_response = AgentStatus::Nominated.into();
+        let get_agents_response = vec![AgentRecord {
+            account_id: Addr::unchecked("bob"),
+            agent: agent_response,
+        }]
         .into();
         let get_tasks_response = vec![task_response_raw.clone()].into();
+        let get_expiring_tasks_response = vec![task_response_raw.clone()].into();
+        let get_underfunded_tasks_response = vec![task_response_raw.clone()].into();
+        let get_tasks_created_between_response = vec![task_response_raw.clone()].into();
         let get_tasks_by_owner_response = vec![task_response_raw.clone()].into();
+        let get_owner_task_by_index_response = Some(task_response_raw.clone()).into();
         let get_task_response = Some(task_response_raw).into();
         let get_task_hash_response = ("asd".to_string()).into();
+        let get_task_funding_history_response = vec![FundingEvent {
+            kind: FundingEventKind::Refill,
+            amount: coins(5, "earth"),
+            block_height: 12345,
+            block_time: Timestamp::from_nanos(67890),
+        }]
+        .into();
+        let get_execution_cost_breakdown_response = Some(Some(GetExecutionCostBreakdownResponse {
+            gas_cost: coin(150_000, "atom"),
+            agent_fee: coin(5, "atom"),
+            proxy_callback_cost: coin(3, "atom"),
+            protocol_fee: coin(0, "atom"),
+            action_funds: coins(10, "earth"),
+            total: vec![coin(150_008, "atom"), coin(10, "earth")],
+        }));
         let get_slot_hashes_response = GetSlotHashesResponse {
             block_id: 5,
             block_task_hash: vec!["bob".to_string()],
             time_id: 4,
             time_task_hash: vec!["alice".to_string()],
+            block_slots: vec![SlotHashes {
+                slot_id: 5,
+                task_hashes: vec!["bob".to_string()],
+            }],
+            time_slots: vec![],
         }
         .into();
         let get_slot_ids_response = GetSlotIdsResponse {
@@ -374,23 +1006,102 @@ mod tests {
             block_ids: vec![3],
         }
         .into();
+        let get_slot_counts_response = GetSlotCountsResponse {
+            time_counts: vec![(1, 2)],
+            block_counts: vec![(3, 1)],
+        }
+        .into();
+        let get_agent_next_slot_response = Some(Some(AgentNextSlotResponse {
+            block_slot: Some(3),
+            time_slot: None,
+        }));
+        let get_agent_projected_earnings_response = Some(Some(AgentProjectedEarningsResponse {
+            estimated_daily_reward: coin(10, "juno"),
+            total_tasks: 4,
+            active_agents: 2,
+        }));
+        let get_ineligible_tasks_response = vec![IneligibleTask {
+            task_hash: "hash".to_string(),
+            reason: "rule_not_satisfied".to_string(),
+        }]
+        .into();
+        let get_agent_churn_response = GetAgentChurnResponse {
+            window_seconds: 86_400,
+            registered: 3,
+            unregistered: 1,
+            ejected: 1,
+        }
+        .into();
+        let get_oldest_overdue_slot_response = Some(Some(GetOldestOverdueSlotResponse {
+            block_slot: Some(OverdueSlot {
+                slot_id: 3,
+                overdue_by: 2,
+            }),
+            time_slot: None,
+        }));
+        let get_task_type_breakdown_response = GetTaskTypeBreakdownResponse {
+            once: 1,
+            immediate: 2,
+            block: 3,
+            cron: 4,
+        }
+        .into();
+        let get_task_count_response = GetTaskCountResponse { total: 10 }.into();
+        let get_next_execution_response = Some(GetNextExecutionResponse {
+            block_slot: None,
+            time_slot: Some(UpcomingSlot {
+                slot_id: 1_000,
+                due_in: 6,
+            }),
+        })
+        .into();
+        let get_next_slot_rewards_response = Some(GetNextSlotRewardsResponse {
+            slot_id: 1_000,
+            slot_kind: SlotType::Cron,
+            tasks: 2,
+            total_reward: coin(10, "juno"),
+        })
+        .into();
         let croncat = Croncat {
             agent,
             task,
             config_response,
+            get_paused_response,
             balance_response,
+            get_balance_reconciliation_response,
             get_agent_ids_response,
             get_agent_tasks_response,
             task_request,
             task_response,
             validate_interval_response,
+            check_task_funding_response,
+            simulate_create_task_response,
+            check_task_rules_response,
             get_agent_response,
+            get_agent_status_response,
+            get_agents_response,
             get_tasks_response,
+            get_expiring_tasks_response,
+            get_underfunded_tasks_response,
+            get_tasks_created_between_response,
             get_tasks_by_owner_response,
             get_task_response,
+            get_owner_task_by_index_response,
             get_task_hash_response,
+            get_task_funding_history_response,
+            get_execution_cost_breakdown_response,
             get_slot_hashes_response,
             get_slot_ids_response,
+            get_slot_counts_response,
+            get_agent_next_slot_response,
+            get_agent_projected_earnings_response,
+            get_agent_churn_response,
+            get_ineligible_tasks_response,
+            get_oldest_overdue_slot_response,
+            get_task_type_breakdown_response,
+            get_task_count_response,
+            get_next_execution_response,
+            get_next_slot_rewards_response,
         };
 
         let ser = serde_json_wasm::to_string(&croncat);
@@ -399,4 +1110,159 @@ mod tests {
         let deser: Result<Croncat, _> = serde_json_wasm::from_str(&ser.unwrap());
         assert!(deser.is_ok());
     }
+
+    #[test]
+    fn croncat_covers_all_queries() {
+        let schema = schemars::schema_for!(Croncat);
+        let object = schema.schema.object.unwrap();
+        let fields = &object.properties;
+        let task = Task {
+            owner_id: Addr::unchecked("bob"),
+            interval: Interval::Once,
+            boundary: BoundaryValidated {
+                start: None,
+                end: None,
+                max_executions: None,
+            },
+            stop_on_fail: false,
+            total_deposit: vec![],
+            actions: vec![],
+            rules: None,
+            metadata: None,
+            successor: None,
+            generation: 0,
+            action_order: ActionOrder::Sequential,
+            total_cw20_deposit: vec![],
+        };
+        let queries = vec![
+            QueryMsg::GetConfig {},
+            QueryMsg::GetPaused {},
+            QueryMsg::GetBalances {},
+            QueryMsg::GetBalanceReconciliation {},
+            QueryMsg::GetAgent {
+                account_id: Addr::unchecked("bob"),
+            },
+            QueryMsg::GetAgentStatus {
+                account_id: Addr::unchecked("bob"),
+            },
+            QueryMsg::GetAgentIds {},
+            QueryMsg::GetAgents {
+                from_index: None,
+                limit: None,
+            },
+            QueryMsg::GetAgentTasks {
+                account_id: Addr::unchecked("bob"),
+            },
+            QueryMsg::GetAgentNextSlot {
+                account_id: Addr::unchecked("bob"),
+            },
+            QueryMsg::GetAgentProjectedEarnings {
+                account_id: Addr::unchecked("bob"),
+            },
+            QueryMsg::GetAgentChurn {},
+            QueryMsg::GetIneligibleTasks {
+                agent_id: Addr::unchecked("bob"),
+                limit: None,
+            },
+            QueryMsg::GetTasks {
+                from_index: None,
+                limit: None,
+                include_status: None,
+            },
+            QueryMsg::GetExpiringTasks {
+                within_seconds: 60,
+                limit: None,
+            },
+            QueryMsg::GetUnderfundedTasks { limit: None },
+            QueryMsg::GetTasksCreatedBetween {
+                start: Timestamp::from_seconds(0),
+                end: Timestamp::from_seconds(100),
+                from_index: None,
+                limit: None,
+            },
+            QueryMsg::GetTasksByOwner {
+                owner_id: Addr::unchecked("bob"),
+                from_index: None,
+                limit: None,
+            },
+            QueryMsg::GetTask {
+                task_hash: "hash".to_string(),
+            },
+            QueryMsg::GetOwnerTaskByIndex {
+                owner_id: Addr::unchecked("bob"),
+                index: 0,
+            },
+            QueryMsg::GetTaskHash {
+                task: Box::new(task),
+            },
+            QueryMsg::GetTaskFundingHistory {
+                task_hash: "hash".to_string(),
+            },
+            QueryMsg::GetExecutionCostBreakdown {
+                task_hash: "hash".to_string(),
+            },
+            QueryMsg::CheckTaskFunding {
+                task: TaskRequest {
+                    interval: Interval::Once,
+                    boundary: None,
+                    stop_on_fail: false,
+                    actions: vec![],
+                    rules: None,
+                    metadata: None,
+                    successor: None,
+                    action_order: None,
+                },
+                deposit: vec![coin(10, "atom")],
+            },
+            QueryMsg::SimulateCreateTask {
+                owner_id: Addr::unchecked("bob"),
+                task: TaskRequest {
+                    interval: Interval::Once,
+                    boundary: None,
+                    stop_on_fail: false,
+                    actions: vec![],
+                    rules: None,
+                    metadata: None,
+                    successor: None,
+                    action_order: None,
+                },
+            },
+            QueryMsg::CheckTaskRules {
+                task_hash: "hash".to_string(),
+            },
+            QueryMsg::ValidateInterval {
+                interval: Interval::Once,
+                boundary: None,
+            },
+            QueryMsg::GetSlotHashes {
+                slot: None,
+                limit: None,
+            },
+            QueryMsg::GetSlotIds {},
+            QueryMsg::GetSlotCounts {
+                from_index: None,
+                limit: None,
+            },
+            QueryMsg::GetOldestOverdueSlot {},
+            QueryMsg::GetTaskTypeBreakdown {},
+            QueryMsg::GetTaskCount {},
+            QueryMsg::GetNextExecution {},
+            QueryMsg::GetNextSlotRewards {},
+        ];
+        for query in queries.iter() {
+            let field = query.croncat_response_field();
+            assert!(fields.contains_key(field), "Croncat is missing {}", field);
+        }
+
+        // Every field is either a query response or one of the shared types
+        let shared = ["Agent", "Task", "TaskRequest", "TaskResponse"];
+        for field in fields.keys() {
+            assert!(
+                shared.contains(&field.as_str())
+                    || queries.iter().any(|q| q.croncat_response_field() == field),
+                "{} doesn't belong to any query",
+                field
+            );
+        }
+    }
 }
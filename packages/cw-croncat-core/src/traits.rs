@@ -9,7 +9,9 @@ pub trait GenericBalances {
 
 pub trait Intervals {
     fn next(&self, env: Env, boundary: BoundaryValidated) -> (u64, SlotType);
-    fn is_valid(&self) -> bool;
+    fn is_valid(&self, boundary: &BoundaryValidated) -> bool;
+    /// Why the interval can't be scheduled within the boundary, None when it's valid
+    fn invalid_reason(&self, boundary: &BoundaryValidated) -> Option<String>;
 }
 
 pub trait TaskHash {
@@ -1,5 +1,6 @@
 use cosmwasm_std::{
-    Addr, BankMsg, Binary, Coin, CosmosMsg, Empty, Env, GovMsg, IbcMsg, Timestamp, Uint64, WasmMsg,
+    Addr, BankMsg, Binary, Coin, CosmosMsg, Empty, Env, Event, GovMsg, IbcMsg, IbcTimeout,
+    IbcTimeoutBlock, Timestamp, Uint64, WasmMsg,
 };
 use cron_schedule::Schedule;
 use cw20::{Balance, Cw20CoinVerified};
@@ -9,7 +10,11 @@ use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 use std::str::FromStr;
 
-use crate::{error::CoreError, traits::Intervals};
+use crate::{error::CoreError, msg::TaskRequest, traits::Intervals};
+
+// Longest relative IBC transfer timeout, a year in seconds or in blocks of 5 seconds
+const MAX_IBC_TIMEOUT_SECONDS: u64 = 31_536_000;
+const MAX_IBC_TIMEOUT_BLOCKS: u64 = 6_307_200;
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug, Default)]
 pub struct GenericBalance {
@@ -51,6 +56,11 @@ pub struct Agent {
     // Agent will be responsible to constantly monitor when it is their turn to join in active agent set (done as part of agent code loops)
     // Example data: 1633890060000000000 or 0
     pub register_start: Timestamp,
+
+    // Block height of the agent's last proxy call, or of when it registered or checked in.
+    // Active agents that don't call for `agents_eject_threshold` blocks can be ejected
+    #[serde(default)]
+    pub last_executed_slot: u64,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -62,6 +72,7 @@ pub struct AgentResponse {
     pub total_tasks_executed: u64,
     pub last_missed_slot: u64,
     pub register_start: Timestamp,
+    pub last_executed_slot: u64,
 }
 
 /// Defines the spacing of execution
@@ -94,12 +105,16 @@ pub enum Boundary {
         start: Option<Timestamp>,
         end: Option<Timestamp>,
     },
+    /// Ends the task once it executed `count` times, for any interval
+    Executions { count: Uint64 },
 }
 
 #[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Eq, JsonSchema)]
 pub struct BoundaryValidated {
     pub start: Option<u64>,
     pub end: Option<u64>,
+    /// Number of executions after which the task ends
+    pub max_executions: Option<u64>,
 }
 
 impl BoundaryValidated {
@@ -112,6 +127,7 @@ impl BoundaryValidated {
                 (Interval::Cron(_), Boundary::Time { start, end }) => Ok(Self {
                     start: start.map(|start| start.nanos()),
                     end: end.map(|end| end.nanos()),
+                    max_executions: None,
                 }),
                 (
                     Interval::Once | Interval::Immediate | Interval::Block(_),
@@ -119,6 +135,12 @@ impl BoundaryValidated {
                 ) => Ok(Self {
                     start: start.map(Into::into),
                     end: end.map(Into::into),
+                    max_executions: None,
+                }),
+                (_, Boundary::Executions { count }) if !count.is_zero() => Ok(Self {
+                    start: None,
+                    end: None,
+                    max_executions: Some(count.u64()),
                 }),
                 _ => Err(CoreError::InvalidBoundary {}),
             }
@@ -126,9 +148,21 @@ impl BoundaryValidated {
             Ok(Self {
                 start: None,
                 end: None,
+                max_executions: None,
             })
         }
     }
+
+    /// Whether a task that executed `executions` times reached its boundary,
+    /// `Intervals::next` covers the height and time boundaries
+    pub fn executions_reached(&self, executions: u64) -> bool {
+        self.max_executions.map_or(false, |max| executions >= max)
+    }
+
+    /// Whether start comes after end, such a task could never run
+    pub fn is_reversed(&self) -> bool {
+        matches!((self.start, self.end), (Some(start), Some(end)) if start > end)
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, std::hash::Hash, Deserialize, Serialize, Clone, JsonSchema)]
@@ -138,15 +172,75 @@ pub enum SlotType {
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
-pub struct Rule {
-    /// TBD: Interchain query support (See ibc::IbcMsg)
-    // pub chain_id: Option<String>,
+pub enum Rule {
+    /// Smart query, passes when the contract answers `(true, _)`
+    Query {
+        /// TBD: Interchain query support (See ibc::IbcMsg)
+        // chain_id: Option<String>,
 
-    /// Account to direct all view calls against
-    pub contract_addr: Addr,
+        /// Account to direct all view calls against
+        contract_addr: Addr,
 
-    // NOTE: Only allow static pre-defined query msg
-    pub msg: Binary,
+        // NOTE: Only allow static pre-defined query msg
+        msg: Binary,
+    },
+    /// Passes when `address` holds at least `required`, checked with a bank query
+    NativeBalanceGte { address: Addr, required: Coin },
+    /// Smart query, passes when the JSON at `path` of the response equals `value`
+    /// `path` is dot separated, numbers index into arrays, empty compares the whole response
+    SmartQueryEquals {
+        contract_addr: Addr,
+        msg: Binary,
+        path: String,
+        value: Binary,
+    },
+    /// Combines rules with AND/OR/NOT, the rules of a task are an implicit AND
+    Expr(Box<RuleExpr>),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub enum RuleExpr {
+    /// Passes when all of them pass
+    And(Vec<RuleExpr>),
+    /// Passes when any of them passes
+    Or(Vec<RuleExpr>),
+    /// Passes when the inner expression isn't satisfied
+    Not(Box<RuleExpr>),
+    Leaf(Rule),
+}
+
+impl RuleExpr {
+    /// Levels of expressions, counting the ones nested in leaf rules
+    pub fn depth(&self) -> usize {
+        let inner = match self {
+            RuleExpr::And(exprs) | RuleExpr::Or(exprs) => {
+                exprs.iter().map(RuleExpr::depth).max().unwrap_or_default()
+            }
+            RuleExpr::Not(expr) => expr.depth(),
+            RuleExpr::Leaf(rule) => rule.depth(),
+        };
+        inner + 1
+    }
+}
+
+impl Rule {
+    /// Address the rule queries, None for expressions
+    pub fn addr(&self) -> Option<&Addr> {
+        match self {
+            Rule::Query { contract_addr, .. } => Some(contract_addr),
+            Rule::NativeBalanceGte { address, .. } => Some(address),
+            Rule::SmartQueryEquals { contract_addr, .. } => Some(contract_addr),
+            Rule::Expr(_) => None,
+        }
+    }
+
+    /// Levels of rule expressions, 0 for plain rules
+    pub fn depth(&self) -> usize {
+        match self {
+            Rule::Expr(expr) => expr.depth(),
+            _ => 0,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -159,6 +253,49 @@ pub struct Action<T = Empty> {
     pub gas_limit: Option<u64>,
 }
 
+/// Where funds slashed from an agent are sent
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub enum SlashDestination {
+    /// Sent to the treasury account, or the owner while no treasury is set
+    Treasury,
+    Burn,
+    /// Added back to the deposit of the affected task, or the available balance without one
+    Task,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub enum FundingEventKind {
+    /// Deposit attached when the task was created
+    Deposit,
+    Refill,
+    /// Remaining balance returned to the owner
+    Withdraw,
+}
+
+/// Change of a task's balance, kept for owners auditing their tasks
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct FundingEvent {
+    pub kind: FundingEventKind,
+    pub amount: Vec<Coin>,
+    pub block_height: u64,
+    pub block_time: Timestamp,
+}
+
+/// How the actions of a task get dispatched on execution
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, JsonSchema)]
+pub enum ActionOrder {
+    /// Each action is sent from the reply of the previous one, a failure stops the rest
+    Sequential,
+    /// All actions are sent at once, a failed action doesn't stop the others
+    Unordered,
+}
+
+impl Default for ActionOrder {
+    fn default() -> Self {
+        ActionOrder::Sequential
+    }
+}
+
 /// The response required by all rule queries. Bool is needed for croncat, T allows flexible rule engine
 pub type RuleResponse<T> = (bool, T);
 
@@ -183,6 +320,19 @@ pub struct Task {
     /// required to complete before task action
     /// Rules MUST return the ResolverResponse type
     pub rules: Option<Vec<Rule>>,
+    /// Owner supplied context, not part of the hash and ignored on execution
+    pub metadata: Option<Binary>,
+    /// Created once this task ends, funded from the remaining balance
+    pub successor: Option<Box<TaskRequest>>,
+    /// How many predecessors spawned this task, bounds successor chains
+    #[serde(default)]
+    pub generation: u64,
+    /// Whether the actions run one after another or all at once
+    #[serde(default)]
+    pub action_order: ActionOrder,
+    /// Cw20 tokens refilled through `Receive`, refunded together with `total_deposit`
+    #[serde(default)]
+    pub total_cw20_deposit: Vec<Cw20CoinVerified>,
     // TODO: funds! should we support funds being attached?
 }
 
@@ -203,21 +353,63 @@ impl Task {
     }
     // /// Returns the base amount required to execute 1 task
     // /// NOTE: this is not the final used amount, just the user-specified amount total needed
-    pub fn task_balance_uses(&self, agent_fee: &Coin, gas_base_fee: u64) -> u128 {
+    pub fn task_balance_uses(
+        &self,
+        agent_fee: &Coin,
+        gas_base_fee: u64,
+        proxy_callback_gas: u32,
+    ) -> u128 {
         // TODO support attaching funds
         // task.deposit.0 +
+        let callback_gas = u128::from(self.to_callback_gas(proxy_callback_gas));
         self.actions
             .iter()
-            .fold(agent_fee.amount.u128(), |sum, action| {
+            .fold(agent_fee.amount.u128() + callback_gas, |sum, action| {
                 sum + u128::from(action.gas_limit.unwrap_or(gas_base_fee))
             })
     }
 
+    /// Funds all actions send along in a single run, summed up per denom
+    pub fn funds_per_run(&self) -> Vec<Coin> {
+        let mut total: Vec<Coin> = vec![];
+        for coin in self.actions.iter().flat_map(Action::funds) {
+            match total.iter_mut().find(|c| c.denom == coin.denom) {
+                Some(c) => c.amount += coin.amount,
+                None => total.push(coin),
+            }
+        }
+        total
+    }
+
+    /// Gas reserved for the proxy callback, every action replies to it once
+    pub fn to_callback_gas(&self, proxy_callback_gas: u32) -> u64 {
+        u64::from(proxy_callback_gas).saturating_mul(self.actions.len() as u64)
+    }
+
+    /// Heartbeat task, every action is a no-op
+    pub fn is_no_op(&self) -> bool {
+        !self.actions.is_empty() && self.actions.iter().all(Action::is_no_op)
+    }
+
+    /// Whether the block is past the boundary end, a time for cron tasks and a height otherwise
+    pub fn is_expired(&self, env: &Env) -> bool {
+        let now = match self.interval {
+            Interval::Cron(_) => env.block.time.nanos(),
+            _ => env.block.height,
+        };
+        self.boundary.end.map_or(false, |end| now > end)
+    }
+
     /// Validate the task actions only use the supported messages
     pub fn is_valid_msg(&self, self_addr: &Addr, sender: &Addr, owner_id: &Addr) -> bool {
         // TODO: Chagne to default FALSE, once all messages are covered in tests
         let mut valid = true;
 
+        // No-op actions never get dispatched, so they can't be mixed with real ones
+        if self.actions.iter().any(Action::is_no_op) && !self.is_no_op() {
+            return false;
+        }
+
         for action in self.actions.iter() {
             match action.clone().msg {
                 CosmosMsg::Wasm(WasmMsg::Execute {
@@ -244,10 +436,29 @@ impl Task {
                     // Restrict bank msg for time being, so contract doesnt get drained, however could allow an escrow type setup
                     valid = false;
                 }
-                // TODO: Setup better support for IBC
-                CosmosMsg::Ibc(IbcMsg::Transfer { .. }) => {
-                    // Restrict bank msg for time being, so contract doesnt get drained, however could allow an escrow type setup
-                    valid = false;
+                // The amount is drawn from the task balance, the timeout is relative to execution
+                CosmosMsg::Ibc(IbcMsg::Transfer {
+                    channel_id,
+                    to_address,
+                    amount,
+                    timeout,
+                }) => {
+                    let timeout_set = timeout.block().map_or(false, |b| b.height > 0)
+                        || timeout.timestamp().map_or(false, |t| t.nanos() > 0);
+                    let timeout_too_long = timeout
+                        .block()
+                        .map_or(false, |b| b.height > MAX_IBC_TIMEOUT_BLOCKS)
+                        || timeout
+                            .timestamp()
+                            .map_or(false, |t| t.seconds() > MAX_IBC_TIMEOUT_SECONDS);
+                    if channel_id.is_empty()
+                        || to_address.is_empty()
+                        || amount.amount.is_zero()
+                        || !timeout_set
+                        || timeout_too_long
+                    {
+                        valid = false;
+                    }
                 }
                 // TODO: Check authZ messages
                 _ => (),
@@ -271,6 +482,88 @@ impl Task {
     }
 }
 
+impl Action {
+    /// Action that does nothing, a task of these only proves the scheduler is alive
+    /// Runs never dispatch it, they just charge the agent fee and gas
+    pub fn no_op(gas_limit: Option<u64>) -> Action {
+        Action {
+            msg: CosmosMsg::Custom(Empty {}),
+            gas_limit,
+        }
+    }
+
+    pub fn is_no_op(&self) -> bool {
+        self.msg == CosmosMsg::Custom(Empty {})
+    }
+
+    /// Substitute `{{prev:<key>}}` placeholders in a wasm execute msg with the matching
+    /// wasm attribute from the previous action's reply, e.g.
+    /// `{"update_agent":{"payable_account_id":"{{prev:account_id}}"}}`
+    /// Unknown keys are left untouched, no other expressions are supported
+    pub fn with_prev_result(&self, events: &[Event]) -> Action {
+        let mut action = self.clone();
+        if let CosmosMsg::Wasm(WasmMsg::Execute { msg, .. }) = &mut action.msg {
+            let mut raw = match String::from_utf8(msg.to_vec()) {
+                Ok(raw) => raw,
+                Err(_) => return action,
+            };
+            if !raw.contains("{{prev:") {
+                return action;
+            }
+            for attr in events
+                .iter()
+                .filter(|e| e.ty == "wasm")
+                .flat_map(|e| e.attributes.iter())
+            {
+                let placeholder = format!("{{{{prev:{}}}}}", attr.key);
+                // Values are only placed inside json strings, keep them from breaking out
+                let value = attr.value.replace('\\', "\\\\").replace('"', "\\\"");
+                raw = raw.replace(&placeholder, &value);
+            }
+            *msg = Binary::from(raw.into_bytes());
+        }
+        action
+    }
+
+    /// IBC transfer timeouts are stored relative to execution, e.g. a timestamp of 600s
+    /// times out 10 minutes after the run. Turns them into absolute ones for this block
+    pub fn with_ibc_timeout(&self, env: &Env) -> Action {
+        let mut action = self.clone();
+        if let CosmosMsg::Ibc(IbcMsg::Transfer { timeout, .. }) = &mut action.msg {
+            let block = timeout.block().map(|b| IbcTimeoutBlock {
+                revision: b.revision,
+                height: env.block.height.saturating_add(b.height),
+            });
+            let timestamp = timeout
+                .timestamp()
+                .map(|t| Timestamp::from_nanos(env.block.time.nanos().saturating_add(t.nanos())));
+            *timeout = match (block, timestamp) {
+                (Some(block), Some(timestamp)) => IbcTimeout::with_both(block, timestamp),
+                (Some(block), None) => IbcTimeout::with_block(block),
+                (None, Some(timestamp)) => IbcTimeout::with_timestamp(timestamp),
+                (None, None) => return action,
+            };
+        }
+        action
+    }
+
+    /// Native funds the action sends along, these are paid out of the task balance
+    pub fn funds(&self) -> Vec<Coin> {
+        let funds = match &self.msg {
+            CosmosMsg::Wasm(WasmMsg::Execute { funds, .. })
+            | CosmosMsg::Wasm(WasmMsg::Instantiate { funds, .. }) => funds.clone(),
+            CosmosMsg::Bank(BankMsg::Send { amount, .. })
+            | CosmosMsg::Bank(BankMsg::Burn { amount }) => amount.clone(),
+            CosmosMsg::Ibc(IbcMsg::Transfer { amount, .. }) => vec![amount.clone()],
+            _ => vec![],
+        };
+        funds
+            .into_iter()
+            .filter(|coin| !coin.amount.is_zero())
+            .collect()
+    }
+}
+
 impl GenericBalance {
     pub fn add_tokens(&mut self, add: Balance) {
         match add {
@@ -405,14 +698,17 @@ impl Intervals for Interval {
             // Uses crontab spec
             Interval::Cron(crontab) => {
                 let current_block_ts: u64 = env.block.time.nanos();
-                // TODO: get current timestamp within boundary
                 let current_ts = match boundary.start {
                     Some(ts) if current_block_ts < ts => ts,
                     _ => current_block_ts,
                 };
                 let schedule = Schedule::from_str(crontab.as_str()).unwrap();
                 let next_ts = schedule.next_after(&current_ts).unwrap();
-                (next_ts, SlotType::Cron)
+                // Past the end boundary the task won't run again
+                match boundary.end {
+                    Some(end) if next_ts > end => (0, SlotType::Cron),
+                    _ => (next_ts, SlotType::Cron),
+                }
             }
             // return the block within a specific range that can be triggered 1 or more times based on block heights.
             // Uses block offset (Example: Block(100) will trigger every 100 blocks)
@@ -423,15 +719,23 @@ impl Intervals for Interval {
         }
     }
 
-    fn is_valid(&self) -> bool {
+    fn is_valid(&self, boundary: &BoundaryValidated) -> bool {
+        self.invalid_reason(boundary).is_none()
+    }
+
+    fn invalid_reason(&self, boundary: &BoundaryValidated) -> Option<String> {
         match self {
-            Interval::Once => true,
-            Interval::Immediate => true,
-            Interval::Block(_) => true,
-            Interval::Cron(crontab) => {
-                let s = Schedule::from_str(crontab);
-                s.is_ok()
+            // A one-shot task fires at or after its start, so it needs one
+            Interval::Once if boundary.start.is_none() => {
+                Some("Once interval needs a boundary start".to_string())
             }
+            Interval::Once => None,
+            Interval::Immediate => None,
+            Interval::Block(0) => Some("Block interval must be greater than zero".to_string()),
+            Interval::Block(_) => None,
+            Interval::Cron(crontab) => Schedule::from_str(crontab)
+                .err()
+                .map(|_| format!("Malformed cron expression: {}", crontab)),
         }
     }
 }
@@ -439,7 +743,8 @@ impl Intervals for Interval {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use cosmwasm_std::{IbcTimeout, VoteOption};
+    use cosmwasm_std::testing::mock_env;
+    use cosmwasm_std::{StakingMsg, VoteOption};
     use hex::ToHex;
 
     #[test]
@@ -450,6 +755,7 @@ mod tests {
             boundary: BoundaryValidated {
                 start: Some(4),
                 end: Some(8),
+                max_executions: None,
             },
             stop_on_fail: false,
             total_deposit: Default::default(),
@@ -461,10 +767,15 @@ mod tests {
                 }),
                 gas_limit: Some(5),
             }],
-            rules: Some(vec![Rule {
+            rules: Some(vec![Rule::Query {
                 contract_addr: Addr::unchecked("foo"),
                 msg: Binary("bar".into()),
             }]),
+            metadata: None,
+            successor: None,
+            generation: 0,
+            action_order: ActionOrder::Sequential,
+            total_cw20_deposit: vec![],
         };
         assert!(task.is_valid_msg(
             &Addr::unchecked("alice2"),
@@ -481,6 +792,7 @@ mod tests {
             boundary: BoundaryValidated {
                 start: Some(1_000_000_000),
                 end: Some(2_000_000_000),
+                max_executions: None,
             },
             stop_on_fail: false,
             total_deposit: Default::default(),
@@ -492,10 +804,15 @@ mod tests {
                 }),
                 gas_limit: Some(5),
             }],
-            rules: Some(vec![Rule {
+            rules: Some(vec![Rule::Query {
                 contract_addr: Addr::unchecked("foo"),
                 msg: Binary("bar".into()),
             }]),
+            metadata: None,
+            successor: None,
+            generation: 0,
+            action_order: ActionOrder::Sequential,
+            total_cw20_deposit: vec![],
         };
         assert!(task.is_valid_msg(
             &Addr::unchecked("alice2"),
@@ -512,6 +829,7 @@ mod tests {
             boundary: BoundaryValidated {
                 start: None,
                 end: None,
+                max_executions: None,
             },
             stop_on_fail: false,
             total_deposit: Default::default(),
@@ -523,10 +841,15 @@ mod tests {
                 }),
                 gas_limit: Some(5),
             }],
-            rules: Some(vec![Rule {
+            rules: Some(vec![Rule::Query {
                 contract_addr: Addr::unchecked("foo"),
                 msg: Binary("bar".into()),
             }]),
+            metadata: None,
+            successor: None,
+            generation: 0,
+            action_order: ActionOrder::Sequential,
+            total_cw20_deposit: vec![],
         };
         assert!(task.is_valid_msg(
             &Addr::unchecked("alice2"),
@@ -544,6 +867,7 @@ mod tests {
             boundary: BoundaryValidated {
                 start: Some(4),
                 end: None,
+                max_executions: None,
             },
             stop_on_fail: false,
             total_deposit: Default::default(),
@@ -555,10 +879,15 @@ mod tests {
                 }),
                 gas_limit: Some(5),
             }],
-            rules: Some(vec![Rule {
+            rules: Some(vec![Rule::Query {
                 contract_addr: Addr::unchecked("foo"),
                 msg: Binary("bar".into()),
             }]),
+            metadata: None,
+            successor: None,
+            generation: 0,
+            action_order: ActionOrder::Sequential,
+            total_cw20_deposit: vec![],
         };
         assert!(!task.is_valid_msg(
             &Addr::unchecked("alice"),
@@ -576,6 +905,7 @@ mod tests {
             boundary: BoundaryValidated {
                 start: Some(4),
                 end: None,
+                max_executions: None,
             },
             stop_on_fail: false,
             total_deposit: Default::default(),
@@ -586,10 +916,15 @@ mod tests {
                 }),
                 gas_limit: Some(5),
             }],
-            rules: Some(vec![Rule {
+            rules: Some(vec![Rule::Query {
                 contract_addr: Addr::unchecked("foo"),
                 msg: Binary("bar".into()),
             }]),
+            metadata: None,
+            successor: None,
+            generation: 0,
+            action_order: ActionOrder::Sequential,
+            total_cw20_deposit: vec![],
         };
         assert!(!task.is_valid_msg(
             &Addr::unchecked("alice"),
@@ -600,13 +935,14 @@ mod tests {
 
     #[test]
     fn is_valid_msg_transfer() {
-        // A task with CosmosMsg::Ibc Transfer should return false
-        let task = Task {
+        // A task with a well formed CosmosMsg::Ibc Transfer should return true
+        let mut task = Task {
             owner_id: Addr::unchecked("bob"),
             interval: Interval::Block(5),
             boundary: BoundaryValidated {
                 start: Some(4),
                 end: None,
+                max_executions: None,
             },
             stop_on_fail: false,
             total_deposit: Default::default(),
@@ -619,16 +955,128 @@ mod tests {
                 }),
                 gas_limit: Some(5),
             }],
-            rules: Some(vec![Rule {
+            rules: Some(vec![Rule::Query {
                 contract_addr: Addr::unchecked("foo"),
                 msg: Binary("bar".into()),
             }]),
+            metadata: None,
+            successor: None,
+            generation: 0,
+            action_order: ActionOrder::Sequential,
+            total_cw20_deposit: vec![],
         };
+        assert!(task.is_valid_msg(
+            &Addr::unchecked("alice"),
+            &Addr::unchecked("sender"),
+            &Addr::unchecked("bob")
+        ));
+
+        // Missing channel
+        task.actions[0].msg = CosmosMsg::Ibc(IbcMsg::Transfer {
+            channel_id: "".to_string(),
+            to_address: "address".to_string(),
+            amount: Coin::new(10, "coin"),
+            timeout: IbcTimeout::with_timestamp(Timestamp::from_nanos(1_000_000_000)),
+        });
+        assert!(!task.is_valid_msg(
+            &Addr::unchecked("alice"),
+            &Addr::unchecked("sender"),
+            &Addr::unchecked("bob")
+        ));
+
+        // Zero timeout
+        task.actions[0].msg = CosmosMsg::Ibc(IbcMsg::Transfer {
+            channel_id: "id".to_string(),
+            to_address: "address".to_string(),
+            amount: Coin::new(10, "coin"),
+            timeout: IbcTimeout::with_timestamp(Timestamp::from_nanos(0)),
+        });
         assert!(!task.is_valid_msg(
             &Addr::unchecked("alice"),
             &Addr::unchecked("sender"),
             &Addr::unchecked("bob")
         ));
+
+        // Timeouts too far out
+        for timeout in [
+            IbcTimeout::with_timestamp(Timestamp::from_seconds(MAX_IBC_TIMEOUT_SECONDS + 1)),
+            IbcTimeout::with_block(IbcTimeoutBlock {
+                revision: 1,
+                height: u64::MAX,
+            }),
+        ] {
+            task.actions[0].msg = CosmosMsg::Ibc(IbcMsg::Transfer {
+                channel_id: "id".to_string(),
+                to_address: "address".to_string(),
+                amount: Coin::new(10, "coin"),
+                timeout,
+            });
+            assert!(!task.is_valid_msg(
+                &Addr::unchecked("alice"),
+                &Addr::unchecked("sender"),
+                &Addr::unchecked("bob")
+            ));
+        }
+    }
+
+    #[test]
+    fn ibc_timeout_is_relative_to_execution() {
+        let env = mock_env();
+        let action = Action {
+            msg: CosmosMsg::Ibc(IbcMsg::Transfer {
+                channel_id: "id".to_string(),
+                to_address: "address".to_string(),
+                amount: Coin::new(10, "coin"),
+                timeout: IbcTimeout::with_both(
+                    IbcTimeoutBlock {
+                        revision: 1,
+                        height: 20,
+                    },
+                    Timestamp::from_seconds(600),
+                ),
+            }),
+            gas_limit: None,
+        };
+        assert_eq!(action.funds(), vec![Coin::new(10, "coin")]);
+        assert_eq!(
+            action.with_ibc_timeout(&env).msg,
+            CosmosMsg::Ibc(IbcMsg::Transfer {
+                channel_id: "id".to_string(),
+                to_address: "address".to_string(),
+                amount: Coin::new(10, "coin"),
+                timeout: IbcTimeout::with_both(
+                    IbcTimeoutBlock {
+                        revision: 1,
+                        height: env.block.height + 20,
+                    },
+                    env.block.time.plus_seconds(600),
+                ),
+            })
+        );
+
+        // Offsets saturate instead of overflowing
+        let mut action = action;
+        action.msg = CosmosMsg::Ibc(IbcMsg::Transfer {
+            channel_id: "id".to_string(),
+            to_address: "address".to_string(),
+            amount: Coin::new(10, "coin"),
+            timeout: IbcTimeout::with_block(IbcTimeoutBlock {
+                revision: 1,
+                height: u64::MAX,
+            }),
+        });
+        assert_eq!(
+            action.with_ibc_timeout(&env).msg,
+            CosmosMsg::Ibc(IbcMsg::Transfer {
+                channel_id: "id".to_string(),
+                to_address: "address".to_string(),
+                amount: Coin::new(10, "coin"),
+                timeout: IbcTimeout::with_block(IbcTimeoutBlock {
+                    revision: 1,
+                    height: u64::MAX,
+                }),
+            })
+        );
     }
 
     #[test]
@@ -640,6 +1088,7 @@ mod tests {
             boundary: BoundaryValidated {
                 start: Some(4),
                 end: None,
+                max_executions: None,
             },
             stop_on_fail: false,
             total_deposit: Default::default(),
@@ -649,10 +1098,15 @@ mod tests {
                 }),
                 gas_limit: Some(5),
             }],
-            rules: Some(vec![Rule {
+            rules: Some(vec![Rule::Query {
                 contract_addr: Addr::unchecked("foo"),
                 msg: Binary("bar".into()),
             }]),
+            metadata: None,
+            successor: None,
+            generation: 0,
+            action_order: ActionOrder::Sequential,
+            total_cw20_deposit: vec![],
         };
         assert!(!task.is_valid_msg(
             &Addr::unchecked("alice"),
@@ -670,6 +1124,7 @@ mod tests {
             boundary: BoundaryValidated {
                 start: Some(4),
                 end: None,
+                max_executions: None,
             },
             stop_on_fail: false,
             total_deposit: Default::default(),
@@ -680,10 +1135,15 @@ mod tests {
                 }),
                 gas_limit: Some(5),
             }],
-            rules: Some(vec![Rule {
+            rules: Some(vec![Rule::Query {
                 contract_addr: Addr::unchecked("foo"),
                 msg: Binary("bar".into()),
             }]),
+            metadata: None,
+            successor: None,
+            generation: 0,
+            action_order: ActionOrder::Sequential,
+            total_cw20_deposit: vec![],
         };
         assert!(!task.is_valid_msg(
             &Addr::unchecked("alice"),
@@ -692,6 +1152,49 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn is_valid_msg_no_op() {
+        let mut task = Task {
+            owner_id: Addr::unchecked("bob"),
+            interval: Interval::Block(5),
+            boundary: BoundaryValidated {
+                start: None,
+                end: None,
+                max_executions: None,
+            },
+            stop_on_fail: false,
+            total_deposit: Default::default(),
+            actions: vec![Action::no_op(Some(5))],
+            rules: None,
+            metadata: None,
+            successor: None,
+            generation: 0,
+            action_order: ActionOrder::Sequential,
+            total_cw20_deposit: vec![],
+        };
+        assert!(task.is_no_op());
+        assert!(task.is_valid_msg(
+            &Addr::unchecked("alice"),
+            &Addr::unchecked("sender"),
+            &Addr::unchecked("bob")
+        ));
+
+        // Mixed with a real action the no-op would never run
+        task.actions.push(Action {
+            msg: CosmosMsg::Staking(StakingMsg::Delegate {
+                validator: "validator".to_string(),
+                amount: Coin::new(10, "coin"),
+            }),
+            gas_limit: Some(5),
+        });
+        assert!(!task.is_no_op());
+        assert!(!task.is_valid_msg(
+            &Addr::unchecked("alice"),
+            &Addr::unchecked("sender"),
+            &Addr::unchecked("bob")
+        ));
+    }
+
     #[test]
     fn test_add_tokens() {
         let mut coins: GenericBalance = GenericBalance::default();
@@ -857,6 +1360,84 @@ mod tests {
         coins.minus_tokens(minus_cw20);
     }
 
+    #[test]
+    fn callback_gas_per_action() {
+        let action = Action {
+            msg: CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: "alice".to_string(),
+                msg: Binary::from(vec![]),
+                funds: vec![],
+            }),
+            gas_limit: Some(5),
+        };
+        let mut task = Task {
+            owner_id: Addr::unchecked("bob"),
+            interval: Interval::Immediate,
+            boundary: BoundaryValidated {
+                start: None,
+                end: None,
+                max_executions: None,
+            },
+            stop_on_fail: false,
+            total_deposit: Default::default(),
+            actions: vec![action.clone()],
+            rules: None,
+            metadata: None,
+            successor: None,
+            generation: 0,
+            action_order: ActionOrder::Sequential,
+            total_cw20_deposit: vec![],
+        };
+        let agent_fee = Coin::new(1, "atom");
+        let one_action_gas = task.to_callback_gas(3);
+        let one_action_uses = task.task_balance_uses(&agent_fee, 10, 3);
+        assert_eq!(one_action_gas, 3);
+        assert_eq!(one_action_uses, 1 + 5 + 3);
+
+        task.actions = vec![action; 3];
+        assert_eq!(task.to_callback_gas(3), one_action_gas * 3);
+        assert_eq!(task.task_balance_uses(&agent_fee, 10, 3), 1 + 5 * 3 + 3 * 3);
+    }
+
+    #[test]
+    fn action_with_prev_result() {
+        let action = Action {
+            msg: CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: "alice".to_string(),
+                msg: Binary::from(
+                    br#"{"a":"{{prev:amount}}","b":"{{prev:memo}}","c":"{{prev:missing}}"}"#
+                        .to_vec(),
+                ),
+                funds: vec![],
+            }),
+            gas_limit: Some(5),
+        };
+        let events = vec![
+            Event::new("message").add_attribute("amount", "1"),
+            Event::new("wasm")
+                .add_attribute("amount", "42")
+                .add_attribute("memo", r#"say "hi""#),
+        ];
+        let expected = CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "alice".to_string(),
+            msg: Binary::from(br#"{"a":"42","b":"say \"hi\"","c":"{{prev:missing}}"}"#.to_vec()),
+            funds: vec![],
+        });
+        let templated = action.with_prev_result(&events);
+        assert_eq!(templated.msg, expected);
+        assert_eq!(templated.gas_limit, Some(5));
+
+        // Non-wasm messages are passed through as is
+        let action = Action {
+            msg: CosmosMsg::Bank(BankMsg::Send {
+                to_address: "{{prev:amount}}".to_string(),
+                amount: vec![],
+            }),
+            gas_limit: None,
+        };
+        assert_eq!(action.with_prev_result(&events), action);
+    }
+
     #[test]
     fn hashing() {
         let task = Task {
@@ -865,6 +1446,7 @@ mod tests {
             boundary: BoundaryValidated {
                 start: Some(4),
                 end: None,
+                max_executions: None,
             },
             stop_on_fail: false,
             total_deposit: Default::default(),
@@ -874,10 +1456,15 @@ mod tests {
                 }),
                 gas_limit: Some(5),
             }],
-            rules: Some(vec![Rule {
+            rules: Some(vec![Rule::Query {
                 contract_addr: Addr::unchecked("foo"),
                 msg: Binary("bar".into()),
             }]),
+            metadata: None,
+            successor: None,
+            generation: 0,
+            action_order: ActionOrder::Sequential,
+            total_cw20_deposit: vec![],
         };
 
         let message = format!(
@@ -894,4 +1481,63 @@ mod tests {
         assert_eq!(encoded, task.to_hash());
         assert_eq!(bytes, task.to_hash_vec());
     }
+
+    #[test]
+    fn action_funds() {
+        let action = Action {
+            msg: CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: "alice".to_string(),
+                msg: Binary::from(vec![]),
+                funds: vec![Coin::new(10, "coin"), Coin::new(0, "empty")],
+            }),
+            gas_limit: None,
+        };
+        assert_eq!(action.funds(), vec![Coin::new(10, "coin")]);
+
+        let action = Action {
+            msg: CosmosMsg::Gov(GovMsg::Vote {
+                proposal_id: 1,
+                vote: VoteOption::Yes,
+            }),
+            gas_limit: None,
+        };
+        assert!(action.funds().is_empty());
+    }
+
+    #[test]
+    fn task_funds_per_run() {
+        let send = |funds: Vec<Coin>| Action {
+            msg: CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: "alice".to_string(),
+                msg: Binary::from(vec![]),
+                funds,
+            }),
+            gas_limit: None,
+        };
+        let task = Task {
+            owner_id: Addr::unchecked("bob"),
+            interval: Interval::Once,
+            boundary: BoundaryValidated {
+                start: None,
+                end: None,
+                max_executions: None,
+            },
+            stop_on_fail: false,
+            total_deposit: Default::default(),
+            actions: vec![
+                send(vec![Coin::new(10, "coin"), Coin::new(5, "other")]),
+                send(vec![Coin::new(3, "coin")]),
+            ],
+            rules: None,
+            metadata: None,
+            successor: None,
+            generation: 0,
+            action_order: ActionOrder::Sequential,
+            total_cw20_deposit: vec![],
+        };
+        assert_eq!(
+            task.funds_per_run(),
+            vec![Coin::new(13, "coin"), Coin::new(5, "other")]
+        );
+    }
 }